@@ -0,0 +1,120 @@
+//! Throughput of the C API on simulated reads.
+//!
+//! Run with `cargo bench`. Each scenario builds a graph from reads simulated from a random
+//! reference, and reports the time per read and the Q-score of the consensus.
+
+use std::hint::black_box;
+use std::os::raw::{c_char, c_int};
+use std::slice;
+use std::time::Instant;
+
+// Link the library, the benchmark only goes through its C API
+use poasta_c as _;
+
+/// Opaque graph handle, as seen from C.
+#[repr(C)]
+struct PoastaGraph {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct PoastaErrorProfile {
+    substitution_rate: f64,
+    insertion_rate: f64,
+    deletion_rate: f64,
+}
+
+#[repr(C)]
+struct PoastaEvaluation {
+    edit_distance: usize,
+    substitutions: usize,
+    insertions: usize,
+    deletions: usize,
+    identity: f64,
+    qscore: f64,
+}
+
+#[repr(C)]
+struct PoastaReads {
+    sequences: *mut *mut c_char,
+    num_sequences: usize,
+}
+
+unsafe extern "C" {
+    fn poasta_create_graph() -> *mut PoastaGraph;
+    fn poasta_free_graph(graph: *mut PoastaGraph);
+    fn poasta_add_sequence(
+        graph: *mut PoastaGraph,
+        seq: *const c_char,
+        len: usize,
+        mismatch_score: i32,
+        gap_extend: i32,
+        gap_open: i32,
+    ) -> c_int;
+    fn poasta_bench_generate_reads(
+        reference: *const c_char,
+        reference_len: usize,
+        n: usize,
+        error_profile: PoastaErrorProfile,
+        seed: u64,
+    ) -> PoastaReads;
+    fn poasta_free_reads(reads: PoastaReads);
+    fn poasta_eval_consensus(graph: *const PoastaGraph, truth_seq: *const c_char, truth_len: usize) -> PoastaEvaluation;
+}
+
+const NUM_READS: usize = 20;
+
+fn random_reference(len: usize, seed: u64) -> Vec<u8> {
+    // Substituting three quarters of a poly-A template gives uniformly random bases
+    let profile = PoastaErrorProfile { substitution_rate: 0.75, insertion_rate: 0.0, deletion_rate: 0.0 };
+    let template = vec![b'A'; len];
+
+    unsafe {
+        let reads = poasta_bench_generate_reads(template.as_ptr() as *const c_char, len, 1, profile, seed);
+        let reference = slice::from_raw_parts(*reads.sequences as *const u8, len).to_vec();
+        poasta_free_reads(reads);
+
+        reference
+    }
+}
+
+fn bench(reference_len: usize, error_rate: f64) {
+    let reference = random_reference(reference_len, 1);
+    let profile = PoastaErrorProfile {
+        substitution_rate: error_rate / 2.0,
+        insertion_rate: error_rate / 4.0,
+        deletion_rate: error_rate / 4.0,
+    };
+
+    unsafe {
+        let reads = poasta_bench_generate_reads(reference.as_ptr() as *const c_char, reference.len(), NUM_READS, profile, 42);
+        let reads_slice = slice::from_raw_parts(reads.sequences, reads.num_sequences);
+
+        let graph = poasta_create_graph();
+        let start = Instant::now();
+        for &read in reads_slice {
+            let len = std::ffi::CStr::from_ptr(read).to_bytes().len();
+            black_box(poasta_add_sequence(graph, read, len, 4, 2, 6));
+        }
+        let elapsed = start.elapsed();
+
+        let eval = poasta_eval_consensus(graph, reference.as_ptr() as *const c_char, reference.len());
+        poasta_free_graph(graph);
+        poasta_free_reads(reads);
+
+        println!(
+            "reference {reference_len:>5} bp, error rate {:>4.1}%: {:>10.3} ms/read, consensus Q{:.1}",
+            error_rate * 100.0,
+            elapsed.as_secs_f64() * 1000.0 / NUM_READS as f64,
+            eval.qscore,
+        );
+    }
+}
+
+fn main() {
+    for reference_len in [500, 1000] {
+        for error_rate in [0.01, 0.05, 0.10] {
+            bench(reference_len, error_rate);
+        }
+    }
+}
@@ -7,6 +7,9 @@ fn main() {
 
     cbindgen::Builder::new()
         .with_crate(crate_dir)
+        .with_define("feature", "test-support", "POASTA_TEST_SUPPORT")
+        // Passed to poasta_supports as a plain value, so no function signature names it
+        .include_item("PoastaFeature")
         .generate()
         .expect("Unable to generate bindings")
         .write_to_file("poasta.h");
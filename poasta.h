@@ -4,13 +4,765 @@
 #include <ostream>
 #include <new>
 
+/// Number of error codes counted separately; status `-i` is counted at index `i`.
+constexpr static const uintptr_t POASTA_NUM_ERROR_CODES = 16;
+
+/// How a sequence is aligned to the graph.
+enum class PoastaAlignmentType {
+  /// The whole sequence is aligned to a full path through the graph.
+  Global = 0,
+  /// Like Global, but gaps at the ends selected in `PoastaAlignmentMode` are free.
+  EndsFree = 1,
+  /// Only the best matching part of the sequence is aligned, to any part of the graph, e.g., for
+  /// fragments partially overlapping the graph. The rest of the sequence is clipped, i.e., added
+  /// as new nodes, and so is a sequence without any matching part.
+  Local = 2,
+};
+
+/// Symbols of the sequences of a graph. Nucleotides include the IUPAC ambiguity codes, and all
+/// alphabets are case-insensitive.
+enum class PoastaAlphabet {
+  /// Detected from the first sequence of the graph.
+  Auto = 0,
+  Dna = 1,
+  /// Like DNA, with U instead of T.
+  Rna = 2,
+  /// The 20 amino acids, B, J, O, U, X, Z and `*` for stop codons.
+  Protein = 3,
+  /// Any bytes; nucleotide-only features are unavailable.
+  Raw = 4,
+};
+
+/// How `poasta_get_consensus_protein` handles codons containing bases other than A, C, G and T.
+enum class PoastaAmbiguity {
+  /// Translate any codon with an ambiguous base to `X`.
+  Unknown = 0,
+  /// Expand IUPAC ambiguity codes, and use the amino acid if all expansions agree (e.g., `GCN`
+  /// is always alanine). Otherwise, translate to `X`.
+  Resolve = 1,
+};
+
+/// How the consensus resolves alternatives with equal support.
+enum class PoastaTieBreak {
+  /// Take the base that was added to the graph first.
+  FirstSeen = 0,
+  /// Take the lexicographically smallest base.
+  Lexicographic = 1,
+  /// Take the base of the first sequence of the graph (the reference), otherwise fall back to
+  /// `FirstSeen`.
+  Reference = 2,
+  /// Emit the IUPAC ambiguity code of the tied bases if they are aligned to each other,
+  /// otherwise (or if the alphabet isn't DNA or RNA) fall back to `FirstSeen`.
+  Ambiguity = 3,
+};
+
+/// Formats of poasta_export_coverage_track.
+enum class PoastaTrackFormat {
+  /// Wiggle with a fixed step of one base.
+  Wig = 0,
+  /// bedGraph, with runs of equal coverage merged into one interval.
+  BedGraph = 1,
+};
+
+/// Direction poasta_get_dot lays out the graph in, Graphviz's `rankdir`.
+enum class PoastaRankDir {
+  /// Left to right, like poasta_get_dot_layers.
+  LeftRight = 0,
+  /// Top to bottom.
+  TopBottom = 1,
+  /// Right to left.
+  RightLeft = 2,
+  /// Bottom to top.
+  BottomTop = 3,
+};
+
+/// Reasons a call can fail. The value of each error is the status code returned for it.
+enum class PoastaError {
+  /// No error.
+  Ok = 0,
+  /// A NULL pointer, or an argument out of range or otherwise invalid.
+  InvalidArgument = -1,
+  /// poasta failed to add the first sequence of a graph.
+  FirstSequenceFailed = -2,
+  /// poasta failed to add an aligned sequence to the graph.
+  CommitFailed = -3,
+  /// The sequence was rejected by the length filter.
+  LengthOutlier = -4,
+  /// Reading or writing a file failed.
+  IoError = -5,
+  /// A scoring configuration is inconsistent with its gap model.
+  InvalidScoring = -6,
+  /// The alignment didn't finish within the time budget.
+  Timeout = -7,
+  /// Serialized data is truncated, fails its checksum or isn't what it should be.
+  Corrupted = -8,
+  /// Serialized data was written by a newer, incompatible version of the library.
+  UnsupportedVersion = -9,
+  /// The library panicked, i.e., hit a bug. Objects passed to the failed call may be left in an
+  /// inconsistent state and should only be freed.
+  Panic = -10,
+  /// The output exceeds the maximum size the caller allowed.
+  Truncated = -11,
+};
+
+/// Formats of poasta_export_all_alignments.
+enum class PoastaAlignmentFormat {
+  /// GAF alignments to the segments of poasta_get_gfa_layers.
+  Gaf = 0,
+  /// SAM alignments to the consensus (poasta_get_consensus), named `consensus`.
+  Sam = 1,
+};
+
+/// Kind of a column of an alignment of a query to a graph path.
+enum class PoastaEditOp {
+  Match = 0,
+  Mismatch = 1,
+  /// Query base not aligned to the graph.
+  Insertion = 2,
+  /// Graph node skipped by the query.
+  Deletion = 3,
+};
+
+#if defined(POASTA_TEST_SUPPORT)
+/// Fixtures of poasta_fixture_graph.
+enum class PoastaFixture {
+#if defined(POASTA_TEST_SUPPORT)
+  /// Three identical reads, a graph that is a single path.
+  Identical = 0,
+#endif
+#if defined(POASTA_TEST_SUPPORT)
+  /// Four reads, one of them with a substitution the consensus outvotes.
+  Snp = 1,
+#endif
+#if defined(POASTA_TEST_SUPPORT)
+  /// Four reads, one with a deletion and one with an insertion.
+  Indel = 2,
+#endif
+};
+#endif
+
+#if defined(POASTA_TEST_SUPPORT)
+/// Outputs of a fixture graph, see poasta_fixture_expected.
+enum class PoastaFixtureOutput {
+#if defined(POASTA_TEST_SUPPORT)
+  /// The sequence of poasta_get_consensus.
+  Consensus = 0,
+#endif
+#if defined(POASTA_TEST_SUPPORT)
+  /// poasta_get_msa_fasta with default options (no padding, no wrapping).
+  MsaFasta = 1,
+#endif
+#if defined(POASTA_TEST_SUPPORT)
+  /// poasta_get_gfa.
+  Gfa = 2,
+#endif
+};
+#endif
+
+/// GFA versions of poasta_get_gfa_version, which differ in how sequences are written.
+enum class PoastaGfaVersion {
+  /// GFA 1.0, with a P line per sequence (like poasta_get_gfa_with_paths).
+  Gfa10 = 0,
+  /// GFA 1.1, with a W line per sequence.
+  Gfa11 = 1,
+};
+
+/// Coordinate system of positions passed to the library.
+enum class PoastaCoordinates {
+  /// Positions on the graph's backbone (see poasta_graph_set_backbone).
+  Reference = 0,
+  /// Positions in the current consensus.
+  Consensus = 1,
+};
+
+/// How exports treat provisional nodes and edges.
+enum class PoastaProvisional {
+  /// Leave them out, like poasta_get_gfa does.
+  Exclude = 0,
+  /// Export them like confirmed elements.
+  Include = 1,
+  /// Export them, marked as provisional.
+  Mark = 2,
+};
+
+/// Operations whose resources are reported.
+enum class PoastaOperation {
+  /// Aligning a sequence to a graph, whether to add it or not.
+  Align = 0,
+  /// Adding an aligned sequence to a graph.
+  Commit = 1,
+  /// Computing an MSA, in any of its output formats.
+  Msa = 2,
+};
+
+/// Built-in protein substitution matrices.
+enum class PoastaMatrixPreset {
+  /// For moderately diverged proteins, BLAST's default.
+  Blosum62 = 0,
+  /// For distantly related proteins.
+  Blosum45 = 1,
+  /// For distantly related proteins, from evolutionary models.
+  Pam250 = 2,
+};
+
+/// How scoring configurations score gaps.
+enum class PoastaGapModel {
+  /// Each gapped base costs gap extend, without an opening cost, which is faster to align.
+  Linear = 0,
+  /// A gap of length `l` costs `gap_open + l * gap_extend` (the default).
+  Affine = 1,
+  /// A gap costs the cheaper of two affine pieces, the second set with
+  /// poasta_scoring_set_long_gaps.
+  TwoPieceAffine = 2,
+};
+
+/// Strand a sequence was read from.
+enum class PoastaStrand {
+  Unknown = 0,
+  Forward = 1,
+  Reverse = 2,
+};
+
+/// Verbosity of the timing spans.
+enum class PoastaTraceLevel {
+  Off = 0,
+  /// Calls into the library, e.g., adding a sequence or computing the MSA.
+  Info = 1,
+  /// Steps within those calls, e.g., aligning and committing a sequence.
+  Debug = 2,
+};
+
+/// Optional features and alignment modes, see poasta_supports.
+enum class PoastaFeature {
+  GlobalAlignment = 0,
+  /// Semi-global alignment, where sequences may start and end anywhere in the graph.
+  EndsFreeAlignment = 1,
+  /// Local alignment, clipping the parts of sequences that don't overlap the graph.
+  LocalAlignment = 2,
+  TwoPieceGaps = 3,
+  /// Per-pair substitution costs (poasta_scoring_create).
+  CustomScoring = 4,
+  CodonAwareGaps = 5,
+  AlignmentTimeout = 6,
+  /// Timing spans, the `tracing` build feature.
+  Tracing = 7,
+  /// Allocation counting in poasta_metrics_snapshot, the `alloc-metrics` build feature.
+  AllocationMetrics = 8,
+  HomopolymerGaps = 9,
+  /// Fixture graphs with expected outputs (poasta_fixture_graph), the `test-support` build
+  /// feature.
+  TestSupport = 10,
+};
+
+/// Scoring and working memory for adding sequences. Use one aligner per thread.
+struct PoastaAligner;
+
+/// Opaque snapshot of a graph in a compact form for fast alignment. Later changes to the
+/// graph don't affect it.
+struct PoastaFrozenGraph;
+
 /// Opaque pointer to the POAGraph<u32>.
 struct PoastaGraph;
 
+/// Opaque set of reference graphs, one per reference, which can't be changed once created.
+struct PoastaPanel;
+
+/// Opaque scoring configuration. Sequences added with it keep the costs they were aligned with,
+/// changing the configuration afterwards only affects sequences added later.
+struct PoastaScoring;
+
+/// Opaque working memory for alignment to frozen graphs, reusable across queries and frozen graphs.
+/// Use it from one thread at a time.
+struct PoastaScratch;
+
+/// Opaque pointer to an open graph store.
+struct PoastaStore;
+
 /// Struct to hold the MSA result.
 struct PoastaMsa {
   char **sequences;
   uintptr_t num_sequences;
+  /// Name of the sequence of each row, as given to poasta_add_sequence_named or `seq_N`.
+  char **names;
+};
+
+/// Alignment type, and for ends-free alignment, which ends are free.
+struct PoastaAlignmentMode {
+  PoastaAlignmentType aln_type;
+  /// Bases before the part of the sequence aligned to the graph are free.
+  bool query_free_begin;
+  /// Bases after the part of the sequence aligned to the graph are free.
+  bool query_free_end;
+  /// The alignment may start at any node, skipping the graph before it for free.
+  bool graph_free_begin;
+  /// The alignment may end at any node, skipping the graph after it for free.
+  bool graph_free_end;
+  /// Bonus for each matching base in Local alignment, without which any single matching base
+  /// would be as good as a long alignment. 0 means half the mismatch cost, rounded up.
+  uint8_t match_bonus;
+};
+
+/// How sequences are aligned by poasta_align_query and poasta_add_sequences.
+struct PoastaAlignParams {
+  int32_t mismatch_score;
+  int32_t gap_extend;
+  int32_t gap_open;
+  PoastaAlignmentMode mode;
+};
+
+/// Struct to hold a list of reads.
+struct PoastaReads {
+  char **sequences;
+  uintptr_t num_sequences;
+};
+
+/// Per-base error rates of simulated reads.
+struct PoastaErrorProfile {
+  double substitution_rate;
+  double insertion_rate;
+  double deletion_rate;
+};
+
+/// Struct to hold a sequence whose path switches between two haplotypes.
+struct PoastaChimera {
+  /// Index of the sequence (in insertion order).
+  uintptr_t sequence;
+  /// First variable MSA column of the sequence that follows the second haplotype.
+  uintptr_t switch_column;
+  /// Position in the sequence of its first base at or after `switch_column`.
+  uintptr_t switch_position;
+  /// A sequence of the haplotype before and of the haplotype after the switch, the one agreeing
+  /// with the chimera on the most variable columns.
+  uintptr_t first_parent;
+  uintptr_t second_parent;
+  /// Number of sequences agreeing with the chimera before (after) the switch but not after
+  /// (before) it.
+  uintptr_t first_support;
+  uintptr_t second_support;
+};
+
+/// Struct to hold the chimeras of a graph.
+struct PoastaChimeras {
+  PoastaChimera *chimeras;
+  uintptr_t num_chimeras;
+};
+
+/// Struct to hold a variable site of either or both of two compared graphs.
+struct PoastaSiteComparison {
+  /// Position of the site in the consensus of each graph, i.e., the number of consensus bases
+  /// before it, or -1 if the graph has no variable site there.
+  int64_t pos_a;
+  int64_t pos_b;
+  /// Alleles of the site in each graph (bases, or `-` for deletions) seen in at least two active
+  /// sequences, most frequent first, NUL-terminated. Empty if the graph has no site there.
+  char *alleles_a;
+  char *alleles_b;
+};
+
+/// Struct to hold the differences between two graphs.
+struct PoastaGraphComparison {
+  /// Identity of the two consensus sequences, `1 - edit_distance / max(len)`.
+  double identity;
+  /// Differences between the consensus sequences: mismatching bases, bases only in the consensus
+  /// of `a` (insertions) and bases only in the consensus of `b` (deletions).
+  uintptr_t substitutions;
+  uintptr_t insertions;
+  uintptr_t deletions;
+  /// Number of sites variable in both graphs with the same alleles, variable in both with
+  /// different alleles, and variable only in `a` or only in `b`.
+  uintptr_t shared;
+  uintptr_t discordant;
+  uintptr_t unique_a;
+  uintptr_t unique_b;
+  /// All variable sites of either graph, ordered by their position in the consensus of `a`.
+  PoastaSiteComparison *sites;
+  uintptr_t num_sites;
+};
+
+/// Struct to hold the segments of a read, as offsets into the read.
+struct PoastaSegments {
+  uintptr_t *starts;
+  uintptr_t *lengths;
+  uintptr_t num_segments;
+};
+
+/// Struct to hold a consensus sequence with the support of each base: the number of sequences
+/// supporting it and their fraction (between 0 and 1) as its confidence.
+struct PoastaConsensus {
+  char *sequence;
+  double *confidence;
+  uintptr_t *support;
+  uintptr_t len;
+};
+
+/// Struct to hold the accuracy of a consensus compared to a known truth sequence.
+struct PoastaEvaluation {
+  uintptr_t edit_distance;
+  uintptr_t substitutions;
+  /// Consensus bases missing in the truth.
+  uintptr_t insertions;
+  /// Truth bases missing in the consensus.
+  uintptr_t deletions;
+  /// Fraction of truth bases correctly called, `1 - edit_distance / truth_len`.
+  double identity;
+  /// Phred-scaled error rate `-10 log10(edit_distance / truth_len)`, at most 60.
+  double qscore;
+};
+
+/// Struct to hold a column of an alignment of a query to a graph path.
+struct PoastaEdit {
+  PoastaEditOp op;
+  /// Graph node (as in poasta_node_ref_position), or -1 for insertions.
+  int64_t node;
+  /// Position in the query, or -1 for deletions.
+  int64_t query_pos;
+};
+
+/// Struct to hold the path of a sequence through the graph.
+struct PoastaSequencePath {
+  /// Nodes the sequence passes through in order, by node index, the segment names of
+  /// poasta_get_gfa_with_paths.
+  uintptr_t *nodes;
+  uintptr_t num_nodes;
+};
+
+/// Struct to hold the joint allele counts of two MSA columns.
+struct PoastaLinkage {
+  uintptr_t col_a;
+  uintptr_t col_b;
+  /// Alleles seen in each column (bases, or `-` for deletions), most frequent first.
+  uint8_t *alleles_a;
+  uintptr_t num_alleles_a;
+  uint8_t *alleles_b;
+  uintptr_t num_alleles_b;
+  /// `counts[i * num_alleles_b + j]` is the number of sequences with allele `alleles_a[i]` in
+  /// column `col_a` and allele `alleles_b[j]` in column `col_b`.
+  uintptr_t *counts;
+  /// Number of sequences spanning both columns, i.e., the sum of all counts.
+  uintptr_t num_sequences;
+};
+
+/// Struct to hold the linkage of each pair of a list of columns.
+struct PoastaLinkageList {
+  PoastaLinkage *pairs;
+  uintptr_t num_pairs;
+};
+
+/// Half-open interval `[start, end)` of 0-based positions.
+struct PoastaInterval {
+  uintptr_t start;
+  uintptr_t end;
+};
+
+/// Struct to hold cumulative counters since the library was loaded.
+struct PoastaMetrics {
+  /// Sequences successfully added by the poasta_add_sequence* functions.
+  uint64_t sequences_added;
+  uint64_t alignments_run;
+  /// Alignment states visited by poasta's aligner, or matrix cells filled by the exhaustive
+  /// aligner.
+  uint64_t cells_computed;
+  /// Total size of all heap allocations made by the library (0 without the `alloc-metrics`
+  /// feature).
+  uint64_t bytes_allocated;
+  /// `errors[i]` is the number of times an add function returned `-i`.
+  uint64_t errors[POASTA_NUM_ERROR_CODES];
+};
+
+/// Options of poasta_get_msa_padded and poasta_get_msa_fasta.
+struct PoastaMsaOptions {
+  /// Character filling each row before the first and after the last base of its sequence, e.g.,
+  /// `.` or ` `; 0 to use `-` like for the gaps within the sequence.
+  char pad;
+  /// Number of MSA columns per line of FASTA output; 0 to write each row on a single line.
+  uintptr_t line_width;
+};
+
+/// MSA symbols in a single array, see poasta_get_msa_matrix.
+struct PoastaMsaMatrix {
+  /// `num_rows * num_cols` symbols, row by row, with `-` for gaps.
+  uint8_t *symbols;
+  uintptr_t num_rows;
+  uintptr_t num_cols;
+};
+
+/// Struct to hold one of several consensus paths of a graph.
+struct PoastaConsensusPath {
+  /// Consensus sequence along the path, NUL-terminated.
+  char *sequence;
+  uintptr_t len;
+  /// Indices (in insertion order) of the active sequences closest to this path.
+  uintptr_t *reads;
+  uintptr_t num_reads;
+};
+
+/// Struct to hold the consensus paths of a graph, most supported first.
+struct PoastaConsensusPaths {
+  PoastaConsensusPath *paths;
+  uintptr_t num_paths;
+};
+
+/// Struct to hold the merged consensus of two sequences.
+struct PoastaPairConsensus {
+  char *sequence;
+  uintptr_t len;
+  /// Consensus positions where the two sequences disagree, in increasing order. A base only
+  /// present in the second sequence is reported at the position of the following consensus base
+  /// (or at `len` if it is at the end).
+  uintptr_t *disagreements;
+  uintptr_t num_disagreements;
+};
+
+/// Configuration of poasta_pair_consensus.
+struct PoastaPairConfig {
+  int32_t mismatch_score;
+  int32_t gap_extend;
+  int32_t gap_open;
+};
+
+/// Struct to hold the reference a read aligns to best.
+struct PoastaPanelHit {
+  /// Index of the reference in the panel, or -1 if there is no hit.
+  int64_t index;
+  /// Name of the reference, owned by the panel (NULL if there is no hit).
+  const char *name;
+  /// Cost of the read's alignment to the reference.
+  int64_t score;
+  /// Index of the reference with the second lowest cost, or -1 if the panel has a single
+  /// reference.
+  int64_t second_index;
+  /// Cost of the second best alignment minus `score` (0 if there is no second best).
+  int64_t margin;
+  /// Whether the margin is below the configured minimum, so the read can't be assigned to a
+  /// reference with confidence.
+  bool ambiguous;
+};
+
+/// How reads are aligned to the references of a panel.
+struct PoastaPanelConfig {
+  int32_t mismatch_score;
+  int32_t gap_extend;
+  int32_t gap_open;
+  /// Mode reads are aligned in, e.g., with free graph ends for reads covering only part of the
+  /// references.
+  PoastaAlignmentMode mode;
+  /// Smallest margin between the best and second best score for a hit to be unambiguous. Ties
+  /// are always ambiguous.
+  uint32_t min_margin;
+};
+
+/// Struct to hold a haplotype of a phase block.
+struct PoastaHaplotype {
+  /// Allele at each column of the block (a base, or `-` for a deletion), NUL-terminated.
+  char *alleles;
+  /// Number of sequences with this haplotype.
+  uintptr_t num_reads;
+};
+
+/// Struct to hold a block of variable MSA columns phased together.
+struct PoastaPhaseBlock {
+  /// The variable columns of the block, in order.
+  uintptr_t *columns;
+  uintptr_t num_columns;
+  /// Haplotypes of the sequences spanning the whole block, most supported first.
+  PoastaHaplotype *haplotypes;
+  uintptr_t num_haplotypes;
+};
+
+/// Struct to hold the phase blocks of a graph.
+struct PoastaPhaseBlocks {
+  PoastaPhaseBlock *blocks;
+  uintptr_t num_blocks;
+};
+
+/// Struct to hold the differences of sequences to the consensus.
+struct PoastaErrorCounts {
+  /// Consensus bases covered by the sequences.
+  uintptr_t bases;
+  uintptr_t substitutions;
+  uintptr_t insertions;
+  uintptr_t deletions;
+  /// Substitutions, insertions and deletions per covered consensus base.
+  double substitution_rate;
+  double insertion_rate;
+  double deletion_rate;
+};
+
+/// Struct to hold the sequencing errors observed in a graph.
+struct PoastaObservedErrors {
+  PoastaErrorCounts total;
+  /// Errors by consensus base: A, C, G, T and any other symbol. Insertions count towards the
+  /// consensus base before them.
+  PoastaErrorCounts by_base[5];
+  /// Errors of each sequence in insertion order, if requested (NULL otherwise).
+  PoastaErrorCounts *by_read;
+  uintptr_t num_reads;
+};
+
+/// Struct to hold when and in which batch a sequence was added to the graph.
+struct PoastaProvenance {
+  /// Number of sequences added to the graph before this one.
+  uint64_t insertion;
+  /// Milliseconds since the Unix epoch, 0 if unknown.
+  uint64_t timestamp_ms;
+  /// Batch set with poasta_set_batch_id when the sequence was added, 0 for none.
+  uint64_t batch_id;
+};
+
+/// How Phred qualities are turned into base weights.
+struct PoastaQualityWeights {
+  /// Weight per quality point, so a base gets `round(quality * scale)`. 0 means 1, i.e., the
+  /// Phred score itself.
+  double scale;
+  /// Qualities above this are treated as this, since basecallers tend to overstate high
+  /// qualities. 0 means no cap.
+  uint8_t max_quality;
+  /// Lowest weight of a base. A base with weight 0 doesn't count towards the consensus.
+  uint32_t min_weight;
+};
+
+/// Struct to hold the alignment of a query to a graph.
+struct PoastaQueryAlignment {
+  /// 0 on success, otherwise a negative status code and the other fields are empty.
+  int status;
+  /// Cost of the alignment; negative for local alignments with more match bonus than penalties.
+  int64_t score;
+  /// The alignment in CIGAR format with `=`, `X`, `I`, `D`, and `S` for query ends left out
+  /// of ends-free or local alignments, NUL-terminated.
+  char *cigar;
+  /// The alignment column by column.
+  PoastaEdit *edits;
+  uintptr_t num_edits;
+};
+
+/// Struct to hold the alignments of a batch of queries, in the order of the queries.
+struct PoastaQueryAlignments {
+  PoastaQueryAlignment *alignments;
+  uintptr_t num_alignments;
+};
+
+/// Configuration of poasta_rca_consensus.
+struct PoastaRcaConfig {
+  int32_t mismatch_score;
+  int32_t gap_extend;
+  int32_t gap_open;
+  /// Maximum number of edits when locating repeat boundaries.
+  uint8_t max_mismatch;
+};
+
+/// How `poasta_realign_updated` aligns the new version of a sequence.
+struct PoastaRealignConfig {
+  /// Number of query positions the new alignment may deviate from the old one, on top of the
+  /// difference in length between the versions. 0 means 32.
+  uintptr_t band;
+  /// Whether to deactivate the old version (see poasta_sequence_set_active), so only the new
+  /// version takes part in consensus and MSA computation.
+  bool replace;
+};
+
+/// Struct to hold the reference coordinates of the consensus.
+struct PoastaRefMap {
+  /// 0-based backbone position of each consensus base, -1 for bases inserted relative to the
+  /// backbone.
+  int64_t *positions;
+  uintptr_t len;
+};
+
+/// Options of poasta_write_report_html.
+struct PoastaReportOptions {
+  /// Report title (NUL-terminated); NULL for a default title.
+  const char *title;
+  /// Minimum fraction of sequences with a non-consensus allele for a column to be listed in the
+  /// variant table.
+  double min_variant_frequency;
+  /// Whether to include the colored MSA (which can be large for big graphs).
+  bool show_msa;
+};
+
+/// Receives the resources of a finished operation: its wall-clock time in nanoseconds, the most
+/// memory it had allocated at once in bytes, and the alignment cells it computed (like the
+/// `cells_computed` metric).
+using PoastaResourceCallback = void(*)(PoastaOperation operation,
+                                       uint64_t wall_ns,
+                                       uint64_t peak_bytes,
+                                       uint64_t cells,
+                                       void *user_data);
+
+/// A full substitution matrix, e.g., 4x4 for DNA or a 24x24 protein table.
+struct PoastaSubstitutionMatrix {
+  /// The symbols of the rows and columns, e.g., "ACGT". Letters stand for both cases.
+  const char *symbols;
+  uintptr_t num_symbols;
+  /// `num_symbols * num_symbols` costs, row by row: `costs[i * num_symbols + j]` is the cost of
+  /// aligning `symbols[j]` in a sequence to `symbols[i]` in the graph.
+  const int32_t *costs;
+};
+
+/// Struct to hold a serialized graph.
+struct PoastaBytes {
+  uint8_t *data;
+  uintptr_t len;
+};
+
+/// Struct to hold a list of graphs created by the library.
+struct PoastaGraphList {
+  PoastaGraph **graphs;
+  uintptr_t num_graphs;
+};
+
+/// Struct to hold summary statistics of a graph.
+struct PoastaGraphStats {
+  uintptr_t num_sequences;
+  /// Number of nodes, excluding the special start and end node.
+  uintptr_t num_nodes;
+  /// Sequences added without realignment thanks to the alignment cache, and sequences that had
+  /// to be aligned while the cache was enabled.
+  uintptr_t cache_hits;
+  uintptr_t cache_misses;
+};
+
+/// Struct to hold the expected cost of aligning a sequence to a graph.
+struct PoastaCostEstimate {
+  /// Cells of the full alignment matrix, (nodes + 1) x (sequence length + 1).
+  uint64_t matrix_cells;
+  /// Whether the graph's configuration (codon-aware or homopolymer-aware gaps) makes sequences
+  /// align with the full dynamic programming aligner rather than poasta's aligner.
+  bool exhaustive;
+  /// Alignment states the aligner is expected to compute, as counted by the `cells_computed`
+  /// metric.
+  uint64_t expected_states;
+  /// Expected memory of the aligner in bytes.
+  uint64_t memory_bytes;
+  /// Expected alignment time in milliseconds on a single modern x86-64 core.
+  double time_ms;
+};
+
+/// Struct to hold the weight a node gets from sequences of each strand.
+struct PoastaStrandWeights {
+  double forward;
+  double reverse;
+  /// Weight of sequences without strand information.
+  double unknown;
+};
+
+/// Receives a finished span: its name (a static string), level and duration in nanoseconds.
+using PoastaTraceCallback = void(*)(const char *name,
+                                    PoastaTraceLevel level,
+                                    uint64_t duration_ns,
+                                    void *user_data);
+
+/// Struct to hold the UMI family sizes of a graph.
+struct PoastaUmiCounts {
+  char **umis;
+  uintptr_t *counts;
+  uintptr_t num_umis;
+};
+
+/// Struct to hold the result of validating a graph.
+struct PoastaValidation {
+  /// Description of each violated invariant; the graph is consistent if there are none.
+  char **problems;
+  uintptr_t num_problems;
 };
 
 extern "C" {
@@ -21,13 +773,42 @@ PoastaGraph *poasta_create_graph();
 /// Frees the POAGraph.
 void poasta_free_graph(PoastaGraph *graph);
 
+/// Returns an independent copy of the graph, with the same configuration. The copy shares the
+/// graph storage until either graph is changed, so cloning is cheap even for large graphs, and
+/// copies can be given to other threads. Must be freed with poasta_free_graph.
+PoastaGraph *poasta_graph_clone(const PoastaGraph *graph);
+
 /// Adds a sequence to the graph (Global alignment).
 int poasta_add_sequence(PoastaGraph *graph,
                         const char *seq,
                         uintptr_t len,
-                        uint8_t mismatch_score,
-                        uint8_t gap_extend,
-                        uint8_t gap_open);
+                        int32_t mismatch_score,
+                        int32_t gap_extend,
+                        int32_t gap_open);
+
+/// Like poasta_add_sequence, but also writes the cost of the alignment the sequence was added
+/// along into `score` (if not NULL), e.g., to flag reads that fit the graph poorly. The score is
+/// 0 for the first sequence of the graph and is only written if the status is 0, so not for
+/// approximate alignments after a timeout.
+int poasta_add_sequence_scored(PoastaGraph *graph,
+                               const char *seq,
+                               uintptr_t len,
+                               int32_t mismatch_score,
+                               int32_t gap_extend,
+                               int32_t gap_open,
+                               int64_t *score);
+
+/// Adds a sequence called `name` (NUL-terminated) to the graph (Global alignment), e.g., its read
+/// ID. The name is used in the MSA, GFA and other output instead of a made-up `seq_N`.
+/// Returns -1 if the name is empty or contains whitespace, and the status codes of
+/// poasta_add_sequence otherwise.
+int poasta_add_sequence_named(PoastaGraph *graph,
+                              const char *name,
+                              const char *seq,
+                              uintptr_t len,
+                              int32_t mismatch_score,
+                              int32_t gap_extend,
+                              int32_t gap_open);
 
 /// Adds a sequence to the graph with a specified weight (Global alignment).
 /// The weight applies to the entire sequence, meaning all bases will have the same weight.
@@ -37,9 +818,19 @@ int poasta_add_sequence_with_weight(PoastaGraph *graph,
                                     const char *seq,
                                     uintptr_t len,
                                     uint32_t weight,
-                                    uint8_t mismatch_score,
-                                    uint8_t gap_extend,
-                                    uint8_t gap_open);
+                                    int32_t mismatch_score,
+                                    int32_t gap_extend,
+                                    int32_t gap_open);
+
+/// Adds a sequence to the graph with a weight per base (Global alignment), e.g., to down-weight
+/// low quality bases individually. `weights` must hold `len` weights.
+int poasta_add_sequence_with_weights(PoastaGraph *graph,
+                                     const char *seq,
+                                     uintptr_t len,
+                                     const uint32_t *weights,
+                                     int32_t mismatch_score,
+                                     int32_t gap_extend,
+                                     int32_t gap_open);
 
 /// Adds a sequence to the graph using two-piece affine gap model (Global alignment).
 /// This uses two different gap penalty pairs, choosing the cheaper option for each gap.
@@ -47,11 +838,11 @@ int poasta_add_sequence_with_weight(PoastaGraph *graph,
 int poasta_add_sequence_2piece(PoastaGraph *graph,
                                const char *seq,
                                uintptr_t len,
-                               uint8_t mismatch_score,
-                               uint8_t gap_extend1,
-                               uint8_t gap_open1,
-                               uint8_t gap_extend2,
-                               uint8_t gap_open2);
+                               int32_t mismatch_score,
+                               int32_t gap_extend1,
+                               int32_t gap_open1,
+                               int32_t gap_extend2,
+                               int32_t gap_open2);
 
 /// Adds a sequence to the graph with a specified weight using two-piece affine gap model (Global alignment).
 /// The weight applies to the entire sequence, meaning all bases will have the same weight.
@@ -62,21 +853,1146 @@ int poasta_add_sequence_2piece_with_weight(PoastaGraph *graph,
                                            const char *seq,
                                            uintptr_t len,
                                            uint32_t weight,
-                                           uint8_t mismatch_score,
-                                           uint8_t gap_extend1,
-                                           uint8_t gap_open1,
-                                           uint8_t gap_extend2,
-                                           uint8_t gap_open2);
+                                           int32_t mismatch_score,
+                                           int32_t gap_extend1,
+                                           int32_t gap_open1,
+                                           int32_t gap_extend2,
+                                           int32_t gap_open2);
 
-/// Generates the MSA from the graph.
+/// Generates the MSA from the graph, in the columns of poasta's FASTA writer. Like that writer,
+/// it leaves out one of the leading gaps of rows that don't start in the first column, so those
+/// rows are one column short; poasta_get_msa_padded gives rows of the same width.
 /// Returns a PoastaMsa struct. Caller must free it with poasta_free_msa.
-PoastaMsa poasta_get_msa(PoastaGraph *graph);
+PoastaMsa poasta_get_msa(const PoastaGraph *graph);
 
-/// Returns the graph in GFA format as a C string.
+/// Like poasta_get_msa, but computes the MSA only if its rows and names, including their NUL
+/// terminators, take at most `max_bytes` (0 means no cap), so a pathological graph can't make the
+/// library allocate gigabytes. The MSA is stored in `*msa_out`, which is empty on failure; the
+/// caller must free it with poasta_free_msa. Returns 0, -1 if the graph or `msa_out` is NULL, and
+/// -11 if the MSA would be too large.
+int poasta_get_msa_capped(const PoastaGraph *graph, uintptr_t max_bytes, PoastaMsa *msa_out);
+
+/// Returns the graph in GFA format as a C string, without provisional nodes and edges (see
+/// poasta_set_min_support). Like poasta's command line tool, walks end up to two bases early;
+/// poasta_graph_from_gfa corrects that when reading them back.
 /// The caller must free the string using free().
-char *poasta_get_gfa(PoastaGraph *graph);
+char *poasta_get_gfa(const PoastaGraph *graph);
+
+/// Like poasta_get_gfa, but stops writing the GFA once it exceeds `max_bytes` (0 means no cap),
+/// so a pathological graph can't make the library allocate gigabytes. The GFA, or as many
+/// complete lines of it as fit in `max_bytes`, is stored in `*out` (NULL on other failures), which
+/// the caller must free using free(). Returns 0, -1 if the graph or `out` is NULL, and -11 if the
+/// GFA was truncated.
+int poasta_get_gfa_capped(const PoastaGraph *graph, uintptr_t max_bytes, char **out);
 
 /// Frees the PoastaMsa.
 void poasta_free_msa(PoastaMsa msa);
 
+/// Creates an aligner scoring with simple affine gap penalties, like poasta_add_sequence.
+/// Returns NULL if a cost is negative or above 255. Free it with poasta_aligner_free.
+PoastaAligner *poasta_aligner_create(int32_t mismatch_score, int32_t gap_extend, int32_t gap_open);
+
+/// Creates an aligner scoring with the given configuration, like poasta_add_sequence_scoring.
+/// Later changes to the configuration don't affect the aligner. Returns NULL if the configuration
+/// is NULL or invalid (see poasta_scoring_validate). Free it with poasta_aligner_free.
+PoastaAligner *poasta_aligner_create_scoring(const PoastaScoring *config);
+
+/// Frees an aligner.
+void poasta_aligner_free(PoastaAligner *aligner);
+
+/// Sets the mode the aligner aligns sequences in (Global by default, see poasta_add_sequence_mode).
+/// Returns 0 on success and -1 if the aligner is NULL.
+int poasta_aligner_set_mode(PoastaAligner *aligner, PoastaAlignmentMode mode);
+
+/// Adds a sequence to the graph in the aligner's mode with the aligner's scoring, reusing the
+/// aligner's working memory from earlier calls. The result is the same as adding the sequence with
+/// poasta_add_sequence or poasta_add_sequence_scoring, and so are the returned codes.
+int poasta_add_sequence_with_aligner(PoastaGraph *graph,
+                                     PoastaAligner *aligner,
+                                     const char *seq,
+                                     uintptr_t len);
+
+/// Adds the `n` sequences `seqs[i]` of `lens[i]` bases to the graph in order, aligned as configured
+/// in `params` like poasta_add_sequence_mode, with one aligner's working memory shared between
+/// them. A failing sequence doesn't stop the others from being added. The status of each sequence,
+/// as poasta_add_sequence_mode would return it (-1 for NULL sequences), is written to `statuses`
+/// if not NULL, which must have room for `n` entries. Returns the number of sequences added
+/// (including approximately aligned ones), or -1 if the graph, `seqs` or `lens` is NULL or a cost
+/// is negative or above 255.
+int poasta_add_sequences(PoastaGraph *graph,
+                         const char *const *seqs,
+                         const uintptr_t *lens,
+                         uintptr_t n,
+                         PoastaAlignParams params,
+                         int *statuses);
+
+/// Sets the alphabet of the graph's sequences (`PoastaAlphabet::Auto`, detection from the first
+/// sequence, by default). Sequences with other symbols are rejected, and IUPAC ambiguity codes in
+/// the consensus and primer trimming, which reverse complements sequences, need a nucleotide
+/// alphabet. Returns -1 if a sequence of the graph has a symbol outside the alphabet, or primers
+/// are set and the alphabet isn't DNA, RNA or Auto.
+int poasta_graph_set_alphabet(PoastaGraph *graph, PoastaAlphabet alphabet);
+
+/// Returns the alphabet of the graph: the one set with poasta_graph_set_alphabet, or the one
+/// detected from the first sequence, or `PoastaAlphabet::Auto` if there is none yet.
+PoastaAlphabet poasta_graph_get_alphabet(const PoastaGraph *graph);
+
+/// Returns default alignment parameters (Global alignment) for sequences of `alphabet`: poasta's
+/// defaults (mismatch 4, gap extend 2, gap open 6) for nucleotides and raw bytes, and BLAST's
+/// protein gap costs (mismatch 6, gap extend 1, gap open 11) for proteins.
+PoastaAlignParams poasta_alphabet_default_params(PoastaAlphabet alphabet);
+
+/// Simulates `n` reads of `reference` with random substitutions, insertions and deletions
+/// according to `error_profile`, e.g., to benchmark parameter choices. The same seed always yields
+/// the same reads. Caller must free the result with poasta_free_reads.
+PoastaReads poasta_bench_generate_reads(const char *reference,
+                                        uintptr_t reference_len,
+                                        uintptr_t n,
+                                        PoastaErrorProfile error_profile,
+                                        uint64_t seed);
+
+/// Frees a PoastaReads.
+void poasta_free_reads(PoastaReads reads);
+
+/// Creates a scoring configuration for bisulfite-converted reads: a T in a read aligned to a C in
+/// the graph (or an A aligned to a G) is not penalized, while the opposite substitutions and all
+/// other mismatches cost `mismatch_score`. Since the costs are asymmetric, add the unconverted
+/// reference (or a read of it) first. Free the configuration with poasta_scoring_free.
+/// Returns NULL with last error -1 if a cost is negative or above 255.
+PoastaScoring *poasta_scoring_create_bisulfite(int32_t mismatch_score,
+                                               int32_t gap_extend,
+                                               int32_t gap_open);
+
+/// Makes the consensus report the unconverted base (disabled by default): a C (or G) is reported
+/// wherever any active sequence shows it in the column of the consensus base, even if most
+/// sequences were converted to T (or A).
+int poasta_set_bisulfite_consensus(PoastaGraph *graph, bool enabled);
+
+/// Estimates which fraction of the k-mers of `seq` occur in the sequences of the graph, to cheaply
+/// decide whether a read plausibly belongs to it (e.g., to route reads in clustering pipelines).
+/// The k-mers are kept in a Bloom filter next to the graph, which is built on the first query for
+/// a given `k` and then updated as sequences are added, so the estimate can be slightly too high
+/// but never too low. Takes a non-const graph because of the filter. Returns -1 if `k` is 0 or
+/// longer than the sequence.
+double poasta_graph_maybe_contains(PoastaGraph *graph, const char *seq, uintptr_t len, uintptr_t k);
+
+/// Enables a cache of up to `capacity` sequences (0 disables it, the default). When a sequence
+/// identical to a cached one is added, it is committed along the cached sequence's path directly
+/// instead of being realigned, which is much faster for duplicate reads. Hits and misses are
+/// counted in poasta_graph_stats.
+int poasta_set_alignment_cache(PoastaGraph *graph, uintptr_t capacity);
+
+/// Flags the active sequences whose path switches between two otherwise exclusive haplotype
+/// bundles, a common PCR artifact. Sequences are compared at the variable MSA columns (see
+/// poasta_phase_blocks). A sequence is a chimera if, at some switch, at least two other sequences
+/// agree with it on all columns before the switch but not after it, at least two agree with it on
+/// all columns after but not before it, and fewer sequences than in either bundle agree with it
+/// everywhere. The switch with the best supported bundles is reported.
+/// Caller must free the result with poasta_free_chimeras.
+PoastaChimeras poasta_detect_chimeras(const PoastaGraph *graph);
+
+/// Frees PoastaChimeras.
+void poasta_free_chimeras(PoastaChimeras chimeras);
+
+/// Enables or disables circular mode for a graph (disabled by default).
+/// In circular mode, each new sequence is rotated before alignment such that it starts at the
+/// origin of the current consensus, so reads of plasmids or mitochondria no longer get split
+/// alignments at the arbitrary linearization point.
+int poasta_graph_set_circular(PoastaGraph *graph, bool circular);
+
+/// Returns the consensus of a circular graph, rotated to its lexicographically smallest
+/// rotation so that the output has a canonical start.
+/// The caller must free the string using free().
+char *poasta_get_consensus_circular(const PoastaGraph *graph);
+
+/// Returns the consensus of a circular graph, rotated such that it starts at the best match of
+/// `motif` (e.g., the origin of replication or a primer), so repeated runs are directly comparable.
+/// Returns NULL if the motif is empty or longer than the consensus.
+/// The caller must free the string using free().
+char *poasta_consensus_rotate_to(const PoastaGraph *graph, const char *motif, uintptr_t motif_len);
+
+/// Compares two graphs, e.g., technical replicates, or a sample before and after polishing. The
+/// consensus sequences are aligned to each other, and the variable MSA columns of each graph (see
+/// poasta_phase_blocks) are mapped through this alignment to the other graph's consensus. Sites
+/// at the same consensus position in both graphs are paired up. Returns all zeros if either graph
+/// is NULL. Caller must free the result with poasta_free_graph_comparison.
+PoastaGraphComparison poasta_compare_graphs(const PoastaGraph *a, const PoastaGraph *b);
+
+/// Frees a PoastaGraphComparison, including the allele strings of its sites.
+void poasta_free_graph_comparison(PoastaGraphComparison comparison);
+
+/// Enables codon-aware gap penalties for sequences added to the graph from now on.
+/// Gaps whose length is not a multiple of three get `frameshift_penalty` added, and so do gaps
+/// that do not start at a codon boundary of the reading frame (`frame` is the offset of the first
+/// codon in the query: 0, 1 or 2). A penalty of 0 disables codon-aware mode.
+/// Codon-aware alignment uses a full dynamic programming aligner, which is slower than the
+/// default aligner. Returns -1 if the graph is NULL, the frame is above 2 or the penalty is
+/// negative or above 255.
+int poasta_graph_set_codon_aware(PoastaGraph *graph, uint8_t frame, int32_t frameshift_penalty);
+
+/// Returns the consensus translated to protein using the standard genetic code, starting at
+/// reading frame `frame` (0, 1 or 2). Stop codons are written as `*`, and trailing bases that don't
+/// form a complete codon are ignored. Returns NULL if the frame is invalid.
+/// The caller must free the string using free().
+char *poasta_get_consensus_protein(const PoastaGraph *graph,
+                                   uint8_t frame,
+                                   PoastaAmbiguity ambiguity);
+
+/// Joins two graphs end-to-end into a new graph: every sink of `a` is linked to every source of `b`.
+/// The sequences of `a` are followed by the sequences of `b`, and their paths are kept as is.
+/// Sequences added to the new graph align across the junction. The input graphs are left
+/// untouched. Returns NULL on error. The caller must free the graph with poasta_free_graph.
+PoastaGraph *poasta_graph_concat(const PoastaGraph *a,
+                                 const PoastaGraph *b);
+
+/// Detects tandem copies of an amplicon within a single read (e.g., nanopore rolling-circle
+/// reads), and splits the read in front of each copy. Copies are found by locating an anchor with
+/// at most `max_mismatch` edits: `motif` if given, otherwise the start of the consensus of `graph`
+/// if given, and otherwise the start of the read itself (self-alignment). `graph` and `motif` may
+/// be NULL. Partial copies at the read ends become segments of their own.
+/// Each segment can be added to a graph individually. Caller must free the result with
+/// poasta_free_segments.
+PoastaSegments poasta_split_concatemers(const char *seq,
+                                        uintptr_t len,
+                                        const PoastaGraph *graph,
+                                        const char *motif,
+                                        uintptr_t motif_len,
+                                        uint8_t max_mismatch);
+
+/// Frees a PoastaSegments.
+void poasta_free_segments(PoastaSegments segments);
+
+/// Returns the consensus of the graph, the heaviest path through the weighted graph (heaviest
+/// bundle traversal), with the number of active sequences passing through each consensus base as
+/// its support. The tie-break, minimum support and bisulfite settings of the graph apply.
+/// Returns an empty consensus if the graph is NULL or has no sequences.
+/// Caller must free the result with poasta_free_consensus.
+PoastaConsensus poasta_get_consensus(const PoastaGraph *graph);
+
+/// Frees a PoastaConsensus.
+void poasta_free_consensus(PoastaConsensus consensus);
+
+/// Sets the policy used to resolve ties between equally supported alternatives when computing the
+/// consensus (`PoastaTieBreak::FirstSeen` by default).
+int poasta_set_consensus_tie_break(PoastaGraph *graph, PoastaTieBreak policy);
+
+/// Writes the coverage of each consensus position (the number of active sequences passing through
+/// its base, as in poasta_get_consensus) to the file at `path` (NUL-terminated) in `format`, on a
+/// chromosome named `consensus`, so it can be shown against the consensus in genome browsers.
+/// Returns 0 on success, -1 on invalid arguments and -5 if the file couldn't be written.
+int poasta_export_coverage_track(const PoastaGraph *graph,
+                                 const char *path,
+                                 PoastaTrackFormat format);
+
+/// Returns the graph in Graphviz DOT format to visualize small graphs, one node per base, with
+/// provisional nodes and edges left out like in poasta_get_gfa. With `include_weights`, node labels
+/// also show the node's weight, the summed (decayed) base weights of the sequences through it.
+/// Edges are drawn with a width proportional to the number of sequences passing through them.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+char *poasta_get_dot(const PoastaGraph *graph, bool include_weights, PoastaRankDir rankdir);
+
+/// Saves the changes to the graph since `since_snapshot` (see poasta_graph_snapshot) to the file
+/// at `path` (NUL-terminated): the sequences added since, the current state of the sequences
+/// added before and the current configuration. Apply it to the graph as it was at the snapshot
+/// with poasta_graph_apply_delta, e.g., the graph loaded from the last full checkpoint and the
+/// deltas before this one. A delta since snapshot 0 can be applied to an empty graph.
+/// Returns 0 on success, -1 on invalid arguments or a snapshot from the future and -5 if the file
+/// couldn't be written.
+int poasta_graph_save_delta(const PoastaGraph *graph, uintptr_t since_snapshot, const char *path);
+
+/// Applies the delta saved with poasta_graph_save_delta in the file at `path` (NUL-terminated),
+/// bringing the graph to the state it was saved in. The graph must be in the state of the
+/// snapshot the delta was saved since.
+/// Returns 0 on success, -1 on invalid arguments or if the graph doesn't match the snapshot, -3 if
+/// adding a sequence failed, -5 if the file couldn't be read, -8 if it is corrupted and -9 if it
+/// was written by a newer, incompatible version of the library. The graph is unchanged unless 0
+/// or -3 is returned.
+int poasta_graph_apply_delta(PoastaGraph *graph, const char *path);
+
+/// Returns the error of the last call on this thread that failed, or `PoastaError::Ok` if none
+/// did. Successful calls don't reset it.
+PoastaError poasta_last_error();
+
+/// Returns a description of the last error on this thread, with details such as which argument
+/// was invalid. The string is owned by the library and valid until the next error on this thread.
+const char *poasta_last_error_message();
+
+/// Returns a static description of a status code returned by the library (a `PoastaError` value,
+/// or 1 for sequences added along an approximate alignment).
+/// Part of the fast query tier: never allocates and never panics.
+const char *poasta_strerror(int status);
+
+/// Compares the consensus of the graph to a known truth sequence, e.g., for validating
+/// parameter choices on control samples. Returns all zeros if the graph or the truth is NULL or
+/// the truth is empty.
+PoastaEvaluation poasta_eval_consensus(const PoastaGraph *graph,
+                                       const char *truth_seq,
+                                       uintptr_t truth_len);
+
+/// Returns the alignment of every sequence of the graph (in insertion order, including inactive
+/// ones) in `format`, taken from the path it was added along.
+/// GAF lines refer to the segments of poasta_get_gfa_layers without provisional elements, and
+/// sequences without a walk there (because they pass through provisional nodes) are unmapped. SAM
+/// records align each sequence to the consensus, with `=` and `X` CIGAR operations, the sequence
+/// ends outside the consensus soft clipped, and sequences sharing no column with the consensus
+/// unmapped.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+char *poasta_export_all_alignments(const PoastaGraph *graph, PoastaAlignmentFormat format);
+
+/// Freezes a copy of the graph for alignment with poasta_frozen_align_score or poasta_frozen_align. This
+/// allocates, but aligning to the frozen graph doesn't. The frozen graph can be shared between
+/// threads. Returns NULL if the graph is NULL. Free it with poasta_frozen_graph_free.
+PoastaFrozenGraph *poasta_graph_freeze(const PoastaGraph *graph);
+
+/// Frees a frozen graph.
+void poasta_frozen_graph_free(PoastaFrozenGraph *frozen);
+
+/// Creates working memory for aligning queries of up to `max_query_len` bases to the frozen graph,
+/// allocating all of it at once, so aligning with it doesn't. It takes about
+/// `12 * (num_nodes + 1) * (max_query_len + 1)` bytes. Returns NULL if the frozen graph is NULL.
+/// Free it with poasta_scratch_free.
+PoastaScratch *poasta_scratch_create(const PoastaFrozenGraph *frozen, uintptr_t max_query_len);
+
+/// Grows the scratch, if needed, so it can also be used with another frozen graph. This allocates,
+/// so call it before entering the latency-critical part. Returns -1 if either is NULL.
+int poasta_scratch_reserve(PoastaScratch *scratch, const PoastaFrozenGraph *frozen);
+
+/// Frees scratch memory.
+void poasta_scratch_free(PoastaScratch *scratch);
+
+/// Computes the cost of the best global alignment of `seq` to the frozen graph with simple affine
+/// gap scoring (the score poasta_add_sequence would align with) into `score`, without the
+/// alignment itself. Part of the fast query tier: never allocates and never panics, in time
+/// proportional to the graph size times the query length. Doesn't set the last error.
+/// Returns -1 if an argument is NULL, a cost is negative or above 255, the query is longer than the
+/// scratch's `max_query_len`, or the scratch isn't reserved for the graph.
+int poasta_frozen_align_score(const PoastaFrozenGraph *frozen,
+                              PoastaScratch *scratch,
+                              const char *seq,
+                              uintptr_t len,
+                              int32_t mismatch_score,
+                              int32_t gap_extend,
+                              int32_t gap_open,
+                              int64_t *score);
+
+/// Like poasta_frozen_align_score, but also writes the alignment column by column into the first
+/// `*num_edits` entries of `edits`, which has room for `capacity` columns. An alignment has at most
+/// as many columns as the query has bases plus the graph had nodes when frozen (see
+/// poasta_graph_stats). Never allocates and never panics, and doesn't set the last error.
+/// Returns -1 if an argument is NULL, a cost is negative or above 255, the query is longer than the
+/// scratch's `max_query_len`, the scratch isn't reserved for the graph, or the alignment doesn't
+/// fit in `capacity` columns.
+int poasta_frozen_align(const PoastaFrozenGraph *frozen,
+                        PoastaScratch *scratch,
+                        const char *seq,
+                        uintptr_t len,
+                        int32_t mismatch_score,
+                        int32_t gap_extend,
+                        int32_t gap_open,
+                        int64_t *score,
+                        PoastaEdit *edits,
+                        uintptr_t capacity,
+                        uintptr_t *num_edits);
+
+/// Whether this build of the library supports `feature`, a `PoastaFeature` value, so hosts
+/// linking against it dynamically can degrade gracefully instead of checking for symbols. Values
+/// this build doesn't know, e.g., features of a newer header, aren't supported.
+/// Part of the fast query tier: never allocates and never panics.
+bool poasta_supports(uint32_t feature);
+
+/// Rejects sequences whose length is far from the current consensus length, e.g., truncated or
+/// concatemeric reads. A sequence added from now on is only accepted if its length is between
+/// `min_ratio` and `max_ratio` times the consensus length (e.g., 0.8 and 1.2 for ±20%); otherwise,
+/// adding it returns -4 and leaves the graph untouched. A ratio of 0 disables that bound.
+/// The first sequence of a graph (or any sequence while there is no consensus) is always accepted.
+int poasta_set_length_filter(PoastaGraph *graph, double min_ratio, double max_ratio);
+
+#if defined(POASTA_TEST_SUPPORT)
+/// Returns the reads of `fixture`, in the order poasta_fixture_graph adds them.
+/// Caller must free the result with poasta_free_reads.
+PoastaReads poasta_fixture_reads(PoastaFixture fixture);
+#endif
+
+#if defined(POASTA_TEST_SUPPORT)
+/// Returns the graph of `fixture`: its reads added in order like poasta_add_sequence with mismatch
+/// score 4, gap extend 2 and gap open 6, named `read1`, `read2` and so on.
+/// Returns NULL if a read can't be added. Must be freed with poasta_free_graph.
+PoastaGraph *poasta_fixture_graph(PoastaFixture fixture);
+#endif
+
+#if defined(POASTA_TEST_SUPPORT)
+/// Returns the expected `output` of the graph of `fixture` (see poasta_fixture_graph), as this
+/// version of the library computes it. The string is static and must not be freed.
+const char *poasta_fixture_expected(PoastaFixture fixture, PoastaFixtureOutput output);
+#endif
+
+/// Returns a snapshot ID for the current state of the graph, to be passed to poasta_get_gfa_delta
+/// or poasta_graph_save_delta later: the number of sequences added so far, which unlike the number
+/// of sequences doesn't go down when sequences are removed. Returns 0 if the graph is NULL.
+uintptr_t poasta_graph_snapshot(const PoastaGraph *graph);
+
+/// Returns the segments and links added or changed since `since_snapshot` (see
+/// poasta_graph_snapshot) as GFA, so live viewers can update incrementally. Unlike
+/// poasta_get_gfa, each segment is a single node named by its node index, so segments never
+/// change identity; a changed segment or link is emitted again with its new `RC` tag (the number
+/// of active sequences through it, i.e., not counting masked ones). A delta since snapshot 0 is
+/// the full graph.
+/// Returns NULL if the graph is NULL or the snapshot is from the future.
+/// The caller must free the string using free().
+char *poasta_get_gfa_delta(const PoastaGraph *graph, uintptr_t since_snapshot);
+
+/// Returns the graph as GFA 1.0 with a P line per sequence (in insertion order, including inactive
+/// ones), for tools that read paths rather than walks. Like in poasta_get_gfa_delta, each segment
+/// is a single node named by its node index, which stays the same as sequences are added, so each
+/// path spells its sequence exactly. Provisional nodes and edges are left out like in
+/// poasta_get_gfa, and so are the paths through them.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+char *poasta_get_gfa_with_paths(const PoastaGraph *graph);
+
+/// Like poasta_get_gfa_with_paths, in the given GFA version. With GFA 1.1, each sequence gets a W
+/// line instead of a P line, with its sample, haplotype and sequence ID taken from names following
+/// the PanSN convention (`sample#haplotype#contig`, with a numeric haplotype), like in
+/// poasta_get_gfa.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+char *poasta_get_gfa_version(const PoastaGraph *graph, PoastaGfaVersion version);
+
+/// Returns the nodes the sequence at `seq_index` (in insertion order, including inactive
+/// sequences) passes through, by node index, e.g., for phasing or coverage analysis without
+/// parsing the GFA. These are the node IDs of PoastaEdit and the steps of the sequence's P line in
+/// poasta_get_gfa_with_paths, which leaves out paths through provisional nodes or edges (see
+/// poasta_set_min_support); this returns them anyway. The path is empty if the graph is NULL or the
+/// index is out of range (with -1 as the last error). Caller must free the result with
+/// poasta_free_sequence_path.
+PoastaSequencePath poasta_get_sequence_path(const PoastaGraph *graph, uintptr_t seq_index);
+
+/// Frees a PoastaSequencePath.
+void poasta_free_sequence_path(PoastaSequencePath path);
+
+/// Enables homopolymer-aware gap penalties for sequences added to the graph from now on, matching
+/// the tendency of nanopore sequencing to miscount homopolymer bases. Deleting a base of a graph
+/// homopolymer of at least `min_length` bases, or inserting another copy of its base, costs
+/// `discount` less per gap base for every base the homopolymer has beyond `min_length - 1`, down
+/// to 0 (e.g., with `min_length` 3 and `discount` 1, gap extension within a homopolymer of 5 bases
+/// is 3 cheaper). Gap opening costs are unchanged. A discount or minimum length of 0 disables
+/// homopolymer-aware mode.
+/// Homopolymer-aware alignment uses a full dynamic programming aligner, which is slower than the
+/// default aligner. Returns -1 if the graph is NULL or the discount is negative or above 255.
+int poasta_graph_set_homopolymer_gaps(PoastaGraph *graph, uint8_t min_length, int32_t discount);
+
+/// Builds a graph from GFA text (`len` bytes), e.g., the output of poasta_get_gfa of an earlier
+/// run, to continue adding sequences to it. Each P or W line
+/// becomes a sequence, added in the order of the lines along the segments it walks, so every base
+/// a path passes through becomes a node and each sequence gets weight 1 per base. Links that no
+/// path follows are kept as extra edges new sequences can align across. Segments no path passes
+/// through are left out, and H and other lines are ignored. GFA doesn't record which nodes are
+/// aligned to each other, so mismatching bases of different paths aren't aligned in the new graph.
+/// poasta_get_gfa and poasta's command line tool write walk ranges with offsets up to two bases
+/// too low; GFA in which all walks end early like that is read accordingly. Their offsets don't
+/// tell the first two bases of a segment apart, so a sequence starting at the second base of a
+/// segment is read with the base before it, and one ending at the first base with the base after.
+/// Returns NULL with -1 as the last error if the GFA is NULL, malformed, uses reverse strands or
+/// overlaps, or describes a cyclic graph. The caller must free the graph with poasta_free_graph.
+PoastaGraph *poasta_graph_from_gfa(const char *gfa, uintptr_t len);
+
+/// Builds a graph from a multiple sequence alignment of `n` rows of `width` symbols each, e.g.,
+/// computed by another tool, to continue adding sequences to it. `-` and `.` are gaps. Each row
+/// becomes a sequence of weight 1 per base, called `seq_N` like sequences added without a name.
+/// Bases in the same column share a node if they have the same symbol, and are aligned to each
+/// other otherwise. Neighbouring columns without bases in common (e.g., insertions of different
+/// rows) may be in a different order in the MSA of the new graph. Returns NULL with -1 as the last
+/// error if `rows` or a row is NULL, `n` is 0 or a row has no bases. The caller must free the
+/// graph with poasta_free_graph.
+PoastaGraph *poasta_graph_from_msa(const char *const *rows, uintptr_t n, uintptr_t width);
+
+/// Computes a layered drawing of the graph and returns it as JSON, so viewers can draw the graph
+/// without running their own DAG layout:
+/// `{"nodes": [{"id", "symbol", "x", "y"}, ...], "edges": [{"from", "to", "weight"}, ...]}`.
+/// `x` is the length of the longest path from the graph start to the node, `y` is 0 for
+/// the consensus path, and other nodes of the same layer are stacked below it.
+/// Node IDs are the internal node indices. Returns NULL if the graph is NULL.
+/// The caller must free the string using free().
+char *poasta_graph_layout(const PoastaGraph *graph);
+
+/// Returns the joint allele counts of MSA columns `col_a` and `col_b` over the active sequences,
+/// from the paths the sequences were added along, for linkage and phasing analysis of nearby
+/// variants. Only sequences spanning both columns are counted; a gap within a sequence counts as
+/// a deletion allele (`-`). The result has no alleles if a column is out of range.
+/// Caller must free the result with poasta_free_linkage.
+PoastaLinkage poasta_column_linkage(const PoastaGraph *graph, uintptr_t col_a, uintptr_t col_b);
+
+/// Returns the linkage (see poasta_column_linkage) of every pair of the `n` columns in `cols`, in
+/// the order (0, 1), (0, 2), ..., (1, 2), ... of their indices in `cols`. The alleles of all
+/// sequences are looked up once, which is much faster than one call per pair.
+/// Caller must free the result with poasta_free_linkage_list.
+PoastaLinkageList poasta_column_linkage_bulk(const PoastaGraph *graph,
+                                             const uintptr_t *cols,
+                                             uintptr_t n);
+
+/// Frees a PoastaLinkage.
+void poasta_free_linkage(PoastaLinkage linkage);
+
+/// Frees a PoastaLinkageList, including its pairs.
+void poasta_free_linkage_list(PoastaLinkageList list);
+
+/// Masks the given regions (an array of `n` intervals) of the graph, replacing any regions masked
+/// before: variants in them are left out of the HTML report and, if `mask_confidence` is set, the
+/// report doesn't show the consensus confidence of their bases either. With
+/// `PoastaCoordinates::Reference`, the intervals are backbone positions (e.g., from a BED file)
+/// and also mask nodes added later. With `PoastaCoordinates::Consensus`, they are positions in
+/// the current consensus. Pass `n = 0` to unmask everything.
+/// Returns -1 if reference coordinates are given for a graph without backbone.
+int poasta_set_masked_regions(PoastaGraph *graph,
+                              const PoastaInterval *regions,
+                              uintptr_t n,
+                              PoastaCoordinates coordinates,
+                              bool mask_confidence);
+
+/// Attaches a pointer of the host's (e.g., to its own record of the cluster) to the graph, which
+/// the library never dereferences. It isn't copied to clones or saved with the graph.
+/// Returns -1 if the graph is NULL.
+int poasta_graph_set_user_data(PoastaGraph *graph, void *user_data);
+
+/// Returns the pointer set with poasta_graph_set_user_data, or NULL if none was set.
+void *poasta_graph_get_user_data(const PoastaGraph *graph);
+
+/// Stores a copy of `len` bytes at `data` with the graph (e.g., cluster IDs or provenance JSON),
+/// replacing earlier metadata. Unlike the user data, the metadata is copied to clones and saved
+/// with the graph. A `len` of 0 clears it. Returns -1 if the graph is NULL, or `data` is NULL and
+/// `len` isn't 0.
+int poasta_graph_set_metadata(PoastaGraph *graph, const uint8_t *data, uintptr_t len);
+
+/// Returns the metadata set with poasta_graph_set_metadata and stores its length in `*len`, or
+/// NULL (and 0) if there is none. The bytes belong to the graph and stay valid until its metadata
+/// is changed or it is freed.
+const uint8_t *poasta_graph_get_metadata(const PoastaGraph *graph, uintptr_t *len);
+
+/// Returns the current value of all counters.
+/// Part of the fast query tier: never allocates and never panics.
+PoastaMetrics poasta_metrics_snapshot();
+
+/// Returns all counters in the Prometheus text exposition format.
+/// The caller must free the string using free().
+char *poasta_metrics_text();
+
+/// Adds a sequence to the graph like poasta_add_sequence, aligned in the given mode. With free
+/// ends, e.g., for amplicon reads with ragged ends, overhanging bases become new nodes instead of
+/// being forced into gaps, and missing ends of the graph are skipped. Freeing the same end of both
+/// the sequence and the graph lets the alignment skip that end entirely. Local alignment clips
+/// both ends of the sequence to its best matching part instead. Modes other than Global use a full
+/// dynamic programming aligner, which is slower than the default aligner.
+/// Returns the same codes as poasta_add_sequence.
+int poasta_add_sequence_mode(PoastaGraph *graph,
+                             const char *seq,
+                             uintptr_t len,
+                             int32_t mismatch_score,
+                             int32_t gap_extend,
+                             int32_t gap_open,
+                             PoastaAlignmentMode mode);
+
+/// Returns columns `col_start` (inclusive) to `col_end` (exclusive) of the MSA, e.g., for
+/// scrollable alignment viewers. The range is clamped to the MSA width. Only the requested columns
+/// are materialized, so this works on graphs whose full MSA wouldn't fit in memory.
+/// Returns a PoastaMsa struct. Caller must free it with poasta_free_msa.
+PoastaMsa poasta_get_msa_window(const PoastaGraph *graph, uintptr_t col_start, uintptr_t col_end);
+
+/// Returns the full-width MSA (the columns of poasta_get_msa_window) with the end gaps of every
+/// row filled with `options.pad`. `options.line_width` is ignored.
+/// Returns a PoastaMsa struct. Caller must free it with poasta_free_msa.
+PoastaMsa poasta_get_msa_padded(const PoastaGraph *graph, PoastaMsaOptions options);
+
+/// Returns the full-width MSA as FASTA text, with the end gaps of every row filled with
+/// `options.pad` and the rows wrapped after `options.line_width` columns, for parsers that expect
+/// fixed-width records.
+/// The caller must free the string using free().
+char *poasta_get_msa_fasta(const PoastaGraph *graph, PoastaMsaOptions options);
+
+/// Like poasta_get_msa_fasta, but stops writing the FASTA once it exceeds `max_bytes` (0 means no
+/// cap), computing only the rows written, so a pathological graph can't make the library allocate
+/// gigabytes. The FASTA, or as many complete lines of it as fit in `max_bytes`, is stored in
+/// `*out` (NULL on other failures), which the caller must free using free(). Returns 0, -1 if the
+/// graph or `out` is NULL, and -11 if the FASTA was truncated.
+int poasta_get_msa_fasta_capped(const PoastaGraph *graph,
+                                PoastaMsaOptions options,
+                                uintptr_t max_bytes,
+                                char **out);
+
+/// Returns the full-width MSA (the columns of poasta_get_msa_window) in CLUSTAL format, for tools
+/// such as Jalview or MEGA: blocks of 60 columns with a line per active sequence, its name padded
+/// to the width of the longest one, followed by a line marking the columns in which all sequences
+/// have the same base with `*`.
+/// The caller must free the string using free().
+char *poasta_get_msa_clustal(const PoastaGraph *graph);
+
+/// Returns the full-width MSA (the columns of poasta_get_msa_window) as a matrix with a row per
+/// active sequence, in insertion order, e.g., to hand it to numpy without parsing a string per
+/// row. The names of the rows are those of poasta_get_msa_window. The matrix is empty if the
+/// graph is NULL or has no active sequences. Caller must free it with poasta_free_msa_matrix.
+PoastaMsaMatrix poasta_get_msa_matrix(const PoastaGraph *graph);
+
+/// Frees a PoastaMsaMatrix.
+void poasta_free_msa_matrix(PoastaMsaMatrix matrix);
+
+/// Extracts up to `max_paths` mutually divergent consensus paths, for inputs with several
+/// haplotypes or a mixed amplicon. The heaviest path of the active sequences is taken first; the
+/// sequences agreeing with it at the variable MSA columns (see poasta_phase_blocks), up to a few
+/// sequencing errors, are set aside, and the heaviest path of the remaining sequences is taken
+/// next, and so on. A path is only kept if at least `min_support` sequences agree with it and it
+/// differs from the paths before it at some variable column. Each sequence is then counted for the
+/// kept path it differs least from. Caller must free the result with poasta_free_consensus_multi.
+PoastaConsensusPaths poasta_get_consensus_multi(const PoastaGraph *graph,
+                                                uintptr_t max_paths,
+                                                uintptr_t min_support);
+
+/// Frees PoastaConsensusPaths, including their sequences and reads.
+void poasta_free_consensus_multi(PoastaConsensusPaths paths);
+
+/// Merges exactly two sequences (e.g., the template and complement strand of a duplex read, or
+/// the two reads of a pair) by aligning them to each other, without building a graph.
+/// Where the two sequences disagree, the consensus follows the first sequence: mismatches take
+/// its base, bases only present in the first sequence are kept, and bases only present in the
+/// second sequence are dropped. Each disagreement is reported. The consensus is empty if a
+/// sequence is NULL or a cost is negative or above 255.
+/// Caller must free the result with poasta_free_pair_consensus.
+PoastaPairConsensus poasta_pair_consensus(const char *seq1,
+                                          uintptr_t len1,
+                                          const char *seq2,
+                                          uintptr_t len2,
+                                          PoastaPairConfig config);
+
+/// Frees a PoastaPairConsensus.
+void poasta_free_pair_consensus(PoastaPairConsensus consensus);
+
+/// Creates a panel of `n` references from the NUL-terminated sequences in `seqs`, each in a graph
+/// of its own, for typing reads against the references with poasta_panel_best_hit. `names` gives
+/// a NUL-terminated name for each reference, or is NULL to call them `seq_N`.
+/// Returns NULL if a sequence is NULL or empty, or a name is NULL, empty or contains whitespace.
+/// Free the panel with poasta_panel_free.
+PoastaPanel *poasta_panel_create(const char *const *seqs, const char *const *names, uintptr_t n);
+
+/// Frees a panel.
+void poasta_panel_free(PoastaPanel *panel);
+
+/// Aligns a read to every reference of the panel and returns the one with the lowest alignment
+/// cost, the first of them on ties, which types the read against the panel in a single call. The
+/// hit also gives the runner-up and how much worse it is, and is flagged as ambiguous if the
+/// margin is below `config.min_margin`, so unresolvable reads can be set aside instead of being
+/// assigned anyway. The references are split over `n_threads` threads (0 means one per available
+/// core). The panel isn't changed. Returns no hit (index -1) if the panel or read is NULL, the
+/// read is empty, a cost is negative or above 255 or the panel has no references.
+PoastaPanelHit poasta_panel_best_hit(const PoastaPanel *panel,
+                                     const char *read,
+                                     uintptr_t len,
+                                     PoastaPanelConfig config,
+                                     uintptr_t n_threads);
+
+/// Groups the variable MSA columns into phase blocks, from the alleles of the active sequences (see
+/// poasta_column_linkage). A column is variable if at least two of its alleles are each seen in at
+/// least two sequences; masked regions are left out. Consecutive variable columns are in the same
+/// block while the linkage (r² of their most frequent alleles) between them is at least
+/// `min_linkage`. Each block lists its haplotypes with the number of sequences supporting them.
+/// Caller must free the result with poasta_free_phase_blocks.
+PoastaPhaseBlocks poasta_phase_blocks(const PoastaGraph *graph, double min_linkage);
+
+/// Frees PoastaPhaseBlocks, including their blocks and haplotypes.
+void poasta_free_phase_blocks(PoastaPhaseBlocks blocks);
+
+/// Returns a copy of the graph with at most `max_nodes` nodes, for rendering or exporting (e.g.,
+/// with poasta_get_gfa) graphs too large to draw. The nodes with the highest weight (summed over
+/// the sequences passing through them) are kept; of the nodes tied at the cutoff weight, evenly
+/// spaced ones are. Each sequence keeps the bases in kept nodes, which are connected in its order,
+/// so rare variants and errors collapse into the structure around them. Sequences without kept
+/// bases are left out, and so are masked regions; links and the backbone are kept where their
+/// nodes and sequence are. The graph itself is left untouched, so analysis can continue on the
+/// full structure. Returns NULL if the graph is NULL or `max_nodes` is 0. Must be freed with
+/// poasta_free_graph.
+PoastaGraph *poasta_graph_preview(const PoastaGraph *graph, uintptr_t max_nodes);
+
+/// Configures primers that are located, trimmed and used to orient every sequence added to the
+/// graph from now on. `fwd` is expected at the start of the amplicon, and the reverse complement
+/// of `rev` at its end. Sequences that match the primers better when reverse complemented are
+/// reverse complemented before alignment. A primer is only trimmed if it is found with at most
+/// `max_mismatch` edits. Pass two empty primers to disable trimming. Returns -1 if the graph's
+/// alphabet isn't DNA or RNA (see poasta_graph_set_alphabet).
+int poasta_set_primers(PoastaGraph *graph,
+                       const char *fwd,
+                       uintptr_t fwd_len,
+                       const char *rev,
+                       uintptr_t rev_len,
+                       uint8_t max_mismatch);
+
+/// Returns the consensus (see poasta_get_consensus) between the forward primer `fwd` and the
+/// reverse complement of the reverse primer `rev`, both left out, so consensus sequences of
+/// amplicons start and end at the same place however ragged the ends of their reads are. The
+/// forward primer is searched in the whole consensus and the reverse one after it, each found if
+/// it matches with at most `max_mismatch` edits; an empty or NULL primer leaves that end of the
+/// consensus as is. Returns an empty consensus with last error -1 if the graph is NULL, its
+/// alphabet isn't DNA or RNA, or a primer isn't found.
+/// Caller must free the result with poasta_free_consensus.
+PoastaConsensus poasta_get_consensus_between(const PoastaGraph *graph,
+                                             const char *fwd,
+                                             const char *rev,
+                                             uint8_t max_mismatch);
+
+/// Compares every sequence in the graph (including inactive ones) to the consensus along the
+/// alignment it was added with, and counts substitutions, insertions and deletions, in total, by
+/// consensus base and, if `per_read` is set, for each sequence. This gives a sequencing error QC
+/// report for free. Sequence ends extending past the consensus are ignored.
+/// Caller must free the result with poasta_free_observed_errors.
+PoastaObservedErrors poasta_error_profile(const PoastaGraph *graph, bool per_read);
+
+/// Frees a PoastaObservedErrors.
+void poasta_free_observed_errors(PoastaObservedErrors profile);
+
+/// Tags the sequences added from now on with `batch_id` (0 for none, the default), e.g., the ID
+/// of the chunk of a stream they came from. Returns -1 if the graph is NULL.
+int poasta_set_batch_id(PoastaGraph *graph, uint64_t batch_id);
+
+/// Stores when and in which batch the sequence at `index` (in insertion order) was added in
+/// `*provenance`. Sequences copied from other graphs keep their provenance.
+/// Returns -1 if the graph or `provenance` is NULL or the index is out of range.
+int poasta_sequence_provenance(const PoastaGraph *graph,
+                               uintptr_t index,
+                               PoastaProvenance *provenance);
+
+/// Sets the number of active sequences a node or edge needs to pass through it before it shows up
+/// in the consensus and GFA export (1 by default, 0 behaves like 1). Provisional elements stay in
+/// the graph, so new sequences still align to them and confirm them, which hides singleton
+/// sequencing errors without destructive pruning.
+int poasta_set_min_support(PoastaGraph *graph, uintptr_t min_support);
+
+/// Returns the graph as GFA, with provisional nodes and edges excluded, included, or included and
+/// marked with a `pv:i:1` tag: non-branching runs of nodes as segments, and a walk per sequence
+/// with its offsets in the first and last segment, which poasta_graph_from_gfa reads back.
+/// Segments are never partly provisional when marking.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+char *poasta_get_gfa_layers(const PoastaGraph *graph, PoastaProvisional provisional);
+
+/// Returns the graph in Graphviz DOT format, one node per base, with provisional nodes and edges
+/// excluded, included, or included and drawn in gray with dashed edges.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+char *poasta_get_dot_layers(const PoastaGraph *graph, PoastaProvisional provisional);
+
+/// Adds a sequence to the graph (Global alignment) with base weights from its Phred+33 qualities
+/// (`qual`, `len` characters as in FASTQ files), scaled as configured in `weights`.
+/// Returns -1 if `qual` is NULL or contains characters below `!`, and the status codes of
+/// poasta_add_sequence otherwise.
+int poasta_add_sequence_with_quals(PoastaGraph *graph,
+                                   const char *seq,
+                                   const char *qual,
+                                   uintptr_t len,
+                                   PoastaQualityWeights weights,
+                                   int32_t mismatch_score,
+                                   int32_t gap_extend,
+                                   int32_t gap_open);
+
+/// Aligns a sequence to the graph like poasta_add_sequence_mode would, but without adding it, e.g.,
+/// to score candidate reads, filter them and add only the best ones. The sequence is aligned as
+/// given: primer trimming, the length filter and rotation of circular sequences don't apply.
+/// `status` is -1 if the graph or sequence is NULL or empty, or a cost is negative or above 255.
+/// Caller must free the result with poasta_free_query_alignment.
+PoastaQueryAlignment poasta_align_query(const PoastaGraph *graph,
+                                        const char *seq,
+                                        uintptr_t len,
+                                        PoastaAlignParams params);
+
+/// Frees a PoastaQueryAlignment.
+void poasta_free_query_alignment(PoastaQueryAlignment alignment);
+
+/// Aligns the `n` sequences `seqs[i]` of `lens[i]` bases to the graph like poasta_align_query,
+/// spread over `n_threads` threads (0 means one per available CPU) that take the next query as
+/// they finish one, so queries of different lengths keep all threads busy. The graph isn't
+/// changed, so threads share it without copying or locking. The threads are started for each call
+/// and stopped before it returns, which takes tens of microseconds, negligible next to aligning
+/// a batch; for many tiny batches, call poasta_align_query from the host's own threads instead. A
+/// thread pool (e.g., rayon's) isn't used so the library adds no dependency and leaves no threads
+/// running in the host's process between calls. An alignment's `status` is a
+/// `PoastaError` code of its own query: `InvalidArgument` if its sequence is NULL or empty, and
+/// `Panic` if aligning it hit a bug, which leaves the other queries unaffected. These per-query
+/// failures aren't recorded in poasta_last_error, which only reflects failures of the whole call.
+/// The result is empty if the graph is NULL or empty, `seqs` or `lens` is NULL or a cost is
+/// negative or above 255. Caller must free it with poasta_free_query_alignments.
+PoastaQueryAlignments poasta_align_queries_parallel(const PoastaGraph *graph,
+                                                    const char *const *seqs,
+                                                    const uintptr_t *lens,
+                                                    uintptr_t n,
+                                                    PoastaAlignParams params,
+                                                    uintptr_t n_threads);
+
+/// Frees PoastaQueryAlignments.
+void poasta_free_query_alignments(PoastaQueryAlignments alignments);
+
+/// Computes the consensus of the repeats within a single rolling-circle read.
+/// The read is split into repeats by locating its own start (see poasta_split_concatemers), and
+/// the repeats whose length is within 20% of the median repeat length are aligned into a graph.
+/// The support of a consensus base is the number of repeats supporting it, and its confidence
+/// their fraction. The consensus is empty if a cost is negative or above 255.
+/// Caller must free the result with poasta_free_consensus.
+PoastaConsensus poasta_rca_consensus(const char *seq, uintptr_t len, PoastaRcaConfig config);
+
+/// Adds `new_seq`, an updated version of the sequence at `old_index` (in insertion order). The new
+/// version gets the scoring, alignment mode, name and other details of the old one, and weight 1
+/// per base. Where the dynamic programming aligner is used (custom scoring, codon-aware or
+/// homopolymer-aware gaps, or modes other than Global), it only fills a band around the old
+/// version's alignment, which is much faster than aligning from scratch when the versions are
+/// nearly identical; if no alignment fits in the band, the sequence is aligned without it.
+/// poasta's aligner is already quick for nearly identical sequences and is used as usual.
+/// Primer trimming and the length filter apply as for poasta_add_sequence.
+/// Returns the same codes as poasta_add_sequence, and -1 if the index is out of range.
+int poasta_realign_updated(PoastaGraph *graph,
+                           uintptr_t old_index,
+                           const char *new_seq,
+                           uintptr_t len,
+                           PoastaRealignConfig config);
+
+/// Refines the graph in rounds: each sequence in turn is removed and realigned against the graph
+/// of all the others (with its scoring and alignment mode), so the first sequences, which were
+/// aligned against a tiny graph, benefit from the later ones. Rounds repeat until the total cost
+/// of the realignments stops decreasing, or `iterations` rounds (0 for no limit) ran; a round that
+/// increased the total is undone. Sequences keep their index, name and provenance, but nodes are
+/// renumbered, and links and masked regions on nodes only a realigned sequence passed through are
+/// dropped. If a round ends with a sequence added along an approximate alignment after a timeout,
+/// its cost is unknown and refinement stops. Returns 0 on success, -1 if the graph is NULL, and
+/// the code of poasta_add_sequence if realigning a sequence failed, leaving the graph unchanged.
+int poasta_refine(PoastaGraph *graph, uintptr_t iterations);
+
+/// Seeds an empty graph with a reference sequence, which becomes the graph's backbone (see
+/// poasta_graph_set_backbone), so reads align against a stable coordinate system. Every base of
+/// the reference gets `weight`. A reference with weight 0 is inactive (see
+/// poasta_sequence_set_active): it guides the alignment of reads but is left out of the
+/// consensus and MSA. Primers and the length filter don't apply to the reference.
+/// Returns -1 if the graph already has sequences and -2 if adding the reference failed.
+int poasta_graph_init_reference(PoastaGraph *graph,
+                                const char *ref_seq,
+                                uintptr_t len,
+                                uint32_t weight);
+
+/// Designates the sequence at `index` (in insertion order) as the graph's backbone, the reference
+/// sequence that the positions of mapped reads (e.g., in SAM records) refer to.
+/// Returns -1 if the index is out of range.
+int poasta_graph_set_backbone(PoastaGraph *graph, uintptr_t index);
+
+/// Returns the 0-based backbone position of `node` (a node index). Nodes aligned to a backbone
+/// node, e.g., a variant base, get the position of that node. Returns -1 if the graph has no
+/// backbone, the node doesn't exist or it is inserted relative to the backbone.
+int64_t poasta_node_ref_position(const PoastaGraph *graph, uintptr_t node);
+
+/// Returns the backbone position of each consensus base, to compare the consensus to linear
+/// annotations. The map is empty if the graph has no backbone.
+/// Caller must free the result with poasta_free_ref_map.
+PoastaRefMap poasta_consensus_to_ref_map(const PoastaGraph *graph);
+
+/// Returns the part of the consensus aligned to backbone positions `ref_start` (inclusive) to
+/// `ref_end` (exclusive), including bases inserted within that interval, e.g., to extract a single
+/// exon. Returns NULL if the graph has no backbone or no consensus base is aligned to the interval.
+/// The caller must free the string using free().
+char *poasta_get_consensus_region(const PoastaGraph *graph, uintptr_t ref_start, uintptr_t ref_end);
+
+/// Frees a PoastaRefMap.
+void poasta_free_ref_map(PoastaRefMap map);
+
+/// Writes a standalone HTML report of the graph to the file at `path` (NUL-terminated): a summary,
+/// the consensus colored by confidence, a coverage track, the colored MSA and a table of variant
+/// columns outside the masked regions (see poasta_set_masked_regions). Returns 0 on success, -1 on invalid arguments and -5 if the file couldn't be written.
+int poasta_write_report_html(const PoastaGraph *graph,
+                             const char *path,
+                             PoastaReportOptions options);
+
+/// Calls `callback` with `user_data` at the end of every alignment, commit and MSA computation,
+/// from the thread that ran it. Sequences copied into a graph without realignment (e.g., by
+/// poasta_graph_deserialize) are reported as commits, and operations nested in others count
+/// towards the outer one. Peak memory is only measured with the `alloc-metrics` feature and is 0
+/// otherwise. Pass NULL to stop reporting. Returns 0.
+int poasta_set_resource_callback(PoastaResourceCallback callback, void *user_data);
+
+/// Adds the read of a SAM record (one line of SAM text, NUL-terminated) to the graph, with weight
+/// 1 for every base, named by its QNAME, and records its strand from the flags.
+/// If the graph has a backbone (see poasta_graph_set_backbone), the record is mapped and the
+/// graph neither trims primers nor rotates circular sequences, the read is added along the
+/// alignment given by its position and CIGAR on the backbone instead of being aligned, which is
+/// much faster. Otherwise it is aligned like in poasta_add_sequence.
+/// Returns the same status codes as poasta_add_sequence, and -1 for header lines, secondary and
+/// supplementary records and records without a sequence.
+int poasta_add_from_sam(PoastaGraph *graph,
+                        const char *sam_line,
+                        int32_t mismatch_score,
+                        int32_t gap_extend,
+                        int32_t gap_open);
+
+/// Creates a scoring configuration where every mismatch costs `mismatch_score`, with the given
+/// affine gap penalties. Free it with poasta_scoring_free.
+/// Returns NULL with last error -1 if a cost is negative or above 255.
+PoastaScoring *poasta_scoring_create(int32_t mismatch_score, int32_t gap_extend, int32_t gap_open);
+
+/// Creates a scoring configuration from a substitution matrix, e.g., to make transitions cheaper
+/// than transversions. Pairs with a symbol outside the matrix cost as much as its most expensive
+/// substitution. Free it with poasta_scoring_free.
+/// Returns NULL with last error -1 for NULL arrays, repeated symbols or costs that are negative or
+/// above 255, and -6 if a cost is inconsistent with the gap model (a nonzero cost for a match, or a
+/// cost above that of a deletion plus an insertion).
+PoastaScoring *poasta_scoring_create_matrix(PoastaSubstitutionMatrix matrix,
+                                            int32_t gap_extend,
+                                            int32_t gap_open);
+
+/// Creates a scoring configuration from a built-in protein matrix over the 20 amino acids, B, Z,
+/// X and `*`. Its similarity scores are turned into costs: a pair costs how much less similar it
+/// is than the average of its two symbols aligned to themselves, rounded up, and pairs with other
+/// symbols cost as much as the most expensive pair. Sequences added with it must be proteins, and
+/// a graph without an alphabet set (see poasta_graph_set_alphabet) becomes a protein graph when
+/// its first sequence is added with it. BLAST's protein gap costs (gap extend 1, gap open 11, see
+/// poasta_alphabet_default_params) suit all presets. Free it with poasta_scoring_free.
+/// Returns NULL with last error -1 if a gap cost is negative or above 255, and -6 if the gaps are
+/// too cheap for the matrix (the most expensive pair would cost more than a deletion plus an
+/// insertion).
+PoastaScoring *poasta_scoring_create_preset(PoastaMatrixPreset preset,
+                                            int32_t gap_extend,
+                                            int32_t gap_open);
+
+/// Frees a scoring configuration.
+void poasta_scoring_free(PoastaScoring *config);
+
+/// Sets the cost of aligning `a` to `b` (in both directions and for both cases of letters), e.g.,
+/// to make C<->T cheap for bisulfite-converted reads.
+/// Returns 0 on success, -1 for invalid arguments (including costs that are negative or above 255)
+/// and -6 if the cost is inconsistent with the gap model (a nonzero cost for a match, or a cost
+/// above that of a deletion plus an insertion).
+int poasta_scoring_set_pair(PoastaScoring *config, char a, char b, int32_t score);
+
+/// Makes N cost `cost` against any nucleotide or other ambiguity code instead of a mismatch, so
+/// masked bases neither attract nor repel alignment, e.g., with a cost of 0 or half a mismatch.
+/// With `all_codes`, every IUPAC ambiguity code (R, Y, S, W, K, M, B, D, H, V and N) costs `cost`
+/// against the bases it stands for and the codes sharing one of them with it. Both cases, and U
+/// for T, are set. Returns 0 on success, -1 if the configuration is NULL or the cost is negative or
+/// above 255, and -6 if the cost is above that of a deletion plus an insertion.
+int poasta_scoring_set_ambiguity(PoastaScoring *config, int32_t cost, bool all_codes);
+
+/// Adds a second gap piece with `gap_extend2` and `gap_open2` to the configuration and selects the
+/// TwoPieceAffine gap model, where each gap costs whichever piece is cheaper for its length, to
+/// model the long indels of ONT and PacBio reads. Setting it again replaces the piece.
+/// Returns 0 on success, -1 if the configuration is NULL, a cost is negative or above 255 or
+/// `gap_extend2` isn't below the configuration's gap extend cost (so the piece would never be
+/// cheaper for long gaps), and -6 if a substitution cost becomes higher than a deletion plus an
+/// insertion with the cheaper short gaps, leaving the configuration unchanged.
+int poasta_scoring_set_long_gaps(PoastaScoring *config, int32_t gap_extend2, int32_t gap_open2);
+
+/// Selects how the configuration scores gaps. With Linear, gap open costs are ignored. Switching
+/// models keeps the gap costs, so switching back restores the previous model.
+/// Returns 0 on success, -1 if the configuration is NULL or TwoPieceAffine is selected before a
+/// second piece was set, and -6 if a substitution cost becomes higher than a deletion plus an
+/// insertion with the model's gaps, leaving the configuration unchanged.
+int poasta_scoring_set_gap_model(PoastaScoring *config, PoastaGapModel model);
+
+/// Checks that the configuration is consistent with its gap model.
+/// Returns 0 if it is, -1 for invalid arguments and -6 otherwise.
+int poasta_scoring_validate(const PoastaScoring *config);
+
+/// Adds a sequence to the graph with a specified weight, scored with the given configuration
+/// (Global alignment).
+/// Returns the same codes as poasta_add_sequence, or -6 if the configuration is invalid, in which
+/// case the graph is left unchanged.
+int poasta_add_sequence_scoring(PoastaGraph *graph,
+                                const char *seq,
+                                uintptr_t len,
+                                uint32_t weight,
+                                const PoastaScoring *config);
+
+/// Includes or excludes the sequence at `index` (in insertion order) from consensus and MSA
+/// computation, without removing it from the graph. Inactive sequences still shape the graph
+/// that new sequences are aligned to. Returns -1 if the index is out of range.
+int poasta_sequence_set_active(PoastaGraph *graph, uintptr_t index, bool active);
+
+/// Measures the influence of the sequence at `index` on the consensus: returns the edit distance
+/// between the consensus with and without that sequence (regardless of whether it is currently
+/// active). Useful for contamination screening. Returns -1 if the index is out of range.
+int64_t poasta_loo_consensus_delta(const PoastaGraph *graph, uintptr_t seq_index);
+
+/// Counts `delta` more reads for the sequence at `index` (in insertion order), for reads the host
+/// already knows to be identical to it (e.g., from its own deduplication), without aligning them or
+/// adding sequences: the sequence's weights count once more per read in the consensus and other
+/// weighted output, and the bumped reads are kept when the graph is copied, rebuilt or saved.
+/// Returns 0 on success and -1 if the index is out of range or `delta` is 0.
+int poasta_bump_path_weight(PoastaGraph *graph, uintptr_t index, uint32_t delta);
+
+/// Like poasta_bump_path_weight, but adds `delta` to the weight of every node of the path of
+/// `num_nodes` nodes at `nodes` (node indices, the segment names of poasta_get_gfa_with_paths and
+/// poasta_get_gfa_delta), which must be connected one after the other, e.g., a path an external
+/// exact matcher found. Bumping the same path again adds to its weight. The weight is kept as long
+/// as the path is, but not by graphs built by realigning the sequences.
+/// Returns 0 on success and -1 for invalid arguments, nodes that don't exist or aren't connected
+/// and a `delta` of 0.
+int poasta_bump_node_path_weight(PoastaGraph *graph,
+                                 const uintptr_t *nodes,
+                                 uintptr_t num_nodes,
+                                 uint32_t delta);
+
+/// Removes the sequence at `index` (in insertion order) from the graph, e.g., to realign it after
+/// the graph was refined. poasta's graph can't be edited outside of poasta, so this rebuilds it
+/// from the other sequences along their paths: nothing is aligned again and they keep their
+/// alignments, but each of them is committed anew, which takes about as long as building the graph
+/// without the alignments. For leave-one-out analyses, poasta_sequence_set_active is much cheaper.
+/// Nodes only the removed sequence passed through are gone afterwards, and all weights are those
+/// of the other sequences. Later sequences move up one index and nodes are renumbered, so node IDs
+/// of earlier output no longer apply. Returns -1 if the index is out of range and -3 if the
+/// remaining sequences couldn't be added again, leaving the graph unchanged.
+int poasta_remove_sequence(PoastaGraph *graph, uintptr_t index);
+
+/// Like poasta_remove_sequence, for the first sequence named `name` (NUL-terminated) in the output,
+/// including made-up `seq_N` names. Returns -1 if there is none.
+int poasta_remove_sequence_named(PoastaGraph *graph, const char *name);
+
+/// Serializes the graph with its sequences, weights, metadata and configuration, so it can be
+/// loaded with poasta_graph_deserialize in a later run instead of realigning everything. The bytes
+/// can be read by later versions of the library. Returns an empty buffer if the graph is NULL.
+/// Caller must free the result with poasta_free_bytes.
+PoastaBytes poasta_graph_serialize(const PoastaGraph *graph);
+
+/// Loads a graph from `len` bytes written by poasta_graph_serialize. The graph behaves exactly
+/// like the serialized one, including the output of all functions and sequences added later. Its
+/// sequences are added again along their saved paths, without aligning them.
+/// Returns NULL with the reason as the last error: -1 if `buf` is NULL, -8 if the bytes are
+/// corrupted and -9 if they were written by a newer, incompatible version of the library.
+/// The caller must free the graph with poasta_free_graph.
+PoastaGraph *poasta_graph_deserialize(const uint8_t *buf, uintptr_t len);
+
+/// Frees a PoastaBytes.
+void poasta_free_bytes(PoastaBytes bytes);
+
+/// Splits a graph that contains divergent sequence families into one graph per family.
+/// Sequences are greedily clustered: each sequence joins the first-added member of the most
+/// similar family if their identity is at least `min_identity`, otherwise it founds a new family.
+/// Each family is realigned into a new graph (in the original insertion order).
+/// The input graph is left untouched. If all sequences belong to a single family, an empty list
+/// is returned. Each returned graph must be freed with poasta_free_graph, the list itself with
+/// poasta_free_graph_list.
+PoastaGraphList poasta_graph_autosplit(const PoastaGraph *graph, double min_identity);
+
+/// Frees the array of a PoastaGraphList. The graphs themselves are not freed.
+void poasta_free_graph_list(PoastaGraphList list);
+
+/// Returns summary statistics of the graph. Returns all zeros if the graph is NULL.
+/// Part of the fast query tier: never allocates and never panics.
+PoastaGraphStats poasta_graph_stats(const PoastaGraph *graph);
+
+/// Returns a counter that changes whenever the graph's sequences, structure or output settings
+/// (consensus tie break, bisulfite consensus, minimum support, masked regions, backbone) change,
+/// so bindings can cache the MSA, consensus or GFA until it changes. Settings that only affect
+/// how later sequences are added don't change it. New, cloned and loaded graphs start at 0.
+/// Returns 0 if the graph is NULL. Part of the fast query tier: never allocates and never panics.
+uint64_t poasta_graph_generation(const PoastaGraph *graph);
+
+/// Estimates the cost of aligning a sequence of `seq_len` bases to the graph with simple affine
+/// gap scoring (poasta_add_sequence), before running it, so schedulers can route large jobs to
+/// suitable workers or reject infeasible ones. Custom scoring and modes other than Global use the
+/// DP aligner like `exhaustive` configurations do, and cost about as much. The first sequence of
+/// a graph isn't aligned and costs nothing. Returns all zeros if the graph is NULL.
+/// Part of the fast query tier: never allocates and never panics.
+PoastaCostEstimate poasta_estimate_alignment_cost(const PoastaGraph *graph, uintptr_t seq_len);
+
+/// Opens the graph store at `path` (NUL-terminated), creating the file if it doesn't exist.
+/// Returns NULL if the file couldn't be opened or isn't a graph store; poasta_last_error_message
+/// says why.
+/// The store must be closed with poasta_store_close.
+PoastaStore *poasta_store_open(const char *path);
+
+/// Closes the store. Graphs loaded from it stay valid.
+void poasta_store_close(PoastaStore *store);
+
+/// Stores a copy of the graph under `key` (NUL-terminated), replacing any graph stored under the
+/// same key. Returns 0 on success, -1 on invalid arguments and -5 if the file couldn't be written.
+int poasta_store_put(PoastaStore *store, const char *key, const PoastaGraph *graph);
+
+/// Loads the graph stored under `key` (NUL-terminated). Returns NULL if there is no such graph or
+/// it couldn't be read. The graph must be freed with poasta_free_graph.
+PoastaGraph *poasta_store_get(PoastaStore *store, const char *key);
+
+/// Like poasta_store_get, but stores the graph in `*graph_out` and tells why loading failed.
+/// Returns 0 on success, -1 on invalid arguments or if there is no graph under `key`, -5 if the
+/// file couldn't be read, -8 if the stored graph is corrupted and -9 if it was written by a newer,
+/// incompatible version of the library. `*graph_out` is set to NULL on failure.
+int poasta_store_load(PoastaStore *store, const char *key, PoastaGraph **graph_out);
+
+/// Records the strand of the sequence at `index` (in insertion order); sequences are of unknown
+/// strand by default. Returns -1 if the index is out of range.
+int poasta_sequence_set_strand(PoastaGraph *graph, uintptr_t index, PoastaStrand strand);
+
+/// Returns the summed base weights of the active sequences passing through `node` (a node ID as
+/// in the GFA output), per strand, e.g., to test variant nodes for strand bias. Weight bumped onto
+/// a sequence counts for its strand, and weight bumped onto a node path as unknown.
+/// Returns all zeros if the graph is NULL or the node doesn't exist.
+PoastaStrandWeights poasta_node_strand_weights(const PoastaGraph *graph, uintptr_t node);
+
+/// Limits the time spent aligning each sequence added to the graph to `timeout_ms` milliseconds
+/// (0 disables the limit, the default). When the limit is hit, the add functions return -7
+/// (TIMEOUT) without adding the sequence, or, if `approximate` is set, add it along its alignment
+/// to the consensus and return 1 (see poasta_sequence_is_approximate).
+/// Each alignment runs on a thread of its own, which is stopped when the limit is hit; the add
+/// functions only return once it has stopped.
+int poasta_set_alignment_timeout(PoastaGraph *graph, uint64_t timeout_ms, bool approximate);
+
+/// Returns 1 if the sequence at `index` (in insertion order) was added along an approximate
+/// alignment after a timeout, 0 if it was aligned exactly and -1 if the index is out of range.
+int poasta_sequence_is_approximate(const PoastaGraph *graph, uintptr_t index);
+
+/// Records spans up to `level` (`PoastaTraceLevel::Off` by default).
+/// Returns -1 if the library was built without the `tracing` feature.
+int poasta_set_trace_level(PoastaTraceLevel level);
+
+/// Sends finished spans to `callback` (called from the thread that ran the operation) together
+/// with `user_data`. Without a callback (the default, or after passing NULL), spans are dropped.
+/// Returns -1 if the library was built without the `tracing` feature.
+int poasta_set_trace_callback(PoastaTraceCallback callback, void *user_data);
+
+/// Adds a sequence tagged with a UMI to the graph (Global alignment).
+/// The first sequence seen for a UMI gets `weight` for all its bases, every following sequence
+/// with the same UMI gets `duplicate_weight` instead. This keeps PCR duplicates from inflating
+/// the consensus weights. `umi` must be a NUL-terminated string; pass NULL to add an untagged
+/// sequence with `weight`.
+int poasta_add_sequence_umi(PoastaGraph *graph,
+                            const char *seq,
+                            uintptr_t len,
+                            const char *umi,
+                            uint32_t weight,
+                            uint32_t duplicate_weight,
+                            int32_t mismatch_score,
+                            int32_t gap_extend,
+                            int32_t gap_open);
+
+/// Returns the number of sequences added for each UMI, in order of first occurrence.
+/// Caller must free the result with poasta_free_umi_counts.
+PoastaUmiCounts poasta_umi_counts(const PoastaGraph *graph);
+
+/// Frees the PoastaUmiCounts.
+void poasta_free_umi_counts(PoastaUmiCounts counts);
+
+/// Checks the invariants of the graph: it is acyclic, every node lies on a sequence path, aligned
+/// nodes are aligned to each other and have different symbols, every sequence is in the graph's
+/// alphabet and its path spells it along existing edges, and the weight and sequences of every edge match the paths
+/// through it. Useful after loading, merging or otherwise changing a graph.
+/// Caller must free the result with poasta_free_validation.
+PoastaValidation poasta_graph_validate(const PoastaGraph *graph);
+
+/// Frees a PoastaValidation.
+void poasta_free_validation(PoastaValidation validation);
+
+/// Returns the graph in vg's JSON graph format, to load it with `vg view -Jv` or `odgi build`:
+/// nodes are the same segments as in poasta_get_gfa_layers (with ids counting from 1), and each
+/// sequence gets a path named like its GFA walk, whose mappings give the offset and length of the
+/// part of each node it covers. Like poasta_get_gfa, nodes and edges below the minimum support
+/// are left out, and so are the paths through them.
+/// The caller must free the string using free().
+char *poasta_get_vg_json(const PoastaGraph *graph);
+
+/// Like poasta_get_vg_json, but stops writing the JSON once it exceeds `max_bytes` (0 means no
+/// cap), so a pathological graph can't make the library allocate gigabytes. The JSON is stored in
+/// `*out`, or an empty string if it was truncated, since JSON that is cut off can't be parsed; the
+/// caller must free it using free(). `*out` is NULL on other failures. Returns 0, -1 if the graph
+/// or `out` is NULL, and -11 if the JSON was truncated.
+int poasta_get_vg_json_capped(const PoastaGraph *graph, uintptr_t max_bytes, char **out);
+
+/// Multiplies the consensus weights of all sequences added so far by `factor` (between 0 and 1
+/// to decay them), so streaming applications can emphasize recent reads without rebuilding the
+/// graph, including the weights bumped onto sequences and node paths. Sequences added and weights
+/// bumped afterwards start at their full weight.
+/// Returns -1 if the factor isn't positive and finite.
+int poasta_graph_decay_weights(PoastaGraph *graph, double factor);
+
 }  // extern "C"
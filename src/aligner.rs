@@ -0,0 +1,243 @@
+//! Aligner handles, which keep the scoring and the aligner's working memory across calls, so tight
+//! loops adding many reads don't set them up again for each read.
+
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+
+
+use crate::dp::DpBuffers;
+use crate::mode::PoastaAlignmentMode;
+use crate::query::PoastaAlignParams;
+use crate::scoring::{invalid_scoring, PoastaScoring};
+use crate::trace::{self, PoastaTraceLevel};
+use crate::{error, metrics, PoastaGraph, Scoring, SequenceInfo};
+
+/// Scoring and working memory for adding sequences. Use one aligner per thread.
+pub struct PoastaAligner {
+    scoring: Scoring,
+    mode: PoastaAlignmentMode,
+    buffers: DpBuffers,
+    weights: Vec<usize>,
+}
+
+impl PoastaAligner {
+    fn new(scoring: Scoring) -> Self {
+        PoastaAligner { scoring, mode: PoastaAlignmentMode::default(), buffers: DpBuffers::default(), weights: Vec::new() }
+    }
+}
+
+/// Creates an aligner scoring with simple affine gap penalties, like poasta_add_sequence.
+/// Returns NULL if a cost is negative or above 255. Free it with poasta_aligner_free.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_aligner_create(mismatch_score: i32, gap_extend: i32, gap_open: i32) -> *mut PoastaAligner {
+    error::catch(|| {
+        let Ok(scoring) = Scoring::affine(mismatch_score, gap_extend, gap_open) else {
+            return ptr::null_mut();
+        };
+
+        Box::into_raw(Box::new(PoastaAligner::new(scoring)))
+    })
+}
+
+/// Creates an aligner scoring with the given configuration, like poasta_add_sequence_scoring.
+/// Later changes to the configuration don't affect the aligner. Returns NULL if the configuration
+/// is NULL or invalid (see poasta_scoring_validate). Free it with poasta_aligner_free.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_aligner_create_scoring(config: *const PoastaScoring) -> *mut PoastaAligner {
+    error::catch(|| {
+        if config.is_null() {
+            error::fail(-1, "scoring configuration is NULL");
+            return ptr::null_mut();
+        }
+
+        let config = unsafe { &*config };
+        if !config.params.is_valid() {
+            invalid_scoring();
+            return ptr::null_mut();
+        }
+
+        let scoring = Scoring::Custom(Arc::clone(&config.params));
+        Box::into_raw(Box::new(PoastaAligner::new(scoring)))
+    })
+}
+
+/// Frees an aligner.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_aligner_free(aligner: *mut PoastaAligner) {
+    error::catch(|| {
+        if !aligner.is_null() {
+            unsafe {
+                drop(Box::from_raw(aligner));
+            }
+        }
+    })
+}
+
+/// Sets the mode the aligner aligns sequences in (Global by default, see poasta_add_sequence_mode).
+/// Returns 0 on success and -1 if the aligner is NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_aligner_set_mode(aligner: *mut PoastaAligner, mode: PoastaAlignmentMode) -> c_int {
+    error::catch(|| {
+        if aligner.is_null() {
+            return error::fail(-1, "aligner is NULL");
+        }
+
+        unsafe { (*aligner).mode = mode.normalized() };
+
+        0
+    })
+}
+
+/// Adds a sequence to the graph in the aligner's mode with the aligner's scoring, reusing the
+/// aligner's working memory from earlier calls. The result is the same as adding the sequence with
+/// poasta_add_sequence or poasta_add_sequence_scoring, and so are the returned codes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_add_sequence_with_aligner(
+    graph: *mut PoastaGraph,
+    aligner: *mut PoastaAligner,
+    seq: *const c_char,
+    len: usize,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || aligner.is_null() || seq.is_null() {
+            metrics::record_status(-1);
+            return error::fail(-1, "graph, aligner or sequence is NULL");
+        }
+
+        let (graph, aligner) = unsafe { (&mut *graph, &mut *aligner) };
+        let seq = unsafe { slice::from_raw_parts(seq as *const u8, len) };
+        aligner.weights.clear();
+        aligner.weights.resize(len, 1);
+
+        let _span = trace::span(PoastaTraceLevel::Info, c"add_sequence");
+        let scoring = aligner.scoring.clone();
+        let info = SequenceInfo { mode: aligner.mode, ..SequenceInfo::default() };
+        let status = graph.add_sequence_with(seq, &aligner.weights, scoring, info, &mut aligner.buffers);
+        metrics::record_status(status);
+
+        status
+    })
+}
+
+/// Adds the `n` sequences `seqs[i]` of `lens[i]` bases to the graph in order, aligned as configured
+/// in `params` like poasta_add_sequence_mode, with one aligner's working memory shared between
+/// them. A failing sequence doesn't stop the others from being added. The status of each sequence,
+/// as poasta_add_sequence_mode would return it (-1 for NULL sequences), is written to `statuses`
+/// if not NULL, which must have room for `n` entries. Returns the number of sequences added
+/// (including approximately aligned ones), or -1 if the graph, `seqs` or `lens` is NULL or a cost
+/// is negative or above 255.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_add_sequences(
+    graph: *mut PoastaGraph,
+    seqs: *const *const c_char,
+    lens: *const usize,
+    n: usize,
+    params: PoastaAlignParams,
+    statuses: *mut c_int,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || seqs.is_null() || lens.is_null() {
+            return error::fail(-1, "graph, sequences or lengths are NULL");
+        }
+
+        let scoring = match params.scoring() {
+            Ok(scoring) => scoring,
+            Err(status) => return status,
+        };
+
+        let graph = unsafe { &mut *graph };
+        let (seqs, lens) = unsafe { (slice::from_raw_parts(seqs, n), slice::from_raw_parts(lens, n)) };
+        let mut aligner = PoastaAligner { mode: params.mode.normalized(), ..PoastaAligner::new(scoring) };
+
+        let _span = trace::span(PoastaTraceLevel::Info, c"add_sequences");
+        let mut added = 0;
+        for (i, (seq, len)) in seqs.iter().zip(lens).enumerate() {
+            let status = if seq.is_null() {
+                error::fail(-1, "sequence is NULL")
+            } else {
+                let seq = unsafe { slice::from_raw_parts(*seq as *const u8, *len) };
+                aligner.weights.clear();
+                aligner.weights.resize(seq.len(), 1);
+
+                let info = SequenceInfo { mode: aligner.mode, ..SequenceInfo::default() };
+                graph.add_sequence_with(seq, &aligner.weights, aligner.scoring.clone(), info, &mut aligner.buffers)
+            };
+            metrics::record_status(status);
+
+            if status >= 0 {
+                added += 1;
+            }
+            if !statuses.is_null() {
+                unsafe { *statuses.add(i) = status };
+            }
+        }
+
+        added
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::{poasta_add_sequence_scoring, poasta_scoring_create, poasta_scoring_free, poasta_scoring_set_pair};
+    use crate::testing::{graph_of, outputs};
+
+    const READS: [&str; 5] = ["ACGTTGCAGGATCC", "ACGATGCAGGATCC", "ACGTTGCAGGTCC", "ACGTTGCAGGATCCA", "CGTTGCAGGAT"];
+
+    #[test]
+    fn aligners_add_sequences_like_poasta_add_sequence() {
+        let aligner = poasta_aligner_create(4, 2, 6);
+        let mut graph = PoastaGraph::new();
+        for read in READS {
+            assert_eq!(unsafe { poasta_add_sequence_with_aligner(&mut graph, aligner, read.as_ptr().cast(), read.len()) }, 0);
+        }
+        assert_eq!(outputs(&graph), outputs(&graph_of(&READS)));
+
+        let status = unsafe { poasta_add_sequence_with_aligner(&mut graph, ptr::null_mut(), c"ACGT".as_ptr(), 4) };
+        assert_eq!(status, -1);
+        assert_eq!(unsafe { poasta_aligner_set_mode(aligner, PoastaAlignmentMode::default()) }, 0);
+        unsafe { poasta_aligner_free(aligner) };
+
+        assert!(poasta_aligner_create(4, 2, 256).is_null());
+    }
+
+    #[test]
+    fn scoring_aligners_add_sequences_like_poasta_add_sequence_scoring() {
+        let config = poasta_scoring_create(4, 2, 6);
+        unsafe { poasta_scoring_set_pair(config, b'A' as c_char, b'T' as c_char, 1) };
+        let aligner = unsafe { poasta_aligner_create_scoring(config) };
+
+        let (mut with_aligner, mut with_config) = (PoastaGraph::new(), PoastaGraph::new());
+        for read in READS {
+            let (seq, len) = (read.as_ptr().cast(), read.len());
+            assert_eq!(unsafe { poasta_add_sequence_with_aligner(&mut with_aligner, aligner, seq, len) }, 0);
+            assert_eq!(unsafe { poasta_add_sequence_scoring(&mut with_config, seq, len, 1, config) }, 0);
+        }
+        assert_eq!(outputs(&with_aligner), outputs(&with_config));
+
+        unsafe { poasta_aligner_free(aligner) };
+        unsafe { poasta_scoring_free(config) };
+    }
+
+    #[test]
+    fn batches_are_added_in_order_with_a_status_each() {
+        let params = || PoastaAlignParams { mismatch_score: 4, gap_extend: 2, gap_open: 6, mode: PoastaAlignmentMode::default() };
+        let mut seqs: Vec<*const c_char> = READS.iter().map(|read| read.as_ptr().cast()).collect();
+        let mut lens: Vec<usize> = READS.iter().map(|read| read.len()).collect();
+        seqs.insert(2, ptr::null());
+        lens.insert(2, 4);
+
+        let mut graph = PoastaGraph::new();
+        let mut statuses = [1; 6];
+        let added = unsafe { poasta_add_sequences(&mut graph, seqs.as_ptr(), lens.as_ptr(), seqs.len(), params(), statuses.as_mut_ptr()) };
+        assert_eq!(added, 5);
+        assert_eq!(statuses, [0, 0, -1, 0, 0, 0]);
+        assert_eq!(outputs(&graph), outputs(&graph_of(&READS)));
+
+        let invalid = PoastaAlignParams { gap_open: -1, ..params() };
+        assert_eq!(unsafe { poasta_add_sequences(&mut graph, seqs.as_ptr(), lens.as_ptr(), seqs.len(), invalid, ptr::null_mut()) }, -1);
+        assert_eq!(graph.sequences.len(), 5);
+    }
+}
@@ -0,0 +1,152 @@
+//! The kind of symbols a graph holds, which decides how sequences are checked, which defaults
+//! apply and which nucleotide-only features are available.
+
+use std::os::raw::c_int;
+
+use poasta::aligner::scoring::GapAffine;
+use serde::{Deserialize, Serialize};
+
+use crate::error;
+use crate::query::PoastaAlignParams;
+use crate::{PoastaGraph, Scoring};
+
+/// Symbols of the sequences of a graph. Nucleotides include the IUPAC ambiguity codes, and all
+/// alphabets are case-insensitive.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PoastaAlphabet {
+    /// Detected from the first sequence of the graph.
+    #[default]
+    Auto = 0,
+    Dna = 1,
+    /// Like DNA, with U instead of T.
+    Rna = 2,
+    /// The 20 amino acids, B, J, O, U, X, Z and `*` for stop codons.
+    Protein = 3,
+    /// Any bytes; nucleotide-only features are unavailable.
+    Raw = 4,
+}
+
+const DNA: &[u8] = b"ACGTRYSWKMBDHVN";
+const RNA: &[u8] = b"ACGURYSWKMBDHVN";
+const PROTEIN: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ*";
+
+impl PoastaAlphabet {
+    /// The narrowest of DNA, RNA, protein and raw bytes that has all symbols of `seq`.
+    pub(crate) fn detect(seq: &[u8]) -> Self {
+        [PoastaAlphabet::Dna, PoastaAlphabet::Rna, PoastaAlphabet::Protein]
+            .into_iter()
+            .find(|alphabet| seq.iter().all(|symbol| alphabet.contains(*symbol)))
+            .unwrap_or(PoastaAlphabet::Raw)
+    }
+
+    /// Whether `symbol` belongs to the alphabet. Any symbol belongs to raw bytes and to an
+    /// alphabet that wasn't detected yet.
+    pub(crate) fn contains(self, symbol: u8) -> bool {
+        let symbols = match self {
+            PoastaAlphabet::Dna => DNA,
+            PoastaAlphabet::Rna => RNA,
+            PoastaAlphabet::Protein => PROTEIN,
+            PoastaAlphabet::Auto | PoastaAlphabet::Raw => return true,
+        };
+
+        symbols.contains(&symbol.to_ascii_uppercase())
+    }
+
+    /// The first symbol of `seq` (with its position) that doesn't belong to the alphabet.
+    pub(crate) fn first_foreign(self, seq: &[u8]) -> Option<(usize, u8)> {
+        seq.iter()
+            .copied()
+            .enumerate()
+            .find(|(_, symbol)| !self.contains(*symbol))
+    }
+
+    /// Whether sequences can be reverse complemented and IUPAC codes apply.
+    pub(crate) fn is_nucleotide(self) -> bool {
+        matches!(self, PoastaAlphabet::Dna | PoastaAlphabet::Rna)
+    }
+
+    /// poasta's default costs for nucleotides and raw bytes; BLAST's protein gap costs.
+    pub(crate) fn default_params(self) -> (u8, u8, u8) {
+        match self {
+            PoastaAlphabet::Protein => (6, 1, 11),
+            _ => (4, 2, 6),
+        }
+    }
+
+    pub(crate) fn default_scoring(self) -> Scoring {
+        let (mismatch, gap_extend, gap_open) = self.default_params();
+        Scoring::Affine(GapAffine::new(mismatch, gap_extend, gap_open))
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            PoastaAlphabet::Auto => "auto",
+            PoastaAlphabet::Dna => "DNA",
+            PoastaAlphabet::Rna => "RNA",
+            PoastaAlphabet::Protein => "protein",
+            PoastaAlphabet::Raw => "raw bytes",
+        }
+    }
+}
+
+/// Sets the alphabet of the graph's sequences (`PoastaAlphabet::Auto`, detection from the first
+/// sequence, by default). Sequences with other symbols are rejected, and IUPAC ambiguity codes in
+/// the consensus and primer trimming, which reverse complements sequences, need a nucleotide
+/// alphabet. Returns -1 if a sequence of the graph has a symbol outside the alphabet, or primers
+/// are set and the alphabet isn't DNA, RNA or Auto.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_set_alphabet(graph: *mut PoastaGraph, alphabet: PoastaAlphabet) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let graph = unsafe { &mut *graph };
+        for (ix, record) in graph.sequences.iter().enumerate() {
+            if let Some((pos, symbol)) = alphabet.first_foreign(&record.seq) {
+                let message = format!("sequence {ix} has {} at position {pos}, which isn't {}", symbol.escape_ascii(), alphabet.name());
+                return error::fail(-1, &message);
+            }
+        }
+
+        if graph.primers.is_some() && !matches!(alphabet, PoastaAlphabet::Auto) && !alphabet.is_nucleotide() {
+            return error::fail(-1, "primer trimming needs a nucleotide alphabet");
+        }
+
+        graph.alphabet = alphabet;
+        graph.touch();
+
+        0
+    })
+}
+
+/// Returns the alphabet of the graph: the one set with poasta_graph_set_alphabet, or the one
+/// detected from the first sequence, or `PoastaAlphabet::Auto` if there is none yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_get_alphabet(graph: *const PoastaGraph) -> PoastaAlphabet {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaAlphabet::Auto;
+        }
+
+        unsafe { &*graph }.alphabet()
+    })
+}
+
+/// Returns default alignment parameters (Global alignment) for sequences of `alphabet`: poasta's
+/// defaults (mismatch 4, gap extend 2, gap open 6) for nucleotides and raw bytes, and BLAST's
+/// protein gap costs (mismatch 6, gap extend 1, gap open 11) for proteins.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_alphabet_default_params(alphabet: PoastaAlphabet) -> PoastaAlignParams {
+    error::catch(|| {
+        let (mismatch_score, gap_extend, gap_open) = alphabet.default_params();
+
+        PoastaAlignParams {
+            mismatch_score: mismatch_score.into(),
+            gap_extend: gap_extend.into(),
+            gap_open: gap_open.into(),
+            mode: Default::default(),
+        }
+    })
+}
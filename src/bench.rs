@@ -0,0 +1,151 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use crate::{error, free_c_array, into_c_array};
+
+/// Per-base error rates of simulated reads.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PoastaErrorProfile {
+    pub substitution_rate: f64,
+    pub insertion_rate: f64,
+    pub deletion_rate: f64,
+}
+
+/// Struct to hold a list of reads.
+#[repr(C)]
+pub struct PoastaReads {
+    pub sequences: *mut *mut c_char,
+    pub num_sequences: usize,
+}
+
+/// Simulates `n` reads of `reference` with random substitutions, insertions and deletions
+/// according to `error_profile`, e.g., to benchmark parameter choices. The same seed always yields
+/// the same reads. Caller must free the result with poasta_free_reads.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_bench_generate_reads(
+    reference: *const c_char,
+    reference_len: usize,
+    n: usize,
+    error_profile: PoastaErrorProfile,
+    seed: u64,
+) -> PoastaReads {
+    error::catch(|| {
+        if reference.is_null() {
+            return PoastaReads { sequences: ptr::null_mut(), num_sequences: 0 };
+        }
+
+        let reference = unsafe { slice::from_raw_parts(reference as *const u8, reference_len) };
+        let mut rng = SplitMix64(seed);
+        let reads: Vec<*mut c_char> = (0..n)
+            .map(|_| CString::new(simulate_read(reference, &error_profile, &mut rng)).unwrap().into_raw())
+            .collect();
+
+        PoastaReads {
+            num_sequences: reads.len(),
+            sequences: into_c_array(reads),
+        }
+    })
+}
+
+/// Frees a PoastaReads.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_reads(reads: PoastaReads) {
+    error::catch(|| {
+        for read in unsafe { free_c_array(reads.sequences, reads.num_sequences) } {
+            if !read.is_null() {
+                drop(unsafe { CString::from_raw(read) });
+            }
+        }
+    })
+}
+
+fn simulate_read(reference: &[u8], profile: &PoastaErrorProfile, rng: &mut SplitMix64) -> Vec<u8> {
+    const BASES: &[u8; 4] = b"ACGT";
+    let mut read = Vec::with_capacity(reference.len());
+
+    for &base in reference {
+        if rng.next_f64() < profile.insertion_rate {
+            read.push(BASES[rng.below(4)]);
+        }
+
+        if rng.next_f64() < profile.deletion_rate {
+            continue;
+        }
+
+        if rng.next_f64() < profile.substitution_rate {
+            // Any base but the reference base
+            let others: Vec<u8> = BASES.iter().copied().filter(|b| *b != base.to_ascii_uppercase()).collect();
+            read.push(others[rng.below(others.len())]);
+        } else {
+            read.push(base);
+        }
+    }
+
+    read
+}
+
+/// Small, portable PRNG, so the simulated reads only depend on the seed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+    use crate::testing::{consensus, graph_of};
+
+    const REFERENCE: &str = "ACGTTGCAGGATCCTTAGCAGGTACCAATTCGGACTAGCTTGACCGTAAGCTTCAGGATC";
+
+    fn generate(n: usize, substitution_rate: f64, indel_rate: f64, seed: u64) -> Vec<String> {
+        let profile = PoastaErrorProfile { substitution_rate, insertion_rate: indel_rate, deletion_rate: indel_rate };
+        let reads = unsafe { poasta_bench_generate_reads(REFERENCE.as_ptr().cast(), REFERENCE.len(), n, profile, seed) };
+        let sequences = unsafe { slice::from_raw_parts(reads.sequences, reads.num_sequences) }.iter()
+            .map(|read| unsafe { CStr::from_ptr(*read) }.to_str().unwrap().to_string())
+            .collect();
+        unsafe { poasta_free_reads(reads) };
+
+        sequences
+    }
+
+    #[test]
+    fn reads_only_depend_on_the_seed() {
+        assert_eq!(generate(5, 0.05, 0.02, 7), generate(5, 0.05, 0.02, 7));
+        assert_ne!(generate(5, 0.05, 0.02, 7), generate(5, 0.05, 0.02, 8));
+        assert_eq!(generate(3, 0.0, 0.0, 7), vec![REFERENCE; 3]);
+    }
+
+    #[test]
+    fn errors_follow_the_profile() {
+        for read in generate(3, 1.0, 0.0, 7) {
+            assert_eq!(read.len(), REFERENCE.len());
+            assert!(read.bytes().zip(REFERENCE.bytes()).all(|(a, b)| a != b));
+        }
+
+        // Consensus of noisy reads recovers the reference
+        let reads = generate(15, 0.05, 0.02, 7);
+        let graph = graph_of(&reads.iter().map(String::as_str).collect::<Vec<_>>());
+        assert_eq!(consensus(&graph), REFERENCE);
+    }
+}
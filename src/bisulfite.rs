@@ -0,0 +1,97 @@
+//! Bisulfite (and EM-seq) conversion aware alignment and consensus.
+//!
+//! Conversion turns unmethylated Cs into Ts on the converted strand, which shows up as G to A on
+//! the opposite strand. Methylated Cs are left unchanged.
+
+use std::os::raw::c_int;
+use std::ptr;
+use std::sync::Arc;
+
+use crate::error;
+use crate::graph::NodeIx;
+use crate::scoring::{PoastaScoring, ScoringParams};
+use crate::PoastaGraph;
+
+/// Creates a scoring configuration for bisulfite-converted reads: a T in a read aligned to a C in
+/// the graph (or an A aligned to a G) is not penalized, while the opposite substitutions and all
+/// other mismatches cost `mismatch_score`. Since the costs are asymmetric, add the unconverted
+/// reference (or a read of it) first. Free the configuration with poasta_scoring_free.
+/// Returns NULL with last error -1 if a cost is negative or above 255.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_scoring_create_bisulfite(mismatch_score: i32, gap_extend: i32, gap_open: i32) -> *mut PoastaScoring {
+    error::catch(|| {
+        let Ok(mut params) = ScoringParams::checked(mismatch_score, gap_extend, gap_open) else {
+            return ptr::null_mut();
+        };
+        for (original, converted) in CONVERSIONS {
+            for case in [0, b'a' - b'A'] {
+                params.set_substitution(original + case, converted + case, 0);
+            }
+        }
+
+        Box::into_raw(Box::new(PoastaScoring { params: Arc::new(params) }))
+    })
+}
+
+/// Makes the consensus report the unconverted base (disabled by default): a C (or G) is reported
+/// wherever any active sequence shows it in the column of the consensus base, even if most
+/// sequences were converted to T (or A).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_set_bisulfite_consensus(graph: *mut PoastaGraph, enabled: bool) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let graph = unsafe { &mut *graph };
+        graph.bisulfite = enabled;
+        graph.touch();
+
+        0
+    })
+}
+
+/// (Original, converted) bases, in uppercase.
+const CONVERSIONS: [(u8, u8); 2] = [(b'C', b'T'), (b'G', b'A')];
+
+/// The symbol of a node aligned to `node` from which `node`'s symbol may have been converted,
+/// if any active sequence passes through it.
+pub(crate) fn unconverted_symbol(graph: &PoastaGraph, node: NodeIx, support: &[usize]) -> Option<u8> {
+    let symbol = graph.graph.get_symbol(node).to_ascii_uppercase();
+    let (original, _) = CONVERSIONS.iter().find(|(_, converted)| *converted == symbol)?;
+
+    graph.graph.get_aligned_nodes(node).iter()
+        .filter(|other| support[other.index()] > 0)
+        .map(|other| graph.graph.get_symbol(*other))
+        .find(|other| other.to_ascii_uppercase() == *original)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::{poasta_add_sequence_scoring, poasta_scoring_free};
+    use crate::testing::consensus;
+
+    #[test]
+    fn conversions_are_free_in_one_direction() {
+        let config = poasta_scoring_create_bisulfite(4, 2, 6);
+        let params = &unsafe { &*config }.params;
+        assert_eq!((params.substitution(b'C', b'T'), params.substitution(b'g', b'a')), (0, 0));
+        assert_eq!((params.substitution(b'T', b'C'), params.substitution(b'C', b'A')), (4, 4));
+        unsafe { poasta_scoring_free(config) };
+    }
+
+    #[test]
+    fn consensus_reports_the_unconverted_base() {
+        let config = poasta_scoring_create_bisulfite(4, 2, 6);
+        let mut graph = PoastaGraph::new();
+        for read in [c"ACGTCGA", c"ATGTTGA", c"ATGTCGA"] {
+            assert_eq!(unsafe { poasta_add_sequence_scoring(&mut graph, read.as_ptr(), 7, 1, config) }, 0);
+        }
+        unsafe { poasta_scoring_free(config) };
+
+        assert_eq!(consensus(&graph), "ATGTCGA");
+        assert_eq!(unsafe { poasta_set_bisulfite_consensus(&mut graph, true) }, 0);
+        assert_eq!(consensus(&graph), "ACGTCGA");
+    }
+}
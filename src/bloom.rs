@@ -0,0 +1,126 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::os::raw::c_char;
+use std::slice;
+
+use crate::error;
+use crate::PoastaGraph;
+
+/// Bits reserved per k-mer, giving a false positive rate of about 1% with `NUM_HASHES`.
+const BITS_PER_KMER: usize = 10;
+const NUM_HASHES: u64 = 7;
+const MIN_BITS: usize = 1 << 12;
+
+/// Estimates which fraction of the k-mers of `seq` occur in the sequences of the graph, to cheaply
+/// decide whether a read plausibly belongs to it (e.g., to route reads in clustering pipelines).
+/// The k-mers are kept in a Bloom filter next to the graph, which is built on the first query for
+/// a given `k` and then updated as sequences are added, so the estimate can be slightly too high
+/// but never too low. Takes a non-const graph because of the filter. Returns -1 if `k` is 0 or
+/// longer than the sequence.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_maybe_contains(graph: *mut PoastaGraph, seq: *const c_char, len: usize, k: usize) -> f64 {
+    error::catch(|| {
+        if graph.is_null() || seq.is_null() || k == 0 || k > len {
+            error::fail(-1, "graph or sequence is NULL, or k is 0 or longer than the sequence");
+            return -1.0;
+        }
+
+        let graph = unsafe { &mut *graph };
+        let seq = unsafe { slice::from_raw_parts(seq as *const u8, len) };
+
+        if graph.kmer_filter.as_ref().is_none_or(|filter| filter.k != k) {
+            graph.kmer_filter = Some(KmerFilter::build(graph, k));
+        }
+
+        let filter = graph.kmer_filter.as_ref().unwrap();
+        let kmers = seq.windows(k);
+        let num_kmers = kmers.len();
+        let found = kmers.filter(|kmer| filter.contains(kmer)).count();
+
+        found as f64 / num_kmers as f64
+    })
+}
+
+/// Bloom filter of the k-mers of all sequences in a graph.
+#[derive(Clone, Debug)]
+pub(crate) struct KmerFilter {
+    pub k: usize,
+    bits: Vec<u64>,
+    num_kmers: usize,
+}
+
+impl KmerFilter {
+    fn build(graph: &PoastaGraph, k: usize) -> Self {
+        let total: usize = graph.sequences.iter().map(|record| record.seq.len().saturating_sub(k - 1)).sum();
+        let mut filter = KmerFilter::with_capacity(k, 2 * total);
+        for record in graph.sequences.iter() {
+            filter.insert_kmers(&record.seq);
+        }
+
+        filter
+    }
+
+    fn with_capacity(k: usize, num_kmers: usize) -> Self {
+        let num_bits = (num_kmers * BITS_PER_KMER).max(MIN_BITS);
+
+        KmerFilter { k, bits: vec![0; num_bits.div_ceil(64)], num_kmers: 0 }
+    }
+
+    /// Whether the filter holds more k-mers than it was sized for.
+    pub(crate) fn is_saturated(&self) -> bool {
+        self.num_kmers * BITS_PER_KMER > self.bits.len() * 64
+    }
+
+    pub(crate) fn insert_kmers(&mut self, seq: &[u8]) {
+        for kmer in seq.windows(self.k) {
+            for bit in self.bit_positions(kmer) {
+                self.bits[bit / 64] |= 1 << (bit % 64);
+            }
+            self.num_kmers += 1;
+        }
+    }
+
+    fn contains(&self, kmer: &[u8]) -> bool {
+        self.bit_positions(kmer).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Bit positions of a k-mer (double hashing).
+    fn bit_positions(&self, kmer: &[u8]) -> impl Iterator<Item = usize> + use<> {
+        let mut hasher = DefaultHasher::new();
+        kmer.hash(&mut hasher);
+        let hash = hasher.finish();
+        let (h1, h2) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+        let num_bits = self.bits.len() as u64 * 64;
+
+        (0..NUM_HASHES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add, graph_of};
+
+    fn contains(graph: &mut PoastaGraph, seq: &str, k: usize) -> f64 {
+        unsafe { poasta_graph_maybe_contains(graph, seq.as_ptr().cast(), seq.len(), k) }
+    }
+
+    #[test]
+    fn kmers_of_added_sequences_are_found() {
+        let mut graph = graph_of(&["ACGTTGCAGGATCCTTAGCA"]);
+        assert_eq!(contains(&mut graph, "GCAGGATCC", 5), 1.0);
+        // 5 of the 7 5-mers span the substitution
+        assert_eq!(contains(&mut graph, "ACGTTCCAGGA", 5), 2.0 / 7.0);
+        assert_eq!(contains(&mut graph, "GGGGGGGGGG", 5), 0.0);
+
+        // The filter follows sequences added after it was built
+        add(&mut graph, "GGGGGGGGGGTTTT");
+        assert_eq!(contains(&mut graph, "GGGGGGGGGG", 5), 1.0);
+    }
+
+    #[test]
+    fn k_must_fit_the_sequence() {
+        let mut graph = graph_of(&["ACGTTGCA"]);
+        assert_eq!(contains(&mut graph, "ACGT", 0), -1.0);
+        assert_eq!(contains(&mut graph, "ACGT", 5), -1.0);
+    }
+}
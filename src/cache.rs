@@ -0,0 +1,106 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::os::raw::c_int;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error;
+use crate::PoastaGraph;
+
+/// Enables a cache of up to `capacity` sequences (0 disables it, the default). When a sequence
+/// identical to a cached one is added, it is committed along the cached sequence's path directly
+/// instead of being realigned, which is much faster for duplicate reads. Hits and misses are
+/// counted in poasta_graph_stats.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_set_alignment_cache(graph: *mut PoastaGraph, capacity: usize) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let graph = unsafe { &mut *graph };
+        graph.cache = AlignmentCache::new(capacity);
+        for ix in 0..graph.sequences.len() {
+            graph.cache.insert(&graph.sequences[ix].seq, ix);
+        }
+
+        0
+    })
+}
+
+/// Maps hashes of committed sequences to their record, evicting the oldest entries first.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct AlignmentCache {
+    capacity: usize,
+    records: HashMap<u64, usize>,
+    order: VecDeque<u64>,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl AlignmentCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        AlignmentCache { capacity, ..AlignmentCache::default() }
+    }
+
+    /// An empty cache with the same capacity.
+    pub(crate) fn empty_like(&self) -> Self {
+        AlignmentCache::new(self.capacity)
+    }
+
+    /// Index of the record a sequence with the same hash was committed as. The caller must check
+    /// that the sequences are equal.
+    pub(crate) fn lookup(&self, seq: &[u8]) -> Option<usize> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        self.records.get(&hash(seq)).copied()
+    }
+
+    pub(crate) fn insert(&mut self, seq: &[u8], record: usize) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let key = hash(seq);
+        if self.records.insert(key, record).is_none() {
+            self.order.push_back(key);
+        }
+
+        while self.order.len() > self.capacity {
+            let oldest = self.order.pop_front().unwrap();
+            self.records.remove(&oldest);
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+}
+
+fn hash(seq: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seq.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add, graph_of, outputs};
+
+    #[test]
+    fn cache_hits_add_like_alignments() {
+        let reads = ["ACGTTGCA", "ACGATGCA", "ACGTTGCA", "ACGTTCCA", "ACGATGCA", "ACGTTGCA"];
+        let mut cached = PoastaGraph::new();
+        assert_eq!(unsafe { poasta_set_alignment_cache(&mut cached, 8) }, 0);
+        for read in reads {
+            add(&mut cached, read);
+        }
+
+        // The first sequence isn't aligned, so it is neither
+        assert_eq!((cached.cache.hits, cached.cache.misses), (3, 2));
+        assert_eq!(outputs(&cached), outputs(&graph_of(&reads)));
+    }
+}
@@ -0,0 +1,180 @@
+//! Detection of chimeric sequences, e.g., PCR artifacts joining the first part of one haplotype to
+//! the rest of another.
+
+use std::ptr;
+
+use crate::linkage::read_alleles;
+use crate::msa::Columns;
+use crate::phase::variable_columns;
+use crate::{error, free_c_array, into_c_array, PoastaGraph};
+
+/// Struct to hold a sequence whose path switches between two haplotypes.
+#[repr(C)]
+pub struct PoastaChimera {
+    /// Index of the sequence (in insertion order).
+    pub sequence: usize,
+
+    /// First variable MSA column of the sequence that follows the second haplotype.
+    pub switch_column: usize,
+
+    /// Position in the sequence of its first base at or after `switch_column`.
+    pub switch_position: usize,
+
+    /// A sequence of the haplotype before and of the haplotype after the switch, the one agreeing
+    /// with the chimera on the most variable columns.
+    pub first_parent: usize,
+    pub second_parent: usize,
+
+    /// Number of sequences agreeing with the chimera before (after) the switch but not after
+    /// (before) it.
+    pub first_support: usize,
+    pub second_support: usize,
+}
+
+/// Struct to hold the chimeras of a graph.
+#[repr(C)]
+pub struct PoastaChimeras {
+    pub chimeras: *mut PoastaChimera,
+    pub num_chimeras: usize,
+}
+
+/// Flags the active sequences whose path switches between two otherwise exclusive haplotype
+/// bundles, a common PCR artifact. Sequences are compared at the variable MSA columns (see
+/// poasta_phase_blocks). A sequence is a chimera if, at some switch, at least two other sequences
+/// agree with it on all columns before the switch but not after it, at least two agree with it on
+/// all columns after but not before it, and fewer sequences than in either bundle agree with it
+/// everywhere. The switch with the best supported bundles is reported.
+/// Caller must free the result with poasta_free_chimeras.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_detect_chimeras(graph: *const PoastaGraph) -> PoastaChimeras {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaChimeras { chimeras: ptr::null_mut(), num_chimeras: 0 };
+        }
+
+        let chimeras = detect_chimeras(unsafe { &*graph });
+        let num_chimeras = chimeras.len();
+        PoastaChimeras { chimeras: into_c_array(chimeras), num_chimeras }
+    })
+}
+
+/// Frees PoastaChimeras.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_chimeras(chimeras: PoastaChimeras) {
+    error::catch(|| {
+        unsafe { free_c_array(chimeras.chimeras, chimeras.num_chimeras) };
+    })
+}
+
+/// How another sequence compares to a possible chimera at the chimera's variable columns.
+struct Comparison {
+    /// Number of leading and trailing columns with the same allele in both.
+    leading: usize,
+    trailing: usize,
+
+    /// Whether the sequences have different alleles at any column.
+    conflicts: bool,
+}
+
+fn detect_chimeras(graph: &PoastaGraph) -> Vec<PoastaChimera> {
+    let columns = Columns::new(graph);
+    let alleles = read_alleles(graph, &columns, &(0..columns.count).collect::<Vec<_>>());
+    let variable = variable_columns(graph, &columns, &alleles);
+    let indices: Vec<usize> = graph.sequences.iter()
+        .enumerate()
+        .filter(|(_, record)| !record.info.masked)
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut chimeras = Vec::new();
+    for (read, index) in indices.iter().enumerate() {
+        let cols: Vec<usize> = variable.iter().copied().filter(|col| alleles[read][*col].is_some()).collect();
+        let n = cols.len();
+        if n < 2 {
+            continue;
+        }
+
+        let comparisons: Vec<(usize, Comparison)> = (0..alleles.len())
+            .filter(|other| *other != read)
+            .map(|other| {
+                let same = |col: &usize| alleles[other][*col] == alleles[read][*col];
+                let comparison = Comparison {
+                    leading: cols.iter().take_while(|col| same(col)).count(),
+                    trailing: cols.iter().rev().take_while(|col| same(col)).count(),
+                    conflicts: cols.iter().any(|col| alleles[other][*col].is_some() && !same(col)),
+                };
+
+                (other, comparison)
+            })
+            .collect();
+
+        let full_matches = comparisons.iter()
+            .filter(|(_, comparison)| !comparison.conflicts && comparison.leading == n)
+            .count();
+
+        // Sequences agreeing before (after) a switch before column k, but conflicting elsewhere
+        let first_bundle = |k: usize| comparisons.iter().filter(move |(_, c)| c.conflicts && c.leading >= k);
+        let second_bundle = |k: usize| comparisons.iter().filter(move |(_, c)| c.conflicts && c.trailing >= n - k);
+
+        let best = (1..n)
+            .map(|k| (k, first_bundle(k).count(), second_bundle(k).count()))
+            .filter(|(_, first, second)| *first >= 2 && *second >= 2 && full_matches < (*first).min(*second))
+            .max_by_key(|(k, first, second)| ((*first).min(*second), std::cmp::Reverse(*k)));
+
+        let Some((k, first_support, second_support)) = best else {
+            continue;
+        };
+
+        let first_parent = first_bundle(k).max_by_key(|(other, c)| (c.leading, std::cmp::Reverse(*other))).unwrap().0;
+        let second_parent = second_bundle(k).max_by_key(|(other, c)| (c.trailing, std::cmp::Reverse(*other))).unwrap().0;
+
+        let record = &graph.sequences[*index];
+        let switch_column = cols[k];
+        let switch_position = record.path.iter()
+            .position(|node| columns.of_node[node] >= switch_column)
+            .unwrap_or(record.seq.len());
+
+        chimeras.push(PoastaChimera {
+            sequence: *index,
+            switch_column,
+            switch_position,
+            first_parent: indices[first_parent],
+            second_parent: indices[second_parent],
+            first_support,
+            second_support,
+        });
+    }
+
+    chimeras
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::graph_of;
+
+    // Two haplotypes differing at four SNPs, and a read of the first one's first half joined to
+    // the second one's second half
+    const FIRST: &str = "ACGTACGTACGTACGTACGTACGT";
+    const SECOND: &str = "ACGAACGTTCGTACCTACGTAGGT";
+    const CHIMERA: &str = "ACGTACGTACGTACCTACGTAGGT";
+
+    #[test]
+    fn switch_between_haplotypes_is_a_chimera() {
+        let graph = graph_of(&[FIRST, SECOND, FIRST, SECOND, CHIMERA, FIRST]);
+        let chimeras = detect_chimeras(&graph);
+
+        assert_eq!(chimeras.len(), 1);
+        let chimera = &chimeras[0];
+        assert_eq!((chimera.sequence, chimera.switch_position), (4, 14));
+        assert_eq!((chimera.first_parent, chimera.second_parent), (0, 1));
+        assert_eq!((chimera.first_support, chimera.second_support), (3, 2));
+    }
+
+    #[test]
+    fn haplotypes_alone_have_no_chimeras() {
+        let graph = graph_of(&[FIRST, SECOND, FIRST, SECOND, FIRST]);
+
+        assert!(detect_chimeras(&graph).is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::slice;
+
+use crate::consensus::consensus_sequence;
+use crate::error;
+use crate::pairwise::locate;
+use crate::PoastaGraph;
+
+/// Maximum number of consensus bases used to locate the graph origin in a circular read.
+pub(crate) const ORIGIN_ANCHOR_LEN: usize = 32;
+
+/// Enables or disables circular mode for a graph (disabled by default).
+/// In circular mode, each new sequence is rotated before alignment such that it starts at the
+/// origin of the current consensus, so reads of plasmids or mitochondria no longer get split
+/// alignments at the arbitrary linearization point.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_set_circular(graph: *mut PoastaGraph, circular: bool) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        unsafe { (*graph).circular = circular };
+
+        0
+    })
+}
+
+/// Returns the consensus of a circular graph, rotated to its lexicographically smallest
+/// rotation so that the output has a canonical start.
+/// The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_consensus_circular(graph: *const PoastaGraph) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let mut consensus = consensus_sequence(unsafe { &*graph });
+        let start = minimal_rotation(&consensus);
+        consensus.rotate_left(start);
+
+        CString::new(consensus).unwrap().into_raw()
+    })
+}
+
+/// Returns the consensus of a circular graph, rotated such that it starts at the best match of
+/// `motif` (e.g., the origin of replication or a primer), so repeated runs are directly comparable.
+/// Returns NULL if the motif is empty or longer than the consensus.
+/// The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_consensus_rotate_to(
+    graph: *const PoastaGraph,
+    motif: *const c_char,
+    motif_len: usize,
+) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() || motif.is_null() {
+            return ptr::null_mut();
+        }
+
+        let motif = unsafe { slice::from_raw_parts(motif as *const u8, motif_len) };
+        let mut consensus = consensus_sequence(unsafe { &*graph });
+        if motif.is_empty() || motif.len() > consensus.len() {
+            return ptr::null_mut();
+        }
+
+        let doubled = [consensus.as_slice(), consensus.as_slice()].concat();
+        let start = locate(motif, &doubled).start % consensus.len();
+        consensus.rotate_left(start);
+
+        CString::new(consensus).unwrap().into_raw()
+    })
+}
+
+/// Determines by how many bases `seq` should be rotated to the left, such that it starts at the
+/// origin of the graph's consensus. Returns 0 if the origin can't be located reliably.
+pub(crate) fn origin_offset(graph: &PoastaGraph, seq: &[u8]) -> usize {
+    let consensus = consensus_sequence(graph);
+    let anchor = &consensus[..consensus.len().min(ORIGIN_ANCHOR_LEN)];
+    if anchor.is_empty() || seq.is_empty() {
+        return 0;
+    }
+
+    let doubled = [seq, seq].concat();
+    let occurrence = locate(anchor, &doubled);
+    if occurrence.distance * 4 > anchor.len() {
+        return 0;
+    }
+
+    occurrence.start % seq.len()
+}
+
+/// Start of the lexicographically smallest rotation (minimum expression algorithm).
+pub(crate) fn minimal_rotation(seq: &[u8]) -> usize {
+    let n = seq.len();
+    let (mut i, mut j, mut k) = (0, 1, 0);
+
+    while i < n && j < n && k < n {
+        let (a, b) = (seq[(i + k) % n], seq[(j + k) % n]);
+        if a == b {
+            k += 1;
+            continue;
+        }
+
+        if a > b {
+            i += k + 1;
+        } else {
+            j += k + 1;
+        }
+
+        if i == j {
+            j += 1;
+        }
+        k = 0;
+    }
+
+    i.min(j).min(n.saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use poasta::graphs::AlignableRefGraph;
+
+    use super::*;
+    use crate::testing::{add, graph_of, take};
+
+    const PLASMID: &str = "ACGTTGCAAGGCCTTAGCATCGAT";
+
+    fn rotated(seq: &str, by: usize) -> String {
+        format!("{}{}", &seq[by..], &seq[..by])
+    }
+
+    #[test]
+    fn circular_reads_are_rotated_to_the_origin() {
+        let mut graph = graph_of(&[PLASMID]);
+        assert_eq!(unsafe { poasta_graph_set_circular(&mut graph, true) }, 0);
+        let nodes = graph.graph.node_count_with_start_and_end();
+
+        for by in [5, 13, 20] {
+            add(&mut graph, &rotated(PLASMID, by));
+        }
+        assert_eq!(graph.graph.node_count_with_start_and_end(), nodes);
+        assert!(graph.sequences.iter().all(|record| record.seq == PLASMID.as_bytes()));
+
+        // Without circular mode, the linearization point splits the alignment
+        let mut linear = graph_of(&[PLASMID]);
+        add(&mut linear, &rotated(PLASMID, 13));
+        assert!(linear.graph.node_count_with_start_and_end() > nodes);
+    }
+
+    #[test]
+    fn consensus_rotations_have_a_canonical_start() {
+        for by in [0, 7, 19] {
+            let graph = graph_of(&[&rotated(PLASMID, by)]);
+            let canonical = take(unsafe { poasta_get_consensus_circular(&graph) });
+            assert_eq!(canonical, rotated(PLASMID, minimal_rotation(PLASMID.as_bytes())));
+
+            let motif = "CCTTAG";
+            let at_motif = take(unsafe { poasta_consensus_rotate_to(&graph, motif.as_ptr().cast(), motif.len()) });
+            assert_eq!(at_motif, rotated(PLASMID, PLASMID.find(motif).unwrap()));
+        }
+
+        assert_eq!(minimal_rotation(b"CABAB"), 1);
+        let graph = graph_of(&[PLASMID]);
+        assert!(unsafe { poasta_consensus_rotate_to(&graph, "A".as_ptr().cast(), 0) }.is_null());
+    }
+}
@@ -0,0 +1,176 @@
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use crate::consensus::consensus_sequence;
+use crate::dp::CodonPenalty;
+use crate::error;
+use crate::scoring::checked_cost;
+use crate::PoastaGraph;
+
+/// Enables codon-aware gap penalties for sequences added to the graph from now on.
+/// Gaps whose length is not a multiple of three get `frameshift_penalty` added, and so do gaps
+/// that do not start at a codon boundary of the reading frame (`frame` is the offset of the first
+/// codon in the query: 0, 1 or 2). A penalty of 0 disables codon-aware mode.
+/// Codon-aware alignment uses a full dynamic programming aligner, which is slower than the
+/// default aligner. Returns -1 if the graph is NULL, the frame is above 2 or the penalty is
+/// negative or above 255.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_set_codon_aware(graph: *mut PoastaGraph, frame: u8, frameshift_penalty: i32) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || frame > 2 {
+            return error::fail(-1, "graph is NULL or the frame isn't 0, 1 or 2");
+        }
+        if let Err(status) = checked_cost("frameshift_penalty", frameshift_penalty) {
+            return status;
+        }
+
+        let codon = (frameshift_penalty > 0).then_some(CodonPenalty {
+            frame: frame as usize,
+            penalty: frameshift_penalty,
+        });
+
+        unsafe { (*graph).codon = codon };
+
+        0
+    })
+}
+
+/// How `poasta_get_consensus_protein` handles codons containing bases other than A, C, G and T.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PoastaAmbiguity {
+    /// Translate any codon with an ambiguous base to `X`.
+    Unknown = 0,
+    /// Expand IUPAC ambiguity codes, and use the amino acid if all expansions agree (e.g., `GCN`
+    /// is always alanine). Otherwise, translate to `X`.
+    Resolve = 1,
+}
+
+/// Standard genetic code, indexed by codon with bases ordered T, C, A, G.
+const GENETIC_CODE: &[u8; 64] = b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+/// Returns the consensus translated to protein using the standard genetic code, starting at
+/// reading frame `frame` (0, 1 or 2). Stop codons are written as `*`, and trailing bases that don't
+/// form a complete codon are ignored. Returns NULL if the frame is invalid.
+/// The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_consensus_protein(
+    graph: *const PoastaGraph,
+    frame: u8,
+    ambiguity: PoastaAmbiguity,
+) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() || frame > 2 {
+            return ptr::null_mut();
+        }
+
+        let consensus = consensus_sequence(unsafe { &*graph });
+        let protein = translate(consensus.get(frame as usize..).unwrap_or_default(), ambiguity);
+
+        CString::new(protein).unwrap().into_raw()
+    })
+}
+
+/// Translates a nucleotide sequence codon by codon.
+pub(crate) fn translate(seq: &[u8], ambiguity: PoastaAmbiguity) -> Vec<u8> {
+    seq.chunks_exact(3)
+        .map(|codon| translate_codon(codon, ambiguity))
+        .collect()
+}
+
+fn translate_codon(codon: &[u8], ambiguity: PoastaAmbiguity) -> u8 {
+    let expansions: Vec<&[u8]> = codon.iter()
+        .map(|&base| match ambiguity {
+            PoastaAmbiguity::Unknown => iupac_bases(base).filter(|b| b.len() == 1).unwrap_or(b""),
+            PoastaAmbiguity::Resolve => iupac_bases(base).unwrap_or(b""),
+        })
+        .collect();
+
+    let mut amino_acid = None;
+    for &b1 in expansions[0] {
+        for &b2 in expansions[1] {
+            for &b3 in expansions[2] {
+                let aa = GENETIC_CODE[base_index(b1) * 16 + base_index(b2) * 4 + base_index(b3)];
+                match amino_acid {
+                    None => amino_acid = Some(aa),
+                    Some(prev) if prev != aa => return b'X',
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    amino_acid.unwrap_or(b'X')
+}
+
+/// Unambiguous bases represented by a (case-insensitive) IUPAC nucleotide code.
+pub(crate) fn iupac_bases(code: u8) -> Option<&'static [u8]> {
+    let bases: &[u8] = match code.to_ascii_uppercase() {
+        b'A' => b"A",
+        b'C' => b"C",
+        b'G' => b"G",
+        b'T' | b'U' => b"T",
+        b'R' => b"AG",
+        b'Y' => b"CT",
+        b'S' => b"CG",
+        b'W' => b"AT",
+        b'K' => b"GT",
+        b'M' => b"AC",
+        b'B' => b"CGT",
+        b'D' => b"AGT",
+        b'H' => b"ACT",
+        b'V' => b"ACG",
+        b'N' => b"ACGT",
+        _ => return None,
+    };
+
+    Some(bases)
+}
+
+fn base_index(base: u8) -> usize {
+    match base {
+        b'T' => 0,
+        b'C' => 1,
+        b'A' => 2,
+        _ => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add, graph_of, take};
+
+    #[test]
+    fn codon_deletions_start_at_codon_boundaries() {
+        // Without codon-aware gaps, the deleted codon starts at base 10
+        let mut graph = graph_of(&["ATGAGCAGCAGCATAA"]);
+        assert_eq!(unsafe { poasta_graph_set_codon_aware(&mut graph, 0, 8) }, 0);
+        add(&mut graph, "ATGAGCAGCATAA");
+
+        let (reference, read) = (&graph.sequences[0].path, &graph.sequences[1].path);
+        let deleted: Vec<usize> = (0..reference.len()).filter(|pos| !read.contains(&reference[*pos])).collect();
+        assert_eq!(deleted.len(), 3);
+        assert_eq!(deleted[0] % 3, 0, "deleted {deleted:?}");
+        assert_eq!(deleted[2], deleted[0] + 2);
+
+        assert_eq!(unsafe { poasta_graph_set_codon_aware(&mut graph, 3, 8) }, -1);
+        assert_eq!(unsafe { poasta_graph_set_codon_aware(&mut graph, 0, 256) }, -1);
+        assert_eq!(unsafe { poasta_graph_set_codon_aware(&mut graph, 0, 0) }, 0);
+        assert!(graph.codon.is_none());
+    }
+
+    #[test]
+    fn consensus_is_translated_in_the_given_frame() {
+        let graph = graph_of(&["ATGGCATGGTAAC"]);
+        let protein = |frame, ambiguity| take(unsafe { poasta_get_consensus_protein(&graph, frame, ambiguity) });
+
+        assert_eq!(protein(0, PoastaAmbiguity::Unknown), "MAW*");
+        assert_eq!(protein(1, PoastaAmbiguity::Unknown), "WHGN");
+        assert!(unsafe { poasta_get_consensus_protein(&graph, 3, PoastaAmbiguity::Unknown) }.is_null());
+
+        assert_eq!(translate(b"GCNATHTGN", PoastaAmbiguity::Resolve), b"AIX");
+        assert_eq!(translate(b"GCNATHTGN", PoastaAmbiguity::Unknown), b"XXX");
+    }
+}
@@ -0,0 +1,270 @@
+//! Comparison of two graphs, e.g., of technical replicates or of a sample before and after
+//! polishing.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::consensus::consensus_bases;
+use crate::linkage::{by_frequency, read_alleles};
+use crate::msa::Columns;
+use crate::pairwise::{align_affine, PairColumn};
+use crate::phase::variable_columns;
+use crate::{error, free_c_array, into_c_array, PoastaGraph};
+
+/// Struct to hold a variable site of either or both of two compared graphs.
+#[repr(C)]
+pub struct PoastaSiteComparison {
+    /// Position of the site in the consensus of each graph, i.e., the number of consensus bases
+    /// before it, or -1 if the graph has no variable site there.
+    pub pos_a: i64,
+    pub pos_b: i64,
+
+    /// Alleles of the site in each graph (bases, or `-` for deletions) seen in at least two active
+    /// sequences, most frequent first, NUL-terminated. Empty if the graph has no site there.
+    pub alleles_a: *mut c_char,
+    pub alleles_b: *mut c_char,
+}
+
+/// Struct to hold the differences between two graphs.
+#[repr(C)]
+pub struct PoastaGraphComparison {
+    /// Identity of the two consensus sequences, `1 - edit_distance / max(len)`.
+    pub identity: f64,
+
+    /// Differences between the consensus sequences: mismatching bases, bases only in the consensus
+    /// of `a` (insertions) and bases only in the consensus of `b` (deletions).
+    pub substitutions: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+
+    /// Number of sites variable in both graphs with the same alleles, variable in both with
+    /// different alleles, and variable only in `a` or only in `b`.
+    pub shared: usize,
+    pub discordant: usize,
+    pub unique_a: usize,
+    pub unique_b: usize,
+
+    /// All variable sites of either graph, ordered by their position in the consensus of `a`.
+    pub sites: *mut PoastaSiteComparison,
+    pub num_sites: usize,
+}
+
+/// Compares two graphs, e.g., technical replicates, or a sample before and after polishing. The
+/// consensus sequences are aligned to each other, and the variable MSA columns of each graph (see
+/// poasta_phase_blocks) are mapped through this alignment to the other graph's consensus. Sites
+/// at the same consensus position in both graphs are paired up. Returns all zeros if either graph
+/// is NULL. Caller must free the result with poasta_free_graph_comparison.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_compare_graphs(a: *const PoastaGraph, b: *const PoastaGraph) -> PoastaGraphComparison {
+    error::catch(|| {
+        if a.is_null() || b.is_null() {
+            return PoastaGraphComparison {
+                identity: 0.0,
+                substitutions: 0,
+                insertions: 0,
+                deletions: 0,
+                shared: 0,
+                discordant: 0,
+                unique_a: 0,
+                unique_b: 0,
+                sites: ptr::null_mut(),
+                num_sites: 0,
+            };
+        }
+
+        compare_graphs(unsafe { &*a }, unsafe { &*b })
+    })
+}
+
+/// Frees a PoastaGraphComparison, including the allele strings of its sites.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_graph_comparison(comparison: PoastaGraphComparison) {
+    error::catch(|| {
+        unsafe {
+            for site in free_c_array(comparison.sites, comparison.num_sites) {
+                drop(CString::from_raw(site.alleles_a));
+                drop(CString::from_raw(site.alleles_b));
+            }
+        }
+    })
+}
+
+/// A variable site of a graph: its consensus position and alleles.
+struct Site {
+    pos: usize,
+    alleles: Vec<u8>,
+}
+
+fn compare_graphs(a: &PoastaGraph, b: &PoastaGraph) -> PoastaGraphComparison {
+    let (consensus_a, sites_a) = consensus_and_sites(a);
+    let (consensus_b, sites_b) = consensus_and_sites(b);
+
+    // Unit costs make the alignment cost the edit distance
+    let (mut substitutions, mut insertions, mut deletions) = (0, 0, 0);
+    let mut a_to_b = vec![0; consensus_a.len() + 1];
+    let mut b_to_a = vec![0; consensus_b.len() + 1];
+    let (mut next_a, mut next_b) = (0, 0);
+    for column in align_affine(&consensus_a, &consensus_b, 1, 0, 1) {
+        match column {
+            PairColumn::Aligned(i, j) => {
+                substitutions += usize::from(consensus_a[i] != consensus_b[j]);
+                (a_to_b[i], b_to_a[j]) = (j, i);
+                (next_a, next_b) = (i + 1, j + 1);
+            },
+            PairColumn::OnlyFirst(i) => {
+                insertions += 1;
+                a_to_b[i] = next_b;
+                next_a = i + 1;
+            },
+            PairColumn::OnlySecond(j) => {
+                deletions += 1;
+                b_to_a[j] = next_a;
+                next_b = j + 1;
+            },
+        }
+    }
+    (a_to_b[consensus_a.len()], b_to_a[consensus_b.len()]) = (consensus_b.len(), consensus_a.len());
+
+    // Pair each site of a with a site of b at the same position, preferring one with the same alleles
+    let same_alleles = |x: &Site, y: &Site| {
+        let (mut x, mut y) = (x.alleles.clone(), y.alleles.clone());
+        x.sort_unstable();
+        y.sort_unstable();
+        x == y
+    };
+
+    let mut by_pos: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, site) in sites_b.iter().enumerate() {
+        by_pos.entry(site.pos).or_default().push(i);
+    }
+
+    let mut pairs: Vec<(Option<&Site>, Option<&Site>, usize)> = Vec::new();
+    let mut paired_b = vec![false; sites_b.len()];
+    for site in &sites_a {
+        let candidates: Vec<usize> = by_pos.get(&a_to_b[site.pos]).into_iter()
+            .flatten()
+            .copied()
+            .filter(|i| !paired_b[*i])
+            .collect();
+
+        let other = candidates.iter()
+            .find(|i| same_alleles(site, &sites_b[**i]))
+            .or(candidates.first())
+            .copied();
+
+        if let Some(i) = other {
+            paired_b[i] = true;
+        }
+
+        pairs.push((Some(site), other.map(|i| &sites_b[i]), site.pos));
+    }
+
+    for (site, _) in sites_b.iter().zip(&paired_b).filter(|(_, paired)| !**paired) {
+        pairs.push((None, Some(site), b_to_a[site.pos]));
+    }
+    pairs.sort_by_key(|(_, _, pos)| *pos);
+
+    let (mut shared, mut discordant, mut unique_a, mut unique_b) = (0, 0, 0, 0);
+    let sites: Vec<PoastaSiteComparison> = pairs.into_iter()
+        .map(|(site_a, site_b, _)| {
+            match (site_a, site_b) {
+                (Some(x), Some(y)) if same_alleles(x, y) => shared += 1,
+                (Some(_), Some(_)) => discordant += 1,
+                (Some(_), None) => unique_a += 1,
+                _ => unique_b += 1,
+            }
+
+            let pos = |site: Option<&Site>| site.map_or(-1, |site| site.pos as i64);
+            let alleles = |site: Option<&Site>| {
+                CString::new(site.map(|site| site.alleles.clone()).unwrap_or_default()).unwrap().into_raw()
+            };
+
+            PoastaSiteComparison {
+                pos_a: pos(site_a),
+                pos_b: pos(site_b),
+                alleles_a: alleles(site_a),
+                alleles_b: alleles(site_b),
+            }
+        })
+        .collect();
+
+    let max_len = consensus_a.len().max(consensus_b.len());
+    let edit_distance = substitutions + insertions + deletions;
+    let num_sites = sites.len();
+
+    PoastaGraphComparison {
+        identity: if max_len == 0 { 1.0 } else { 1.0 - edit_distance as f64 / max_len as f64 },
+        substitutions,
+        insertions,
+        deletions,
+        shared,
+        discordant,
+        unique_a,
+        unique_b,
+        sites: into_c_array(sites),
+        num_sites,
+    }
+}
+
+/// The consensus (uppercase) of a graph and its variable sites. A site in a column without a
+/// consensus base is placed before the next consensus base.
+fn consensus_and_sites(graph: &PoastaGraph) -> (Vec<u8>, Vec<Site>) {
+    let bases = consensus_bases(graph);
+    let columns = Columns::new(graph);
+    let alleles = read_alleles(graph, &columns, &(0..columns.count).collect::<Vec<_>>());
+
+    let consensus_columns: Vec<usize> = bases.iter().map(|(node, _)| columns.of_node[node]).collect();
+    let sites = variable_columns(graph, &columns, &alleles).into_iter()
+        .map(|col| {
+            let seen = |allele: &u8| alleles.iter().filter(|read| read[col] == Some(*allele)).count() >= 2;
+            Site {
+                pos: consensus_columns.partition_point(|consensus_col| *consensus_col < col),
+                alleles: by_frequency(alleles.iter().filter_map(|read| read[col])).into_iter().filter(seen).collect(),
+            }
+        })
+        .collect();
+
+    (bases.into_iter().map(|(_, base)| base.to_ascii_uppercase()).collect(), sites)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+    use crate::testing::{array, graph_of};
+
+    #[test]
+    fn sites_are_paired_by_consensus_position() {
+        // Both graphs vary at position 2, only b at position 9, and the consensus sequences
+        // differ at position 12
+        let a = graph_of(&["ACGTTGCAGGATCC", "ACGTTGCAGGATCC", "ACGTTGCAGGATCC", "ACCTTGCAGGATCC", "ACCTTGCAGGATCC"]);
+        let b = graph_of(&["ACGTTGCAGGATAC", "ACGTTGCAGGATAC", "ACGTTGCAGGATAC", "ACCTTGCAGTATAC", "ACCTTGCAGTATAC"]);
+        let comparison = unsafe { poasta_compare_graphs(&a, &b) };
+        assert_eq!((comparison.substitutions, comparison.insertions, comparison.deletions), (1, 0, 0));
+        assert_eq!(comparison.identity, 1.0 - 1.0 / 14.0);
+        assert_eq!((comparison.shared, comparison.discordant, comparison.unique_a, comparison.unique_b), (1, 0, 0, 1));
+
+        let sites: Vec<_> = array(comparison.sites, comparison.num_sites).iter()
+            .map(|site| unsafe { (site.pos_a, site.pos_b, CStr::from_ptr(site.alleles_a), CStr::from_ptr(site.alleles_b)) })
+            .collect();
+        assert_eq!(sites, [(2, 2, c"GC", c"GC"), (-1, 9, c"", c"GT")]);
+        unsafe { poasta_free_graph_comparison(comparison) };
+    }
+
+    #[test]
+    fn bases_only_in_one_consensus_are_indels() {
+        let (a, b) = (graph_of(&["ACGTTGCAGGATCC"]), graph_of(&["ACGTTGCAGATCC"]));
+        let comparison = unsafe { poasta_compare_graphs(&a, &b) };
+        assert_eq!((comparison.substitutions, comparison.insertions, comparison.deletions), (0, 1, 0));
+        assert_eq!(comparison.num_sites, 0);
+        unsafe { poasta_free_graph_comparison(comparison) };
+
+        let comparison = unsafe { poasta_compare_graphs(&b, &a) };
+        assert_eq!((comparison.substitutions, comparison.insertions, comparison.deletions), (0, 0, 1));
+        assert_eq!(comparison.identity, 1.0 - 1.0 / 14.0);
+        unsafe { poasta_free_graph_comparison(comparison) };
+    }
+}
@@ -0,0 +1,68 @@
+use std::ptr;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::error;
+use crate::view::GraphView;
+use crate::PoastaGraph;
+
+/// Joins two graphs end-to-end into a new graph: every sink of `a` is linked to every source of `b`.
+/// The sequences of `a` are followed by the sequences of `b`, and their paths are kept as is.
+/// Sequences added to the new graph align across the junction. The input graphs are left
+/// untouched. Returns NULL on error. The caller must free the graph with poasta_free_graph.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_concat(a: *const PoastaGraph, b: *const PoastaGraph) -> *mut PoastaGraph {
+    error::catch(|| {
+        if a.is_null() || b.is_null() {
+            return ptr::null_mut();
+        }
+
+        let (a, b) = unsafe { (&*a, &*b) };
+        let mut joined = a.empty_like();
+
+        let (Ok(map_a), Ok(map_b)) = (joined.append_graph(a), joined.append_graph(b)) else {
+            return ptr::null_mut();
+        };
+
+        if !a.graph.is_empty() && !b.graph.is_empty() {
+            let view_a = GraphView::new(a);
+            let view_b = GraphView::new(b);
+
+            for sink in view_a.predecessors(view_a.end_node()) {
+                for source in view_b.successors(view_b.start_node()) {
+                    joined.links.push((map_a[&sink], map_b[&source]));
+                }
+            }
+        }
+
+        Box::into_raw(Box::new(joined))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poasta_free_graph;
+    use crate::testing::{add, consensus, graph_of};
+
+    #[test]
+    fn sequences_align_across_the_junction() {
+        let (a, b) = (graph_of(&["ACGTACGT", "ACGTACGT"]), graph_of(&["TTGGCCAA", "TTGGCCAA"]));
+        let joined = unsafe { poasta_graph_concat(&a, &b) };
+        assert!(!joined.is_null());
+
+        let mut joined = unsafe { Box::from_raw(joined) };
+        let seqs: Vec<&[u8]> = joined.sequences.iter().map(|record| &record.seq[..]).collect();
+        assert_eq!(seqs, [b"ACGTACGT", b"ACGTACGT", b"TTGGCCAA", b"TTGGCCAA"]);
+
+        // Every base of a read spanning both matches a node of the joined graph
+        let nodes = joined.graph.node_count_with_start_and_end();
+        add(&mut joined, "ACGTACGTTTGGCCAA");
+        assert_eq!(joined.graph.node_count_with_start_and_end(), nodes);
+        assert_eq!(consensus(&joined), "ACGTACGTTTGGCCAA");
+        unsafe { poasta_free_graph(Box::into_raw(joined)) };
+
+        assert_eq!(a.sequences.len(), 2);
+        assert!(a.links.is_empty());
+    }
+}
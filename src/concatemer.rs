@@ -0,0 +1,120 @@
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use crate::circular::ORIGIN_ANCHOR_LEN;
+use crate::consensus::consensus_sequence;
+use crate::pairwise::locate_all;
+use crate::{error, free_c_array, into_c_array, PoastaGraph};
+
+/// Struct to hold the segments of a read, as offsets into the read.
+#[repr(C)]
+pub struct PoastaSegments {
+    pub starts: *mut usize,
+    pub lengths: *mut usize,
+    pub num_segments: usize,
+}
+
+/// Detects tandem copies of an amplicon within a single read (e.g., nanopore rolling-circle
+/// reads), and splits the read in front of each copy. Copies are found by locating an anchor with
+/// at most `max_mismatch` edits: `motif` if given, otherwise the start of the consensus of `graph`
+/// if given, and otherwise the start of the read itself (self-alignment). `graph` and `motif` may
+/// be NULL. Partial copies at the read ends become segments of their own.
+/// Each segment can be added to a graph individually. Caller must free the result with
+/// poasta_free_segments.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_split_concatemers(
+    seq: *const c_char,
+    len: usize,
+    graph: *const PoastaGraph,
+    motif: *const c_char,
+    motif_len: usize,
+    max_mismatch: u8,
+) -> PoastaSegments {
+    error::catch(|| {
+        if seq.is_null() || len == 0 {
+            return PoastaSegments { starts: ptr::null_mut(), lengths: ptr::null_mut(), num_segments: 0 };
+        }
+
+        let seq = unsafe { slice::from_raw_parts(seq as *const u8, len) };
+        let anchor = if !motif.is_null() && motif_len > 0 {
+            unsafe { slice::from_raw_parts(motif as *const u8, motif_len) }.to_vec()
+        } else if !graph.is_null() && !unsafe { &*graph }.graph.is_empty() {
+            let mut consensus = consensus_sequence(unsafe { &*graph });
+            consensus.truncate(ORIGIN_ANCHOR_LEN);
+            consensus
+        } else {
+            seq[..len.min(ORIGIN_ANCHOR_LEN)].to_vec()
+        };
+
+        let segments = concatemer_segments(seq, &anchor, max_mismatch as usize);
+        let num_segments = segments.len();
+
+        PoastaSegments {
+            starts: into_c_array(segments.iter().map(|(start, _)| *start).collect()),
+            lengths: into_c_array(segments.iter().map(|(start, end)| end - start).collect()),
+            num_segments,
+        }
+    })
+}
+
+/// Frees a PoastaSegments.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_segments(segments: PoastaSegments) {
+    error::catch(|| {
+        unsafe {
+            free_c_array(segments.starts, segments.num_segments);
+            free_c_array(segments.lengths, segments.num_segments);
+        }
+    })
+}
+
+/// Splits `seq` in front of each occurrence of `anchor`. Returns the (start, end) of each
+/// non-empty segment.
+pub(crate) fn concatemer_segments(seq: &[u8], anchor: &[u8], max_distance: usize) -> Vec<(usize, usize)> {
+    let mut boundaries: Vec<usize> = locate_all(anchor, seq, max_distance).iter()
+        .map(|occurrence| occurrence.start)
+        .collect();
+    boundaries.insert(0, 0);
+    boundaries.push(seq.len());
+    boundaries.dedup();
+
+    boundaries.windows(2)
+        .map(|w| (w[0], w[1]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COPY: &str = "ACGTTGCAGGATCCTTAGCA";
+
+    fn segments(read: &str, motif: &str, max_mismatch: u8) -> Vec<(usize, usize)> {
+        let segments = unsafe { poasta_split_concatemers(read.as_ptr().cast(), read.len(), ptr::null(), motif.as_ptr().cast(), motif.len(), max_mismatch) };
+        let starts = unsafe { slice::from_raw_parts(segments.starts, segments.num_segments) };
+        let lengths = unsafe { slice::from_raw_parts(segments.lengths, segments.num_segments) };
+        let found = starts.iter().zip(lengths).map(|(start, len)| (*start, *len)).collect();
+        unsafe { poasta_free_segments(segments) };
+
+        found
+    }
+
+    #[test]
+    fn reads_are_split_in_front_of_each_copy() {
+        let read = format!("GCA{COPY}{COPY}{}", &COPY[..12]);
+        assert_eq!(segments(&read, "ACGTTGCA", 0), vec![(0, 3), (3, 20), (23, 20), (43, 12)]);
+
+        // Without a motif, the start of the read is the anchor
+        let copy = format!("{COPY}{}", "GGTACCAATTCGGACTAGCT");
+        let read = format!("{copy}{copy}{copy}");
+        assert_eq!(segments(&read, "", 0), vec![(0, 40), (40, 40), (80, 40)]);
+    }
+
+    #[test]
+    fn anchors_match_within_max_mismatch() {
+        let read = format!("{COPY}{}", COPY.replacen("ACGTTG", "ACCTTG", 1));
+        assert_eq!(segments(&read, "ACGTTGCA", 0), vec![(0, 40)]);
+        assert_eq!(segments(&read, "ACGTTGCA", 1), vec![(0, 20), (20, 20)]);
+    }
+}
@@ -0,0 +1,399 @@
+//! Consensus calling on the weighted graph.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use poasta::graphs::AlignableRefGraph;
+use serde::{Deserialize, Serialize};
+
+use crate::alphabet::PoastaAlphabet;
+use crate::bisulfite::unconverted_symbol;
+use crate::error;
+use crate::graph::{NodeIx, PoastaGraph, Selection};
+use crate::provisional::Evidence;
+use crate::view::GraphView;
+use crate::{free_c_array, into_c_array};
+
+/// Struct to hold a consensus sequence with the support of each base: the number of sequences
+/// supporting it and their fraction (between 0 and 1) as its confidence.
+#[repr(C)]
+pub struct PoastaConsensus {
+    pub sequence: *mut c_char,
+    pub confidence: *mut f64,
+    pub support: *mut usize,
+    pub len: usize,
+}
+
+impl PoastaConsensus {
+    pub(crate) fn empty() -> Self {
+        PoastaConsensus { sequence: ptr::null_mut(), confidence: ptr::null_mut(), support: ptr::null_mut(), len: 0 }
+    }
+
+    /// The consensus of `sequence` with the given per-base support out of `num_sequences`.
+    pub(crate) fn new(sequence: Vec<u8>, support: Vec<usize>, num_sequences: usize) -> Self {
+        let confidence = support.iter().map(|count| *count as f64 / num_sequences.max(1) as f64).collect();
+
+        PoastaConsensus {
+            len: sequence.len(),
+            sequence: CString::new(sequence).unwrap().into_raw(),
+            confidence: into_c_array(confidence),
+            support: into_c_array(support),
+        }
+    }
+}
+
+/// Returns the consensus of the graph, the heaviest path through the weighted graph (heaviest
+/// bundle traversal), with the number of active sequences passing through each consensus base as
+/// its support. The tie-break, minimum support and bisulfite settings of the graph apply.
+/// Returns an empty consensus if the graph is NULL or has no sequences.
+/// Caller must free the result with poasta_free_consensus.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_consensus(graph: *const PoastaGraph) -> PoastaConsensus {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaConsensus::empty();
+        }
+
+        let graph = unsafe { &*graph };
+        let node_support = node_support(graph);
+        let (support, sequence) = consensus_bases(graph).into_iter()
+            .map(|(node, base)| (node_support[node.index()], base))
+            .unzip();
+
+        PoastaConsensus::new(sequence, support, graph.active_sequences().count())
+    })
+}
+
+/// Frees a PoastaConsensus.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_consensus(consensus: PoastaConsensus) {
+    error::catch(|| {
+        if !consensus.sequence.is_null() {
+            drop(unsafe { CString::from_raw(consensus.sequence) });
+        }
+
+        unsafe {
+            free_c_array(consensus.confidence, consensus.len);
+            free_c_array(consensus.support, consensus.len);
+        }
+    })
+}
+
+/// Total weight of each edge, summed over all weighted paths (see `PoastaGraph::weighted_paths`).
+/// An edge between two consecutive bases gets the sum of both base weights, just like poasta
+/// does, scaled by the sequence's decay factor and bumped reads.
+pub(crate) fn edge_weights(graph: &PoastaGraph) -> HashMap<(NodeIx, NodeIx), f64> {
+    selected_edge_weights(graph, Selection::default())
+}
+
+/// Like `edge_weights`, over the paths of the sequences `selection` includes.
+fn selected_edge_weights(graph: &PoastaGraph, selection: Selection) -> HashMap<(NodeIx, NodeIx), f64> {
+    let mut weights = HashMap::new();
+    for weighted in graph.selected_weighted_paths(selection) {
+        for (i, pair) in weighted.path.windows(2).enumerate() {
+            let weight = weighted.weight(i) + weighted.weight(i + 1);
+            *weights.entry((pair[0], pair[1])).or_default() += weight;
+        }
+    }
+
+    weights
+}
+
+/// Number of (active) sequences passing through each node.
+pub(crate) fn node_support(graph: &PoastaGraph) -> Vec<usize> {
+    selected_node_support(graph, Selection::default())
+}
+
+/// Like `node_support`, counting the sequences `selection` includes.
+pub(crate) fn selected_node_support(graph: &PoastaGraph, selection: Selection) -> Vec<usize> {
+    let mut support = vec![0; graph.graph.node_count_with_start_and_end()];
+    for node in graph.selected_sequences(selection).flat_map(|record| &record.path) {
+        support[node.index()] += 1;
+    }
+
+    support
+}
+
+/// Nodes of the view (excluding start and end node) in topological order.
+pub(crate) fn topological_order(view: &GraphView) -> Vec<NodeIx> {
+    let ranks = view.get_node_ordering();
+    let mut nodes: Vec<NodeIx> = view.all_nodes()
+        .filter(|n| *n != view.start_node() && *n != view.end_node())
+        .collect();
+    nodes.sort_by_key(|n| ranks[n.index()]);
+
+    nodes
+}
+
+/// How the consensus resolves alternatives with equal support.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PoastaTieBreak {
+    /// Take the base that was added to the graph first.
+    #[default]
+    FirstSeen = 0,
+    /// Take the lexicographically smallest base.
+    Lexicographic = 1,
+    /// Take the base of the first sequence of the graph (the reference), otherwise fall back to
+    /// `FirstSeen`.
+    Reference = 2,
+    /// Emit the IUPAC ambiguity code of the tied bases if they are aligned to each other,
+    /// otherwise (or if the alphabet isn't DNA or RNA) fall back to `FirstSeen`.
+    Ambiguity = 3,
+}
+
+/// Sets the policy used to resolve ties between equally supported alternatives when computing the
+/// consensus (`PoastaTieBreak::FirstSeen` by default).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_set_consensus_tie_break(graph: *mut PoastaGraph, policy: PoastaTieBreak) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let graph = unsafe { &mut *graph };
+        graph.tie_break = policy;
+        graph.touch();
+
+        0
+    })
+}
+
+/// Finds the heaviest path through the graph (heaviest bundle traversal). Each node picks the
+/// incoming edge with the highest weight, ties are resolved by the highest path score of the
+/// predecessor, and then by the tie-break policy of the graph. The path ends at the highest
+/// scoring sink.
+pub(crate) fn heaviest_path(graph: &PoastaGraph) -> Vec<NodeIx> {
+    heaviest_path_with_ties(graph, Selection::default()).into_iter()
+        .map(|(node, _)| node)
+        .collect()
+}
+
+/// Like `heaviest_path` through the sequences `selection` includes, but also returns for each path
+/// node the aligned nodes of tied alternative branches. Provisional nodes are left out.
+fn heaviest_path_with_ties(graph: &PoastaGraph, selection: Selection) -> Vec<(NodeIx, Vec<NodeIx>)> {
+    if graph.graph.is_empty() {
+        return Vec::new();
+    }
+
+    let view = GraphView::new(graph);
+    let weights = selected_edge_weights(graph, selection);
+    let num_nodes = view.node_count_with_start_and_end();
+    let mut scores = vec![0.0; num_nodes];
+    let support = selected_node_support(graph, selection);
+    let evidence = Evidence::selected(graph, selection);
+
+    let on_reference: Vec<bool> = {
+        let mut on_reference = vec![false; num_nodes];
+        for node in graph.selected_sequences(selection).take(1).flat_map(|record| &record.path) {
+            on_reference[node.index()] = true;
+        }
+        on_reference
+    };
+
+    // Candidates compare by (weight, score), then by preference of the tie-break policy
+    let preference = |node: NodeIx| {
+        let symbol = match graph.tie_break {
+            PoastaTieBreak::Lexicographic => graph.graph.get_symbol(node),
+            _ => 0,
+        };
+        let reference = graph.tie_break == PoastaTieBreak::Reference && on_reference[node.index()];
+
+        (reference, Reverse(symbol), Reverse(node.index()))
+    };
+
+    // Besides the end node's predecessors, active sequences may end in a node that only inactive
+    // sequences continue from
+    let last_nodes: Vec<NodeIx> = topological_order(&view).into_iter()
+        .filter(|node| {
+            let successors: Vec<NodeIx> = view.successors(*node).collect();
+            successors.contains(&view.end_node())
+                || successors.iter().all(|succ| support[succ.index()] == 0)
+        })
+        .collect();
+
+    let candidates = |node: NodeIx, scores: &[f64]| -> Vec<(f64, f64, NodeIx)> {
+        let preds: Vec<NodeIx> = if node == view.end_node() { last_nodes.clone() } else { view.predecessors(node).collect() };
+        preds.into_iter()
+            .filter(|pred| *pred != view.start_node() && support[pred.index()] > 0)
+            .map(|pred| {
+                // Provisional edges can still be traversed, but don't add any weight
+                let weight = if node == view.end_node() || !evidence.is_confirmed_edge(pred, node) {
+                    0.0
+                } else {
+                    weights.get(&(pred, node)).copied().unwrap_or(0.0)
+                };
+                (weight, scores[pred.index()], pred)
+            })
+            .collect()
+    };
+
+    let best = |candidates: &[(f64, f64, NodeIx)]| {
+        candidates.iter()
+            .copied()
+            .max_by(|(w1, s1, p1), (w2, s2, p2)| {
+                w1.total_cmp(w2)
+                    .then(s1.total_cmp(s2))
+                    .then_with(|| preference(*p1).cmp(&preference(*p2)))
+            })
+    };
+
+    for node in topological_order(&view) {
+        if let Some((weight, score, _)) = best(&candidates(node, &scores)) {
+            scores[node.index()] = weight + score;
+        }
+    }
+
+    let best_pred = |node: NodeIx| best(&candidates(node, &scores)).map(|(_, _, pred)| pred);
+
+    // Walk back from the end node, recording the candidates tied with the chosen one. A tied
+    // branch is followed back as long as it runs parallel to the path (aligned node by node).
+    let mut path = Vec::new();
+    let mut ties: HashMap<NodeIx, Vec<NodeIx>> = HashMap::new();
+    let mut node = view.end_node();
+    loop {
+        let candidates = candidates(node, &scores);
+        let Some((weight, score, pred)) = best(&candidates) else {
+            break;
+        };
+
+        for (_, _, other) in candidates.iter().filter(|(w, s, other)| (*w, *s) == (weight, score) && *other != pred) {
+            let (mut on_path, mut on_branch) = (Some(pred), Some(*other));
+            while let (Some(a), Some(b)) = (on_path, on_branch) {
+                if a == b || !graph.graph.get_aligned_nodes(a).contains(&b) {
+                    break;
+                }
+
+                ties.entry(a).or_default().push(b);
+                (on_path, on_branch) = (best_pred(a), best_pred(b));
+            }
+        }
+
+        path.push(pred);
+        node = pred;
+    }
+
+    path.reverse();
+    path.into_iter()
+        .filter(|node| evidence.is_confirmed_node(*node))
+        .map(|node| {
+            let mut node_ties = ties.remove(&node).unwrap_or_default();
+            node_ties.retain(|other| evidence.is_confirmed_node(*other));
+
+            (node, node_ties)
+        })
+        .collect()
+}
+
+/// The consensus sequence along the heaviest path.
+pub(crate) fn consensus_sequence(graph: &PoastaGraph) -> Vec<u8> {
+    selected_consensus_sequence(graph, Selection::default())
+}
+
+/// Like `consensus_sequence`, of the sequences `selection` includes.
+pub(crate) fn selected_consensus_sequence(graph: &PoastaGraph, selection: Selection) -> Vec<u8> {
+    selected_consensus_bases(graph, selection).into_iter().map(|(_, base)| base).collect()
+}
+
+/// The nodes of the heaviest path with the consensus base called for each.
+pub(crate) fn consensus_bases(graph: &PoastaGraph) -> Vec<(NodeIx, u8)> {
+    selected_consensus_bases(graph, Selection::default())
+}
+
+/// Like `consensus_bases`, of the sequences `selection` includes.
+fn selected_consensus_bases(graph: &PoastaGraph, selection: Selection) -> Vec<(NodeIx, u8)> {
+    let support = if graph.bisulfite { selected_node_support(graph, selection) } else { Vec::new() };
+    let alphabet = graph.alphabet();
+    let ambiguity = graph.tie_break == PoastaTieBreak::Ambiguity && alphabet.is_nucleotide();
+
+    heaviest_path_with_ties(graph, selection).into_iter()
+        .map(|(node, ties)| {
+            let symbol = graph.graph.get_symbol(node);
+            if graph.bisulfite && let Some(unconverted) = unconverted_symbol(graph, node, &support) {
+                return (node, unconverted);
+            }
+
+            if !ambiguity {
+                return (node, symbol);
+            }
+
+            let mut bases = vec![symbol.to_ascii_uppercase()];
+            bases.extend(ties.iter().map(|other| graph.graph.get_symbol(*other).to_ascii_uppercase()));
+
+            (node, iupac_code(&bases, alphabet).unwrap_or(symbol))
+        })
+        .collect()
+}
+
+/// IUPAC code representing all of the given bases, if they are all A, C, G or T (U in RNA).
+fn iupac_code(bases: &[u8], alphabet: PoastaAlphabet) -> Option<u8> {
+    let mut mask = 0;
+    for base in bases {
+        mask |= match base {
+            b'A' => 1,
+            b'C' => 2,
+            b'G' => 4,
+            b'T' | b'U' => 8,
+            _ => return None,
+        };
+    }
+
+    match b"-ACMGRSVTWYHKDBN"[mask] {
+        b'T' if alphabet == PoastaAlphabet::Rna => Some(b'U'),
+        code => Some(code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+    use crate::sequence::poasta_sequence_set_active;
+    use crate::testing::{array, consensus, graph_of};
+
+    #[test]
+    fn ties_are_resolved_by_the_policy() {
+        // The first, inactive read adds the T seen first; the others support T, G and C once each
+        let mut graph = graph_of(&["ATGTA", "AGGTA", "ACGTA", "ATGTA"]);
+        assert_eq!(unsafe { poasta_sequence_set_active(&mut graph, 0, false) }, 0);
+
+        let expected = [
+            (PoastaTieBreak::FirstSeen, "ATGTA"),
+            (PoastaTieBreak::Lexicographic, "ACGTA"),
+            (PoastaTieBreak::Reference, "AGGTA"),
+            (PoastaTieBreak::Ambiguity, "ABGTA"),
+        ];
+        for (policy, sequence) in expected {
+            assert_eq!(unsafe { poasta_set_consensus_tie_break(&mut graph, policy) }, 0);
+            assert_eq!(consensus(&graph), sequence, "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn supported_alternatives_arent_ties() {
+        let mut graph = graph_of(&["ATGTA", "ACGTA", "ACGTA"]);
+        for policy in [PoastaTieBreak::FirstSeen, PoastaTieBreak::Reference, PoastaTieBreak::Ambiguity] {
+            unsafe { poasta_set_consensus_tie_break(&mut graph, policy) };
+            assert_eq!(consensus(&graph), "ACGTA", "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn bases_are_supported_by_the_sequences_through_them() {
+        let graph = graph_of(&["ACGTA", "ACGTA", "ACCTA", "ACGT"]);
+        let result = unsafe { poasta_get_consensus(&graph) };
+        let sequence = unsafe { CStr::from_ptr(result.sequence) }.to_str().unwrap();
+        assert_eq!(sequence, "ACGTA");
+        assert_eq!(array(result.support, result.len), [4, 4, 3, 4, 3]);
+        assert_eq!(array(result.confidence, result.len), [1.0, 1.0, 0.75, 1.0, 0.75]);
+        unsafe { poasta_free_consensus(result) };
+
+        let empty = unsafe { poasta_get_consensus(&PoastaGraph::new()) };
+        assert_eq!(empty.len, 0);
+        unsafe { poasta_free_consensus(empty) };
+    }
+}
@@ -0,0 +1,119 @@
+//! Coverage of the consensus as a track for genome browsers.
+
+use std::ffi::CStr;
+use std::fmt::{self, Write};
+use std::fs;
+use std::os::raw::{c_char, c_int};
+
+use crate::consensus::{consensus_bases, node_support};
+use crate::error;
+use crate::export::CONSENSUS;
+use crate::{PoastaGraph, IO_ERROR};
+
+/// Formats of poasta_export_coverage_track.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)] // Only C callers construct formats
+pub enum PoastaTrackFormat {
+    /// Wiggle with a fixed step of one base.
+    Wig = 0,
+    /// bedGraph, with runs of equal coverage merged into one interval.
+    BedGraph = 1,
+}
+
+/// Writes the coverage of each consensus position (the number of active sequences passing through
+/// its base, as in poasta_get_consensus) to the file at `path` (NUL-terminated) in `format`, on a
+/// chromosome named `consensus`, so it can be shown against the consensus in genome browsers.
+/// Returns 0 on success, -1 on invalid arguments and -5 if the file couldn't be written.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_export_coverage_track(
+    graph: *const PoastaGraph,
+    path: *const c_char,
+    format: PoastaTrackFormat,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || path.is_null() {
+            return error::fail(-1, "graph or path is NULL");
+        }
+
+        let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
+            return error::fail(-1, "path isn't valid UTF-8");
+        };
+
+        let coverage = coverage(unsafe { &*graph });
+        let mut out = String::new();
+        match format {
+            PoastaTrackFormat::Wig => write_wig(&mut out, &coverage),
+            PoastaTrackFormat::BedGraph => write_bedgraph(&mut out, &coverage),
+        }
+        .unwrap();
+
+        match fs::write(path, out) {
+            Ok(()) => 0,
+            Err(err) => error::fail(IO_ERROR, &err.to_string()),
+        }
+    })
+}
+
+/// Coverage of each consensus position.
+fn coverage(graph: &PoastaGraph) -> Vec<usize> {
+    let support = node_support(graph);
+    consensus_bases(graph).into_iter()
+        .map(|(node, _)| support[node.index()])
+        .collect()
+}
+
+fn write_wig(out: &mut impl Write, coverage: &[usize]) -> fmt::Result {
+    writeln!(out, "track type=wiggle_0 name=coverage")?;
+    if coverage.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "fixedStep chrom={CONSENSUS} start=1 step=1")?;
+    for value in coverage {
+        writeln!(out, "{value}")?;
+    }
+
+    Ok(())
+}
+
+fn write_bedgraph(out: &mut impl Write, coverage: &[usize]) -> fmt::Result {
+    writeln!(out, "track type=bedGraph name=coverage")?;
+    let mut start = 0;
+    for run in coverage.chunk_by(|a, b| a == b) {
+        writeln!(out, "{CONSENSUS}\t{start}\t{}\t{}", start + run.len(), run[0])?;
+        start += run.len();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{graph_of, temp_path};
+
+    fn track(graph: &PoastaGraph, format: PoastaTrackFormat) -> String {
+        let path = temp_path("coverage.track");
+        assert_eq!(unsafe { poasta_export_coverage_track(graph, path.as_ptr(), format) }, 0);
+        let track = fs::read_to_string(path.to_str().unwrap()).unwrap();
+        fs::remove_file(path.to_str().unwrap()).unwrap();
+
+        track
+    }
+
+    #[test]
+    fn tracks_give_the_coverage_of_each_consensus_base() {
+        let graph = graph_of(&["ACGTACGT", "ACGTACGT", "ACGTAC"]);
+        assert_eq!(coverage(&graph), [3, 3, 3, 3, 3, 3, 2, 2]);
+
+        let wig = track(&graph, PoastaTrackFormat::Wig);
+        assert_eq!(wig, "track type=wiggle_0 name=coverage\nfixedStep chrom=consensus start=1 step=1\n3\n3\n3\n3\n3\n3\n2\n2\n");
+        let bedgraph = track(&graph, PoastaTrackFormat::BedGraph);
+        assert_eq!(bedgraph, "track type=bedGraph name=coverage\nconsensus\t0\t6\t3\nconsensus\t6\t8\t2\n");
+
+        assert_eq!(track(&PoastaGraph::new(), PoastaTrackFormat::Wig), "track type=wiggle_0 name=coverage\n");
+        let missing = c"/nonexistent/coverage.wig";
+        assert_eq!(unsafe { poasta_export_coverage_track(&graph, missing.as_ptr(), PoastaTrackFormat::Wig) }, IO_ERROR);
+    }
+}
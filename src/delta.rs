@@ -0,0 +1,251 @@
+//! Checkpoints of the changes to a graph since a snapshot, so streaming graphs can be saved often
+//! without rewriting everything.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fs;
+use std::mem;
+use std::os::raw::{c_char, c_int};
+use std::sync::Arc;
+
+use poasta::graphs::AlignableRefGraph;
+use serde::{Deserialize, Serialize};
+
+use crate::error;
+use crate::gfa;
+use crate::graph::{saved_records, NodeIx, SequenceInfo, SequenceRecord};
+use crate::serialize::{self, CORRUPTED};
+use crate::{PoastaGraph, IO_ERROR};
+
+const MAGIC: &[u8; 8] = b"POADELTA";
+
+/// The delta, see `Delta`.
+const SECTION_DELTA: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Delta {
+    /// Number of sequences and nodes of the graph at the snapshot, which the delta applies to.
+    base_sequences: usize,
+    base_nodes: usize,
+
+    /// Current info of the sequences at the snapshot, which may have changed since.
+    infos: Vec<SequenceInfo>,
+
+    /// Sequences added since the snapshot.
+    #[serde(with = "saved_records")]
+    records: Vec<SequenceRecord>,
+
+    /// Nodes aligned to each node created since the snapshot.
+    aligned: HashMap<NodeIx, Vec<NodeIx>>,
+
+    /// Configuration, links, path weights, cache, backbone and masked regions, without any
+    /// sequences.
+    state: PoastaGraph,
+}
+
+/// Saves the changes to the graph since `since_snapshot` (see poasta_graph_snapshot) to the file
+/// at `path` (NUL-terminated): the sequences added since, the current state of the sequences
+/// added before and the current configuration. Apply it to the graph as it was at the snapshot
+/// with poasta_graph_apply_delta, e.g., the graph loaded from the last full checkpoint and the
+/// deltas before this one. A delta since snapshot 0 can be applied to an empty graph.
+/// Returns 0 on success, -1 on invalid arguments or a snapshot from the future and -5 if the file
+/// couldn't be written.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_save_delta(graph: *const PoastaGraph, since_snapshot: usize, path: *const c_char) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || path.is_null() {
+            return error::fail(-1, "graph or path is NULL");
+        }
+
+        let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
+            return error::fail(-1, "path isn't valid UTF-8");
+        };
+
+        let Some(delta) = Delta::since(unsafe { &*graph }, since_snapshot) else {
+            return error::fail(-1, "the snapshot is from the future");
+        };
+
+        let data = bincode::serialize(&delta).expect("deltas can be serialized");
+        match fs::write(path, serialize::encode(MAGIC, &[(SECTION_DELTA, &data)])) {
+            Ok(()) => 0,
+            Err(err) => error::fail(IO_ERROR, &err.to_string()),
+        }
+    })
+}
+
+/// Applies the delta saved with poasta_graph_save_delta in the file at `path` (NUL-terminated),
+/// bringing the graph to the state it was saved in. The graph must be in the state of the
+/// snapshot the delta was saved since.
+/// Returns 0 on success, -1 on invalid arguments or if the graph doesn't match the snapshot, -3 if
+/// adding a sequence failed, -5 if the file couldn't be read, -8 if it is corrupted and -9 if it
+/// was written by a newer, incompatible version of the library. The graph is unchanged unless 0
+/// or -3 is returned.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_apply_delta(graph: *mut PoastaGraph, path: *const c_char) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || path.is_null() {
+            return error::fail(-1, "graph or path is NULL");
+        }
+
+        let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
+            return error::fail(-1, "path isn't valid UTF-8");
+        };
+
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) => return error::fail(IO_ERROR, &err.to_string()),
+        };
+
+        let delta: Delta = match serialize::decode(MAGIC, &bytes).and_then(|sections| {
+            let data = serialize::section(&sections, SECTION_DELTA)?;
+            bincode::deserialize(data).map_err(|err| error::fail(CORRUPTED, &err.to_string()))
+        }) {
+            Ok(delta) => delta,
+            Err(status) => return status,
+        };
+
+        match delta.apply(unsafe { &mut *graph }) {
+            Ok(()) => 0,
+            Err(status) => status,
+        }
+    })
+}
+
+impl Delta {
+    /// None if the snapshot is from the future.
+    fn since(graph: &PoastaGraph, since: usize) -> Option<Self> {
+        let (old, new) = gfa::split_at_snapshot(graph, since)?;
+
+        // Nodes are only created for sequences, in order
+        let base_nodes = old.iter()
+            .flat_map(|record| &record.path)
+            .map(|node| node.index() + 1)
+            .max()
+            .unwrap_or(0)
+            .max(2);
+
+        let aligned = new.iter()
+            .flat_map(|record| &record.path)
+            .filter(|node| node.index() >= base_nodes)
+            .map(|node| (*node, graph.graph.get_aligned_nodes(*node).to_vec()))
+            .filter(|(_, aligned)| !aligned.is_empty())
+            .collect();
+
+        Some(Delta {
+            base_sequences: old.len(),
+            base_nodes,
+            infos: old.iter().map(|record| record.info.clone()).collect(),
+            records: new.to_vec(),
+            aligned,
+            state: PoastaGraph {
+                links: graph.links.clone(),
+                path_weights: graph.path_weights.clone(),
+                cache: graph.cache.clone(),
+                backbone: graph.backbone,
+                masked_regions: graph.masked_regions.clone(),
+                ..graph.empty_like()
+            },
+        })
+    }
+
+    fn apply(self, graph: &mut PoastaGraph) -> Result<(), c_int> {
+        let base_nodes = graph.graph.node_count_with_start_and_end();
+        if graph.sequences.len() != self.base_sequences || base_nodes != self.base_nodes || self.infos.len() != self.base_sequences {
+            return Err(error::fail(-1, "the graph isn't in the state of the delta's snapshot"));
+        }
+
+        let is_old = |node: &NodeIx| node.index() < base_nodes;
+        let mut node_map: HashMap<NodeIx, NodeIx> = self.records.iter()
+            .flat_map(|record| &record.path)
+            .chain(self.aligned.values().flatten())
+            .filter(|node| is_old(node))
+            .map(|node| (*node, *node))
+            .collect();
+
+        for (record, info) in Arc::make_mut(&mut graph.sequences).iter_mut().zip(self.infos) {
+            record.info = info;
+        }
+
+        graph.append_records(&self.records, |node| self.aligned.get(&node).map_or(&[], Vec::as_slice), &mut node_map)?;
+
+        // Nodes of the saved graph are the same here if they existed at the snapshot
+        let translate = |node: NodeIx| node_map.get(&node).copied().or(is_old(&node).then_some(node));
+
+        let mut state = self.state;
+        mem::swap(&mut state.graph, &mut graph.graph);
+        mem::swap(&mut state.sequences, &mut graph.sequences);
+        state.kmer_filter = graph.kmer_filter.take();
+        state.links = state.links.iter()
+            .filter_map(|(from, to)| Some((translate(*from)?, translate(*to)?)))
+            .collect();
+        state.masked_regions = state.masked_regions.map(|regions| regions.translated(translate));
+        state.path_weights = state.path_weights.iter().filter_map(|bump| bump.translated(translate)).collect();
+
+        state.generation = graph.generation + 1;
+        state.user_data = graph.user_data;
+        *graph = state;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use super::*;
+    use crate::gfa::poasta_graph_snapshot;
+    use crate::sequence::poasta_sequence_set_active;
+    use crate::serialize::{from_bytes, to_bytes};
+    use crate::testing::{add, graph_of, outputs, temp_path};
+
+    const READS: [&str; 6] = ["ACGTTGCAGGATCC", "ACGATGCAGGATCC", "ACGTTGCAGGTCC", "ACGTTGCAGGATCCA", "ACCTTGCAGGATCC", "ACGTTGCATGATCC"];
+
+    fn apply(graph: &mut PoastaGraph, path: &CString) -> c_int {
+        unsafe { poasta_graph_apply_delta(graph, path.as_ptr()) }
+    }
+
+    #[test]
+    fn applying_a_delta_to_the_checkpoint_gives_the_full_graph() {
+        let mut graph = graph_of(&READS[..3]);
+        let checkpoint = to_bytes(&graph);
+        let snapshot = unsafe { poasta_graph_snapshot(&graph) };
+
+        for read in &READS[3..] {
+            add(&mut graph, read);
+        }
+        unsafe { poasta_sequence_set_active(&mut graph, 1, false) };
+
+        let path = temp_path("graph.delta");
+        assert_eq!(unsafe { poasta_graph_save_delta(&graph, snapshot, path.as_ptr()) }, 0);
+
+        let mut restored = from_bytes(&checkpoint).unwrap();
+        assert_eq!(apply(&mut restored, &path), 0);
+        assert_eq!(outputs(&restored), outputs(&graph));
+        assert!(restored.sequences[1].info.masked);
+
+        // The restored graph is no longer in the state of the snapshot
+        assert_eq!(apply(&mut restored, &path), -1);
+        assert_eq!(restored.sequences.len(), READS.len());
+        fs::remove_file(path.to_str().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn deltas_since_snapshot_0_apply_to_empty_graphs() {
+        let graph = graph_of(&READS);
+        let path = temp_path("empty.delta");
+        assert_eq!(unsafe { poasta_graph_save_delta(&graph, 0, path.as_ptr()) }, 0);
+        assert_eq!(unsafe { poasta_graph_save_delta(&graph, READS.len() + 1, path.as_ptr()) }, -1);
+
+        let mut restored = PoastaGraph::new();
+        assert_eq!(apply(&mut restored, &path), 0);
+        assert_eq!(outputs(&restored), outputs(&graph));
+
+        // A flipped bit fails the checksum
+        let mut bytes = fs::read(path.to_str().unwrap()).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        fs::write(path.to_str().unwrap(), bytes).unwrap();
+        assert_eq!(apply(&mut PoastaGraph::new(), &path), CORRUPTED);
+        fs::remove_file(path.to_str().unwrap()).unwrap();
+    }
+}
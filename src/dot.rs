@@ -0,0 +1,205 @@
+//! Graphviz DOT export.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fmt::Write;
+use std::os::raw::c_char;
+use std::ptr;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::consensus::topological_order;
+use crate::error;
+use crate::provisional::{Evidence, PoastaProvisional};
+use crate::view::GraphView;
+use crate::PoastaGraph;
+
+/// Color of provisional nodes and edges when they are marked.
+const PROVISIONAL_COLOR: &str = "#a0a0a0";
+
+/// Pen width of the edges with the highest coverage in poasta_get_dot, others are scaled down
+/// from it, but never below MIN_PENWIDTH so they stay visible.
+const MAX_PENWIDTH: f64 = 5.0;
+const MIN_PENWIDTH: f64 = 0.5;
+
+/// Direction poasta_get_dot lays out the graph in, Graphviz's `rankdir`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoastaRankDir {
+    /// Left to right, like poasta_get_dot_layers.
+    #[default]
+    LeftRight = 0,
+    /// Top to bottom.
+    TopBottom = 1,
+    /// Right to left.
+    RightLeft = 2,
+    /// Bottom to top.
+    BottomTop = 3,
+}
+
+impl PoastaRankDir {
+    fn as_str(self) -> &'static str {
+        match self {
+            PoastaRankDir::LeftRight => "LR",
+            PoastaRankDir::TopBottom => "TB",
+            PoastaRankDir::RightLeft => "RL",
+            PoastaRankDir::BottomTop => "BT",
+        }
+    }
+}
+
+/// How graph_dot draws the graph.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct DotStyle {
+    pub(crate) rankdir: PoastaRankDir,
+    /// Add the weight of each node to its label.
+    pub(crate) weights: bool,
+    /// Draw edges with a width proportional to the number of sequences passing through them.
+    pub(crate) coverage: bool,
+}
+
+/// Returns the graph in Graphviz DOT format to visualize small graphs, one node per base, with
+/// provisional nodes and edges left out like in poasta_get_gfa. With `include_weights`, node labels
+/// also show the node's weight, the summed (decayed) base weights of the sequences through it.
+/// Edges are drawn with a width proportional to the number of sequences passing through them.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_dot(graph: *const PoastaGraph, include_weights: bool, rankdir: PoastaRankDir) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let style = DotStyle { rankdir, weights: include_weights, coverage: true };
+        let dot = graph_dot(unsafe { &*graph }, PoastaProvisional::Exclude, style);
+        CString::new(dot).unwrap_or_default().into_raw()
+    })
+}
+
+/// Weight of each node, the sum of the weights of the bases through it, scaled by the decay factor
+/// of their sequence and bumped reads, and of the path weights through it.
+fn node_weights(graph: &PoastaGraph) -> Vec<f64> {
+    let mut weights = vec![0.0; graph.graph.node_count_with_start_and_end()];
+    for weighted in graph.weighted_paths() {
+        for (pos, node) in weighted.path.iter().enumerate() {
+            weights[node.index()] += weighted.weight(pos);
+        }
+    }
+
+    weights
+}
+
+/// The graph in DOT format, one node (named by node index) per base, with aligned nodes ranked
+/// together.
+pub(crate) fn graph_dot(graph: &PoastaGraph, provisional: PoastaProvisional, style: DotStyle) -> String {
+    let view = GraphView::new(graph);
+    let evidence = Evidence::new(graph);
+    let nodes: Vec<_> = topological_order(&view).into_iter()
+        .filter(|node| provisional != PoastaProvisional::Exclude || evidence.is_confirmed_node(*node))
+        .collect();
+    let shown: HashSet<_> = nodes.iter().copied().collect();
+    let marked = |confirmed: bool| provisional == PoastaProvisional::Mark && !confirmed;
+
+    let weights = node_weights(graph);
+    let mut coverage: HashMap<_, usize> = HashMap::new();
+    for pair in graph.active_sequences().flat_map(|record| record.path.windows(2)) {
+        *coverage.entry((pair[0], pair[1])).or_default() += 1;
+    }
+    let max_coverage = coverage.values().copied().max().unwrap_or(0).max(1);
+
+    let mut dot = format!(
+        "digraph {{\nrankdir=\"{}\"\nnode [shape=square, style=filled, fillcolor=\"#e3e3e3\", penwidth=0]\n\n",
+        style.rankdir.as_str(),
+    );
+
+    for node in &nodes {
+        let symbol = graph.graph.get_symbol(*node) as char;
+        let mut attributes = if style.weights {
+            // Rounded, so decayed weights don't show float noise
+            vec![format!("label=\"{symbol}\\n{}\"", (weights[node.index()] * 100.0).round() / 100.0)]
+        } else {
+            vec![format!("label=\"{symbol}\"")]
+        };
+        if marked(evidence.is_confirmed_node(*node)) {
+            attributes.push(format!("fontcolor=\"{PROVISIONAL_COLOR}\""));
+        }
+
+        writeln!(dot, "{} [{}]", node.index(), attributes.join(", ")).unwrap();
+    }
+
+    let mut ranked = HashSet::new();
+    for node in &nodes {
+        if ranked.contains(node) {
+            continue;
+        }
+
+        let column: Vec<_> = std::iter::once(*node)
+            .chain(graph.graph.get_aligned_nodes(*node).iter().copied().filter(|other| shown.contains(other)))
+            .collect();
+        if column.len() > 1 {
+            let ids: Vec<String> = column.iter().map(|n| n.index().to_string()).collect();
+            writeln!(dot, "{{rank=same; {}}}", ids.join("; ")).unwrap();
+        }
+
+        ranked.extend(column);
+    }
+
+    for node in &nodes {
+        for succ in view.successors(*node).filter(|succ| shown.contains(succ)) {
+            if provisional == PoastaProvisional::Exclude && !evidence.is_confirmed_edge(*node, succ) {
+                continue;
+            }
+
+            let mut attributes = Vec::new();
+            if marked(evidence.is_confirmed_edge(*node, succ)) {
+                attributes.push(format!("color=\"{PROVISIONAL_COLOR}\", style=dashed"));
+            }
+            if style.coverage {
+                let count = coverage.get(&(*node, succ)).copied().unwrap_or(0);
+                let width = (MAX_PENWIDTH * count as f64 / max_coverage as f64).max(MIN_PENWIDTH);
+                attributes.push(format!("penwidth={width:.2}"));
+            }
+
+            if attributes.is_empty() {
+                writeln!(dot, "{} -> {}", node.index(), succ.index()).unwrap();
+            } else {
+                writeln!(dot, "{} -> {} [{}]", node.index(), succ.index(), attributes.join(", ")).unwrap();
+            }
+        }
+    }
+
+    dot.push_str("}\n");
+
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{graph_of, take};
+
+    #[test]
+    fn nodes_show_weights_and_edges_coverage() {
+        let graph = graph_of(&["ACGT", "AGGT", "ACGT"]);
+        let dot = take(unsafe { poasta_get_dot(&graph, true, PoastaRankDir::TopBottom) });
+        assert_eq!(dot, "digraph {\n\
+            rankdir=\"TB\"\n\
+            node [shape=square, style=filled, fillcolor=\"#e3e3e3\", penwidth=0]\n\n\
+            2 [label=\"A\\n3\"]\n3 [label=\"C\\n2\"]\n6 [label=\"G\\n1\"]\n4 [label=\"G\\n3\"]\n5 [label=\"T\\n3\"]\n\
+            {rank=same; 3; 6}\n\
+            2 -> 6 [penwidth=1.67]\n2 -> 3 [penwidth=3.33]\n3 -> 4 [penwidth=3.33]\n6 -> 4 [penwidth=1.67]\n4 -> 5 [penwidth=5.00]\n\
+            }\n");
+
+        let dot = take(unsafe { poasta_get_dot(&graph, false, PoastaRankDir::BottomTop) });
+        assert!(dot.contains("rankdir=\"BT\"") && dot.contains("2 [label=\"A\"]\n"), "{dot}");
+        assert!(unsafe { poasta_get_dot(ptr::null(), false, PoastaRankDir::LeftRight) }.is_null());
+    }
+
+    #[test]
+    fn edges_stay_visible_at_low_coverage() {
+        let reads: Vec<&str> = std::iter::repeat_n("ACGT", 19).chain(["AGGT"]).collect();
+        let dot = graph_dot(&graph_of(&reads), PoastaProvisional::Include, DotStyle { coverage: true, ..DotStyle::default() });
+        assert!(dot.contains("2 -> 6 [penwidth=0.50]") && dot.contains("2 -> 3 [penwidth=4.75]"), "{dot}");
+        assert!(dot.contains("rankdir=\"LR\""));
+    }
+}
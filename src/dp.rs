@@ -0,0 +1,595 @@
+//! A straightforward dynamic programming aligner of a sequence to the graph.
+//!
+//! poasta's A* aligner is much faster but only supports its built-in cost models. This aligner
+//! fills the full (nodes x query) matrix, which makes it easy to support position-dependent
+//! costs such as codon-aware gap penalties.
+
+use std::cmp::Reverse;
+use std::mem;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use poasta::aligner::scoring::AlignmentCosts;
+use poasta::aligner::{AlignedPair, Alignment};
+use poasta::graphs::AlignableRefGraph;
+use serde::{Deserialize, Serialize};
+
+use crate::graph::{NodeIx, Scoring};
+use crate::metrics;
+use crate::mode::PoastaAlignmentMode;
+use crate::scoring::ScoringParams;
+use crate::timeout;
+
+const INF: i32 = i32::MAX / 4;
+
+/// Extra penalties for gaps that would shift the reading frame of a coding sequence.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub(crate) struct CodonPenalty {
+    /// Offset of the first codon in the query (0, 1 or 2).
+    pub frame: usize,
+
+    /// Added once if a gap's length is not a multiple of three, and once if it does not start at
+    /// a codon boundary.
+    pub penalty: i32,
+}
+
+/// Cheaper gaps within homopolymers of the graph, where sequencers tend to miscount bases.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub(crate) struct HomopolymerGaps {
+    /// Shortest homopolymer with cheaper gaps.
+    pub min_length: usize,
+
+    /// Taken off the gap extension cost per base the homopolymer has beyond `min_length - 1`.
+    pub discount: i32,
+}
+
+/// Costs used by the DP aligner.
+#[derive(Clone, Debug)]
+pub(crate) struct DpCosts {
+    pub mismatch: i32,
+
+    /// Per-pair substitution costs, used instead of `mismatch` when set.
+    pub matrix: Option<Arc<ScoringParams>>,
+
+    /// (open, extend) pairs; a gap uses whichever is cheapest.
+    pub gaps: Vec<(i32, i32)>,
+
+    pub codon: Option<CodonPenalty>,
+    pub homopolymer: Option<HomopolymerGaps>,
+
+    /// Which ends of the query and the graph are free.
+    pub mode: PoastaAlignmentMode,
+
+    /// Taken off the cost of matching bases, for local alignment.
+    pub match_bonus: i32,
+}
+
+impl DpCosts {
+    pub(crate) fn from_scoring(scoring: &Scoring) -> Self {
+        let gaps = match scoring {
+            Scoring::Affine(costs) => vec![(costs.gap_open() as i32, costs.gap_extend() as i32)],
+            Scoring::TwoPiece(costs) => vec![
+                (costs.gap_open() as i32, costs.gap_extend() as i32),
+                (costs.gap_open2() as i32, costs.gap_extend2() as i32),
+            ],
+            Scoring::Custom(params) => params.gaps().into_iter().map(|(extend, open)| (open as i32, extend as i32)).collect(),
+        };
+
+        let mismatch = match scoring {
+            Scoring::Affine(costs) => costs.mismatch(),
+            Scoring::TwoPiece(costs) => costs.mismatch(),
+            Scoring::Custom(params) => params.mismatch_score,
+        };
+
+        let matrix = match scoring {
+            Scoring::Custom(params) => Some(Arc::clone(params)),
+            _ => None,
+        };
+
+        DpCosts { mismatch: mismatch as i32, matrix, gaps, codon: None, homopolymer: None, mode: PoastaAlignmentMode::default(), match_bonus: 0 }
+    }
+
+    /// Cost of aligning the symbols, which are compared regardless of case like in
+    /// `insertion_extend`.
+    fn substitution(&self, graph_symbol: u8, query_symbol: u8) -> i32 {
+        let (graph_symbol, query_symbol) = (graph_symbol.to_ascii_uppercase(), query_symbol.to_ascii_uppercase());
+        let is_match = graph_symbol == query_symbol;
+        let cost = match &self.matrix {
+            Some(params) => params.substitution(graph_symbol, query_symbol) as i32,
+            None if is_match => 0,
+            None => self.mismatch,
+        };
+
+        if is_match { cost - self.match_bonus } else { cost }
+    }
+
+    /// Whether gaps cost the same per base wherever they start, so opening a gap from the best
+    /// closed score is never worse than extending one, and gaps don't need to be extended.
+    fn is_linear(&self) -> bool {
+        self.codon.is_none() && self.gaps.iter().all(|(open, _)| *open == 0)
+    }
+
+    /// Number of gap length residues tracked per gap piece.
+    fn residues(&self) -> usize {
+        if self.codon.is_some() { 3 } else { 1 }
+    }
+
+    /// Penalty for closing a gap with the given length residue.
+    fn close_penalty(&self, residue: usize) -> i32 {
+        match self.codon {
+            Some(codon) if residue != 0 => codon.penalty,
+            _ => 0,
+        }
+    }
+
+    /// Penalty for opening a gap right before query position `qpos`.
+    fn open_penalty(&self, qpos: usize) -> i32 {
+        match self.codon {
+            Some(codon) if !(qpos + 3 - codon.frame % 3).is_multiple_of(3) => codon.penalty,
+            _ => 0,
+        }
+    }
+}
+
+/// Index of a DP state: 0 is (mis)match, followed by insertion states and deletion states for
+/// each gap piece and gap length residue.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    Match,
+    Insertion(usize, usize),
+    Deletion(usize, usize),
+}
+
+/// Working memory of the aligner, kept to align many sequences without allocating it each time.
+#[derive(Default)]
+pub(crate) struct DpBuffers {
+    match_scores: Vec<i32>,
+    closed: Vec<i32>,
+    ins: Vec<i32>,
+    del: Vec<i32>,
+
+    /// Set to stop the alignment early, see `timeout::check_cancelled`.
+    pub(crate) cancel: Option<Arc<AtomicBool>>,
+}
+
+struct Matrix<'a> {
+    costs: &'a DpCosts,
+    symbols: &'a [u8],
+    seq: &'a [u8],
+
+    /// Gap extension discount of each row, from the homopolymer its node is in.
+    discounts: Vec<i32>,
+
+    /// (first, last) column of each row that is filled, and the index of the row's first cell.
+    ranges: Vec<(usize, usize)>,
+    offsets: Vec<usize>,
+    residues: usize,
+    match_scores: Vec<i32>,
+    closed: Vec<i32>,
+    ins: Vec<i32>,
+    del: Vec<i32>,
+}
+
+impl<'a> Matrix<'a> {
+    /// A matrix with the columns in `ranges` of each row, in the memory of `buffers`, which must be
+    /// given back with `release`.
+    fn new(
+        costs: &'a DpCosts,
+        symbols: &'a [u8],
+        seq: &'a [u8],
+        discounts: Vec<i32>,
+        ranges: Vec<(usize, usize)>,
+        buffers: &mut DpBuffers,
+    ) -> Self {
+        let mut offsets = Vec::with_capacity(ranges.len());
+        let mut cells = 0;
+        for (first, last) in &ranges {
+            offsets.push(cells);
+            cells += last + 1 - first;
+        }
+
+        let gap_states = costs.gaps.len() * costs.residues();
+        let filled = |buffer: &mut Vec<i32>, len: usize| {
+            let mut buffer = mem::take(buffer);
+            buffer.clear();
+            buffer.resize(len, INF);
+            buffer
+        };
+
+        Matrix {
+            costs,
+            symbols,
+            seq,
+            discounts,
+            ranges,
+            offsets,
+            residues: costs.residues(),
+            match_scores: filled(&mut buffers.match_scores, cells),
+            closed: filled(&mut buffers.closed, cells),
+            ins: filled(&mut buffers.ins, cells * gap_states),
+            del: filled(&mut buffers.del, cells * gap_states),
+        }
+    }
+
+    fn release(self, buffers: &mut DpBuffers) {
+        buffers.match_scores = self.match_scores;
+        buffers.closed = self.closed;
+        buffers.ins = self.ins;
+        buffers.del = self.del;
+    }
+
+    /// Index of the cell at (row, col), None if the column isn't filled in the row.
+    #[inline]
+    fn cell(&self, row: usize, col: usize) -> Option<usize> {
+        let (first, last) = self.ranges[row];
+        (first..=last).contains(&col).then(|| self.offsets[row] + col - first)
+    }
+
+    /// Like `cell`, for cells that are filled.
+    fn filled_cell(&self, row: usize, col: usize) -> usize {
+        self.cell(row, col).expect("the cell is filled")
+    }
+
+    #[inline]
+    fn gap_ix(&self, cell: usize, piece: usize, residue: usize) -> usize {
+        (cell * self.costs.gaps.len() + piece) * self.residues + residue
+    }
+
+    /// Score of `state` at (row, col), INF for cells that aren't filled.
+    fn get(&self, row: usize, col: usize, state: State) -> i32 {
+        let Some(cell) = self.cell(row, col) else {
+            return INF;
+        };
+
+        match state {
+            State::Match => self.match_scores[cell],
+            State::Insertion(p, r) => self.ins[self.gap_ix(cell, p, r)],
+            State::Deletion(p, r) => self.del[self.gap_ix(cell, p, r)],
+        }
+    }
+
+    /// Cost of deleting the node of `row` as part of a gap.
+    fn deletion_extend(&self, row: usize, extend: i32) -> i32 {
+        (extend - self.discounts[row]).max(0)
+    }
+
+    /// Cost of inserting query base `col - 1` after the node of `row` as part of a gap, which is
+    /// cheaper if it extends the node's homopolymer.
+    fn insertion_extend(&self, row: usize, col: usize, extend: i32) -> i32 {
+        if row > 0 && self.symbols[row - 1].eq_ignore_ascii_case(&self.seq[col - 1]) {
+            (extend - self.discounts[row]).max(0)
+        } else {
+            extend
+        }
+    }
+
+    /// Best score of any state at (row, col), with gaps closed.
+    fn closed(&self, row: usize, col: usize) -> i32 {
+        self.cell(row, col).map_or(INF, |cell| self.closed[cell])
+    }
+
+    fn update_closed(&mut self, row: usize, col: usize) {
+        let cell = self.filled_cell(row, col);
+        let mut best = self.match_scores[cell];
+        for piece in 0..self.costs.gaps.len() {
+            for residue in 0..self.residues {
+                let penalty = self.costs.close_penalty(residue);
+                let ix = self.gap_ix(cell, piece, residue);
+                best = best.min(self.ins[ix].saturating_add(penalty)).min(self.del[ix].saturating_add(penalty));
+            }
+        }
+
+        self.closed[cell] = best;
+    }
+}
+
+/// Aligns `seq` to `graph` in the mode of `costs`, in the memory of `buffers`, which grows as needed and is kept
+/// for reuse. Returns the alignment and its cost.
+pub(crate) fn align<G>(graph: &G, seq: &[u8], costs: &DpCosts, buffers: &mut DpBuffers) -> (Alignment<NodeIx>, i64)
+where
+    G: AlignableRefGraph<NodeIndex = NodeIx>,
+{
+    let (alignment, score) = align_banded(graph, seq, costs, None, buffers).expect("unbanded alignments always exist");
+
+    (alignment, score.into())
+}
+
+/// Like `align`, but only fills the (first, last) query columns of each node in `band`, indexed
+/// by node index, if given. None if no alignment fits in the band.
+pub(crate) fn align_banded<G>(
+    graph: &G,
+    seq: &[u8],
+    costs: &DpCosts,
+    band: Option<&[(usize, usize)]>,
+    buffers: &mut DpBuffers,
+) -> Option<(Alignment<NodeIx>, i32)>
+where
+    G: AlignableRefGraph<NodeIndex = NodeIx>,
+{
+    // Row 0 represents the start node, the other rows are the graph nodes in topological order
+    let ranks = graph.get_node_ordering();
+    let mut nodes: Vec<NodeIx> = graph.all_nodes()
+        .filter(|n| *n != graph.start_node() && *n != graph.end_node())
+        .collect();
+    nodes.sort_by_key(|n| ranks[n.index()]);
+
+    let mut row_of = vec![0; graph.node_count_with_start_and_end()];
+    for (row, node) in nodes.iter().enumerate() {
+        row_of[node.index()] = row + 1;
+    }
+
+    let symbols: Vec<u8> = nodes.iter().map(|n| graph.get_symbol_char(*n) as u8).collect();
+    let mut pred_rows: Vec<Vec<usize>> = nodes.iter()
+        .map(|node| graph.predecessors(*node).map(|p| row_of[p.index()]).collect())
+        .collect();
+
+    let discounts = match costs.homopolymer {
+        Some(homopolymer) => homopolymer_discounts(&symbols, &pred_rows, homopolymer),
+        None => vec![0; nodes.len() + 1],
+    };
+
+    // With a free graph start, any node may follow the start row. Real predecessors come first,
+    // so the backtrace prefers them on ties.
+    if costs.mode.graph_free_begin {
+        for preds in &mut pred_rows {
+            if !preds.contains(&0) {
+                preds.push(0);
+            }
+        }
+    }
+
+    let rows = nodes.len() + 1;
+    let cols = seq.len() + 1;
+    let residues = costs.residues();
+    let linear = costs.is_linear();
+
+    // The start row always includes column 0
+    let ranges: Vec<(usize, usize)> = [graph.start_node()].iter()
+        .chain(&nodes)
+        .map(|node| match band {
+            Some(band) => (band[node.index()].0.min(cols - 1), band[node.index()].1.min(cols - 1)),
+            None => (0, cols - 1),
+        })
+        .enumerate()
+        .map(|(row, (first, last))| if row == 0 { (0, last) } else { (first, last.max(first)) })
+        .collect();
+
+    metrics::record_alignment(ranges.iter().map(|(first, last)| last + 1 - first).sum());
+    let cancel = buffers.cancel.clone();
+    let mut m = Matrix::new(costs, &symbols, seq, discounts, ranges, buffers);
+
+    // Start row: only insertions
+    m.match_scores[0] = 0;
+    m.update_closed(0, 0);
+    let (_, start_last) = m.ranges[0];
+    for col in 1..=start_last {
+        fill_insertions(&mut m, 0, col);
+        m.update_closed(0, col);
+    }
+
+    // With a free query start, the alignment may start after any number of query bases
+    if costs.mode.query_free_begin {
+        m.closed[..=start_last].fill(0);
+    }
+
+    for row in 1..rows {
+        if let Some(cancel) = &cancel {
+            timeout::check_cancelled(cancel);
+        }
+
+        let symbol = symbols[row - 1];
+        let (first, last) = m.ranges[row];
+        for col in first..=last {
+            for (piece, (open, extend)) in costs.gaps.iter().enumerate() {
+                for residue in 0..residues {
+                    let prev_residue = (residue + residues - 1) % residues;
+                    let extend = m.deletion_extend(row, *extend);
+                    let mut best = INF;
+                    for pred in &pred_rows[row - 1] {
+                        if residue == 1 % residues {
+                            best = best.min(m.closed(*pred, col) + open + extend + costs.open_penalty(col));
+                        }
+                        if !linear {
+                            best = best.min(m.get(*pred, col, State::Deletion(piece, prev_residue)) + extend);
+                        }
+                    }
+
+                    let ix = m.gap_ix(m.filled_cell(row, col), piece, residue);
+                    m.del[ix] = best.min(INF);
+                }
+            }
+
+            if col > 0 {
+                let substitution = costs.substitution(symbol, seq[col - 1]);
+                let best = pred_rows[row - 1].iter()
+                    .map(|pred| m.closed(*pred, col - 1) + substitution)
+                    .min()
+                    .unwrap_or(INF);
+
+                let cell = m.filled_cell(row, col);
+                m.match_scores[cell] = best.min(INF);
+                fill_insertions(&mut m, row, col);
+            }
+
+            m.update_closed(row, col);
+        }
+    }
+
+    // Alignments end in the end node's predecessors, or any node with a free graph end, after the
+    // whole query, or any prefix of it with a free query end. Ties go to the longest alignment.
+    let end_rows: Vec<usize> = if costs.mode.graph_free_end && rows > 1 {
+        (1..rows).collect()
+    } else {
+        graph.predecessors(graph.end_node()).map(|p| row_of[p.index()]).collect()
+    };
+    let end_cols = if costs.mode.query_free_end { 0 } else { cols - 1 };
+    let (end_row, end_col) = end_rows.iter()
+        .flat_map(|row| (end_cols..cols).map(move |col| (*row, col)))
+        .min_by_key(|(row, col)| (m.closed(*row, *col), Reverse(*col), *row))
+        .unwrap_or((0, cols - 1));
+
+    // A local alignment without any matching part clips the whole query
+    let score = m.closed(end_row, end_col);
+    let alignment = if score >= INF {
+        None
+    } else if costs.match_bonus > 0 && score >= 0 {
+        Some(((0..seq.len()).map(|qpos| AlignedPair::new(None, Some(qpos))).collect(), 0))
+    } else {
+        Some((backtrace(&m, &nodes, &pred_rows, end_row, end_col), score))
+    };
+    m.release(buffers);
+
+    alignment
+}
+
+/// Gap extension discount of each row (0 for the start row), based on the length of the longest
+/// homopolymer through its node.
+fn homopolymer_discounts(symbols: &[u8], pred_rows: &[Vec<usize>], homopolymer: HomopolymerGaps) -> Vec<i32> {
+    let same = |row: usize, other: usize| other > 0 && symbols[row - 1].eq_ignore_ascii_case(&symbols[other - 1]);
+
+    // Longest run of the same symbol ending and starting in each row, rows being topologically sorted
+    let mut ending = vec![0usize; symbols.len() + 1];
+    let mut succ_rows: Vec<Vec<usize>> = vec![Vec::new(); symbols.len() + 1];
+    for row in 1..=symbols.len() {
+        ending[row] = 1 + pred_rows[row - 1].iter().filter(|p| same(row, **p)).map(|p| ending[*p]).max().unwrap_or(0);
+        for pred in &pred_rows[row - 1] {
+            succ_rows[*pred].push(row);
+        }
+    }
+
+    let mut starting = vec![0; symbols.len() + 1];
+    for row in (1..=symbols.len()).rev() {
+        starting[row] = 1 + succ_rows[row].iter().filter(|s| same(row, **s)).map(|s| starting[*s]).max().unwrap_or(0);
+    }
+
+    (0..=symbols.len())
+        .map(|row| {
+            let length = (ending[row] + starting[row]).saturating_sub(1);
+            if row == 0 || length < homopolymer.min_length {
+                0
+            } else {
+                homopolymer.discount * (length + 1 - homopolymer.min_length) as i32
+            }
+        })
+        .collect()
+}
+
+fn fill_insertions(m: &mut Matrix, row: usize, col: usize) {
+    let costs = m.costs;
+    let residues = m.residues;
+    for (piece, (open, extend)) in costs.gaps.iter().enumerate() {
+        for residue in 0..residues {
+            let prev_residue = (residue + residues - 1) % residues;
+            let extend = m.insertion_extend(row, col, *extend);
+            let mut best = if costs.is_linear() { INF } else { m.get(row, col - 1, State::Insertion(piece, prev_residue)) + extend };
+            if residue == 1 % residues {
+                best = best.min(m.closed(row, col - 1) + open + extend + costs.open_penalty(col - 1));
+            }
+
+            let ix = m.gap_ix(m.filled_cell(row, col), piece, residue);
+            m.ins[ix] = best.min(INF);
+        }
+    }
+}
+
+/// Alignment ending with query base `end_col - 1` in `end_row`; query bases after it are inserted.
+fn backtrace(m: &Matrix, nodes: &[NodeIx], pred_rows: &[Vec<usize>], end_row: usize, end_col: usize) -> Alignment<NodeIx> {
+    let (costs, symbols, seq) = (m.costs, m.symbols, m.seq);
+    let residues = m.residues;
+    let mut alignment: Alignment<NodeIx> = (end_col..seq.len()).rev()
+        .map(|qpos| AlignedPair::new(None, Some(qpos)))
+        .collect();
+
+    let (mut row, mut col) = (end_row, end_col);
+    let mut state: Option<State> = None;
+    while row > 0 || col > 0 {
+        // Query bases before a free start are inserted
+        if row == 0 && state.is_none() && costs.mode.query_free_begin {
+            alignment.extend((0..col).rev().map(|qpos| AlignedPair::new(None, Some(qpos))));
+            break;
+        }
+
+        // Resolve which state the closed score came from
+        let current = state.unwrap_or_else(|| {
+            let target = m.closed(row, col);
+            if m.get(row, col, State::Match) == target {
+                return State::Match;
+            }
+
+            (0..costs.gaps.len())
+                .flat_map(|p| (0..residues).map(move |r| (p, r)))
+                .flat_map(|(p, r)| [State::Insertion(p, r), State::Deletion(p, r)])
+                .find(|s| {
+                    let residue = match s { State::Insertion(_, r) | State::Deletion(_, r) => *r, _ => 0 };
+                    m.get(row, col, *s).saturating_add(costs.close_penalty(residue)) == target
+                })
+                .unwrap()
+        });
+
+        let score = m.get(row, col, current);
+        match current {
+            State::Match => {
+                alignment.push(AlignedPair::new(Some(nodes[row - 1]), Some(col - 1)));
+                let substitution = costs.substitution(symbols[row - 1], seq[col - 1]);
+                row = *pred_rows[row - 1].iter()
+                    .find(|p| m.closed(**p, col - 1) + substitution == score)
+                    .unwrap();
+                col -= 1;
+                state = None;
+            },
+            State::Insertion(piece, residue) => {
+                let (open, extend) = costs.gaps[piece];
+                let extend = m.insertion_extend(row, col, extend);
+                alignment.push(AlignedPair::new(None, Some(col - 1)));
+                let prev_residue = (residue + residues - 1) % residues;
+                state = if residue == 1 % residues
+                    && m.closed(row, col - 1) + open + extend + costs.open_penalty(col - 1) == score
+                {
+                    None
+                } else {
+                    Some(State::Insertion(piece, prev_residue))
+                };
+                col -= 1;
+            },
+            State::Deletion(piece, residue) => {
+                let (open, extend) = costs.gaps[piece];
+                let extend = m.deletion_extend(row, extend);
+                alignment.push(AlignedPair::new(Some(nodes[row - 1]), None));
+                let prev_residue = (residue + residues - 1) % residues;
+                let opened = pred_rows[row - 1].iter().find(|p| {
+                    residue == 1 % residues && m.closed(**p, col) + open + extend + costs.open_penalty(col) == score
+                });
+
+                if let Some(pred) = opened {
+                    row = *pred;
+                    state = None;
+                } else {
+                    row = *pred_rows[row - 1].iter()
+                        .find(|p| m.get(**p, col, State::Deletion(piece, prev_residue)) + extend == score)
+                        .unwrap();
+                    state = Some(State::Deletion(piece, prev_residue));
+                }
+            },
+        }
+    }
+
+    alignment.reverse();
+    alignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scoring;
+
+    #[test]
+    fn symbols_are_compared_regardless_of_case() {
+        let mut costs = DpCosts::from_scoring(&scoring());
+        costs.match_bonus = 1;
+        assert_eq!([costs.substitution(b'a', b'A'), costs.substitution(b'A', b'a')], [-1, -1]);
+        assert_eq!(costs.substitution(b'a', b'C'), 4);
+
+        let custom = DpCosts::from_scoring(&Scoring::Custom(Arc::new(ScoringParams::new(4, 2, 6))));
+        assert_eq!([custom.substitution(b'a', b'A'), custom.substitution(b'g', b'T')], [0, 4]);
+    }
+}
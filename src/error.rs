@@ -0,0 +1,365 @@
+//! Status codes and the last error of each thread, so C callers can print meaningful diagnostics.
+
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// Reasons a call can fail. The value of each error is the status code returned for it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoastaError {
+    /// No error.
+    Ok = 0,
+
+    /// A NULL pointer, or an argument out of range or otherwise invalid.
+    InvalidArgument = -1,
+
+    /// poasta failed to add the first sequence of a graph.
+    FirstSequenceFailed = -2,
+
+    /// poasta failed to add an aligned sequence to the graph.
+    CommitFailed = -3,
+
+    /// The sequence was rejected by the length filter.
+    LengthOutlier = -4,
+
+    /// Reading or writing a file failed.
+    IoError = -5,
+
+    /// A scoring configuration is inconsistent with its gap model.
+    InvalidScoring = -6,
+
+    /// The alignment didn't finish within the time budget.
+    Timeout = -7,
+
+    /// Serialized data is truncated, fails its checksum or isn't what it should be.
+    Corrupted = -8,
+
+    /// Serialized data was written by a newer, incompatible version of the library.
+    UnsupportedVersion = -9,
+
+    /// The library panicked, i.e., hit a bug. Objects passed to the failed call may be left in an
+    /// inconsistent state and should only be freed.
+    Panic = -10,
+
+    /// The output exceeds the maximum size the caller allowed.
+    Truncated = -11,
+}
+
+/// Status of a NULL pointer or otherwise invalid argument.
+pub(crate) const INVALID_ARGUMENT: c_int = PoastaError::InvalidArgument as c_int;
+
+/// Returned by functions with a status code if they panicked.
+pub(crate) const PANIC: c_int = PoastaError::Panic as c_int;
+
+thread_local! {
+    static LAST_ERROR: RefCell<(PoastaError, CString)> = RefCell::new((PoastaError::Ok, CString::default()));
+
+    /// Whether the last error was recorded with details during the current call.
+    static DETAILED: Cell<bool> = const { Cell::new(false) };
+}
+
+impl PoastaError {
+    /// The error of a (negative) status code, None for success and unknown codes.
+    fn from_status(status: c_int) -> Option<Self> {
+        let error = match status {
+            -1 => PoastaError::InvalidArgument,
+            -2 => PoastaError::FirstSequenceFailed,
+            -3 => PoastaError::CommitFailed,
+            -4 => PoastaError::LengthOutlier,
+            -5 => PoastaError::IoError,
+            -6 => PoastaError::InvalidScoring,
+            -7 => PoastaError::Timeout,
+            -8 => PoastaError::Corrupted,
+            -9 => PoastaError::UnsupportedVersion,
+            -10 => PoastaError::Panic,
+            -11 => PoastaError::Truncated,
+            _ => return None,
+        };
+
+        Some(error)
+    }
+}
+
+/// Returns the error of the last call on this thread that failed, or `PoastaError::Ok` if none
+/// did. Successful calls don't reset it.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_last_error() -> PoastaError {
+    catch(|| {
+        LAST_ERROR.with(|last| last.borrow().0)
+    })
+}
+
+/// Returns a description of the last error on this thread, with details such as which argument
+/// was invalid. The string is owned by the library and valid until the next error on this thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_last_error_message() -> *const c_char {
+    catch(|| {
+        LAST_ERROR.with(|last| {
+            let last = last.borrow();
+            if last.0 == PoastaError::Ok {
+                return poasta_strerror(0);
+            }
+
+            last.1.as_ptr()
+        })
+    })
+}
+
+/// Returns a static description of a status code returned by the library (a `PoastaError` value,
+/// or 1 for sequences added along an approximate alignment).
+/// Part of the fast query tier: never allocates and never panics.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_strerror(status: c_int) -> *const c_char {
+    catch(|| {
+        description(status).as_ptr()
+    })
+}
+
+fn description(status: c_int) -> &'static CStr {
+    match status {
+        0 => c"no error",
+        1 => c"sequence added along an approximate alignment after a timeout",
+        -1 => c"invalid argument",
+        -2 => c"adding the first sequence failed",
+        -3 => c"adding an aligned sequence failed",
+        -4 => c"sequence length is an outlier",
+        -5 => c"reading or writing a file failed",
+        -6 => c"invalid scoring configuration",
+        -7 => c"alignment timed out",
+        -8 => c"data is corrupted",
+        -9 => c"data was written by an incompatible version",
+        -10 => c"internal error",
+        -11 => c"output exceeds the maximum size",
+        _ => c"unknown status",
+    }
+}
+
+/// Records a failed status as the last error of this thread, with the status description as
+/// message. Successful statuses are ignored, and so is the status of an error that was already
+/// recorded with details during the current call.
+pub(crate) fn record(status: c_int) {
+    if let Some(error) = PoastaError::from_status(status) {
+        let detailed = DETAILED.get() && LAST_ERROR.with(|last| last.borrow().0 == error);
+        if !detailed {
+            set(error, description(status).to_str().unwrap());
+        }
+    }
+}
+
+/// Records the status as the last error of this thread with a detailed message, and returns it.
+pub(crate) fn fail(status: c_int, message: &str) -> c_int {
+    if let Some(error) = PoastaError::from_status(status) {
+        set(error, &format!("{}: {message}", description(status).to_str().unwrap()));
+        DETAILED.set(true);
+    }
+
+    status
+}
+
+fn set(error: PoastaError, message: &str) {
+    DETAILED.set(false);
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = (error, message));
+}
+
+/// Runs the body of an exported function, turning a panic, which must not unwind into C, into the
+/// function's panic result (see `OnPanic`) with the panic message as the last error.
+pub(crate) fn catch<T: OnPanic>(body: impl FnOnce() -> T) -> T {
+    DETAILED.set(false);
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("panic without a message");
+            fail(PANIC, message);
+
+            T::on_panic()
+        },
+    }
+}
+
+/// What an exported function returns if it panicked.
+pub(crate) trait OnPanic {
+    fn on_panic() -> Self;
+}
+
+impl OnPanic for () {
+    fn on_panic() {}
+}
+
+impl OnPanic for c_int {
+    fn on_panic() -> Self {
+        PANIC
+    }
+}
+
+impl OnPanic for i64 {
+    fn on_panic() -> Self {
+        PANIC as i64
+    }
+}
+
+impl OnPanic for usize {
+    fn on_panic() -> Self {
+        0
+    }
+}
+
+impl OnPanic for u64 {
+    fn on_panic() -> Self {
+        0
+    }
+}
+
+impl OnPanic for f64 {
+    fn on_panic() -> Self {
+        f64::NAN
+    }
+}
+
+impl OnPanic for bool {
+    fn on_panic() -> Self {
+        false
+    }
+}
+
+impl OnPanic for PoastaError {
+    fn on_panic() -> Self {
+        PoastaError::Panic
+    }
+}
+
+impl OnPanic for crate::alphabet::PoastaAlphabet {
+    fn on_panic() -> Self {
+        crate::alphabet::PoastaAlphabet::Auto
+    }
+}
+
+impl OnPanic for crate::query::PoastaAlignParams {
+    fn on_panic() -> Self {
+        crate::query::PoastaAlignParams { mismatch_score: 0, gap_extend: 0, gap_open: 0, mode: Default::default() }
+    }
+}
+
+impl OnPanic for crate::panel::PoastaPanelHit {
+    fn on_panic() -> Self {
+        crate::panel::PoastaPanelHit::none()
+    }
+}
+
+impl OnPanic for crate::query::PoastaQueryAlignment {
+    fn on_panic() -> Self {
+        crate::query::PoastaQueryAlignment::failed(PANIC)
+    }
+}
+
+impl<T> OnPanic for *mut T {
+    fn on_panic() -> Self {
+        ptr::null_mut()
+    }
+}
+
+impl<T> OnPanic for *const T {
+    fn on_panic() -> Self {
+        ptr::null()
+    }
+}
+
+/// Result structs come back empty: all their fields are pointers, numbers and flags, so all zeros
+/// means NULL arrays of length 0.
+macro_rules! on_panic_zeroed {
+    ($($ty:ty),* $(,)?) => {
+        $(impl OnPanic for $ty {
+            fn on_panic() -> Self {
+                // SAFETY: all fields are raw pointers, integers, floats or booleans, for which zero is valid
+                unsafe { mem::zeroed() }
+            }
+        })*
+    };
+}
+
+on_panic_zeroed!(
+    crate::PoastaMsa,
+    crate::bench::PoastaReads,
+    crate::chimera::PoastaChimeras,
+    crate::compare::PoastaGraphComparison,
+    crate::concatemer::PoastaSegments,
+    crate::consensus::PoastaConsensus,
+    crate::eval::PoastaEvaluation,
+    crate::gfa::PoastaSequencePath,
+    crate::profile::PoastaObservedErrors,
+    crate::linkage::PoastaLinkage,
+    crate::linkage::PoastaLinkageList,
+    crate::metrics::PoastaMetrics,
+    crate::msa::PoastaMsaMatrix,
+    crate::multi::PoastaConsensusPaths,
+    crate::pair::PoastaPairConsensus,
+    crate::phase::PoastaPhaseBlocks,
+    crate::query::PoastaQueryAlignments,
+    crate::reference::PoastaRefMap,
+    crate::serialize::PoastaBytes,
+    crate::split::PoastaGraphList,
+    crate::stats::PoastaCostEstimate,
+    crate::stats::PoastaGraphStats,
+    crate::strand::PoastaStrandWeights,
+    crate::umi::PoastaUmiCounts,
+    crate::validate::PoastaValidation,
+);
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::poasta_add_sequence;
+    use crate::testing::graph_of;
+
+    fn last_message() -> String {
+        unsafe { CStr::from_ptr(poasta_last_error_message()) }.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn failed_calls_leave_a_detailed_last_error() {
+        let mut graph = graph_of(&["ACGT"]);
+        let status = unsafe { poasta_add_sequence(&mut graph, c"ACGT".as_ptr(), 4, 4, 2, 300) };
+        assert_eq!(status, INVALID_ARGUMENT);
+        assert_eq!(poasta_last_error(), PoastaError::InvalidArgument);
+        assert_eq!(last_message(), "invalid argument: gap_open is 300, costs must be between 0 and 255");
+
+        // Successful calls don't reset it
+        assert_eq!(unsafe { poasta_add_sequence(&mut graph, c"ACGT".as_ptr(), 4, 4, 2, 6) }, 0);
+        assert_eq!(poasta_last_error(), PoastaError::InvalidArgument);
+
+        // Errors are per thread
+        thread::spawn(|| {
+            assert_eq!(poasta_last_error(), PoastaError::Ok);
+            assert_eq!(last_message(), "no error");
+        }).join().unwrap();
+    }
+
+    #[test]
+    fn statuses_map_to_errors_and_descriptions() {
+        for status in -11..=0 {
+            let error = PoastaError::from_status(status).map_or(0, |error| error as c_int);
+            assert_eq!(error, status);
+            assert_ne!(description(status), c"unknown status");
+        }
+        assert_eq!(PoastaError::from_status(-12), None);
+        assert_eq!(unsafe { CStr::from_ptr(poasta_strerror(-12)) }, c"unknown status");
+
+        // Recording a status keeps the details recorded for it during the call
+        catch(|| {
+            fail(-5, "disk full");
+            record(-5);
+        });
+        assert_eq!(last_message(), "reading or writing a file failed: disk full");
+        catch(|| record(-5));
+        assert_eq!(last_message(), "reading or writing a file failed");
+    }
+}
@@ -0,0 +1,105 @@
+use std::os::raw::c_char;
+use std::slice;
+
+use crate::consensus::consensus_sequence;
+use crate::error;
+use crate::pairwise::{align_affine, PairColumn};
+use crate::PoastaGraph;
+
+/// Q-score reported for a consensus without errors.
+const MAX_QSCORE: f64 = 60.0;
+
+/// Struct to hold the accuracy of a consensus compared to a known truth sequence.
+#[repr(C)]
+#[derive(Default)]
+pub struct PoastaEvaluation {
+    pub edit_distance: usize,
+    pub substitutions: usize,
+
+    /// Consensus bases missing in the truth.
+    pub insertions: usize,
+
+    /// Truth bases missing in the consensus.
+    pub deletions: usize,
+
+    /// Fraction of truth bases correctly called, `1 - edit_distance / truth_len`.
+    pub identity: f64,
+
+    /// Phred-scaled error rate `-10 log10(edit_distance / truth_len)`, at most 60.
+    pub qscore: f64,
+}
+
+/// Compares the consensus of the graph to a known truth sequence, e.g., for validating
+/// parameter choices on control samples. Returns all zeros if the graph or the truth is NULL or
+/// the truth is empty.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_eval_consensus(
+    graph: *const PoastaGraph,
+    truth_seq: *const c_char,
+    truth_len: usize,
+) -> PoastaEvaluation {
+    error::catch(|| {
+        if graph.is_null() || truth_seq.is_null() || truth_len == 0 {
+            return PoastaEvaluation::default();
+        }
+
+        let truth = unsafe { slice::from_raw_parts(truth_seq as *const u8, truth_len) };
+        let consensus = consensus_sequence(unsafe { &*graph });
+
+        // Unit costs make the alignment cost the edit distance
+        let mut eval = PoastaEvaluation::default();
+        for column in align_affine(&consensus, truth, 1, 0, 1) {
+            match column {
+                PairColumn::Aligned(i, j) if consensus[i] != truth[j] => eval.substitutions += 1,
+                PairColumn::Aligned(..) => (),
+                PairColumn::OnlyFirst(_) => eval.insertions += 1,
+                PairColumn::OnlySecond(_) => eval.deletions += 1,
+            }
+        }
+
+        eval.edit_distance = eval.substitutions + eval.insertions + eval.deletions;
+        let error_rate = eval.edit_distance as f64 / truth_len as f64;
+        eval.identity = 1.0 - error_rate;
+        eval.qscore = if eval.edit_distance == 0 { MAX_QSCORE } else { (-10.0 * error_rate.log10()).min(MAX_QSCORE) };
+
+        eval
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+    use crate::testing::graph_of;
+
+    const TRUTH: &str = "ACGTTGCAGGATCCTTAGCA";
+
+    fn eval(graph: &PoastaGraph, truth: &str) -> PoastaEvaluation {
+        unsafe { poasta_eval_consensus(graph, truth.as_ptr().cast(), truth.len()) }
+    }
+
+    #[test]
+    fn errors_are_counted_by_type() {
+        let graph = graph_of(&[TRUTH; 3]);
+        let exact = eval(&graph, TRUTH);
+        assert_eq!(exact.edit_distance, 0);
+        assert_eq!(exact.identity, 1.0);
+        assert_eq!(exact.qscore, MAX_QSCORE);
+
+        // The truth has a substitution, an extra base and a missing base compared to the consensus
+        let truth = "ACGTTCCAGGTCCTTAGTCA";
+        let errors = eval(&graph, truth);
+        assert_eq!((errors.substitutions, errors.insertions, errors.deletions), (1, 1, 1));
+        assert_eq!(errors.edit_distance, 3);
+        assert!((errors.identity - 0.85).abs() < 1e-9);
+        assert!((errors.qscore - -10.0 * 0.15f64.log10()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn missing_inputs_give_zeros() {
+        let graph = graph_of(&[TRUTH]);
+        let missing = unsafe { poasta_eval_consensus(&graph, ptr::null(), 0) };
+        assert_eq!((missing.edit_distance, missing.identity), (0, 0.0));
+    }
+}
@@ -0,0 +1,197 @@
+//! Export of the alignments of all sequences of a graph, recovered from their stored paths, so
+//! read-level detail doesn't require aligning them again.
+
+use std::ffi::CString;
+use std::fmt::{self, Write};
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::consensus::consensus_bases;
+use crate::error;
+use crate::gfa::Segments;
+use crate::graph::SequenceRecord;
+use crate::msa::Columns;
+use crate::provisional::PoastaProvisional;
+use crate::PoastaGraph;
+
+/// Formats of poasta_export_all_alignments.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)] // Only C callers construct formats
+pub enum PoastaAlignmentFormat {
+    /// GAF alignments to the segments of poasta_get_gfa_layers.
+    Gaf = 0,
+    /// SAM alignments to the consensus (poasta_get_consensus), named `consensus`.
+    Sam = 1,
+}
+
+/// Name of the consensus in SAM output and coverage tracks.
+pub(crate) const CONSENSUS: &str = "consensus";
+
+/// Returns the alignment of every sequence of the graph (in insertion order, including inactive
+/// ones) in `format`, taken from the path it was added along.
+/// GAF lines refer to the segments of poasta_get_gfa_layers without provisional elements, and
+/// sequences without a walk there (because they pass through provisional nodes) are unmapped. SAM
+/// records align each sequence to the consensus, with `=` and `X` CIGAR operations, the sequence
+/// ends outside the consensus soft clipped, and sequences sharing no column with the consensus
+/// unmapped.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_export_all_alignments(graph: *const PoastaGraph, format: PoastaAlignmentFormat) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let graph = unsafe { &*graph };
+        let mut out = String::new();
+        match format {
+            PoastaAlignmentFormat::Gaf => write_gaf(&mut out, graph),
+            PoastaAlignmentFormat::Sam => write_sam(&mut out, graph),
+        }
+        .unwrap();
+
+        CString::new(out).unwrap().into_raw()
+    })
+}
+
+fn write_gaf(out: &mut impl Write, graph: &PoastaGraph) -> fmt::Result {
+    let segments = Segments::new(graph, PoastaProvisional::Exclude);
+    for (ix, record) in graph.sequences.iter().enumerate() {
+        let (name, len) = (graph.sequence_name(ix), record.seq.len());
+        let Some(walk) = segments.walk(&record.path) else {
+            writeln!(out, "{name}\t{len}\t0\t0\t*\t*\t0\t0\t0\t0\t0\t0")?;
+            continue;
+        };
+
+        let ids: String = walk.segments.iter().map(|id| format!(">s{id}")).collect();
+        let path_len: usize = walk.segments.iter().map(|id| segments.nodes[*id].len()).sum();
+        let ops: Vec<u8> = record.path.iter()
+            .zip(&record.seq)
+            .map(|(node, base)| if graph.graph.get_symbol(*node) == *base { b'=' } else { b'X' })
+            .collect();
+        let matches = ops.iter().filter(|op| **op == b'=').count();
+
+        writeln!(
+            out,
+            "{name}\t{len}\t0\t{len}\t+\t{ids}\t{path_len}\t{}\t{}\t{matches}\t{len}\t255\tcg:Z:{}",
+            walk.start,
+            walk.end,
+            run_length(&ops),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_sam(out: &mut impl Write, graph: &PoastaGraph) -> fmt::Result {
+    let columns = Columns::new(graph);
+    let consensus = consensus_bases(graph);
+
+    // Consensus position and base of each MSA column
+    let mut at_column = vec![None; columns.count];
+    for (pos, (node, base)) in consensus.iter().enumerate() {
+        at_column[columns.of_node[node]] = Some((pos, *base));
+    }
+
+    writeln!(out, "@HD\tVN:1.6\tSO:unsorted")?;
+    if !consensus.is_empty() {
+        writeln!(out, "@SQ\tSN:{CONSENSUS}\tLN:{}", consensus.len())?;
+    }
+
+    for (ix, record) in graph.sequences.iter().enumerate() {
+        let seq = String::from_utf8_lossy(&record.seq);
+        match consensus_alignment(record, &columns, &at_column) {
+            Some((pos, cigar)) => writeln!(out, "{}\t0\t{CONSENSUS}\t{}\t255\t{cigar}\t*\t0\t0\t{seq}\t*", graph.sequence_name(ix), pos + 1)?,
+            None => writeln!(out, "{}\t4\t*\t0\t0\t*\t*\t0\t0\t{seq}\t*", graph.sequence_name(ix))?,
+        }
+    }
+
+    Ok(())
+}
+
+/// The consensus position the sequence's alignment starts at and its CIGAR, None if no base of
+/// the sequence shares a column with the consensus.
+fn consensus_alignment(record: &SequenceRecord, columns: &Columns, at_column: &[Option<(usize, u8)>]) -> Option<(usize, String)> {
+    let mut ops = Vec::new();
+    let mut start = None;
+    let mut last_column = None;
+    for (node, base) in record.path.iter().zip(&record.seq) {
+        let column = columns.of_node[node];
+
+        // Consensus positions the sequence skipped since its last base
+        if let Some(last) = last_column {
+            ops.extend((last + 1..column).filter(|c| at_column[*c].is_some()).map(|_| b'D'));
+        }
+        last_column = Some(column);
+
+        ops.push(match at_column[column] {
+            Some((pos, consensus_base)) => {
+                start.get_or_insert(pos);
+                if base.eq_ignore_ascii_case(&consensus_base) { b'=' } else { b'X' }
+            },
+            None => b'I',
+        });
+    }
+
+    let start = start?;
+
+    // Bases before the first and after the last aligned one are clipped, deletions there dropped
+    let aligned = |op: &u8| matches!(op, b'=' | b'X');
+    let first = ops.iter().position(aligned).unwrap();
+    let last = ops.iter().rposition(aligned).unwrap();
+    let clip = |ops: &[u8]| vec![b'S'; ops.iter().filter(|op| **op == b'I').count()];
+
+    let ops = [clip(&ops[..first]), ops[first..=last].to_vec(), clip(&ops[last + 1..])].concat();
+
+    Some((start, run_length(&ops)))
+}
+
+/// CIGAR string of the operations, one per column.
+fn run_length(ops: &[u8]) -> String {
+    ops.chunk_by(|a, b| a == b)
+        .map(|run| format!("{}{}", run.len(), run[0] as char))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provisional::poasta_set_min_support;
+    use crate::testing::{graph_of, take};
+
+    const READS: [&str; 6] = ["ACGTACGT", "ACGTACGT", "ACCTACGT", "ACGTCGT", "GGACGTACGT", "TTACGTACGT"];
+
+    fn export(graph: &PoastaGraph, format: PoastaAlignmentFormat) -> Vec<String> {
+        take(unsafe { poasta_export_all_alignments(graph, format) }).lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn sam_records_align_sequences_to_the_consensus() {
+        // The consensus starts with the GG of seq_4, and mismatches share its columns
+        let sam = export(&graph_of(&READS), PoastaAlignmentFormat::Sam);
+        assert_eq!(sam[..2], ["@HD\tVN:1.6\tSO:unsorted", "@SQ\tSN:consensus\tLN:10"]);
+        assert_eq!(sam[2], "seq_0\t0\tconsensus\t3\t255\t8=\t*\t0\t0\tACGTACGT\t*");
+        assert_eq!(sam[4], "seq_2\t0\tconsensus\t3\t255\t2=1X5=\t*\t0\t0\tACCTACGT\t*");
+        assert_eq!(sam[5], "seq_3\t0\tconsensus\t3\t255\t4=1D3=\t*\t0\t0\tACGTCGT\t*");
+        assert_eq!(sam[7], "seq_5\t0\tconsensus\t1\t255\t2X8=\t*\t0\t0\tTTACGTACGT\t*");
+        assert_eq!(sam.len(), 8);
+    }
+
+    #[test]
+    fn gaf_lines_follow_the_segments() {
+        let gaf = export(&graph_of(&READS), PoastaAlignmentFormat::Gaf);
+        assert_eq!(gaf.len(), READS.len());
+        assert_eq!(gaf[2], "seq_2\t8\t0\t8\t+\t>s2>s4>s5>s6>s7\t8\t0\t8\t8\t8\t255\tcg:Z:8=");
+        assert_eq!(gaf[3], "seq_3\t7\t0\t7\t+\t>s2>s3>s5>s7\t7\t0\t7\t7\t7\t255\tcg:Z:7=");
+        assert_eq!(gaf[4], "seq_4\t10\t0\t10\t+\t>s0>s2>s3>s5>s6>s7\t10\t0\t10\t10\t10\t255\tcg:Z:10=");
+        assert!(unsafe { poasta_export_all_alignments(ptr::null(), PoastaAlignmentFormat::Gaf) }.is_null());
+
+        // The C of seq_2 is provisional with a minimum support of 2, so seq_2 has no walk
+        let mut graph = graph_of(&READS);
+        assert_eq!(unsafe { poasta_set_min_support(&mut graph, 2) }, 0);
+        let gaf = export(&graph, PoastaAlignmentFormat::Gaf);
+        assert_eq!(gaf[2], "seq_2\t8\t0\t0\t*\t*\t0\t0\t0\t0\t0\t0");
+        assert!(gaf[0].ends_with("cg:Z:8="));
+    }
+}
@@ -0,0 +1,596 @@
+//! The fast query tier: functions that never allocate and never panic, for latency-critical
+//! callers such as real-time basecall stream processing.
+//!
+//! Alignment in this tier is global, against a frozen copy of a graph whose topological order and
+//! adjacency are computed once, in working memory the caller sets up beforehand.
+
+use std::os::raw::{c_char, c_int};
+use std::slice;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::consensus::topological_order;
+use crate::query::{PoastaEdit, PoastaEditOp};
+use crate::view::GraphView;
+use crate::{error, metrics, PoastaGraph};
+
+const INF: i32 = i32::MAX / 4;
+
+/// Opaque snapshot of a graph in a compact form for fast alignment. Later changes to the
+/// graph don't affect it.
+pub struct PoastaFrozenGraph {
+    /// Symbol and graph node of each row; row 0 is the start node, the others are the nodes in
+    /// topological order.
+    symbols: Vec<u8>,
+    nodes: Vec<i64>,
+
+    /// Predecessor rows of row `r` in `preds[pred_offsets[r]..pred_offsets[r + 1]]`.
+    pred_offsets: Vec<usize>,
+    preds: Vec<usize>,
+
+    /// Rows alignments end in, i.e., the end node's predecessors.
+    end_rows: Vec<usize>,
+
+    /// Scratch slot of each row, reused once all successors of a row are filled.
+    slots: Vec<usize>,
+    num_slots: usize,
+}
+
+/// Opaque working memory for alignment to frozen graphs, reusable across queries and frozen graphs.
+/// Use it from one thread at a time.
+pub struct PoastaScratch {
+    max_len: usize,
+    num_slots: usize,
+
+    /// Best score and best score ending in a deletion of each column, per slot.
+    closed: Vec<i32>,
+    del: Vec<i32>,
+
+    /// Full matrices of all rows for alignments with traceback, with the best score ending in an
+    /// insertion too.
+    num_rows: usize,
+    rows_closed: Vec<i32>,
+    rows_ins: Vec<i32>,
+    rows_del: Vec<i32>,
+}
+
+impl PoastaScratch {
+    /// Grows the memory, if needed, to align queries of up to `max_len` bases to `frozen`.
+    fn reserve(&mut self, frozen: &PoastaFrozenGraph) {
+        let stride = self.max_len + 1;
+        if frozen.num_slots > self.num_slots {
+            self.num_slots = frozen.num_slots;
+            self.closed.resize(self.num_slots * stride, INF);
+            self.del.resize(self.num_slots * stride, INF);
+        }
+
+        if frozen.symbols.len() > self.num_rows {
+            self.num_rows = frozen.symbols.len();
+            self.rows_closed.resize(self.num_rows * stride, INF);
+            self.rows_ins.resize(self.num_rows * stride, INF);
+            self.rows_del.resize(self.num_rows * stride, INF);
+        }
+    }
+
+    /// Whether the memory suffices to align a query of `len` bases to `frozen`.
+    fn fits(&self, frozen: &PoastaFrozenGraph, len: usize) -> bool {
+        len <= self.max_len && frozen.num_slots <= self.num_slots && frozen.symbols.len() <= self.num_rows
+    }
+}
+
+/// Freezes a copy of the graph for alignment with poasta_frozen_align_score or poasta_frozen_align. This
+/// allocates, but aligning to the frozen graph doesn't. The frozen graph can be shared between
+/// threads. Returns NULL if the graph is NULL. Free it with poasta_frozen_graph_free.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_freeze(graph: *const PoastaGraph) -> *mut PoastaFrozenGraph {
+    error::catch(|| {
+        if graph.is_null() {
+            return std::ptr::null_mut();
+        }
+
+        Box::into_raw(Box::new(freeze(unsafe { &*graph })))
+    })
+}
+
+/// Frees a frozen graph.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_frozen_graph_free(frozen: *mut PoastaFrozenGraph) {
+    error::catch(|| {
+        if !frozen.is_null() {
+            unsafe {
+                drop(Box::from_raw(frozen));
+            }
+        }
+    })
+}
+
+/// Creates working memory for aligning queries of up to `max_query_len` bases to the frozen graph,
+/// allocating all of it at once, so aligning with it doesn't. It takes about
+/// `12 * (num_nodes + 1) * (max_query_len + 1)` bytes. Returns NULL if the frozen graph is NULL.
+/// Free it with poasta_scratch_free.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_scratch_create(frozen: *const PoastaFrozenGraph, max_query_len: usize) -> *mut PoastaScratch {
+    error::catch(|| {
+        if frozen.is_null() {
+            error::fail(-1, "frozen graph is NULL");
+            return std::ptr::null_mut();
+        }
+
+        let mut scratch = PoastaScratch {
+            max_len: max_query_len,
+            num_slots: 0,
+            closed: Vec::new(),
+            del: Vec::new(),
+            num_rows: 0,
+            rows_closed: Vec::new(),
+            rows_ins: Vec::new(),
+            rows_del: Vec::new(),
+        };
+        scratch.reserve(unsafe { &*frozen });
+
+        Box::into_raw(Box::new(scratch))
+    })
+}
+
+/// Grows the scratch, if needed, so it can also be used with another frozen graph. This allocates,
+/// so call it before entering the latency-critical part. Returns -1 if either is NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_scratch_reserve(scratch: *mut PoastaScratch, frozen: *const PoastaFrozenGraph) -> c_int {
+    error::catch(|| {
+        if scratch.is_null() || frozen.is_null() {
+            return error::fail(-1, "scratch or frozen graph is NULL");
+        }
+
+        unsafe { (*scratch).reserve(&*frozen) };
+
+        0
+    })
+}
+
+/// Frees scratch memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_scratch_free(scratch: *mut PoastaScratch) {
+    error::catch(|| {
+        if !scratch.is_null() {
+            unsafe {
+                drop(Box::from_raw(scratch));
+            }
+        }
+    })
+}
+
+/// Computes the cost of the best global alignment of `seq` to the frozen graph with simple affine
+/// gap scoring (the score poasta_add_sequence would align with) into `score`, without the
+/// alignment itself. Part of the fast query tier: never allocates and never panics, in time
+/// proportional to the graph size times the query length. Doesn't set the last error.
+/// Returns -1 if an argument is NULL, a cost is negative or above 255, the query is longer than the
+/// scratch's `max_query_len`, or the scratch isn't reserved for the graph.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_frozen_align_score(
+    frozen: *const PoastaFrozenGraph,
+    scratch: *mut PoastaScratch,
+    seq: *const c_char,
+    len: usize,
+    mismatch_score: i32,
+    gap_extend: i32,
+    gap_open: i32,
+    score: *mut i64,
+) -> c_int {
+    error::catch(|| {
+        if frozen.is_null() || scratch.is_null() || seq.is_null() || score.is_null() {
+            return -1;
+        }
+
+        let costs = (mismatch_score, gap_extend, gap_open);
+        let (frozen, scratch) = unsafe { (&*frozen, &mut *scratch) };
+        if !valid_costs(costs) || !scratch.fits(frozen, len) {
+            return -1;
+        }
+
+        let seq = unsafe { slice::from_raw_parts(seq as *const u8, len) };
+        unsafe { *score = align_score(frozen, scratch, seq, costs).into() };
+
+        0
+    })
+}
+
+/// Like poasta_frozen_align_score, but also writes the alignment column by column into the first
+/// `*num_edits` entries of `edits`, which has room for `capacity` columns. An alignment has at most
+/// as many columns as the query has bases plus the graph had nodes when frozen (see
+/// poasta_graph_stats). Never allocates and never panics, and doesn't set the last error.
+/// Returns -1 if an argument is NULL, a cost is negative or above 255, the query is longer than the
+/// scratch's `max_query_len`, the scratch isn't reserved for the graph, or the alignment doesn't
+/// fit in `capacity` columns.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_frozen_align(
+    frozen: *const PoastaFrozenGraph,
+    scratch: *mut PoastaScratch,
+    seq: *const c_char,
+    len: usize,
+    mismatch_score: i32,
+    gap_extend: i32,
+    gap_open: i32,
+    score: *mut i64,
+    edits: *mut PoastaEdit,
+    capacity: usize,
+    num_edits: *mut usize,
+) -> c_int {
+    error::catch(|| {
+        if frozen.is_null() || scratch.is_null() || seq.is_null() || score.is_null() || edits.is_null() || num_edits.is_null() {
+            return -1;
+        }
+
+        let costs = (mismatch_score, gap_extend, gap_open);
+        let (frozen, scratch) = unsafe { (&*frozen, &mut *scratch) };
+        if !valid_costs(costs) || !scratch.fits(frozen, len) {
+            return -1;
+        }
+
+        let seq = unsafe { slice::from_raw_parts(seq as *const u8, len) };
+        let Some((cost, count)) = (unsafe { align_traceback(frozen, scratch, seq, costs, edits, capacity) }) else {
+            return -1;
+        };
+
+        unsafe {
+            *score = cost.into();
+            *num_edits = count;
+        }
+
+        0
+    })
+}
+
+fn freeze(graph: &PoastaGraph) -> PoastaFrozenGraph {
+    let view = GraphView::new(graph);
+    let nodes = topological_order(&view);
+
+    let mut row_of = vec![0; view.node_count_with_start_and_end()];
+    for (row, node) in nodes.iter().enumerate() {
+        row_of[node.index()] = row + 1;
+    }
+
+    let mut symbols = vec![0];
+    let mut node_ids = vec![-1];
+    let mut pred_offsets = vec![0, 0];
+    let mut preds = Vec::new();
+    for node in &nodes {
+        symbols.push(view.get_symbol_char(*node) as u8);
+        node_ids.push(node.index() as i64);
+        preds.extend(view.predecessors(*node).map(|pred| row_of[pred.index()]));
+        pred_offsets.push(preds.len());
+    }
+
+    let mut end_rows: Vec<usize> = view.predecessors(view.end_node()).map(|pred| row_of[pred.index()]).collect();
+    if end_rows.is_empty() {
+        end_rows.push(0);
+    }
+
+    // A row's slot is free again after its last successor; end rows are kept until the end
+    let rows = symbols.len();
+    let mut last_use = vec![0; rows];
+    for row in 1..rows {
+        for pred in &preds[pred_offsets[row]..pred_offsets[row + 1]] {
+            last_use[*pred] = row;
+        }
+    }
+    for row in &end_rows {
+        last_use[*row] = rows;
+    }
+
+    let mut slots = vec![0; rows];
+    let mut free = Vec::new();
+    let mut num_slots = 0;
+    for row in 0..rows {
+        slots[row] = free.pop().unwrap_or_else(|| {
+            num_slots += 1;
+            num_slots - 1
+        });
+
+        if row > 0 {
+            for pred in &preds[pred_offsets[row]..pred_offsets[row + 1]] {
+                if last_use[*pred] == row {
+                    free.push(slots[*pred]);
+                }
+            }
+        }
+    }
+
+    PoastaFrozenGraph { symbols, nodes: node_ids, pred_offsets, preds, end_rows, slots, num_slots }
+}
+
+/// Whether (mismatch, gap extend, gap open) costs given over the FFI are within the range of the
+/// other aligners, like scoring::checked_cost but without reporting the error, which allocates.
+fn valid_costs((mismatch, extend, open): (i32, i32, i32)) -> bool {
+    [mismatch, extend, open].iter().all(|cost| (0..=u8::MAX as i32).contains(cost))
+}
+
+/// Gotoh's algorithm over the rows in topological order, keeping the scores of each row in its
+/// slot of the scratch only while later rows need them.
+fn align_score(frozen: &PoastaFrozenGraph, scratch: &mut PoastaScratch, seq: &[u8], (mismatch, extend, open): (i32, i32, i32)) -> i32 {
+    let width = seq.len() + 1;
+    let stride = scratch.max_len + 1;
+
+    // Start row: only insertions
+    let start = frozen.slots[0] * stride;
+    for col in 0..width {
+        scratch.closed[start + col] = if col == 0 { 0 } else { open + extend * col as i32 };
+        scratch.del[start + col] = INF;
+    }
+
+    for row in 1..frozen.symbols.len() {
+        let preds = &frozen.preds[frozen.pred_offsets[row]..frozen.pred_offsets[row + 1]];
+        let cell = frozen.slots[row] * stride;
+        let mut ins = INF;
+
+        for col in 0..width {
+            let mut del = INF;
+            let mut best = INF;
+            for pred in preds {
+                let pred_cell = frozen.slots[*pred] * stride;
+                del = del.min(scratch.closed[pred_cell + col] + open + extend).min(scratch.del[pred_cell + col] + extend);
+
+                if col > 0 {
+                    let substitution = if frozen.symbols[row] == seq[col - 1] { 0 } else { mismatch };
+                    best = best.min(scratch.closed[pred_cell + col - 1] + substitution);
+                }
+            }
+
+            if col > 0 {
+                ins = (scratch.closed[cell + col - 1] + open + extend).min(ins + extend);
+            }
+
+            scratch.del[cell + col] = del.min(INF);
+            scratch.closed[cell + col] = best.min(del).min(ins).min(INF);
+        }
+    }
+
+    metrics::record_alignment(frozen.symbols.len() * width);
+
+    frozen.end_rows.iter()
+        .map(|row| scratch.closed[frozen.slots[*row] * stride + seq.len()])
+        .min()
+        .unwrap_or(INF)
+}
+
+/// Gotoh's algorithm like `align_score`, but keeping all rows, followed by a traceback from the best
+/// end row that writes the alignment columns to `edits`. Returns the score and the number of
+/// columns, or None if they don't fit in `capacity`.
+unsafe fn align_traceback(
+    frozen: &PoastaFrozenGraph,
+    scratch: &mut PoastaScratch,
+    seq: &[u8],
+    (mismatch, extend, open): (i32, i32, i32),
+    edits: *mut PoastaEdit,
+    capacity: usize,
+) -> Option<(i32, usize)> {
+    let width = seq.len() + 1;
+    let stride = scratch.max_len + 1;
+    let PoastaScratch { rows_closed: closed, rows_ins: ins, rows_del: del, .. } = scratch;
+    let substitution = |row: usize, col: usize| if frozen.symbols[row] == seq[col - 1] { 0 } else { mismatch };
+
+    // Start row: only insertions
+    for col in 0..width {
+        closed[col] = if col == 0 { 0 } else { open + extend * col as i32 };
+        ins[col] = if col == 0 { INF } else { closed[col] };
+        del[col] = INF;
+    }
+
+    for row in 1..frozen.symbols.len() {
+        let preds = &frozen.preds[frozen.pred_offsets[row]..frozen.pred_offsets[row + 1]];
+        let cell = row * stride;
+
+        for col in 0..width {
+            let mut best_del = INF;
+            let mut best = INF;
+            for pred in preds {
+                let pred_cell = pred * stride;
+                best_del = best_del.min(closed[pred_cell + col] + open + extend).min(del[pred_cell + col] + extend);
+
+                if col > 0 {
+                    best = best.min(closed[pred_cell + col - 1] + substitution(row, col));
+                }
+            }
+
+            let best_ins = if col > 0 { (closed[cell + col - 1] + open + extend).min(ins[cell + col - 1] + extend) } else { INF };
+
+            del[cell + col] = best_del.min(INF);
+            ins[cell + col] = best_ins.min(INF);
+            closed[cell + col] = best.min(best_del).min(best_ins).min(INF);
+        }
+    }
+
+    metrics::record_alignment(frozen.symbols.len() * width);
+
+    let end_row = frozen.end_rows.iter()
+        .copied()
+        .min_by_key(|row| closed[row * stride + seq.len()])
+        .unwrap_or(0);
+    let score = closed[end_row * stride + seq.len()];
+
+    // Traceback, writing the columns back to front and reversing them at the end
+    let mut count = 0;
+    let mut push = |op, row: usize, col: Option<usize>| {
+        if count == capacity {
+            return false;
+        }
+
+        let node = if op == PoastaEditOp::Insertion { -1 } else { frozen.nodes[row] };
+        let query_pos = col.map_or(-1, |col| col as i64);
+        unsafe { edits.add(count).write(PoastaEdit { op, node, query_pos }) };
+        count += 1;
+
+        true
+    };
+
+    #[derive(PartialEq)]
+    enum State { Closed, Ins, Del }
+
+    let (mut row, mut col, mut state) = (end_row, seq.len(), State::Closed);
+    while row > 0 || col > 0 {
+        let cell = row * stride + col;
+        let preds = &frozen.preds[frozen.pred_offsets[row]..frozen.pred_offsets[row + 1]];
+
+        match state {
+            State::Closed => {
+                let diagonal = if col > 0 {
+                    preds.iter().find(|pred| closed[*pred * stride + col - 1] + substitution(row, col) == closed[cell])
+                } else {
+                    None
+                };
+
+                if let Some(pred) = diagonal {
+                    let op = if frozen.symbols[row] == seq[col - 1] { PoastaEditOp::Match } else { PoastaEditOp::Mismatch };
+                    if !push(op, row, Some(col - 1)) {
+                        return None;
+                    }
+                    row = *pred;
+                    col -= 1;
+                } else if del[cell] == closed[cell] {
+                    state = State::Del;
+                } else {
+                    state = State::Ins;
+                }
+            },
+            State::Ins => {
+                if !push(PoastaEditOp::Insertion, row, Some(col - 1)) {
+                    return None;
+                }
+                if closed[cell - 1] + open + extend == ins[cell] {
+                    state = State::Closed;
+                }
+                col -= 1;
+            },
+            State::Del => {
+                if !push(PoastaEditOp::Deletion, row, None) {
+                    return None;
+                }
+                let pred = preds.iter().find(|pred| closed[*pred * stride + col] + open + extend == del[cell]);
+                match pred {
+                    Some(pred) => {
+                        row = *pred;
+                        state = State::Closed;
+                    },
+                    None => {
+                        row = *preds.iter().find(|pred| del[*pred * stride + col] + extend == del[cell])?;
+                    },
+                }
+            },
+        }
+    }
+
+    unsafe { slice::from_raw_parts_mut(edits, count) }.reverse();
+
+    Some((score, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+    use crate::mode::PoastaAlignmentMode;
+    use crate::query::{poasta_align_query, poasta_free_query_alignment, PoastaAlignParams};
+    use crate::testing::graph_of;
+
+    const COSTS: (i32, i32, i32) = (4, 2, 6);
+
+    /// Score and columns of the full aligner (poasta_align_query).
+    fn full_alignment(graph: &PoastaGraph, query: &str) -> (i64, Vec<(PoastaEditOp, i64, i64)>) {
+        let (mismatch_score, gap_extend, gap_open) = COSTS;
+        let params = PoastaAlignParams { mismatch_score, gap_extend, gap_open, mode: PoastaAlignmentMode::default() };
+        let alignment = unsafe { poasta_align_query(graph, query.as_ptr() as *const c_char, query.len(), params) };
+        assert_eq!(alignment.status, 0);
+        let edits = unsafe { slice::from_raw_parts(alignment.edits, alignment.num_edits) }.iter()
+            .map(|edit| (edit.op, edit.node, edit.query_pos))
+            .collect();
+        let score = alignment.score;
+        unsafe { poasta_free_query_alignment(alignment) };
+
+        (score, edits)
+    }
+
+    /// Cost of alignment columns, with gaps costing `gap_open + len * gap_extend`.
+    fn cost(edits: &[(PoastaEditOp, i64, i64)]) -> i64 {
+        let (mismatch, extend, open) = COSTS;
+        edits.chunk_by(|a, b| a.0 == b.0)
+            .map(|run| match run[0].0 {
+                PoastaEditOp::Match => 0,
+                PoastaEditOp::Mismatch => mismatch * run.len() as i32,
+                PoastaEditOp::Insertion | PoastaEditOp::Deletion => open + extend * run.len() as i32,
+            })
+            .map(i64::from)
+            .sum()
+    }
+
+    #[test]
+    fn frozen_alignments_match_the_full_aligner() {
+        let graph = graph_of(&["ACGTTGCA", "ACGATGCA", "ACGTTCCA", "ACGTTGCAT"]);
+        let frozen = unsafe { poasta_graph_freeze(&graph) };
+        let scratch = unsafe { poasta_scratch_create(frozen, 16) };
+        let mut edits: Vec<PoastaEdit> = (0..32).map(|_| PoastaEdit { op: PoastaEditOp::Match, node: -1, query_pos: -1 }).collect();
+
+        for query in ["ACGTTGCA", "ACGATCCA", "ACGTGCA", "ACGTTTGCAT", "TTACGTTGCA", "A", "GGGGGGGG"] {
+            let (expected_score, expected_edits) = full_alignment(&graph, query);
+
+            let (seq, len) = (query.as_ptr() as *const c_char, query.len());
+            let (mismatch, extend, open) = COSTS;
+            let (mut score, mut num_edits) = (0, 0);
+            assert_eq!(unsafe { poasta_frozen_align_score(frozen, scratch, seq, len, mismatch, extend, open, &mut score) }, 0);
+            assert_eq!(score, expected_score, "{query}");
+
+            let status = unsafe {
+                poasta_frozen_align(frozen, scratch, seq, len, mismatch, extend, open, &mut score, edits.as_mut_ptr(), edits.len(), &mut num_edits)
+            };
+            assert_eq!((status, score), (0, expected_score), "{query}");
+
+            // Ties can be broken differently, but the columns must add up to the same cost
+            let columns: Vec<_> = edits[..num_edits].iter().map(|edit| (edit.op, edit.node, edit.query_pos)).collect();
+            assert_eq!(cost(&columns), score, "{query}");
+            let query_positions: Vec<_> = columns.iter().filter(|column| column.2 >= 0).map(|column| column.2).collect();
+            assert_eq!(query_positions, (0..len as i64).collect::<Vec<_>>(), "{query}");
+            if expected_score == 0 {
+                assert_eq!(columns, expected_edits, "{query}");
+            }
+        }
+
+        let mut score = 0;
+        let long = "ACGT".repeat(5);
+        assert_eq!(unsafe { poasta_frozen_align_score(frozen, scratch, long.as_ptr() as *const c_char, long.len(), 4, 2, 6, &mut score) }, -1);
+        assert_eq!(unsafe { poasta_frozen_align_score(frozen, ptr::null_mut(), long.as_ptr() as *const c_char, 1, 4, 2, 6, &mut score) }, -1);
+
+        unsafe {
+            poasta_scratch_free(scratch);
+            poasta_frozen_graph_free(frozen);
+        }
+    }
+
+    #[test]
+    fn scratch_is_reserved_for_each_frozen_graph() {
+        let small = unsafe { poasta_graph_freeze(&graph_of(&["ACGT"])) };
+        let large = unsafe { poasta_graph_freeze(&graph_of(&["ACGTTGCA", "ACGATGCA", "ACGTTCCA"])) };
+        let scratch = unsafe { poasta_scratch_create(small, 8) };
+        let (query, mut score) = ("ACGTTGCA", -1);
+        let (seq, len) = (query.as_ptr() as *const c_char, query.len());
+
+        // The scratch has room for the rows of the small graph only, until reserved
+        assert_eq!(unsafe { poasta_frozen_align_score(large, scratch, seq, len, 4, 2, 6, &mut score) }, -1);
+        assert_eq!(unsafe { poasta_scratch_reserve(scratch, large) }, 0);
+        assert_eq!(unsafe { poasta_frozen_align_score(large, scratch, seq, len, 4, 2, 6, &mut score) }, 0);
+        assert_eq!(score, 0);
+        assert_eq!(unsafe { poasta_frozen_align_score(small, scratch, seq, len, 4, 2, 6, &mut score) }, 0);
+        assert_eq!(score, 14);
+
+        // Alignments that don't fit in the edits are rejected
+        let mut edits: Vec<PoastaEdit> = (0..7).map(|_| PoastaEdit { op: PoastaEditOp::Match, node: -1, query_pos: -1 }).collect();
+        let mut num_edits = 0;
+        let status = unsafe { poasta_frozen_align(large, scratch, seq, len, 4, 2, 6, &mut score, edits.as_mut_ptr(), edits.len(), &mut num_edits) };
+        assert_eq!(status, -1);
+        assert_eq!(unsafe { poasta_frozen_align_score(large, scratch, seq, len, -1, 2, 6, &mut score) }, -1);
+
+        unsafe {
+            poasta_scratch_free(scratch);
+            poasta_frozen_graph_free(small);
+            poasta_frozen_graph_free(large);
+        }
+    }
+}
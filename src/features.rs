@@ -0,0 +1,87 @@
+use crate::error;
+
+/// Optional features and alignment modes, see poasta_supports.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoastaFeature {
+    GlobalAlignment = 0,
+    /// Semi-global alignment, where sequences may start and end anywhere in the graph.
+    EndsFreeAlignment = 1,
+    /// Local alignment, clipping the parts of sequences that don't overlap the graph.
+    LocalAlignment = 2,
+    TwoPieceGaps = 3,
+    /// Per-pair substitution costs (poasta_scoring_create).
+    CustomScoring = 4,
+    CodonAwareGaps = 5,
+    AlignmentTimeout = 6,
+    /// Timing spans, the `tracing` build feature.
+    Tracing = 7,
+    /// Allocation counting in poasta_metrics_snapshot, the `alloc-metrics` build feature.
+    AllocationMetrics = 8,
+    HomopolymerGaps = 9,
+    /// Fixture graphs with expected outputs (poasta_fixture_graph), the `test-support` build
+    /// feature.
+    TestSupport = 10,
+}
+
+impl PoastaFeature {
+    /// The feature with the given value, None for values this version doesn't know.
+    fn from_value(value: u32) -> Option<Self> {
+        let feature = match value {
+            0 => PoastaFeature::GlobalAlignment,
+            1 => PoastaFeature::EndsFreeAlignment,
+            2 => PoastaFeature::LocalAlignment,
+            3 => PoastaFeature::TwoPieceGaps,
+            4 => PoastaFeature::CustomScoring,
+            5 => PoastaFeature::CodonAwareGaps,
+            6 => PoastaFeature::AlignmentTimeout,
+            7 => PoastaFeature::Tracing,
+            8 => PoastaFeature::AllocationMetrics,
+            9 => PoastaFeature::HomopolymerGaps,
+            10 => PoastaFeature::TestSupport,
+            _ => return None,
+        };
+
+        Some(feature)
+    }
+}
+
+/// Whether this build of the library supports `feature`, a `PoastaFeature` value, so hosts
+/// linking against it dynamically can degrade gracefully instead of checking for symbols. Values
+/// this build doesn't know, e.g., features of a newer header, aren't supported.
+/// Part of the fast query tier: never allocates and never panics.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_supports(feature: u32) -> bool {
+    error::catch(|| {
+        let Some(feature) = PoastaFeature::from_value(feature) else {
+            return false;
+        };
+
+        match feature {
+            PoastaFeature::GlobalAlignment
+            | PoastaFeature::TwoPieceGaps
+            | PoastaFeature::CustomScoring
+            | PoastaFeature::CodonAwareGaps
+            | PoastaFeature::AlignmentTimeout
+            | PoastaFeature::HomopolymerGaps
+            | PoastaFeature::EndsFreeAlignment
+            | PoastaFeature::LocalAlignment => true,
+            PoastaFeature::Tracing => cfg!(feature = "tracing"),
+            PoastaFeature::AllocationMetrics => cfg!(feature = "alloc-metrics"),
+            PoastaFeature::TestSupport => cfg!(feature = "test-support"),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_features_are_unsupported() {
+        assert!(poasta_supports(PoastaFeature::GlobalAlignment as u32));
+        assert!(poasta_supports(PoastaFeature::LocalAlignment as u32));
+        assert!(!poasta_supports(PoastaFeature::TestSupport as u32 + 1));
+        assert!(!poasta_supports(u32::MAX));
+    }
+}
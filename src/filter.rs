@@ -0,0 +1,82 @@
+use std::os::raw::c_int;
+
+use serde::{Deserialize, Serialize};
+
+use crate::consensus::consensus_sequence;
+use crate::error::{self, PoastaError};
+use crate::PoastaGraph;
+
+/// Status returned when adding a sequence whose length is outside the configured length filter.
+pub(crate) const LENGTH_OUTLIER: c_int = PoastaError::LengthOutlier as c_int;
+
+/// Rejects sequences whose length is far from the current consensus length, e.g., truncated or
+/// concatemeric reads. A sequence added from now on is only accepted if its length is between
+/// `min_ratio` and `max_ratio` times the consensus length (e.g., 0.8 and 1.2 for ±20%); otherwise,
+/// adding it returns -4 and leaves the graph untouched. A ratio of 0 disables that bound.
+/// The first sequence of a graph (or any sequence while there is no consensus) is always accepted.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_set_length_filter(graph: *mut PoastaGraph, min_ratio: f64, max_ratio: f64) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || min_ratio.is_nan() || max_ratio.is_nan() || min_ratio < 0.0 || max_ratio < 0.0 {
+            return error::fail(-1, "graph is NULL or a ratio is negative or NaN");
+        }
+
+        if min_ratio > 0.0 && max_ratio > 0.0 && min_ratio > max_ratio {
+            return error::fail(-1, "min_ratio is greater than max_ratio");
+        }
+
+        let filter = (min_ratio > 0.0 || max_ratio > 0.0).then_some(LengthFilter {
+            min_ratio: (min_ratio > 0.0).then_some(min_ratio),
+            max_ratio: (max_ratio > 0.0).then_some(max_ratio),
+        });
+
+        unsafe { (*graph).length_filter = filter };
+
+        0
+    })
+}
+
+/// Accepted sequence lengths, relative to the consensus length.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub(crate) struct LengthFilter {
+    pub min_ratio: Option<f64>,
+    pub max_ratio: Option<f64>,
+}
+
+impl LengthFilter {
+    /// Whether a sequence of length `len` may be added to `graph`.
+    pub(crate) fn accepts(&self, graph: &PoastaGraph, len: usize) -> bool {
+        let consensus_len = *graph.consensus_len.get_or_init(|| consensus_sequence(graph).len()) as f64;
+        if consensus_len == 0.0 {
+            return true;
+        }
+
+        let len = len as f64;
+
+        self.min_ratio.is_none_or(|ratio| len >= ratio * consensus_len)
+            && self.max_ratio.is_none_or(|ratio| len <= ratio * consensus_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::poasta_sequence_set_active;
+    use crate::testing::{add, graph_of};
+
+    #[test]
+    fn consensus_length_follows_changes() {
+        let filter = LengthFilter { min_ratio: Some(0.8), max_ratio: Some(1.2) };
+        let mut graph = graph_of(&["ACGTTGCA"]);
+        assert!(filter.accepts(&graph, 8));
+        assert!(!filter.accepts(&graph, 12));
+
+        unsafe { poasta_sequence_set_active(&mut graph, 0, false) };
+        assert!(filter.accepts(&graph, 12));
+
+        add(&mut graph, "ACGTTGCAACGT");
+        add(&mut graph, "ACGTTGCAACGT");
+        assert!(filter.accepts(&graph, 12));
+        assert!(!filter.accepts(&graph, 8));
+    }
+}
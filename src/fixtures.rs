@@ -0,0 +1,249 @@
+//! Small deterministic graphs with their reads and expected outputs, the `test-support` build
+//! feature, so bindings in other languages can check their wrappers against the same ground truth.
+//! The C header declares them if `POASTA_TEST_SUPPORT` is defined.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+use poasta::aligner::scoring::GapAffine;
+
+use crate::bench::PoastaReads;
+use crate::{error, into_c_array, PoastaGraph, Scoring, SequenceInfo};
+
+/// Fixtures of poasta_fixture_graph.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)] // Only C callers construct fixtures
+pub enum PoastaFixture {
+    /// Three identical reads, a graph that is a single path.
+    Identical = 0,
+    /// Four reads, one of them with a substitution the consensus outvotes.
+    Snp = 1,
+    /// Four reads, one with a deletion and one with an insertion.
+    Indel = 2,
+}
+
+/// Outputs of a fixture graph, see poasta_fixture_expected.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)] // Only C callers construct outputs
+pub enum PoastaFixtureOutput {
+    /// The sequence of poasta_get_consensus.
+    Consensus = 0,
+    /// poasta_get_msa_fasta with default options (no padding, no wrapping).
+    MsaFasta = 1,
+    /// poasta_get_gfa.
+    Gfa = 2,
+}
+
+struct Fixture {
+    reads: &'static [&'static str],
+    consensus: &'static CStr,
+    msa_fasta: &'static CStr,
+    gfa: &'static CStr,
+}
+
+const REFERENCE: &str = "ACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTAGCAT";
+
+/// Costs the reads are added with, those of the example in the README.
+const MISMATCH_SCORE: u8 = 4;
+const GAP_EXTEND: u8 = 2;
+const GAP_OPEN: u8 = 6;
+
+fn fixture(fixture: PoastaFixture) -> Fixture {
+    match fixture {
+        PoastaFixture::Identical => Fixture {
+            reads: &[REFERENCE, REFERENCE, REFERENCE],
+            consensus: c"ACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTAGCAT",
+            msa_fasta: c">read1\n\
+                ACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTAGCAT\n\
+                >read2\n\
+                ACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTAGCAT\n\
+                >read3\n\
+                ACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTAGCAT\n",
+            gfa: c"H\tVN:Z:1.1\n\
+                S\ts0\tACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTAGCAT\n\
+                W\t*\t0\tread1\t0\t38\t>s0\n\
+                W\t*\t0\tread2\t0\t38\t>s0\n\
+                W\t*\t0\tread3\t0\t38\t>s0\n",
+        },
+        PoastaFixture::Snp => Fixture {
+            reads: &[REFERENCE, REFERENCE, "ACGTTGCAAGGCTTACCGATTCATCGGATCCAGTTAGCAT", REFERENCE],
+            consensus: c"ACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTAGCAT",
+            msa_fasta: c">read1\n\
+                ACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTAGCAT\n\
+                >read2\n\
+                ACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTAGCAT\n\
+                >read3\n\
+                ACGTTGCAAGGCTTACCGATTCATCGGATCCAGTTAGCAT\n\
+                >read4\n\
+                ACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTAGCAT\n",
+            gfa: c"H\tVN:Z:1.1\n\
+                S\ts0\tACGTTGCAAGGCTTACCGAT\n\
+                S\ts1\tT\n\
+                S\ts2\tG\n\
+                S\ts3\tCATCGGATCCAGTTAGCAT\n\
+                L\ts0\t+\ts2\t+\t0M\n\
+                L\ts2\t+\ts3\t+\t0M\n\
+                L\ts0\t+\ts1\t+\t0M\n\
+                L\ts1\t+\ts3\t+\t0M\n\
+                W\t*\t0\tread1\t0\t38\t>s0>s2>s3\n\
+                W\t*\t0\tread2\t0\t38\t>s0>s2>s3\n\
+                W\t*\t0\tread3\t0\t38\t>s0>s1>s3\n\
+                W\t*\t0\tread4\t0\t38\t>s0>s2>s3\n",
+        },
+        PoastaFixture::Indel => Fixture {
+            reads: &[
+                REFERENCE,
+                "ACGTTGCAAGGCTTAATGCATCGGATCCAGTTAGCAT",
+                "ACGTTGCAAGGCTTACCGATGCATCGGATCGGCAGTTAGCAT",
+                REFERENCE,
+            ],
+            consensus: c"ACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTAGCAT",
+            msa_fasta: c">read1\n\
+                ACGTTGCAAGGCTTACCGATGCATCGGATC--CAGTTAGCAT\n\
+                >read2\n\
+                ACGTTGCAAGGCTTA---ATGCATCGGATC--CAGTTAGCAT\n\
+                >read3\n\
+                ACGTTGCAAGGCTTACCGATGCATCGGATCGGCAGTTAGCAT\n\
+                >read4\n\
+                ACGTTGCAAGGCTTACCGATGCATCGGATC--CAGTTAGCAT\n",
+            gfa: c"H\tVN:Z:1.1\n\
+                S\ts0\tACGTTGCAAGGCTTA\n\
+                S\ts1\tATGCATCGGATC\n\
+                S\ts2\tCCG\n\
+                S\ts3\tGG\n\
+                S\ts4\tCAGTTAGCAT\n\
+                L\ts0\t+\ts2\t+\t0M\n\
+                L\ts2\t+\ts1\t+\t0M\n\
+                L\ts1\t+\ts4\t+\t0M\n\
+                L\ts0\t+\ts1\t+\t0M\n\
+                L\ts1\t+\ts3\t+\t0M\n\
+                L\ts3\t+\ts4\t+\t0M\n\
+                W\t*\t0\tread1\t0\t38\t>s0>s2>s1>s4\n\
+                W\t*\t0\tread2\t0\t35\t>s0>s1>s4\n\
+                W\t*\t0\tread3\t0\t40\t>s0>s2>s1>s3>s4\n\
+                W\t*\t0\tread4\t0\t38\t>s0>s2>s1>s4\n",
+        },
+    }
+}
+
+/// Returns the reads of `fixture`, in the order poasta_fixture_graph adds them.
+/// Caller must free the result with poasta_free_reads.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_fixture_reads(fixture: PoastaFixture) -> PoastaReads {
+    error::catch(|| {
+        let reads: Vec<*mut c_char> = self::fixture(fixture).reads.iter()
+            .map(|read| CString::new(*read).unwrap().into_raw())
+            .collect();
+
+        PoastaReads {
+            num_sequences: reads.len(),
+            sequences: into_c_array(reads),
+        }
+    })
+}
+
+/// Returns the graph of `fixture`: its reads added in order like poasta_add_sequence with mismatch
+/// score 4, gap extend 2 and gap open 6, named `read1`, `read2` and so on.
+/// Returns NULL if a read can't be added. Must be freed with poasta_free_graph.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_fixture_graph(fixture: PoastaFixture) -> *mut PoastaGraph {
+    error::catch(|| {
+        let mut graph = PoastaGraph::new();
+        for (i, read) in self::fixture(fixture).reads.iter().enumerate() {
+            let scoring = Scoring::Affine(GapAffine::new(MISMATCH_SCORE, GAP_EXTEND, GAP_OPEN));
+            let info = SequenceInfo { name: Some(format!("read{}", i + 1)), ..SequenceInfo::default() };
+            let status = graph.add_sequence(read.as_bytes(), &vec![1; read.len()], scoring, info);
+            if status != 0 {
+                error::fail(status, "a fixture read can't be added");
+                return ptr::null_mut();
+            }
+        }
+
+        Box::into_raw(Box::new(graph))
+    })
+}
+
+/// Returns the expected `output` of the graph of `fixture` (see poasta_fixture_graph), as this
+/// version of the library computes it. The string is static and must not be freed.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_fixture_expected(fixture: PoastaFixture, output: PoastaFixtureOutput) -> *const c_char {
+    error::catch(|| {
+        let fixture = self::fixture(fixture);
+        let expected = match output {
+            PoastaFixtureOutput::Consensus => fixture.consensus,
+            PoastaFixtureOutput::MsaFasta => fixture.msa_fasta,
+            PoastaFixtureOutput::Gfa => fixture.gfa,
+        };
+
+        expected.as_ptr()
+    })
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+    use crate::consensus::{poasta_free_consensus, poasta_get_consensus};
+    use crate::msa::{poasta_get_msa_fasta, PoastaMsaOptions};
+    use crate::testing::take;
+    use crate::{poasta_free_graph, poasta_get_gfa};
+
+    const FIXTURES: [PoastaFixture; 3] = [PoastaFixture::Identical, PoastaFixture::Snp, PoastaFixture::Indel];
+
+    fn expected(fixture: PoastaFixture, output: PoastaFixtureOutput) -> &'static str {
+        unsafe { CStr::from_ptr(poasta_fixture_expected(fixture, output)) }.to_str().unwrap()
+    }
+
+    /// Runs `check` on the graph of every fixture.
+    fn for_each_graph(check: impl Fn(PoastaFixture, *const PoastaGraph)) {
+        for fixture in FIXTURES {
+            let graph = poasta_fixture_graph(fixture);
+            assert!(!graph.is_null(), "{fixture:?} graph can't be built");
+            check(fixture, graph);
+            unsafe { poasta_free_graph(graph) };
+        }
+    }
+
+    #[test]
+    fn consensus_matches_expected() {
+        for_each_graph(|fixture, graph| {
+            let consensus = unsafe { poasta_get_consensus(graph) };
+            let sequence = unsafe { CStr::from_ptr(consensus.sequence) }.to_str().unwrap().to_owned();
+            unsafe { poasta_free_consensus(consensus) };
+
+            assert_eq!(sequence, expected(fixture, PoastaFixtureOutput::Consensus), "{fixture:?}");
+        });
+    }
+
+    #[test]
+    fn msa_fasta_matches_expected() {
+        for_each_graph(|fixture, graph| {
+            let options = PoastaMsaOptions { pad: 0, line_width: 0 };
+            let fasta = take(unsafe { poasta_get_msa_fasta(graph, options) });
+
+            assert_eq!(fasta, expected(fixture, PoastaFixtureOutput::MsaFasta), "{fixture:?}");
+        });
+    }
+
+    #[test]
+    fn gfa_matches_expected() {
+        for_each_graph(|fixture, graph| {
+            let gfa = take(unsafe { poasta_get_gfa(graph) });
+
+            assert_eq!(gfa, expected(fixture, PoastaFixtureOutput::Gfa), "{fixture:?}");
+        });
+    }
+
+    #[test]
+    fn reads_match_graph() {
+        for fixture in FIXTURES {
+            let reads = poasta_fixture_reads(fixture);
+            assert_eq!(reads.num_sequences, self::fixture(fixture).reads.len());
+            unsafe { crate::bench::poasta_free_reads(reads) };
+        }
+    }
+}
@@ -0,0 +1,503 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fmt::{self, Write};
+use std::os::raw::c_char;
+use std::ptr;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::consensus::{node_support, topological_order};
+use crate::error;
+use crate::graph::{NodeIx, SequenceRecord};
+use crate::provisional::{Evidence, PoastaProvisional};
+use crate::view::GraphView;
+use crate::{free_c_array, into_c_array, PoastaGraph};
+
+/// Returns a snapshot ID for the current state of the graph, to be passed to poasta_get_gfa_delta
+/// or poasta_graph_save_delta later: the number of sequences added so far, which unlike the number
+/// of sequences doesn't go down when sequences are removed. Returns 0 if the graph is NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_snapshot(graph: *const PoastaGraph) -> usize {
+    error::catch(|| {
+        if graph.is_null() {
+            return 0;
+        }
+
+        unsafe { &*graph }.insertions as usize
+    })
+}
+
+/// The sequences added before and after `snapshot` (see poasta_graph_snapshot), None if the
+/// snapshot is from the future.
+pub(crate) fn split_at_snapshot(graph: &PoastaGraph, snapshot: usize) -> Option<(&[SequenceRecord], &[SequenceRecord])> {
+    if snapshot as u64 > graph.insertions {
+        return None;
+    }
+
+    // Sequences added later are numbered from the snapshot on, and come after all others
+    let added = graph.sequences.iter()
+        .rev()
+        .take_while(|record| record.info.provenance.is_some_and(|provenance| provenance.insertion >= snapshot as u64))
+        .count();
+
+    Some(graph.sequences.split_at(graph.sequences.len() - added))
+}
+
+/// Returns the segments and links added or changed since `since_snapshot` (see
+/// poasta_graph_snapshot) as GFA, so live viewers can update incrementally. Unlike
+/// poasta_get_gfa, each segment is a single node named by its node index, so segments never
+/// change identity; a changed segment or link is emitted again with its new `RC` tag (the number
+/// of active sequences through it, i.e., not counting masked ones). A delta since snapshot 0 is
+/// the full graph.
+/// Returns NULL if the graph is NULL or the snapshot is from the future.
+/// The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_gfa_delta(graph: *const PoastaGraph, since_snapshot: usize) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let graph = unsafe { &*graph };
+        let Some((_, new_records)) = split_at_snapshot(graph, since_snapshot) else {
+            return ptr::null_mut();
+        };
+
+        let support = node_support(graph);
+        let mut edge_support: HashMap<(NodeIx, NodeIx), usize> = HashMap::new();
+        for pair in graph.active_sequences().flat_map(|record| record.path.windows(2)) {
+            *edge_support.entry((pair[0], pair[1])).or_default() += 1;
+        }
+
+        let mut gfa = String::from("H\tVN:Z:1.1\n");
+
+        let mut seen_nodes = HashSet::new();
+        let mut seen_edges = HashSet::new();
+        for record in new_records {
+            for node in &record.path {
+                if seen_nodes.insert(*node) {
+                    writeln!(
+                        gfa,
+                        "S\t{}\t{}\tRC:i:{}",
+                        node.index(),
+                        graph.graph.get_symbol(*node) as char,
+                        support[node.index()],
+                    ).unwrap();
+                }
+            }
+        }
+
+        for record in new_records {
+            for pair in record.path.windows(2) {
+                if seen_edges.insert((pair[0], pair[1])) {
+                    writeln!(
+                        gfa,
+                        "L\t{}\t+\t{}\t+\t0M\tRC:i:{}",
+                        pair[0].index(),
+                        pair[1].index(),
+                        edge_support.get(&(pair[0], pair[1])).copied().unwrap_or(0),
+                    ).unwrap();
+                }
+            }
+        }
+
+        // Extra links are part of the graph from the start
+        if since_snapshot == 0 {
+            for (from, to) in &graph.links {
+                writeln!(gfa, "L\t{}\t+\t{}\t+\t0M\tRC:i:0", from.index(), to.index()).unwrap();
+            }
+        }
+
+        CString::new(gfa).unwrap_or_default().into_raw()
+    })
+}
+
+/// GFA versions of poasta_get_gfa_version, which differ in how sequences are written.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoastaGfaVersion {
+    /// GFA 1.0, with a P line per sequence (like poasta_get_gfa_with_paths).
+    Gfa10 = 0,
+    /// GFA 1.1, with a W line per sequence.
+    Gfa11 = 1,
+}
+
+/// Returns the graph as GFA 1.0 with a P line per sequence (in insertion order, including inactive
+/// ones), for tools that read paths rather than walks. Like in poasta_get_gfa_delta, each segment
+/// is a single node named by its node index, which stays the same as sequences are added, so each
+/// path spells its sequence exactly. Provisional nodes and edges are left out like in
+/// poasta_get_gfa, and so are the paths through them.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_gfa_with_paths(graph: *const PoastaGraph) -> *mut c_char {
+    unsafe { poasta_get_gfa_version(graph, PoastaGfaVersion::Gfa10) }
+}
+
+/// Like poasta_get_gfa_with_paths, in the given GFA version. With GFA 1.1, each sequence gets a W
+/// line instead of a P line, with its sample, haplotype and sequence ID taken from names following
+/// the PanSN convention (`sample#haplotype#contig`, with a numeric haplotype), like in
+/// poasta_get_gfa.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_gfa_version(graph: *const PoastaGraph, version: PoastaGfaVersion) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let mut gfa = String::new();
+        write_node_gfa(&mut gfa, unsafe { &*graph }, version).unwrap();
+
+        CString::new(gfa).unwrap_or_default().into_raw()
+    })
+}
+
+/// The sample, haplotype and sequence ID of a W line for a sequence called `name`: its parts if it
+/// follows the PanSN convention, otherwise no sample (`*`), haplotype 0 and the whole name, so
+/// poasta_graph_from_gfa reads the name back either way.
+fn walk_fields(name: &str) -> (&str, u32, &str) {
+    if let [sample, haplotype, contig] = name.splitn(3, '#').collect::<Vec<_>>()[..]
+        && !sample.is_empty()
+        && sample != "*"
+        && !contig.is_empty()
+        && let Ok(haplotype) = haplotype.parse()
+    {
+        return (sample, haplotype, contig);
+    }
+
+    ("*", 0, name)
+}
+
+/// Writes the GFA of poasta_get_gfa_version to `out`, stopping at the first failed write.
+fn write_node_gfa(out: &mut impl Write, graph: &PoastaGraph, version: PoastaGfaVersion) -> fmt::Result {
+    let view = GraphView::new(graph);
+    let evidence = Evidence::new(graph);
+    let shown = |from: NodeIx, to: NodeIx| {
+        evidence.is_confirmed_node(from) && evidence.is_confirmed_node(to) && evidence.is_confirmed_edge(from, to)
+    };
+
+    let nodes: Vec<NodeIx> = topological_order(&view).into_iter()
+        .filter(|node| evidence.is_confirmed_node(*node))
+        .collect();
+
+    match version {
+        PoastaGfaVersion::Gfa10 => writeln!(out, "H\tVN:Z:1.0")?,
+        PoastaGfaVersion::Gfa11 => writeln!(out, "H\tVN:Z:1.1")?,
+    }
+    for node in &nodes {
+        writeln!(out, "S\t{}\t{}", node.index(), graph.graph.get_symbol(*node) as char)?;
+    }
+
+    for node in &nodes {
+        for succ in view.successors(*node).filter(|succ| *succ != view.end_node() && shown(*node, *succ)) {
+            writeln!(out, "L\t{}\t+\t{}\t+\t0M", node.index(), succ.index())?;
+        }
+    }
+
+    for (seq_id, record) in graph.sequences.iter().enumerate() {
+        let Some(first) = record.path.first() else {
+            continue;
+        };
+        if !evidence.is_confirmed_node(*first) || record.path.windows(2).any(|pair| !shown(pair[0], pair[1])) {
+            continue;
+        }
+
+        let name = graph.sequence_name(seq_id);
+        match version {
+            PoastaGfaVersion::Gfa10 => {
+                let steps: Vec<String> = record.path.iter().map(|node| format!("{}+", node.index())).collect();
+                writeln!(out, "P\t{name}\t{}\t*", steps.join(","))?;
+            },
+            PoastaGfaVersion::Gfa11 => {
+                let (sample, haplotype, seq_id) = walk_fields(&name);
+                let steps: String = record.path.iter().map(|node| format!(">{}", node.index())).collect();
+                writeln!(out, "W\t{sample}\t{haplotype}\t{seq_id}\t0\t{}\t{steps}", record.path.len())?;
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Struct to hold the path of a sequence through the graph.
+#[repr(C)]
+pub struct PoastaSequencePath {
+    /// Nodes the sequence passes through in order, by node index, the segment names of
+    /// poasta_get_gfa_with_paths.
+    pub nodes: *mut usize,
+    pub num_nodes: usize,
+}
+
+/// Returns the nodes the sequence at `seq_index` (in insertion order, including inactive
+/// sequences) passes through, by node index, e.g., for phasing or coverage analysis without
+/// parsing the GFA. These are the node IDs of PoastaEdit and the steps of the sequence's P line in
+/// poasta_get_gfa_with_paths, which leaves out paths through provisional nodes or edges (see
+/// poasta_set_min_support); this returns them anyway. The path is empty if the graph is NULL or the
+/// index is out of range (with -1 as the last error). Caller must free the result with
+/// poasta_free_sequence_path.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_sequence_path(graph: *const PoastaGraph, seq_index: usize) -> PoastaSequencePath {
+    error::catch(|| {
+        let empty = PoastaSequencePath { nodes: ptr::null_mut(), num_nodes: 0 };
+        if graph.is_null() {
+            error::fail(-1, "graph is NULL");
+            return empty;
+        }
+
+        let Some(record) = unsafe { &*graph }.sequences.get(seq_index) else {
+            error::fail(-1, &format!("no sequence {seq_index}"));
+            return empty;
+        };
+
+        let nodes: Vec<usize> = record.path.iter().map(|node| node.index()).collect();
+        PoastaSequencePath {
+            num_nodes: nodes.len(),
+            nodes: into_c_array(nodes),
+        }
+    })
+}
+
+/// Frees a PoastaSequencePath.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_sequence_path(path: PoastaSequencePath) {
+    error::catch(|| {
+        unsafe { free_c_array(path.nodes, path.num_nodes) };
+    })
+}
+
+/// The graph as GFA in the same layout as poasta's `graph_to_gfa` (non-branching runs of nodes
+/// compressed into segments, and a walk per sequence), with provisional nodes and edges left out
+/// or marked with a `pv:i:1` tag. When they are left out, sequences passing through them get no
+/// walk.
+pub(crate) fn layered_gfa(graph: &PoastaGraph, provisional: PoastaProvisional) -> String {
+    let mut gfa = String::new();
+    write_layered_gfa(&mut gfa, graph, provisional).unwrap();
+
+    gfa
+}
+
+/// Writes `layered_gfa` to `out`, stopping at the first failed write.
+pub(crate) fn write_layered_gfa(out: &mut impl Write, graph: &PoastaGraph, provisional: PoastaProvisional) -> fmt::Result {
+    let segments = Segments::new(graph, provisional);
+    let mark = provisional == PoastaProvisional::Mark;
+    let tag = |confirmed: bool| if mark && !confirmed { "\tpv:i:1" } else { "" };
+
+    writeln!(out, "H\tVN:Z:1.1")?;
+    for (id, nodes) in segments.nodes.iter().enumerate() {
+        let symbols: String = nodes.iter().map(|node| graph.graph.get_symbol(*node) as char).collect();
+        writeln!(out, "S\ts{id}\t{symbols}{}", tag(segments.evidence.is_confirmed_node(nodes[0])))?;
+    }
+
+    for (id, nodes) in segments.nodes.iter().enumerate() {
+        let last = *nodes.last().unwrap();
+        for succ in segments.successors(last) {
+            let confirmed = segments.evidence.is_confirmed_edge(last, succ);
+            writeln!(out, "L\ts{id}\t+\ts{}\t+\t0M{}", segments.segment_of[&succ].0, tag(confirmed))?;
+        }
+    }
+
+    for (seq_id, record) in graph.sequences.iter().enumerate() {
+        let Some(walk) = segments.walk(&record.path) else {
+            continue;
+        };
+
+        let name = graph.sequence_name(seq_id);
+        let (sample, haplotype, seq_id) = walk_fields(&name);
+        let ids: String = walk.segments.iter().map(|id| format!(">s{id}")).collect();
+        writeln!(out, "W\t{sample}\t{haplotype}\t{seq_id}\t{}\t{}\t{ids}", walk.start, walk.end)?;
+    }
+
+    Ok(())
+}
+
+/// Non-branching runs of nodes of a graph compressed into segments, with provisional nodes and
+/// edges left out or kept in segments of their own.
+pub(crate) struct Segments<'a> {
+    view: GraphView<'a>,
+    pub evidence: Evidence,
+    provisional: PoastaProvisional,
+
+    /// Nodes of each segment, in order.
+    pub nodes: Vec<Vec<NodeIx>>,
+
+    /// Segment and offset within it of each node.
+    pub segment_of: HashMap<NodeIx, (usize, usize)>,
+}
+
+/// The segments a node path passes through.
+pub(crate) struct Walk {
+    pub segments: Vec<usize>,
+
+    /// Offset of the path's first node in the first segment, and of the end of its last node in
+    /// the concatenated segments.
+    pub start: usize,
+    pub end: usize,
+}
+
+impl<'a> Segments<'a> {
+    pub(crate) fn new(graph: &'a PoastaGraph, provisional: PoastaProvisional) -> Self {
+        let view = GraphView::new(graph);
+        let evidence = Evidence::new(graph);
+        let mut segments = Segments { view, evidence, provisional, nodes: Vec::new(), segment_of: HashMap::new() };
+
+        let nodes: Vec<NodeIx> = topological_order(&segments.view).into_iter()
+            .filter(|node| provisional != PoastaProvisional::Exclude || segments.evidence.is_confirmed_node(*node))
+            .collect();
+
+        // Nodes continue the segment of their predecessor if that is their only link (and, when
+        // marking, if both are provisional or both are confirmed)
+        let mark = provisional == PoastaProvisional::Mark;
+        for node in nodes {
+            let continued = match segments.predecessors(node)[..] {
+                [pred] if segments.successors(pred).len() == 1
+                    && (!mark || segments.evidence.is_confirmed_node(pred) == segments.evidence.is_confirmed_node(node)) => {
+                    Some(segments.segment_of[&pred].0)
+                },
+                _ => None,
+            };
+
+            let segment = continued.unwrap_or_else(|| {
+                segments.nodes.push(Vec::new());
+                segments.nodes.len() - 1
+            });
+
+            segments.segment_of.insert(node, (segment, segments.nodes[segment].len()));
+            segments.nodes[segment].push(node);
+        }
+
+        segments
+    }
+
+    /// Whether the edge is part of the segment graph.
+    pub(crate) fn shown(&self, from: NodeIx, to: NodeIx) -> bool {
+        self.provisional != PoastaProvisional::Exclude
+            || (self.evidence.is_confirmed_node(from) && self.evidence.is_confirmed_node(to) && self.evidence.is_confirmed_edge(from, to))
+    }
+
+    pub(crate) fn successors(&self, node: NodeIx) -> Vec<NodeIx> {
+        self.view.successors(node).filter(|succ| *succ != self.view.end_node() && self.shown(node, *succ)).collect()
+    }
+
+    fn predecessors(&self, node: NodeIx) -> Vec<NodeIx> {
+        self.view.predecessors(node).filter(|pred| *pred != self.view.start_node() && self.shown(*pred, node)).collect()
+    }
+
+    /// None if the path is empty or leaves the segment graph.
+    pub(crate) fn walk(&self, path: &[NodeIx]) -> Option<Walk> {
+        let (first, last) = (path.first()?, path.last()?);
+        if !self.segment_of.contains_key(first) || path.windows(2).any(|pair| !self.shown(pair[0], pair[1])) {
+            return None;
+        }
+
+        let mut segments: Vec<usize> = path.iter().map(|node| self.segment_of[node].0).collect();
+        segments.dedup();
+
+        let start = self.segment_of[first].1;
+        let end = segments[..segments.len() - 1].iter().map(|id| self.nodes[*id].len()).sum::<usize>() + self.segment_of[last].1 + 1;
+
+        Some(Walk { segments, start, end })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::{poasta_remove_sequence, poasta_sequence_set_active};
+    use crate::error::{poasta_last_error, PoastaError};
+    use crate::poasta_add_sequence_named;
+    use crate::provisional::poasta_set_min_support;
+    use crate::testing::{add, array, graph_of, take};
+
+    #[test]
+    fn snapshots_count_removed_sequences() {
+        let mut graph = graph_of(&["ACGTACGT", "ACGAACGT", "ACGTACGA"]);
+        let snapshot = unsafe { poasta_graph_snapshot(&graph) };
+
+        assert_eq!(unsafe { poasta_remove_sequence(&mut graph, 0) }, 0);
+        add(&mut graph, "TTTT");
+
+        // The delta has the sequence added after the snapshot, though there are as many sequences
+        // as there were then
+        let delta = take(unsafe { poasta_get_gfa_delta(&graph, snapshot) });
+        let segments: Vec<&str> = delta.lines().filter(|line| line.starts_with('S')).collect();
+        assert_eq!(segments.len(), 4);
+        assert!(segments.iter().all(|line| line.contains("\tT\t")));
+        assert!(unsafe { poasta_get_gfa_delta(&graph, snapshot + 2) }.is_null());
+    }
+
+    #[test]
+    fn delta_support_leaves_out_masked_sequences() {
+        let mut graph = graph_of(&["ACGT", "ACGT"]);
+        assert_eq!(unsafe { poasta_sequence_set_active(&mut graph, 1, false) }, 0);
+
+        let delta = take(unsafe { poasta_get_gfa_delta(&graph, 0) });
+        let tagged: Vec<&str> = delta.lines().filter(|line| line.starts_with('S') || line.starts_with('L')).collect();
+        assert_eq!(tagged.len(), 7);
+        assert!(tagged.iter().all(|line| line.ends_with("RC:i:1")), "{delta}");
+    }
+
+    fn sequence_path(graph: &PoastaGraph, seq_index: usize) -> Vec<usize> {
+        let path = unsafe { poasta_get_sequence_path(graph, seq_index) };
+        let nodes = array(path.nodes, path.num_nodes).to_vec();
+        unsafe { poasta_free_sequence_path(path) };
+
+        nodes
+    }
+
+    #[test]
+    fn sequence_paths_are_the_steps_of_their_p_lines() {
+        let mut graph = graph_of(&["ACGT", "AGGT", "ACGTT"]);
+        let gfa = take(unsafe { poasta_get_gfa_with_paths(&graph) });
+        for (ix, line) in gfa.lines().filter(|line| line.starts_with('P')).enumerate() {
+            let steps: Vec<usize> = line.split('\t').nth(2).unwrap().split(',')
+                .map(|step| step.trim_end_matches('+').parse().unwrap())
+                .collect();
+            assert_eq!(sequence_path(&graph, ix), steps);
+        }
+        assert_eq!(sequence_path(&graph, 1), [2, 6, 4, 5]);
+
+        // Paths through provisional nodes are returned too
+        assert_eq!(unsafe { poasta_set_min_support(&mut graph, 2) }, 0);
+        assert_eq!(sequence_path(&graph, 1), [2, 6, 4, 5]);
+        assert!(sequence_path(&graph, 3).is_empty());
+        assert_eq!(poasta_last_error(), PoastaError::InvalidArgument);
+    }
+
+    #[test]
+    fn each_sequence_gets_a_p_line_through_single_node_segments() {
+        let mut graph = graph_of(&["ACGT", "AGGT", "ACGT"]);
+        let gfa = take(unsafe { poasta_get_gfa_with_paths(&graph) });
+        assert_eq!(gfa, "H\tVN:Z:1.0\n\
+            S\t2\tA\nS\t3\tC\nS\t6\tG\nS\t4\tG\nS\t5\tT\n\
+            L\t2\t+\t6\t+\t0M\nL\t2\t+\t3\t+\t0M\nL\t3\t+\t4\t+\t0M\nL\t6\t+\t4\t+\t0M\nL\t4\t+\t5\t+\t0M\n\
+            P\tseq_0\t2+,3+,4+,5+\t*\nP\tseq_1\t2+,6+,4+,5+\t*\nP\tseq_2\t2+,3+,4+,5+\t*\n");
+
+        // The G of seq_1 is provisional, and so is its path
+        assert_eq!(unsafe { poasta_set_min_support(&mut graph, 2) }, 0);
+        let gfa = take(unsafe { poasta_get_gfa_with_paths(&graph) });
+        assert!(!gfa.contains("S\t6\t") && !gfa.contains("seq_1"), "{gfa}");
+        assert_eq!(gfa.lines().filter(|line| line.starts_with('P')).count(), 2);
+        assert!(unsafe { poasta_get_gfa_with_paths(std::ptr::null()) }.is_null());
+    }
+
+    #[test]
+    fn walks_take_their_fields_from_pansn_names() {
+        let mut graph = graph_of(&["ACGT", "AGGT"]);
+        for name in [c"HG002#1#chr1", c"HG002#x#chr1", c"#1#chr1"] {
+            let status = unsafe { poasta_add_sequence_named(&mut graph, name.as_ptr(), c"ACGTT".as_ptr(), 5, 4, 2, 6) };
+            assert_eq!(status, 0);
+        }
+
+        let gfa = take(unsafe { poasta_get_gfa_version(&graph, PoastaGfaVersion::Gfa11) });
+        let walks: Vec<&str> = gfa.lines().filter(|line| line.starts_with('W')).collect();
+        assert_eq!(walks, [
+            "W\t*\t0\tseq_0\t0\t4\t>2>3>4>5",
+            "W\t*\t0\tseq_1\t0\t4\t>2>6>4>5",
+            "W\tHG002\t1\tchr1\t0\t5\t>2>3>4>5>7",
+            "W\t*\t0\tHG002#x#chr1\t0\t5\t>2>3>4>5>7",
+            "W\t*\t0\t#1#chr1\t0\t5\t>2>3>4>5>7",
+        ]);
+        assert!(gfa.starts_with("H\tVN:Z:1.1\n"));
+        assert!(!gfa.contains("\nP\t"));
+    }
+}
@@ -0,0 +1,1049 @@
+//! The graph handle handed out over FFI, and the bookkeeping we keep next to poasta's graph.
+
+use std::collections::HashMap;
+use std::os::raw::c_int;
+use std::sync::{Arc, OnceLock};
+
+use poasta::aligner::config::{Affine2PieceMinGapCost, AffineMinGapCost};
+use poasta::aligner::scoring::{AlignmentCosts, AlignmentType, GapAffine, GapAffine2Piece, Score};
+use poasta::aligner::{AlignedPair, Alignment, PoastaAligner};
+use poasta::graphs::AlignableRefGraph;
+use poasta::graphs::poa::{POAGraph, POANodeIndex};
+use serde::{Deserialize, Serialize};
+
+use crate::alphabet::PoastaAlphabet;
+use crate::bloom::KmerFilter;
+use crate::cache::AlignmentCache;
+use crate::circular::origin_offset;
+use crate::consensus::PoastaTieBreak;
+use crate::dp::{self, CodonPenalty, DpBuffers, DpCosts, HomopolymerGaps};
+use crate::error;
+use crate::filter::{LengthFilter, LENGTH_OUTLIER};
+use crate::mask::MaskedRegions;
+use crate::metrics;
+use crate::mode::PoastaAlignmentMode;
+use crate::trace::{self, PoastaTraceLevel};
+use crate::primers::Primers;
+use crate::provenance::PoastaProvenance;
+use crate::resource::{self, PoastaOperation};
+use crate::scoring::{checked_cost, ScoringParams};
+use crate::strand::PoastaStrand;
+use crate::timeout::{align_with_timeout, consensus_alignment, AlignmentTimeout, Cancellable, APPROXIMATE, TIMEOUT};
+use crate::view::GraphView;
+
+/// Node index type of the graphs built by this crate.
+pub(crate) type NodeIx = POANodeIndex<u32>;
+
+/// Opaque pointer to the POAGraph<u32>.
+#[derive(Serialize, Deserialize)]
+pub struct PoastaGraph {
+    /// Shared with clones, in which case the graph is copied before it is changed, and with the
+    /// worker thread of an alignment with a time limit while it runs. Not saved, but rebuilt from
+    /// the sequences when loading, see serialize::from_bytes.
+    #[serde(skip, default = "empty_poa_graph")]
+    pub(crate) graph: Arc<POAGraph<u32>>,
+
+    /// Extra bookkeeping for each sequence, in the same order as `graph.sequences`. Shared with
+    /// clones until either side changes it.
+    #[serde(with = "saved_records")]
+    pub(crate) sequences: Arc<Vec<SequenceRecord>>,
+
+    /// Extra edges the aligner should consider that are not (yet) part of any sequence path,
+    /// e.g., the junctions between two concatenated graphs.
+    pub(crate) links: Vec<(NodeIx, NodeIx)>,
+
+    /// Weight added along paths of nodes by poasta_bump_node_path_weight, in the order they were
+    /// first bumped.
+    pub(crate) path_weights: Vec<PathWeight>,
+
+    /// Whether sequences are rotated to the graph origin before alignment.
+    pub(crate) circular: bool,
+
+    /// Codon-aware gap penalties; sequences are aligned with the DP aligner when set.
+    pub(crate) codon: Option<CodonPenalty>,
+
+    /// Cheaper gaps in homopolymers; sequences are aligned with the DP aligner when set.
+    pub(crate) homopolymer: Option<HomopolymerGaps>,
+
+    /// Primers to trim off new sequences, which are also oriented by them.
+    pub(crate) primers: Option<Primers>,
+
+    /// Sequence lengths accepted by `add_sequence`, relative to the consensus length.
+    pub(crate) length_filter: Option<LengthFilter>,
+
+    /// How the consensus resolves ties.
+    pub(crate) tie_break: PoastaTieBreak,
+
+    /// Whether the consensus reports unconverted bases of bisulfite-converted sequences.
+    pub(crate) bisulfite: bool,
+
+    /// Number of sequences a node or edge needs before the consensus and GFA export show it.
+    pub(crate) min_support: usize,
+
+    /// Committed sequences that can be added again without realignment.
+    pub(crate) cache: AlignmentCache,
+
+    /// Number of sequences tagged with each UMI, counted by every commit, so graphs rebuilt
+    /// without a sequence or from serialized data count again.
+    #[serde(skip)]
+    pub(crate) umi_counts: HashMap<Vec<u8>, usize>,
+
+    /// K-mers of all sequences, built on demand by poasta_graph_maybe_contains.
+    #[serde(skip)]
+    pub(crate) kmer_filter: Option<KmerFilter>,
+
+    /// Time budget for aligning a sequence, unlimited if not set.
+    pub(crate) timeout: Option<AlignmentTimeout>,
+
+    /// Sequence (in insertion order) that reference coordinates, e.g., of SAM records, refer to.
+    pub(crate) backbone: Option<usize>,
+
+    /// Regions left out of variant reporting.
+    pub(crate) masked_regions: Option<MaskedRegions>,
+
+    /// Number of changes to the graph's content or output so far, see poasta_graph_generation.
+    #[serde(skip)]
+    pub(crate) generation: u64,
+
+    /// Length of the consensus for the length filter, computed on first use after a change.
+    #[serde(skip)]
+    pub(crate) consensus_len: OnceLock<usize>,
+
+    /// Number of sequences added so far, numbering the next one, and the batch new sequences are
+    /// tagged with.
+    pub(crate) insertions: u64,
+    pub(crate) batch_id: u64,
+
+    /// Host pointer set with poasta_graph_set_user_data, as an address so graphs can still be
+    /// shared between threads.
+    #[serde(skip)]
+    pub(crate) user_data: usize,
+
+    /// Host bytes set with poasta_graph_set_metadata.
+    pub(crate) metadata: Vec<u8>,
+
+    /// Alphabet set with poasta_graph_set_alphabet.
+    pub(crate) alphabet: PoastaAlphabet,
+}
+
+/// Everything needed to add a sequence to a graph again, e.g., when a graph is split. Saved with
+/// `saved_records`.
+#[derive(Clone, Debug)]
+pub(crate) struct SequenceRecord {
+    pub seq: Vec<u8>,
+    pub weights: Vec<usize>,
+    pub scoring: Scoring,
+    pub info: SequenceInfo,
+
+    /// The graph node each base of the sequence ended up in.
+    pub path: Vec<NodeIx>,
+}
+
+/// Per-sequence data tracked alongside the POA graph.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct SequenceInfo {
+    /// Name given by the caller, if any; otherwise the sequence is called `seq_N`.
+    pub name: Option<String>,
+
+    /// Unique molecular identifier this sequence was tagged with, if any.
+    pub umi: Option<Vec<u8>>,
+
+    /// Whether the sequence is excluded from consensus and MSA computation.
+    pub masked: bool,
+
+    /// Factor applied to the sequence's weights in the consensus, lowered by weight decay.
+    pub scale: f64,
+
+    /// Reads counted for the sequence on top of itself by poasta_bump_path_weight, lowered by
+    /// weight decay like `scale`.
+    pub copies: f64,
+
+    /// Strand the sequence was read from, if known.
+    pub strand: PoastaStrand,
+
+    /// Whether the alignment timed out and the sequence was added along an approximate alignment.
+    pub approximate: bool,
+
+    /// Mode the sequence was aligned in.
+    pub mode: PoastaAlignmentMode,
+
+    /// When and in which batch the sequence was added, None until it is.
+    pub provenance: Option<PoastaProvenance>,
+}
+
+impl Default for SequenceInfo {
+    fn default() -> Self {
+        SequenceInfo {
+            name: None,
+            umi: None,
+            masked: false,
+            scale: 1.0,
+            copies: 0.0,
+            strand: PoastaStrand::Unknown,
+            approximate: false,
+            mode: PoastaAlignmentMode::default(),
+            provenance: None,
+        }
+    }
+}
+
+impl SequenceInfo {
+    /// Factor applied to the sequence's weights in the consensus and other weighted output, the
+    /// sequence itself and the reads bumped onto it, with weight decay.
+    pub(crate) fn weight_factor(&self) -> f64 {
+        self.scale + self.copies
+    }
+}
+
+/// Which sequences take part in the consensus: the active ones, except that one sequence may be
+/// included or left out regardless of whether it is active, for leave-one-out analyses that don't
+/// change the graph (see poasta_loo_consensus_delta).
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Selection {
+    toggled: Option<(usize, bool)>,
+}
+
+impl Selection {
+    /// The active sequences, with the one at `index` included if `included` and left out otherwise.
+    pub(crate) fn toggled(index: usize, included: bool) -> Self {
+        Selection { toggled: Some((index, included)) }
+    }
+
+    fn includes(&self, index: usize, record: &SequenceRecord) -> bool {
+        match self.toggled {
+            Some((toggled, included)) if toggled == index => included,
+            _ => !record.info.masked,
+        }
+    }
+}
+
+/// Weight added to every node of a path without adding a sequence, see
+/// poasta_bump_node_path_weight.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct PathWeight {
+    pub path: Vec<NodeIx>,
+
+    /// Weight of each node, lowered by weight decay like the weights of sequences.
+    pub weight: f64,
+}
+
+impl PathWeight {
+    /// The path with its nodes mapped to another graph, None if a node isn't mapped.
+    pub(crate) fn translated(&self, translate: impl Fn(NodeIx) -> Option<NodeIx>) -> Option<Self> {
+        let path = self.path.iter().map(|node| translate(*node)).collect::<Option<_>>()?;
+
+        Some(PathWeight { path, weight: self.weight })
+    }
+}
+
+/// A path adding weight to the nodes and edges along it, see `PoastaGraph::weighted_paths`.
+pub(crate) struct WeightedPath<'a> {
+    pub path: &'a [NodeIx],
+
+    /// Weight of each base, 1 for all if None, which `factor` applies to.
+    weights: Option<&'a [usize]>,
+    factor: f64,
+}
+
+impl WeightedPath<'_> {
+    /// Weight of the node at `pos` of the path.
+    pub(crate) fn weight(&self, pos: usize) -> f64 {
+        self.weights.map_or(1.0, |weights| weights[pos] as f64) * self.factor
+    }
+}
+
+/// Gap model used to align a sequence to the graph.
+#[derive(Clone, Debug)]
+pub(crate) enum Scoring {
+    Affine(GapAffine),
+    TwoPiece(GapAffine2Piece),
+
+    /// Per-pair substitution costs, which only the DP aligner supports.
+    Custom(Arc<ScoringParams>),
+}
+
+/// `Scoring` as it is stored, since poasta's cost models can't be serialized.
+#[derive(Serialize, Deserialize)]
+enum SavedScoring {
+    /// Mismatch, gap extend and gap open costs.
+    Affine(u8, u8, u8),
+
+    /// Mismatch cost and both (gap extend, gap open) pairs.
+    TwoPiece(u8, (u8, u8), (u8, u8)),
+
+    /// Index into the table of the custom configurations of the saved sequences.
+    Custom(usize),
+}
+
+impl SavedScoring {
+    /// Adds custom configurations to `table` unless they are already in it.
+    fn new(scoring: &Scoring, table: &mut Vec<Arc<ScoringParams>>) -> Self {
+        match scoring {
+            Scoring::Affine(costs) => SavedScoring::Affine(costs.mismatch(), costs.gap_extend(), costs.gap_open()),
+            Scoring::TwoPiece(costs) => SavedScoring::TwoPiece(
+                costs.mismatch(),
+                (costs.gap_extend(), costs.gap_open()),
+                (costs.gap_extend2(), costs.gap_open2()),
+            ),
+            Scoring::Custom(params) => match table.iter().position(|other| Arc::ptr_eq(other, params) || other == params) {
+                Some(ix) => SavedScoring::Custom(ix),
+                None => {
+                    table.push(Arc::clone(params));
+                    SavedScoring::Custom(table.len() - 1)
+                },
+            },
+        }
+    }
+
+    fn restore(self, table: &[Arc<ScoringParams>]) -> Result<Scoring, &'static str> {
+        match self {
+            SavedScoring::Affine(mismatch, extend, open) => Ok(Scoring::Affine(GapAffine::new(mismatch, extend, open))),
+            // poasta asserts this when creating the cost model
+            SavedScoring::TwoPiece(_, (extend1, _), (extend2, _)) if extend1 <= extend2 => {
+                Err("gap_extend1 must be greater than gap_extend2")
+            },
+            SavedScoring::TwoPiece(mismatch, (extend1, open1), (extend2, open2)) => {
+                Ok(Scoring::TwoPiece(GapAffine2Piece::new(mismatch, extend1, open1, extend2, open2)))
+            },
+            SavedScoring::Custom(ix) => table.get(ix).cloned().map(Scoring::Custom).ok_or("unknown scoring configuration"),
+        }
+    }
+}
+
+/// Sequence records as they are stored: each custom scoring configuration holds a 64 KiB
+/// substitution matrix, so the distinct ones are saved once, ahead of the records referring to
+/// them. For `#[serde(with)]` on lists of records.
+pub(crate) mod saved_records {
+    use serde::de::Error;
+    use serde::{Deserializer, Serializer};
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Record<'a> {
+        seq: &'a [u8],
+        weights: &'a [usize],
+        scoring: SavedScoring,
+        info: &'a SequenceInfo,
+        path: &'a [NodeIx],
+    }
+
+    #[derive(Deserialize)]
+    struct OwnedRecord {
+        seq: Vec<u8>,
+        weights: Vec<usize>,
+        scoring: SavedScoring,
+        info: SequenceInfo,
+        path: Vec<NodeIx>,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(records: &[SequenceRecord], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut table = Vec::new();
+        let records: Vec<Record> = records.iter()
+            .map(|record| Record {
+                seq: &record.seq,
+                weights: &record.weights,
+                scoring: SavedScoring::new(&record.scoring, &mut table),
+                info: &record.info,
+                path: &record.path,
+            })
+            .collect();
+
+        (table, records).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: From<Vec<SequenceRecord>>,
+    {
+        let (table, records): (Vec<Arc<ScoringParams>>, Vec<OwnedRecord>) = Deserialize::deserialize(deserializer)?;
+        let records = records.into_iter()
+            .map(|record| {
+                Ok(SequenceRecord {
+                    seq: record.seq,
+                    weights: record.weights,
+                    scoring: record.scoring.restore(&table).map_err(D::Error::custom)?,
+                    info: record.info,
+                    path: record.path,
+                })
+            })
+            .collect::<Result<Vec<_>, D::Error>>()?;
+
+        Ok(records.into())
+    }
+}
+
+impl Scoring {
+    /// Affine gap scoring from costs given over the FFI, see scoring::checked_cost.
+    pub(crate) fn affine(mismatch_score: i32, gap_extend: i32, gap_open: i32) -> Result<Self, c_int> {
+        Ok(Scoring::Affine(GapAffine::new(
+            checked_cost("mismatch_score", mismatch_score)?,
+            checked_cost("gap_extend", gap_extend)?,
+            checked_cost("gap_open", gap_open)?,
+        )))
+    }
+
+    /// Two-piece affine gap scoring from costs given over the FFI, see scoring::checked_cost. Also
+    /// fails with -1 unless the second piece extends gaps more cheaply, which poasta requires.
+    pub(crate) fn two_piece(
+        mismatch_score: i32,
+        gap_extend1: i32,
+        gap_open1: i32,
+        gap_extend2: i32,
+        gap_open2: i32,
+    ) -> Result<Self, c_int> {
+        let mismatch_score = checked_cost("mismatch_score", mismatch_score)?;
+        let (gap_extend1, gap_open1) = (checked_cost("gap_extend1", gap_extend1)?, checked_cost("gap_open1", gap_open1)?);
+        let (gap_extend2, gap_open2) = (checked_cost("gap_extend2", gap_extend2)?, checked_cost("gap_open2", gap_open2)?);
+        if gap_extend1 <= gap_extend2 {
+            return Err(error::fail(-1, "gap_extend1 must be greater than gap_extend2"));
+        }
+
+        Ok(Scoring::TwoPiece(GapAffine2Piece::new(mismatch_score, gap_extend1, gap_open1, gap_extend2, gap_open2)))
+    }
+
+    /// Alphabet the scoring is meant for, Auto for any.
+    pub(crate) fn alphabet(&self) -> PoastaAlphabet {
+        match self {
+            Scoring::Custom(params) => params.alphabet,
+            _ => PoastaAlphabet::Auto,
+        }
+    }
+
+    /// Aligns `seq` to any graph poasta can align to (Global alignment), with the DP aligner's
+    /// memory in `buffers` if it is used. Returns the alignment and its cost.
+    pub(crate) fn align<G>(&self, graph: &G, seq: &[u8], buffers: &mut DpBuffers) -> (Alignment<NodeIx>, i64)
+    where
+        G: AlignableRefGraph<NodeIndex = NodeIx>,
+    {
+        match (self, &buffers.cancel) {
+            // The DP aligner checks the cancel flag itself
+            (Scoring::Custom(_), _) => dp::align(graph, seq, &DpCosts::from_scoring(self), buffers),
+            (_, Some(cancel)) => self.align_poasta(&Cancellable::new(graph, cancel), seq),
+            (_, None) => self.align_poasta(graph, seq),
+        }
+    }
+
+    /// Aligns `seq` with poasta's aligner, which doesn't support custom scoring.
+    fn align_poasta<G>(&self, graph: &G, seq: &[u8]) -> (Alignment<NodeIx>, i64)
+    where
+        G: AlignableRefGraph<NodeIndex = NodeIx>,
+    {
+        // Always use Global alignment
+        let aln_type = AlignmentType::Global;
+
+        let result = match self {
+            Scoring::Affine(costs) => PoastaAligner::new(AffineMinGapCost(*costs), aln_type).align::<u32, _>(graph, seq),
+            Scoring::TwoPiece(costs) => PoastaAligner::new(Affine2PieceMinGapCost(*costs), aln_type).align::<u32, _>(graph, seq),
+            Scoring::Custom(_) => unreachable!("custom scoring is aligned with the DP aligner"),
+        };
+
+        metrics::record_alignment(result.num_visited);
+        let score = match result.score {
+            Score::Score(score) => score.get().into(),
+            Score::Unvisited => i64::MAX,
+        };
+
+        (result.alignment, score)
+    }
+}
+
+impl PoastaGraph {
+    pub(crate) fn new() -> Self {
+        PoastaGraph {
+            graph: empty_poa_graph(),
+            sequences: Arc::new(Vec::new()),
+            links: Vec::new(),
+            path_weights: Vec::new(),
+            circular: false,
+            codon: None,
+            homopolymer: None,
+            primers: None,
+            length_filter: None,
+            tie_break: PoastaTieBreak::default(),
+            bisulfite: false,
+            min_support: 1,
+            cache: AlignmentCache::default(),
+            umi_counts: HashMap::new(),
+            kmer_filter: None,
+            timeout: None,
+            backbone: None,
+            masked_regions: None,
+            generation: 0,
+            consensus_len: OnceLock::new(),
+            insertions: 0,
+            batch_id: 0,
+            user_data: 0,
+            metadata: Vec::new(),
+            alphabet: PoastaAlphabet::Auto,
+        }
+    }
+
+    /// Name of the sequence at `index` (in insertion order) in the output.
+    pub(crate) fn sequence_name(&self, index: usize) -> String {
+        sequence_name(&self.sequences[index].info, index)
+    }
+
+    /// The alphabet that was set, or else the one detected from the first sequence (Auto if there
+    /// is none).
+    pub(crate) fn alphabet(&self) -> PoastaAlphabet {
+        match (self.alphabet, self.sequences.first()) {
+            (PoastaAlphabet::Auto, Some(first)) => PoastaAlphabet::detect(&first.seq),
+            (alphabet, _) => alphabet,
+        }
+    }
+
+    /// Sequences that are not masked, i.e., that take part in consensus and MSA computation.
+    pub(crate) fn active_sequences(&self) -> impl Iterator<Item = &SequenceRecord> {
+        self.selected_sequences(Selection::default())
+    }
+
+    /// The sequences `selection` includes, in insertion order.
+    pub(crate) fn selected_sequences(&self, selection: Selection) -> impl Iterator<Item = &SequenceRecord> {
+        self.sequences.iter()
+            .enumerate()
+            .filter(move |(index, record)| selection.includes(*index, record))
+            .map(|(_, record)| record)
+    }
+
+    /// The paths adding weight to the graph for the consensus and other weighted output: those of
+    /// the active sequences with their weights (see `SequenceInfo::weight_factor`), then those
+    /// bumped by poasta_bump_node_path_weight.
+    pub(crate) fn weighted_paths(&self) -> impl Iterator<Item = WeightedPath<'_>> {
+        self.selected_weighted_paths(Selection::default())
+    }
+
+    /// Like `weighted_paths`, with the paths of the sequences `selection` includes.
+    pub(crate) fn selected_weighted_paths(&self, selection: Selection) -> impl Iterator<Item = WeightedPath<'_>> {
+        let sequences = self.selected_sequences(selection).map(|record| WeightedPath {
+            path: &record.path,
+            weights: Some(&record.weights),
+            factor: record.info.weight_factor(),
+        });
+        let bumped = self.path_weights.iter().map(|bump| WeightedPath { path: &bump.path, weights: None, factor: bump.weight });
+
+        sequences.chain(bumped)
+    }
+
+    /// Whether there is an edge from each node of `path` to the next.
+    pub(crate) fn has_edges(&self, path: &[NodeIx]) -> bool {
+        path.windows(2).all(|pair| self.graph.successors(pair[0]).any(|succ| succ == pair[1]))
+    }
+
+    /// An empty graph with the same configuration as this one.
+    pub(crate) fn empty_like(&self) -> Self {
+        PoastaGraph {
+            circular: self.circular,
+            codon: self.codon,
+            homopolymer: self.homopolymer,
+            primers: self.primers.clone(),
+            length_filter: self.length_filter,
+            tie_break: self.tie_break,
+            bisulfite: self.bisulfite,
+            min_support: self.min_support,
+            cache: self.cache.empty_like(),
+            timeout: self.timeout,
+            insertions: self.insertions,
+            batch_id: self.batch_id,
+            metadata: self.metadata.clone(),
+            alphabet: self.alphabet,
+            ..PoastaGraph::new()
+        }
+    }
+
+    /// Copy of the graph sharing its storage, which is only copied when either side changes it.
+    /// The k-mer filter isn't copied but rebuilt on demand.
+    pub(crate) fn shallow_clone(&self) -> Self {
+        PoastaGraph {
+            graph: Arc::clone(&self.graph),
+            sequences: Arc::clone(&self.sequences),
+            links: self.links.clone(),
+            path_weights: self.path_weights.clone(),
+            cache: self.cache.clone(),
+            umi_counts: self.umi_counts.clone(),
+            backbone: self.backbone,
+            masked_regions: self.masked_regions.clone(),
+            ..self.empty_like()
+        }
+    }
+
+    /// Builds a new graph with the same configuration by adding the given sequences in order.
+    pub(crate) fn rebuild_with<'a>(&self, records: impl IntoIterator<Item = &'a SequenceRecord>) -> Result<Self, c_int> {
+        let mut graph = self.empty_like();
+        for record in records {
+            let (scoring, info) = (record.scoring.clone(), record.info.clone());
+            match graph.insert_sequence(&record.seq, &record.weights, scoring, info, &mut DpBuffers::default()).0 {
+                0 | APPROXIMATE => (),
+                status => return Err(status),
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Aligns `seq` to the graph (Global alignment) and adds it with the given per-base weights,
+    /// after trimming primers if configured.
+    /// Returns 0 on success, 1 if the sequence was added along an approximate alignment after a
+    /// timeout, -1 if it has symbols outside the graph's alphabet, -2 if adding the first sequence
+    /// failed, -3 if adding an aligned sequence failed, -4 if the sequence was rejected by the
+    /// length filter and -7 if the alignment timed out.
+    pub(crate) fn add_sequence(&mut self, seq: &[u8], weights: &[usize], scoring: Scoring, info: SequenceInfo) -> c_int {
+        self.add_sequence_with(seq, weights, scoring, info, &mut DpBuffers::default())
+    }
+
+    /// Like `add_sequence`, with the DP aligner's memory in `buffers`.
+    pub(crate) fn add_sequence_with(
+        &mut self,
+        seq: &[u8],
+        weights: &[usize],
+        scoring: Scoring,
+        info: SequenceInfo,
+        buffers: &mut DpBuffers,
+    ) -> c_int {
+        self.add_sequence_scored(seq, weights, scoring, info, buffers).0
+    }
+
+    /// Like `add_sequence_with`, but also returns the cost of the alignment the sequence was added
+    /// along, if it was added along an exact alignment (0 for the first sequence of the graph).
+    pub(crate) fn add_sequence_scored(
+        &mut self,
+        seq: &[u8],
+        weights: &[usize],
+        scoring: Scoring,
+        info: SequenceInfo,
+        buffers: &mut DpBuffers,
+    ) -> (c_int, Option<i64>) {
+        // Scoring meant for an alphabet also declares it for a graph without one
+        let declared = scoring.alphabet();
+        if declared != PoastaAlphabet::Auto && self.alphabet != PoastaAlphabet::Auto && self.alphabet != declared {
+            let message = format!("the scoring is for {}, but the graph's alphabet is {}", declared.name(), self.alphabet.name());
+            return (error::fail(-1, &message), None);
+        }
+
+        for alphabet in [self.alphabet(), declared] {
+            if let Some((pos, symbol)) = alphabet.first_foreign(seq) {
+                let message = format!("sequence has {} at position {pos}, which isn't {}", symbol.escape_ascii(), alphabet.name());
+                return (error::fail(-1, &message), None);
+            }
+        }
+
+        let alphabet = match self.alphabet() {
+            PoastaAlphabet::Auto => declared,
+            alphabet => alphabet,
+        };
+        let (seq, weights) = match &self.primers {
+            Some(primers) => primers.apply(seq, weights, alphabet),
+            None => (seq.to_vec(), weights.to_vec()),
+        };
+
+        if self.length_filter.is_some_and(|filter| !filter.accepts(self, seq.len())) {
+            return (LENGTH_OUTLIER, None);
+        }
+
+        let result = self.insert_sequence(&seq, &weights, scoring, info, buffers);
+        if result.0 >= 0 && self.alphabet == PoastaAlphabet::Auto && self.sequences.len() == 1 {
+            self.alphabet = declared;
+        }
+
+        result
+    }
+
+    /// Like `add_sequence_scored`, but for sequences that were already preprocessed (e.g., when
+    /// rebuilding a graph from its records).
+    pub(crate) fn insert_sequence(
+        &mut self,
+        seq: &[u8],
+        weights: &[usize],
+        scoring: Scoring,
+        info: SequenceInfo,
+        buffers: &mut DpBuffers,
+    ) -> (c_int, Option<i64>) {
+        let info = SequenceInfo { approximate: false, ..info };
+        if self.graph.is_empty() {
+            // First sequence, just add it
+            return match self.commit(seq, weights, None, scoring, info) {
+                0 => (0, Some(0)),
+                _ => (-2, None),
+            };
+        }
+
+        if self.circular {
+            let offset = origin_offset(self, seq);
+            if offset > 0 {
+                let mut seq = seq.to_vec();
+                let mut weights = weights.to_vec();
+                seq.rotate_left(offset);
+                weights.rotate_left(offset);
+
+                return self.align_and_commit(&seq, &weights, scoring, info, buffers);
+            }
+        }
+
+        self.align_and_commit(seq, weights, scoring, info, buffers)
+    }
+
+    fn align_and_commit(
+        &mut self,
+        seq: &[u8],
+        weights: &[usize],
+        scoring: Scoring,
+        mut info: SequenceInfo,
+        buffers: &mut DpBuffers,
+    ) -> (c_int, Option<i64>) {
+        let cached = self.cache.lookup(seq)
+            .map(|ix| &self.sequences[ix])
+            .filter(|record| record.seq == seq);
+
+        if self.cache.is_enabled() {
+            match cached {
+                Some(_) => self.cache.hits += 1,
+                None => self.cache.misses += 1,
+            }
+        }
+
+        let (alignment, score) = if let Some(record) = cached {
+            // An identical sequence matches the cached path without any edits
+            let alignment = record.path.iter()
+                .enumerate()
+                .map(|(qpos, node)| AlignedPair::new(Some(*node), Some(qpos)))
+                .collect();
+
+            (alignment, Some(0))
+        } else if let Some(timeout) = self.timeout {
+            match align_with_timeout(self, seq, &scoring, info.mode, timeout.budget) {
+                Ok((alignment, score)) => (alignment, Some(score)),
+                Err(TIMEOUT) if timeout.approximate => {
+                    let Some(alignment) = consensus_alignment(self, seq, &scoring) else {
+                        return (TIMEOUT, None);
+                    };
+
+                    info.approximate = true;
+                    (alignment, None)
+                },
+                Err(status) => return (status, None),
+            }
+        } else {
+            let (alignment, score) = self.align_scored(seq, &scoring, info.mode, buffers);
+            (alignment, Some(score))
+        };
+
+        let approximate = info.approximate;
+        match self.commit(seq, weights, Some(&alignment), scoring, info) {
+            0 if approximate => (APPROXIMATE, None),
+            0 => (0, score),
+            _ => (-3, None),
+        }
+    }
+
+    /// Aligns `seq` to the graph in `mode` with the aligner suited to the configuration.
+    pub(crate) fn align(&self, seq: &[u8], scoring: &Scoring, mode: PoastaAlignmentMode) -> Alignment<NodeIx> {
+        self.align_with(seq, scoring, mode, &mut DpBuffers::default())
+    }
+
+    /// Like `align`, with the DP aligner's memory in `buffers`.
+    pub(crate) fn align_with(
+        &self,
+        seq: &[u8],
+        scoring: &Scoring,
+        mode: PoastaAlignmentMode,
+        buffers: &mut DpBuffers,
+    ) -> Alignment<NodeIx> {
+        self.align_scored(seq, scoring, mode, buffers).0
+    }
+
+    /// Like `align_with`, but also returns the cost of the alignment, which is negative for local
+    /// alignments with more match bonus than penalties.
+    pub(crate) fn align_scored(
+        &self,
+        seq: &[u8],
+        scoring: &Scoring,
+        mode: PoastaAlignmentMode,
+        buffers: &mut DpBuffers,
+    ) -> (Alignment<NodeIx>, i64) {
+        let _span = trace::span(PoastaTraceLevel::Debug, c"align");
+        let _usage = resource::track(PoastaOperation::Align);
+        if self.codon.is_some() || self.homopolymer.is_some() || !mode.is_global() {
+            dp::align(&GraphView::new(self), seq, &self.dp_costs(scoring, mode), buffers)
+        } else if self.links.is_empty() {
+            scoring.align(&*self.graph, seq, buffers)
+        } else {
+            scoring.align(&GraphView::new(self), seq, buffers)
+        }
+    }
+
+    /// Whether sequences are aligned with the DP aligner rather than poasta's aligner.
+    pub(crate) fn uses_dp_aligner(&self, scoring: &Scoring, mode: PoastaAlignmentMode) -> bool {
+        self.codon.is_some() || self.homopolymer.is_some() || !mode.is_global() || matches!(scoring, Scoring::Custom(_))
+    }
+
+    /// Costs of the DP aligner for aligning in `mode`, including the graph's gap configuration.
+    pub(crate) fn dp_costs(&self, scoring: &Scoring, mode: PoastaAlignmentMode) -> DpCosts {
+        let costs = DpCosts::from_scoring(scoring);
+        DpCosts {
+            codon: self.codon,
+            homopolymer: self.homopolymer,
+            mode: mode.normalized(),
+            match_bonus: mode.match_bonus(costs.mismatch),
+            ..costs
+        }
+    }
+
+    /// Adds `seq` to the graph according to `alignment`, and records the path it takes, with the
+    /// provenance of a new sequence unless `info` already has one.
+    /// Returns 0 on success and -3 if poasta rejected the alignment.
+    pub(crate) fn commit(
+        &mut self,
+        seq: &[u8],
+        weights: &[usize],
+        alignment: Option<&Alignment<NodeIx>>,
+        scoring: Scoring,
+        info: SequenceInfo,
+    ) -> c_int {
+        let _span = trace::span(PoastaTraceLevel::Debug, c"commit");
+        let _usage = resource::track(PoastaOperation::Commit);
+
+        // The alignment refers to the nodes of the graph it was computed on, which are renumbered if
+        // the graph is copied first
+        let translated: Option<Alignment<NodeIx>> = self.unshare().zip(alignment).map(|(node_map, alignment)| {
+            alignment.iter()
+                .map(|pair| AlignedPair::new(pair.rpos.map(|node| node_map[&node]), pair.qpos))
+                .collect()
+        });
+        let alignment = translated.as_ref().or(alignment);
+
+        let seq_name = sequence_name(&info, self.sequences.len());
+        let path = predict_path(&self.graph, seq, alignment);
+
+        if self.graph_mut().add_alignment_with_weights(&seq_name, seq, alignment, weights).is_err() {
+            return -3;
+        }
+
+        debug_assert_eq!(self.graph.sequences.last().map(|s| s.start_node()), path.first().copied());
+        debug_assert!(path.iter().zip(seq).all(|(node, base)| self.graph.get_symbol(*node) == *base) && self.has_edges(&path));
+
+        self.cache.insert(seq, self.sequences.len());
+        if let Some(filter) = &mut self.kmer_filter {
+            filter.insert_kmers(seq);
+        }
+
+        // Let the k-mer filter be rebuilt with a larger size on the next query
+        if self.kmer_filter.as_ref().is_some_and(|filter| filter.is_saturated()) {
+            self.kmer_filter = None;
+        }
+
+        let provenance = info.provenance.unwrap_or_else(|| PoastaProvenance::now(self));
+        self.insertions = self.insertions.max(provenance.insertion + 1);
+
+        if let Some(umi) = &info.umi {
+            *self.umi_counts.entry(umi.clone()).or_default() += 1;
+        }
+
+        self.sequences_mut().push(SequenceRecord {
+            seq: seq.to_vec(),
+            weights: weights.to_vec(),
+            scoring,
+            info: SequenceInfo { provenance: Some(provenance), ..info },
+            path,
+        });
+
+        0
+    }
+
+    /// Counts a change to the graph's content or output for poasta_graph_generation, and forgets
+    /// the consensus length of the previous state.
+    pub(crate) fn touch(&mut self) {
+        self.generation += 1;
+        self.consensus_len = OnceLock::new();
+    }
+
+    /// The sequence records for changing them, copied first if they are still shared with a clone.
+    pub(crate) fn sequences_mut(&mut self) -> &mut Vec<SequenceRecord> {
+        self.touch();
+        Arc::make_mut(&mut self.sequences)
+    }
+
+    /// poasta's graph, copied first if it is still shared (see `unshare`).
+    fn graph_mut(&mut self) -> &mut POAGraph<u32> {
+        self.unshare();
+        Arc::get_mut(&mut self.graph).unwrap()
+    }
+
+    /// Copies poasta's graph if it is still shared with a clone or a timed out alignment, by
+    /// replaying the committed sequences, which the host's trace and resource callbacks don't see.
+    /// Returns the mapping from the old nodes to the new ones if it was copied.
+    fn unshare(&mut self) -> Option<HashMap<NodeIx, NodeIx>> {
+        if Arc::get_mut(&mut self.graph).is_some() {
+            return None;
+        }
+
+        let _suppressed = (trace::suppress(), resource::suppress());
+        let mut copy = self.empty_like();
+        let node_map = copy.append_graph(self).expect("replaying committed sequences succeeds");
+
+        self.graph = copy.graph;
+        self.sequences = copy.sequences;
+        self.links = copy.links;
+        self.path_weights = copy.path_weights;
+        self.masked_regions = self.masked_regions.as_ref()
+            .map(|regions| regions.translated(|node| node_map.get(&node).copied()));
+
+        Some(node_map)
+    }
+
+    /// Copies all sequences of `other` into this graph without realigning them, such that `other`'s
+    /// structure is reproduced exactly, together with its links and path weights. Returns the
+    /// mapping from `other`'s nodes to the new nodes.
+    pub(crate) fn append_graph(&mut self, other: &PoastaGraph) -> Result<HashMap<NodeIx, NodeIx>, c_int> {
+        let mut node_map = HashMap::new();
+        self.append_records(&other.sequences, |node| other.graph.get_aligned_nodes(node), &mut node_map)?;
+        self.links.extend(other.links.iter().map(|(from, to)| (node_map[from], node_map[to])));
+        self.path_weights.extend(other.path_weights.iter().filter_map(|bump| bump.translated(|node| node_map.get(&node).copied())));
+
+        Ok(node_map)
+    }
+
+    /// Commits sequences of another graph along their paths, given the nodes each of its nodes is
+    /// aligned to. `node_map` maps the other graph's nodes to ours, and is extended with the nodes
+    /// created for the sequences.
+    pub(crate) fn append_records<'a>(
+        &mut self,
+        records: &[SequenceRecord],
+        aligned_nodes: impl Fn(NodeIx) -> &'a [NodeIx],
+        node_map: &mut HashMap<NodeIx, NodeIx>,
+    ) -> Result<(), c_int> {
+        for record in records {
+            // Bases in nodes we already copied are matches, bases in a node aligned to a copied node
+            // become mismatches (creating the node in the right column), everything else is new.
+            // poasta lists the node a new node was aligned to after the nodes aligned to that one,
+            // and before nodes created later, so picking the last copied one keeps the order.
+            let alignment: Alignment<NodeIx> = record.path.iter()
+                .enumerate()
+                .map(|(qpos, node)| {
+                    let rpos = node_map.get(node).copied().or_else(|| {
+                        aligned_nodes(*node).iter().rev().find_map(|n| node_map.get(n).copied())
+                    });
+
+                    AlignedPair::new(rpos, Some(qpos))
+                })
+                .collect();
+
+            let alignment = if self.graph.is_empty() { None } else { Some(&alignment) };
+            match self.commit(&record.seq, &record.weights, alignment, record.scoring.clone(), record.info.clone()) {
+                0 => (),
+                status => return Err(status),
+            }
+
+            let new_path = &self.sequences.last().unwrap().path;
+            node_map.extend(record.path.iter().copied().zip(new_path.iter().copied()));
+        }
+
+        Ok(())
+    }
+}
+
+fn empty_poa_graph() -> Arc<POAGraph<u32>> {
+    Arc::new(POAGraph::new())
+}
+
+/// The caller's name for a sequence, or a made-up one (`seq_N`, by insertion order).
+fn sequence_name(info: &SequenceInfo, index: usize) -> String {
+    info.name.clone().unwrap_or_else(|| format!("seq_{index}"))
+}
+
+/// Determines the node each base of `seq` will be assigned to when poasta adds it to `graph`
+/// with the given alignment. This mirrors `POAGraph::add_alignment_with_weights`, which creates
+/// new nodes with consecutive indices.
+fn predict_path(graph: &POAGraph<u32>, seq: &[u8], alignment: Option<&Alignment<NodeIx>>) -> Vec<NodeIx> {
+    let mut next_ix = graph.node_count_with_start_and_end();
+    let mut new_node = || {
+        next_ix += 1;
+        NodeIx::new(next_ix - 1)
+    };
+
+    let Some(alignment) = alignment else {
+        return seq.iter().map(|_| new_node()).collect();
+    };
+
+    let aligned: Vec<usize> = alignment.iter()
+        .filter_map(|pair| pair.qpos)
+        .filter(|qpos| *qpos < seq.len())
+        .collect();
+
+    let (Some(&first), Some(&last)) = (aligned.first(), aligned.last()) else {
+        return Vec::new();
+    };
+
+    let mut path = vec![None; seq.len()];
+
+    // Unaligned bases at the beginning and the end get new nodes first
+    for node in path.iter_mut().take(first) {
+        *node = Some(new_node());
+    }
+    for node in path.iter_mut().skip(last + 1) {
+        *node = Some(new_node());
+    }
+
+    // Mismatch nodes created while adding this sequence, with the column they were added to
+    let mut created: Vec<(Vec<NodeIx>, NodeIx, u8)> = Vec::new();
+    for pair in alignment {
+        let Some(qpos) = pair.qpos else {
+            continue;
+        };
+
+        let qsymbol = seq[qpos];
+        let node = match pair.rpos {
+            Some(r) if graph.get_symbol(r) == qsymbol => r,
+            Some(r) => {
+                let existing = graph.get_aligned_nodes(r).iter()
+                    .copied()
+                    .find(|other| graph.get_symbol(*other) == qsymbol)
+                    .or_else(|| created.iter()
+                        .find(|(column, _, symbol)| *symbol == qsymbol && column.contains(&r))
+                        .map(|(_, n, _)| *n));
+
+                existing.unwrap_or_else(|| {
+                    let n = new_node();
+                    let mut column = graph.get_aligned_nodes(r).to_vec();
+                    column.push(r);
+                    created.push((column, n, qsymbol));
+
+                    n
+                })
+            },
+            None => new_node(),
+        };
+
+        path[qpos] = Some(node);
+    }
+
+    path.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add, graph_of, outputs};
+    use crate::{poasta_free_graph, poasta_graph_clone};
+
+    const READS: [&str; 3] = ["ACGTTGCA", "ACGATGCA", "ACGTTCCA"];
+
+    #[test]
+    fn clones_change_independently() {
+        let mut graph = graph_of(&READS);
+        let clone = unsafe { poasta_graph_clone(&graph) };
+        let before = outputs(&graph);
+
+        // Copies the shared graph by replaying the sequences, then adds to the copy
+        add(&mut graph, "ACGATGCAT");
+        assert!(!Arc::ptr_eq(&graph.graph, &unsafe { &*clone }.graph));
+        assert_eq!(outputs(unsafe { &*clone }), before);
+        assert_eq!(outputs(&graph), outputs(&graph_of(&[READS[0], READS[1], READS[2], "ACGATGCAT"])));
+
+        add(unsafe { &mut *clone }, "ACGT");
+        assert_eq!(outputs(unsafe { &*clone }), outputs(&graph_of(&[READS[0], READS[1], READS[2], "ACGT"])));
+        assert_eq!(outputs(&graph), outputs(&graph_of(&[READS[0], READS[1], READS[2], "ACGATGCAT"])));
+
+        unsafe { poasta_free_graph(clone) };
+    }
+}
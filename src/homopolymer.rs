@@ -0,0 +1,74 @@
+use std::os::raw::c_int;
+
+use crate::dp::HomopolymerGaps;
+use crate::error;
+use crate::scoring::checked_cost;
+use crate::PoastaGraph;
+
+/// Enables homopolymer-aware gap penalties for sequences added to the graph from now on, matching
+/// the tendency of nanopore sequencing to miscount homopolymer bases. Deleting a base of a graph
+/// homopolymer of at least `min_length` bases, or inserting another copy of its base, costs
+/// `discount` less per gap base for every base the homopolymer has beyond `min_length - 1`, down
+/// to 0 (e.g., with `min_length` 3 and `discount` 1, gap extension within a homopolymer of 5 bases
+/// is 3 cheaper). Gap opening costs are unchanged. A discount or minimum length of 0 disables
+/// homopolymer-aware mode.
+/// Homopolymer-aware alignment uses a full dynamic programming aligner, which is slower than the
+/// default aligner. Returns -1 if the graph is NULL or the discount is negative or above 255.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_set_homopolymer_gaps(graph: *mut PoastaGraph, min_length: u8, discount: i32) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+        if let Err(status) = checked_cost("discount", discount) {
+            return status;
+        }
+
+        let homopolymer = (min_length > 0 && discount > 0).then_some(HomopolymerGaps {
+            min_length: min_length as usize,
+            discount,
+        });
+
+        unsafe { (*graph).homopolymer = homopolymer };
+
+        0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poasta_add_sequence_scored;
+    use crate::testing::graph_of;
+
+    const REFERENCE: &str = "ACGTCAAAAAAGCTGA";
+
+    /// Cost of aligning `read` to a graph of REFERENCE.
+    fn cost(read: &str, min_length: u8, discount: i32) -> i64 {
+        let mut graph = graph_of(&[REFERENCE]);
+        assert_eq!(unsafe { poasta_graph_set_homopolymer_gaps(&mut graph, min_length, discount) }, 0);
+
+        let mut score = -1;
+        let status = unsafe { poasta_add_sequence_scored(&mut graph, read.as_ptr().cast(), read.len(), 4, 2, 6, &mut score) };
+        assert_eq!(status, 0);
+
+        score
+    }
+
+    #[test]
+    fn gaps_within_homopolymers_are_cheaper() {
+        // Two of the six As deleted, and one inserted
+        let (deleted, inserted) = ("ACGTCAAAAGCTGA", "ACGTCAAAAAAAGCTGA");
+        assert_eq!(cost(deleted, 0, 0), 10);
+        assert_eq!(cost(inserted, 0, 0), 8);
+
+        // Extension is 4 cheaper with six bases, but never negative
+        assert_eq!(cost(deleted, 3, 1), 6);
+        assert_eq!(cost(inserted, 3, 1), 6);
+        assert_eq!(cost(deleted, 6, 1), 8);
+
+        // Gaps outside homopolymers cost as before
+        assert_eq!(cost("ACGCAAAAAAGCTGA", 3, 1), 8);
+        assert_eq!(unsafe { poasta_graph_set_homopolymer_gaps(&mut graph_of(&[REFERENCE]), 3, 256) }, -1);
+    }
+}
@@ -0,0 +1,482 @@
+//! Graphs built from the output of earlier runs or other tools.
+
+use std::collections::HashMap;
+use std::mem;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::alphabet::PoastaAlphabet;
+use crate::graph::{NodeIx, SequenceRecord};
+use crate::validate::validate;
+use crate::{error, is_valid_name, PoastaGraph, SequenceInfo};
+
+/// Builds a graph from GFA text (`len` bytes), e.g., the output of poasta_get_gfa of an earlier
+/// run, to continue adding sequences to it. Each P or W line
+/// becomes a sequence, added in the order of the lines along the segments it walks, so every base
+/// a path passes through becomes a node and each sequence gets weight 1 per base. Links that no
+/// path follows are kept as extra edges new sequences can align across. Segments no path passes
+/// through are left out, and H and other lines are ignored. GFA doesn't record which nodes are
+/// aligned to each other, so mismatching bases of different paths aren't aligned in the new graph.
+/// poasta_get_gfa and poasta's command line tool write walk ranges with offsets up to two bases
+/// too low; GFA in which all walks end early like that is read accordingly. Their offsets don't
+/// tell the first two bases of a segment apart, so a sequence starting at the second base of a
+/// segment is read with the base before it, and one ending at the first base with the base after.
+/// Returns NULL with -1 as the last error if the GFA is NULL, malformed, uses reverse strands or
+/// overlaps, or describes a cyclic graph. The caller must free the graph with poasta_free_graph.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_from_gfa(gfa: *const c_char, len: usize) -> *mut PoastaGraph {
+    error::catch(|| {
+        if gfa.is_null() {
+            error::fail(-1, "GFA is NULL");
+            return ptr::null_mut();
+        }
+
+        let gfa = unsafe { slice::from_raw_parts(gfa as *const u8, len) };
+        let graph = match String::from_utf8_lossy(gfa).parse::<Gfa>().and_then(|gfa| gfa.into_graph()) {
+            Ok(graph) => graph,
+            Err(message) => {
+                error::fail(-1, &message);
+                return ptr::null_mut();
+            },
+        };
+
+        Box::into_raw(Box::new(graph))
+    })
+}
+
+/// Builds a graph from a multiple sequence alignment of `n` rows of `width` symbols each, e.g.,
+/// computed by another tool, to continue adding sequences to it. `-` and `.` are gaps. Each row
+/// becomes a sequence of weight 1 per base, called `seq_N` like sequences added without a name.
+/// Bases in the same column share a node if they have the same symbol, and are aligned to each
+/// other otherwise. Neighbouring columns without bases in common (e.g., insertions of different
+/// rows) may be in a different order in the MSA of the new graph. Returns NULL with -1 as the last
+/// error if `rows` or a row is NULL, `n` is 0 or a row has no bases. The caller must free the
+/// graph with poasta_free_graph.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_from_msa(rows: *const *const c_char, n: usize, width: usize) -> *mut PoastaGraph {
+    error::catch(|| {
+        if rows.is_null() || n == 0 {
+            error::fail(-1, "the MSA has no rows");
+            return ptr::null_mut();
+        }
+
+        let rows = unsafe { slice::from_raw_parts(rows, n) };
+        if let Some(ix) = rows.iter().position(|row| row.is_null()) {
+            error::fail(-1, &format!("row {ix} is NULL"));
+            return ptr::null_mut();
+        }
+
+        let rows: Vec<&[u8]> = rows.iter()
+            .map(|row| unsafe { slice::from_raw_parts(*row as *const u8, width) })
+            .collect();
+        match msa_graph(&rows) {
+            Ok(graph) => Box::into_raw(Box::new(graph)),
+            Err(message) => {
+                error::fail(-1, &message);
+                ptr::null_mut()
+            },
+        }
+    })
+}
+
+/// Adds the rows of an MSA to a new graph along nodes numbered per distinct symbol of each column,
+/// aligned to the other symbols of their column.
+fn msa_graph(rows: &[&[u8]]) -> Result<PoastaGraph, String> {
+    let width = rows.first().map_or(0, |row| row.len());
+    let mut columns: Vec<Vec<(u8, NodeIx)>> = vec![Vec::new(); width];
+    let mut records = Vec::with_capacity(rows.len());
+    let mut num_nodes = 0;
+    for (ix, row) in rows.iter().enumerate() {
+        let mut seq = Vec::new();
+        let mut path = Vec::new();
+        for (nodes, symbol) in columns.iter_mut().zip(row.iter()).filter(|(_, symbol)| !matches!(symbol, b'-' | b'.')) {
+            let node = match nodes.iter().find(|(other, _)| other == symbol) {
+                Some((_, node)) => *node,
+                None => {
+                    nodes.push((*symbol, NodeIx::new(num_nodes)));
+                    num_nodes += 1;
+                    NodeIx::new(num_nodes - 1)
+                },
+            };
+
+            seq.push(*symbol);
+            path.push(node);
+        }
+
+        if seq.is_empty() {
+            return Err(format!("row {ix} has no bases"));
+        }
+
+        records.push(imported_record(seq, path, None));
+    }
+
+    let aligned: HashMap<NodeIx, Vec<NodeIx>> = columns.iter()
+        .flat_map(|nodes| nodes.iter().map(|(_, node)| {
+            (*node, nodes.iter().map(|(_, other)| *other).filter(|other| other != node).collect())
+        }))
+        .collect();
+
+    let mut graph = PoastaGraph::new();
+    graph.append_records(&records, |node| aligned.get(&node).map_or(&[], Vec::as_slice), &mut HashMap::new())
+        .map_err(|_| "the rows can't be added to the graph".to_string())?;
+
+    checked(graph)
+}
+
+/// A sequence to import along `path`, with weight 1 per base.
+fn imported_record(seq: Vec<u8>, path: Vec<NodeIx>, name: Option<String>) -> SequenceRecord {
+    SequenceRecord {
+        weights: vec![1; seq.len()],
+        // Sequences are never aligned, but graph rebuilds may realign them with the default costs
+        scoring: PoastaAlphabet::detect(&seq).default_scoring(),
+        seq,
+        info: SequenceInfo { name, ..SequenceInfo::default() },
+        path,
+    }
+}
+
+/// The graph, or the first problem poasta_graph_validate finds in it.
+fn checked(graph: PoastaGraph) -> Result<PoastaGraph, String> {
+    match validate(&graph).first() {
+        Some(problem) => Err(format!("the input describes an invalid graph: {problem}")),
+        None => Ok(graph),
+    }
+}
+
+/// The segments, links and paths of a GFA file, with the paths as lists of segment bases.
+struct Gfa {
+    /// Symbols of each segment, and the segment with each name.
+    segments: Vec<Vec<u8>>,
+    segment_ids: HashMap<String, usize>,
+
+    /// Segments linked by L lines.
+    links: Vec<(usize, usize)>,
+
+    /// Name and (segment, offset) of each base of each path.
+    paths: Vec<(String, Vec<(usize, usize)>)>,
+
+    /// Ranges of the W lines that have one, applied by `apply_ranges`.
+    ranges: Vec<WalkRange>,
+}
+
+/// The range of a W line, in the concatenated segments of its walk.
+struct WalkRange {
+    path: usize,
+    line_no: usize,
+    start: usize,
+    end: usize,
+
+    /// Length of the concatenated segments, and of the first and last segment.
+    len: usize,
+    first_len: usize,
+    last_len: usize,
+}
+
+impl WalkRange {
+    /// Whether the range can be one written by poasta's graph_to_gfa, which gives the first two
+    /// bases of each segment offset 0 and each later base an offset one too low, and ends ranges
+    /// at the offset of the last base rather than after it: it starts at 0 or before the last base
+    /// of the first segment, and ends in the last segment, two bases before its end or one if it
+    /// is a single base.
+    fn fits_poasta(&self) -> bool {
+        (self.start == 0 || self.start + 1 < self.first_len)
+            && self.end + self.last_len >= self.len
+            && self.end + self.last_len.min(2) <= self.len
+    }
+
+    /// The range of bases of a range written by poasta's graph_to_gfa. Offset 0 is taken to be the
+    /// first base of a segment at the start and the second one at the end.
+    fn poasta_bases(&self) -> (usize, usize) {
+        let last_start = self.len - self.last_len;
+        let start = if self.start == 0 { 0 } else { self.start + 1 };
+        let end = if self.last_len == 1 { self.len } else { self.end + 2 };
+        debug_assert!(end > last_start && start < end);
+
+        (start, end)
+    }
+}
+
+impl std::str::FromStr for Gfa {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let lines: Vec<(usize, Vec<&str>)> = text.lines()
+            .enumerate()
+            .map(|(ix, line)| (ix + 1, line.trim_end_matches('\r').split('\t').collect()))
+            .collect();
+
+        // Segments first, since links and paths may refer to segments defined later
+        let mut gfa = Gfa {
+            segments: Vec::new(),
+            segment_ids: HashMap::new(),
+            links: Vec::new(),
+            paths: Vec::new(),
+            ranges: Vec::new(),
+        };
+        for (line_no, fields) in lines.iter().filter(|(_, fields)| fields[0] == "S") {
+            let (Some(name), Some(symbols)) = (fields.get(1), fields.get(2)) else {
+                return Err(format!("line {line_no}: segment without name or sequence"));
+            };
+            if *symbols == "*" || symbols.is_empty() {
+                return Err(format!("line {line_no}: segment {name} has no sequence"));
+            }
+            if gfa.segment_ids.insert(name.to_string(), gfa.segments.len()).is_some() {
+                return Err(format!("line {line_no}: segment {name} is defined twice"));
+            }
+
+            gfa.segments.push(symbols.as_bytes().to_vec());
+        }
+
+        for (line_no, fields) in &lines {
+            match fields[0] {
+                "L" => {
+                    let [_, from, from_strand, to, to_strand, overlap, ..] = fields[..] else {
+                        return Err(format!("line {line_no}: link with missing fields"));
+                    };
+                    if from_strand != "+" || to_strand != "+" {
+                        return Err(format!("line {line_no}: reverse strand links are not supported"));
+                    }
+                    if overlap != "0M" && overlap != "*" {
+                        return Err(format!("line {line_no}: overlapping links are not supported"));
+                    }
+
+                    gfa.links.push((gfa.segment(from, *line_no)?, gfa.segment(to, *line_no)?));
+                },
+                "P" => {
+                    let [_, name, steps, ..] = fields[..] else {
+                        return Err(format!("line {line_no}: path with missing fields"));
+                    };
+
+                    let segments = steps.split(',')
+                        .map(|step| match step.strip_suffix('+') {
+                            Some(segment) => gfa.segment(segment, *line_no),
+                            None => Err(format!("line {line_no}: path step {step} isn't on the forward strand")),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    let bases = gfa.bases(&segments);
+                    gfa.add_path(name.to_string(), bases, *line_no)?;
+                },
+                "W" => {
+                    let [_, sample, hap_index, seq_id, start, end, walk, ..] = fields[..] else {
+                        return Err(format!("line {line_no}: walk with missing fields"));
+                    };
+                    if walk.contains('<') {
+                        return Err(format!("line {line_no}: reverse strand walks are not supported"));
+                    }
+
+                    let segments = walk.split('>')
+                        .skip(1)
+                        .map(|segment| gfa.segment(segment, *line_no))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    let bases = gfa.bases(&segments);
+                    if (start, end) != ("*", "*") {
+                        let (first, last) = (segments[0], segments[segments.len() - 1]);
+                        let range = match (start.parse::<usize>(), end.parse::<usize>()) {
+                            (Ok(start), Ok(end)) if start <= end && end <= bases.len() => WalkRange {
+                                path: gfa.paths.len(),
+                                line_no: *line_no,
+                                start,
+                                end,
+                                len: bases.len(),
+                                first_len: gfa.segments[first].len(),
+                                last_len: gfa.segments[last].len(),
+                            },
+                            _ => return Err(format!("line {line_no}: invalid walk range {start}-{end}")),
+                        };
+                        gfa.ranges.push(range);
+                    }
+
+                    let name = match sample {
+                        "*" => seq_id.to_string(),
+                        _ => format!("{sample}#{hap_index}#{seq_id}"),
+                    };
+                    gfa.add_path(name, bases, *line_no)?;
+                },
+                _ => (),
+            }
+        }
+
+        gfa.apply_ranges()?;
+
+        Ok(gfa)
+    }
+}
+
+impl Gfa {
+    fn segment(&self, name: &str, line_no: usize) -> Result<usize, String> {
+        self.segment_ids.get(name).copied().ok_or(format!("line {line_no}: unknown segment {name}"))
+    }
+
+    /// The (segment, offset) of each base of the concatenated segments.
+    fn bases(&self, segments: &[usize]) -> Vec<(usize, usize)> {
+        segments.iter()
+            .flat_map(|segment| (0..self.segments[*segment].len()).map(|offset| (*segment, offset)))
+            .collect()
+    }
+
+    fn add_path(&mut self, name: String, bases: Vec<(usize, usize)>, line_no: usize) -> Result<(), String> {
+        if bases.is_empty() {
+            return Err(format!("line {line_no}: path {name} is empty"));
+        }
+        if !is_valid_name(&name) {
+            return Err(format!("line {line_no}: invalid path name {name}"));
+        }
+
+        self.paths.push((name, bases));
+
+        Ok(())
+    }
+
+    /// Cuts the paths of W lines to their ranges. poasta_get_gfa and poasta's command line tool
+    /// write them with poasta's graph_to_gfa (see `WalkRange::fits_poasta`), whose walks all end
+    /// early. Any other GFA has a walk to the end of a segment where the graph ends, so the ranges
+    /// are read like graph_to_gfa writes them if they all fit it.
+    fn apply_ranges(&mut self) -> Result<(), String> {
+        let poasta = self.ranges.iter().all(WalkRange::fits_poasta);
+        for range in mem::take(&mut self.ranges) {
+            let (start, end) = if poasta { range.poasta_bases() } else { (range.start, range.end) };
+            let (name, bases) = &mut self.paths[range.path];
+            if start == end {
+                return Err(format!("line {}: path {name} is empty", range.line_no));
+            }
+
+            *bases = bases[start..end].to_vec();
+        }
+
+        Ok(())
+    }
+
+    /// Adds the paths to a new graph along their segment bases, which are numbered consecutively
+    /// to serve as the nodes the paths are copied from.
+    fn into_graph(self) -> Result<PoastaGraph, String> {
+        let first_base: Vec<usize> = self.segments.iter()
+            .scan(0, |next, symbols| {
+                *next += symbols.len();
+                Some(*next - symbols.len())
+            })
+            .collect();
+        let node = |(segment, offset): (usize, usize)| NodeIx::new(first_base[segment] + offset);
+
+        let records: Vec<SequenceRecord> = self.paths.into_iter()
+            .map(|(name, bases)| {
+                let seq = bases.iter().map(|(segment, offset)| self.segments[*segment][*offset]).collect();
+                imported_record(seq, bases.into_iter().map(node).collect(), Some(name))
+            })
+            .collect();
+
+        let mut graph = PoastaGraph::new();
+        let mut node_map = HashMap::new();
+        graph.append_records(&records, |_| &[], &mut node_map)
+            .map_err(|_| "the paths don't form a valid graph".to_string())?;
+
+        for (from, to) in self.links {
+            let last = node((from, self.segments[from].len() - 1));
+            let (Some(from), Some(to)) = (node_map.get(&last), node_map.get(&node((to, 0)))) else {
+                continue;
+            };
+
+            if !graph.graph.successors(*from).any(|succ| succ == *to) {
+                graph.links.push((*from, *to));
+            }
+        }
+
+        checked(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+    use crate::msa::{poasta_get_msa_padded, PoastaMsaOptions};
+    use crate::testing::{add, consensus, graph_of, take};
+    use crate::{poasta_free_msa, poasta_get_gfa};
+
+    fn from_gfa(gfa: &str) -> Box<PoastaGraph> {
+        let graph = unsafe { poasta_graph_from_gfa(gfa.as_ptr() as *const c_char, gfa.len()) };
+        assert!(!graph.is_null(), "{gfa} can't be read");
+
+        unsafe { Box::from_raw(graph) }
+    }
+
+    fn sequences(graph: &PoastaGraph) -> Vec<String> {
+        graph.sequences.iter().map(|record| String::from_utf8(record.seq.clone()).unwrap()).collect()
+    }
+
+    /// As written by poasta's command line tool for two reads with a SNP.
+    const POASTA_GFA: &str = "H\tVN:Z:1.1\n\
+        S\ts0\tACGT\n\
+        S\ts1\tA\n\
+        S\ts2\tT\n\
+        S\ts3\tCGT\n\
+        L\ts0\t+\ts1\t+\t0M\n\
+        L\ts0\t+\ts2\t+\t0M\n\
+        L\ts1\t+\ts3\t+\t0M\n\
+        L\ts2\t+\ts3\t+\t0M\n\
+        W\t*\t0\tread1\t0\t6\t>s0>s1>s3\n\
+        W\t*\t0\tread2\t0\t6\t>s0>s2>s3\n";
+
+    #[test]
+    fn walks_of_poasta_are_read_whole() {
+        let graph = from_gfa(POASTA_GFA);
+        assert_eq!(sequences(&graph), ["ACGTACGT", "ACGTTCGT"]);
+    }
+
+    #[test]
+    fn exact_walks_are_read_as_given() {
+        // The first walk ends at the end of its last segment, so ranges are exact
+        let gfa = POASTA_GFA.replace("0\t6\t>s0>s1>s3", "0\t8\t>s0>s1>s3");
+        let graph = from_gfa(&gfa);
+        assert_eq!(sequences(&graph), ["ACGTACGT", "ACGTTC"]);
+    }
+
+    #[test]
+    fn sequences_round_trip_through_poasta_get_gfa() {
+        // Including sequences starting and ending inside segments
+        let reads = ["ACGTACGTACGTTGCA", "ACGTACCTACGTTGCA", "ACGTACGTACGTTGCAGG", "GTACGTACGTTGCA"];
+        let graph = graph_of(&reads);
+        let gfa = take(unsafe { poasta_get_gfa(&graph) });
+
+        assert_eq!(sequences(&from_gfa(&gfa)), reads, "{gfa}");
+    }
+
+    fn from_msa(rows: &[&CStr]) -> *mut PoastaGraph {
+        let width = rows.first().map_or(0, |row| row.count_bytes());
+        let rows: Vec<*const c_char> = rows.iter().map(|row| row.as_ptr()).collect();
+        unsafe { poasta_graph_from_msa(rows.as_ptr(), rows.len(), width) }
+    }
+
+    #[test]
+    fn msas_round_trip_through_poasta_get_msa_padded() {
+        let rows = [c"ACGT-ACGTA", c"ACGTTACGTA", c"ACCT-ACG--", c"--GT-A.GTA"];
+        let graph = from_msa(&rows);
+        assert!(!graph.is_null());
+        let graph = unsafe { Box::from_raw(graph) };
+        assert_eq!(sequences(&graph), ["ACGTACGTA", "ACGTTACGTA", "ACCTACG", "GTAGTA"]);
+        assert_eq!(consensus(&graph), "ACGTACGTA");
+
+        let msa = unsafe { poasta_get_msa_padded(&*graph, PoastaMsaOptions { pad: 0, line_width: 0 }) };
+        let padded: Vec<String> = unsafe { slice::from_raw_parts(msa.sequences, msa.num_sequences) }.iter()
+            .map(|row| unsafe { CStr::from_ptr(*row) }.to_str().unwrap().to_owned())
+            .collect();
+        unsafe { poasta_free_msa(msa) };
+        assert_eq!(padded, ["ACGT-ACGTA", "ACGTTACGTA", "ACCT-ACG--", "--GT-A-GTA"]);
+
+        // Adding to the graph works as for any other graph
+        let mut graph = graph;
+        add(&mut graph, "ACGTACGTA");
+        assert_eq!(graph.sequences.len(), 5);
+    }
+
+    #[test]
+    fn msas_need_bases_in_every_row() {
+        assert!(from_msa(&[c"ACGT", c"----"]).is_null());
+        assert!(from_msa(&[]).is_null());
+    }
+}
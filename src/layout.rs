@@ -0,0 +1,124 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fmt::Write;
+use std::os::raw::c_char;
+use std::ptr;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::consensus::{edge_weights, heaviest_path, topological_order};
+use crate::error;
+use crate::view::GraphView;
+use crate::PoastaGraph;
+
+/// Computes a layered drawing of the graph and returns it as JSON, so viewers can draw the graph
+/// without running their own DAG layout:
+/// `{"nodes": [{"id", "symbol", "x", "y"}, ...], "edges": [{"from", "to", "weight"}, ...]}`.
+/// `x` is the length of the longest path from the graph start to the node, `y` is 0 for
+/// the consensus path, and other nodes of the same layer are stacked below it.
+/// Node IDs are the internal node indices. Returns NULL if the graph is NULL.
+/// The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_layout(graph: *const PoastaGraph) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let graph = unsafe { &*graph };
+        let view = GraphView::new(graph);
+        let (start, end) = (view.start_node(), view.end_node());
+        let order = topological_order(&view);
+        let consensus: HashSet<_> = heaviest_path(graph).into_iter().collect();
+
+        // Longest path layering
+        let mut layers = vec![0usize; view.node_count_with_start_and_end()];
+        for node in &order {
+            layers[node.index()] = view.predecessors(*node)
+                .filter(|pred| *pred != start)
+                .map(|pred| layers[pred.index()] + 1)
+                .max()
+                .unwrap_or(0);
+        }
+
+        // Rows within each layer, consensus nodes first
+        let mut rows = vec![0usize; layers.len()];
+        let mut layer_sizes: HashMap<usize, usize> = HashMap::new();
+        for node in order.iter().filter(|n| consensus.contains(n)).chain(order.iter().filter(|n| !consensus.contains(n))) {
+            let size = layer_sizes.entry(layers[node.index()]).or_default();
+            rows[node.index()] = *size;
+            *size += 1;
+        }
+
+        let weights = edge_weights(graph);
+        let mut json = String::from("{\"nodes\":[");
+        for (i, node) in order.iter().enumerate() {
+            let sep = if i > 0 { "," } else { "" };
+            write!(
+                json,
+                "{sep}{{\"id\":{},\"symbol\":\"{}\",\"x\":{},\"y\":{}}}",
+                node.index(),
+                json_char(graph.graph.get_symbol(*node)),
+                layers[node.index()],
+                rows[node.index()],
+            ).unwrap();
+        }
+
+        json.push_str("],\"edges\":[");
+        let mut first = true;
+        for node in &order {
+            for succ in view.successors(*node).filter(|succ| *succ != end) {
+                let sep = if first { "" } else { "," };
+                first = false;
+                write!(
+                    json,
+                    "{sep}{{\"from\":{},\"to\":{},\"weight\":{}}}",
+                    node.index(),
+                    succ.index(),
+                    weights.get(&(*node, succ)).copied().unwrap_or(0.0),
+                ).unwrap();
+            }
+        }
+        json.push_str("]}");
+
+        CString::new(json).unwrap().into_raw()
+    })
+}
+
+/// A byte as the contents of a JSON string.
+fn json_char(symbol: u8) -> String {
+    match symbol {
+        b'"' => "\\\"".to_string(),
+        b'\\' => "\\\\".to_string(),
+        0x20..=0x7e => (symbol as char).to_string(),
+        _ => format!("\\u{symbol:04x}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{graph_of, take};
+
+    #[test]
+    fn bubbles_are_stacked_below_the_consensus() {
+        let graph = graph_of(&["ACGT", "ACGT", "AGGT"]);
+        let layout = take(unsafe { poasta_graph_layout(&graph) });
+        let nodes = concat!(
+            r#"{"id":2,"symbol":"A","x":0,"y":0},{"id":3,"symbol":"C","x":1,"y":0},{"id":6,"symbol":"G","x":1,"y":1},"#,
+            r#"{"id":4,"symbol":"G","x":2,"y":0},{"id":5,"symbol":"T","x":3,"y":0}"#,
+        );
+        let edges = concat!(
+            r#"{"from":2,"to":6,"weight":2},{"from":2,"to":3,"weight":4},{"from":3,"to":4,"weight":4},"#,
+            r#"{"from":6,"to":4,"weight":2},{"from":4,"to":5,"weight":6}"#,
+        );
+        assert_eq!(layout, format!(r#"{{"nodes":[{nodes}],"edges":[{edges}]}}"#));
+    }
+
+    #[test]
+    fn symbols_are_escaped() {
+        assert_eq!(json_char(b'"'), "\\\"");
+        assert_eq!(json_char(b'\\'), "\\\\");
+        assert_eq!(json_char(b'\n'), "\\u000a");
+    }
+}
@@ -1,42 +1,160 @@
-use std::ffi::CString;
-use std::io::{Cursor, BufRead};
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int};
 use std::slice;
 use std::ptr;
 
-use poasta::graphs::poa::POAGraph;
-use poasta::aligner::scoring::{GapAffine, GapAffine2Piece, AlignmentType};
-use poasta::aligner::config::{AffineMinGapCost, Affine2PieceMinGapCost};
-use poasta::aligner::PoastaAligner;
-use poasta::io::fasta::poa_graph_to_fasta;
 use poasta::io::graph::graph_to_gfa;
 
-/// Opaque pointer to the POAGraph<u32>.
-pub struct PoastaGraph(POAGraph<u32>);
+mod aligner;
+mod alphabet;
+mod bench;
+mod bisulfite;
+mod bloom;
+mod cache;
+mod chimera;
+mod circular;
+mod compare;
+mod coding;
+mod concat;
+mod concatemer;
+mod consensus;
+mod coverage;
+mod dot;
+mod delta;
+mod dp;
+mod error;
+mod eval;
+mod export;
+mod fast;
+mod features;
+mod filter;
+#[cfg(feature = "test-support")]
+mod fixtures;
+mod gfa;
+mod graph;
+mod homopolymer;
+mod import;
+mod layout;
+mod limit;
+mod linkage;
+mod mask;
+mod matrices;
+mod metadata;
+mod metrics;
+mod mode;
+mod msa;
+mod multi;
+mod pair;
+mod pairwise;
+mod panel;
+mod phase;
+mod preview;
+mod primers;
+mod profile;
+mod provenance;
+mod provisional;
+mod quality;
+mod query;
+mod rca;
+mod realign;
+mod refine;
+mod reference;
+mod report;
+mod resource;
+mod sam;
+mod scoring;
+mod sequence;
+mod serialize;
+mod split;
+mod stats;
+mod store;
+mod strand;
+#[cfg(test)]
+mod testing;
+mod timeout;
+mod trace;
+mod umi;
+mod validate;
+mod vg;
+mod view;
+mod weights;
+
+pub use aligner::PoastaAligner;
+pub use coding::PoastaAmbiguity;
+pub use consensus::PoastaTieBreak;
+pub use dot::PoastaRankDir;
+pub use error::PoastaError;
+pub use fast::{PoastaFrozenGraph, PoastaScratch};
+pub use features::PoastaFeature;
+#[cfg(feature = "test-support")]
+pub use fixtures::{poasta_fixture_expected, poasta_fixture_graph, poasta_fixture_reads, PoastaFixture, PoastaFixtureOutput};
+pub use gfa::PoastaGfaVersion;
+pub use mask::{PoastaCoordinates, PoastaInterval};
+pub use mode::{PoastaAlignmentMode, PoastaAlignmentType};
+pub use panel::PoastaPanel;
+pub use graph::PoastaGraph;
+pub use provisional::PoastaProvisional;
+pub use query::PoastaEditOp;
+pub use scoring::PoastaScoring;
+pub use store::PoastaStore;
+pub use strand::PoastaStrand;
+pub use trace::PoastaTraceLevel;
+pub(crate) use graph::{Scoring, SequenceInfo};
+
+/// Status returned when reading or writing a file failed.
+pub(crate) const IO_ERROR: c_int = PoastaError::IoError as c_int;
 
 /// Struct to hold the MSA result.
 #[repr(C)]
 pub struct PoastaMsa {
     pub sequences: *mut *mut c_char,
     pub num_sequences: usize,
+
+    /// Name of the sequence of each row, as given to poasta_add_sequence_named or `seq_N`.
+    pub names: *mut *mut c_char,
+}
+
+impl PoastaMsa {
+    pub(crate) fn empty() -> Self {
+        PoastaMsa { sequences: ptr::null_mut(), num_sequences: 0, names: ptr::null_mut() }
+    }
 }
 
 
 /// Creates a new empty POAGraph.
 #[unsafe(no_mangle)]
 pub extern "C" fn poasta_create_graph() -> *mut PoastaGraph {
-    let graph = POAGraph::<u32>::new();
-    Box::into_raw(Box::new(PoastaGraph(graph)))
+    error::catch(|| {
+        Box::into_raw(Box::new(PoastaGraph::new()))
+    })
 }
 
 /// Frees the POAGraph.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn poasta_free_graph(graph: *mut PoastaGraph) {
-    if !graph.is_null() {
-        unsafe {
-            drop(Box::from_raw(graph));
+    error::catch(|| {
+        if !graph.is_null() {
+            unsafe {
+                drop(Box::from_raw(graph));
+            }
         }
-    }
+    })
+}
+
+/// Returns an independent copy of the graph, with the same configuration. The copy shares the
+/// graph storage until either graph is changed, so cloning is cheap even for large graphs, and
+/// copies can be given to other threads. Must be freed with poasta_free_graph.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_clone(graph: *const PoastaGraph) -> *mut PoastaGraph {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        Box::into_raw(Box::new(unsafe { &*graph }.shallow_clone()))
+    })
 }
 
 /// Adds a sequence to the graph (Global alignment).
@@ -45,43 +163,92 @@ pub unsafe extern "C" fn poasta_add_sequence(
     graph: *mut PoastaGraph,
     seq: *const c_char,
     len: usize,
-    mismatch_score: u8,
-    gap_extend: u8,
-    gap_open: u8,
+    mismatch_score: i32,
+    gap_extend: i32,
+    gap_open: i32,
 ) -> c_int {
-    if graph.is_null() || seq.is_null() {
-        return -1;
-    }
+    error::catch(|| {
+        let scoring = match Scoring::affine(mismatch_score, gap_extend, gap_open) {
+            Ok(scoring) => scoring,
+            Err(status) => return metrics::record_status(status),
+        };
+
+        unsafe { add_sequence_uniform(graph, seq, len, 1, scoring, SequenceInfo::default()) }
+    })
+}
 
-    let graph_inner = unsafe { &mut (*graph).0 };
-    let seq_slice = unsafe { slice::from_raw_parts(seq as *const u8, len) };
-    
-    // Create a dummy name for the sequence (e.g. "seq_N")
-    let seq_name = format!("seq_{}", graph_inner.sequences.len());
-    let weights = vec![1; len];
-
-    if graph_inner.is_empty() {
-        // First sequence, just add it
-        match graph_inner.add_alignment_with_weights(&seq_name, seq_slice, None, &weights) {
-            Ok(_) => 0,
-            Err(_) => -2,
+/// Like poasta_add_sequence, but also writes the cost of the alignment the sequence was added
+/// along into `score` (if not NULL), e.g., to flag reads that fit the graph poorly. The score is
+/// 0 for the first sequence of the graph and is only written if the status is 0, so not for
+/// approximate alignments after a timeout.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_add_sequence_scored(
+    graph: *mut PoastaGraph,
+    seq: *const c_char,
+    len: usize,
+    mismatch_score: i32,
+    gap_extend: i32,
+    gap_open: i32,
+    score: *mut i64,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || seq.is_null() {
+            metrics::record_status(-1);
+            return error::fail(-1, "graph or sequence is NULL");
         }
-    } else {
-        // Align and then add
-        let scoring = GapAffine::new(mismatch_score, gap_extend, gap_open);
-        
-        // Always use Global alignment
-        let aln_type = AlignmentType::Global;
-
-        let aligner = PoastaAligner::new(AffineMinGapCost(scoring), aln_type);
-        
-        let result = aligner.align::<u32, _>(graph_inner, seq_slice);
-        
-        match graph_inner.add_alignment_with_weights(&seq_name, seq_slice, Some(&result.alignment), &weights) {
-            Ok(_) => 0,
-            Err(_) => -3,
+
+        let graph = unsafe { &mut *graph };
+        let seq = unsafe { slice::from_raw_parts(seq as *const u8, len) };
+        let scoring = match Scoring::affine(mismatch_score, gap_extend, gap_open) {
+            Ok(scoring) => scoring,
+            Err(status) => return metrics::record_status(status),
+        };
+
+        let _span = trace::span(PoastaTraceLevel::Info, c"add_sequence");
+        let (status, cost) = graph.add_sequence_scored(seq, &vec![1; len], scoring, SequenceInfo::default(), &mut dp::DpBuffers::default());
+        metrics::record_status(status);
+
+        if let (0, Some(cost), false) = (status, cost, score.is_null()) {
+            unsafe { *score = cost };
         }
-    }
+
+        status
+    })
+}
+
+/// Adds a sequence called `name` (NUL-terminated) to the graph (Global alignment), e.g., its read
+/// ID. The name is used in the MSA, GFA and other output instead of a made-up `seq_N`.
+/// Returns -1 if the name is empty or contains whitespace, and the status codes of
+/// poasta_add_sequence otherwise.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_add_sequence_named(
+    graph: *mut PoastaGraph,
+    name: *const c_char,
+    seq: *const c_char,
+    len: usize,
+    mismatch_score: i32,
+    gap_extend: i32,
+    gap_open: i32,
+) -> c_int {
+    error::catch(|| {
+        let name = if name.is_null() { None } else { unsafe { CStr::from_ptr(name) }.to_str().ok() };
+        let Some(name) = name.filter(|name| is_valid_name(name)) else {
+            metrics::record_status(-1);
+            return error::fail(-1, "sequence name is NULL, empty or contains whitespace");
+        };
+
+        let scoring = match Scoring::affine(mismatch_score, gap_extend, gap_open) {
+            Ok(scoring) => scoring,
+            Err(status) => return metrics::record_status(status),
+        };
+        let info = SequenceInfo { name: Some(name.to_string()), ..SequenceInfo::default() };
+        unsafe { add_sequence_uniform(graph, seq, len, 1, scoring, info) }
+    })
+}
+
+/// Whether `name` can be used as a sequence name in all output formats.
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(|c| c.is_whitespace() || c.is_control())
 }
 
 /// Adds a sequence to the graph with a specified weight (Global alignment).
@@ -94,44 +261,46 @@ pub unsafe extern "C" fn poasta_add_sequence_with_weight(
     seq: *const c_char,
     len: usize,
     weight: u32,
-    mismatch_score: u8,
-    gap_extend: u8,
-    gap_open: u8,
+    mismatch_score: i32,
+    gap_extend: i32,
+    gap_open: i32,
 ) -> c_int {
-    if graph.is_null() || seq.is_null() {
-        return -1;
-    }
+    error::catch(|| {
+        let scoring = match Scoring::affine(mismatch_score, gap_extend, gap_open) {
+            Ok(scoring) => scoring,
+            Err(status) => return metrics::record_status(status),
+        };
+
+        unsafe { add_sequence_uniform(graph, seq, len, weight as usize, scoring, SequenceInfo::default()) }
+    })
+}
 
-    let graph_inner = unsafe { &mut (*graph).0 };
-    let seq_slice = unsafe { slice::from_raw_parts(seq as *const u8, len) };
-    
-    // Create a dummy name for the sequence (e.g. "seq_N")
-    let seq_name = format!("seq_{}", graph_inner.sequences.len());
-    // Use the provided weight for all bases in the sequence
-    let weights = vec![weight as usize; len];
-
-    if graph_inner.is_empty() {
-        // First sequence, just add it
-        match graph_inner.add_alignment_with_weights(&seq_name, seq_slice, None, &weights) {
-            Ok(_) => 0,
-            Err(_) => -2,
-        }
-    } else {
-        // Align and then add
-        let scoring = GapAffine::new(mismatch_score, gap_extend, gap_open);
-        
-        // Always use Global alignment
-        let aln_type = AlignmentType::Global;
-
-        let aligner = PoastaAligner::new(AffineMinGapCost(scoring), aln_type);
-        
-        let result = aligner.align::<u32, _>(graph_inner, seq_slice);
-        
-        match graph_inner.add_alignment_with_weights(&seq_name, seq_slice, Some(&result.alignment), &weights) {
-            Ok(_) => 0,
-            Err(_) => -3,
+/// Adds a sequence to the graph with a weight per base (Global alignment), e.g., to down-weight
+/// low quality bases individually. `weights` must hold `len` weights.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_add_sequence_with_weights(
+    graph: *mut PoastaGraph,
+    seq: *const c_char,
+    len: usize,
+    weights: *const u32,
+    mismatch_score: i32,
+    gap_extend: i32,
+    gap_open: i32,
+) -> c_int {
+    error::catch(|| {
+        if weights.is_null() {
+            metrics::record_status(-1);
+            return error::fail(-1, "weights are NULL");
         }
-    }
+
+        let weights: Vec<usize> = unsafe { slice::from_raw_parts(weights, len) }.iter().map(|w| *w as usize).collect();
+        let scoring = match Scoring::affine(mismatch_score, gap_extend, gap_open) {
+            Ok(scoring) => scoring,
+            Err(status) => return metrics::record_status(status),
+        };
+
+        unsafe { add_sequence_weighted(graph, seq, &weights, scoring, SequenceInfo::default()) }
+    })
 }
 
 /// Adds a sequence to the graph using two-piece affine gap model (Global alignment).
@@ -142,45 +311,20 @@ pub unsafe extern "C" fn poasta_add_sequence_2piece(
     graph: *mut PoastaGraph,
     seq: *const c_char,
     len: usize,
-    mismatch_score: u8,
-    gap_extend1: u8,
-    gap_open1: u8,
-    gap_extend2: u8,
-    gap_open2: u8,
+    mismatch_score: i32,
+    gap_extend1: i32,
+    gap_open1: i32,
+    gap_extend2: i32,
+    gap_open2: i32,
 ) -> c_int {
-    if graph.is_null() || seq.is_null() {
-        return -1;
-    }
-
-    let graph_inner = unsafe { &mut (*graph).0 };
-    let seq_slice = unsafe { slice::from_raw_parts(seq as *const u8, len) };
-    
-    // Create a dummy name for the sequence (e.g. "seq_N")
-    let seq_name = format!("seq_{}", graph_inner.sequences.len());
-    let weights = vec![1; len];
-
-    if graph_inner.is_empty() {
-        // First sequence, just add it
-        match graph_inner.add_alignment_with_weights(&seq_name, seq_slice, None, &weights) {
-            Ok(_) => 0,
-            Err(_) => -2,
-        }
-    } else {
-        // Align and then add
-        let scoring = GapAffine2Piece::new(mismatch_score, gap_extend1, gap_open1, gap_extend2, gap_open2);
-        
-        // Always use Global alignment
-        let aln_type = AlignmentType::Global;
-
-        let aligner = PoastaAligner::new(Affine2PieceMinGapCost(scoring), aln_type);
-        
-        let result = aligner.align::<u32, _>(graph_inner, seq_slice);
-        
-        match graph_inner.add_alignment_with_weights(&seq_name, seq_slice, Some(&result.alignment), &weights) {
-            Ok(_) => 0,
-            Err(_) => -3,
-        }
-    }
+    error::catch(|| {
+        let scoring = match Scoring::two_piece(mismatch_score, gap_extend1, gap_open1, gap_extend2, gap_open2) {
+            Ok(scoring) => scoring,
+            Err(status) => return metrics::record_status(status),
+        };
+
+        unsafe { add_sequence_uniform(graph, seq, len, 1, scoring, SequenceInfo::default()) }
+    })
 }
 
 /// Adds a sequence to the graph with a specified weight using two-piece affine gap model (Global alignment).
@@ -194,143 +338,296 @@ pub unsafe extern "C" fn poasta_add_sequence_2piece_with_weight(
     seq: *const c_char,
     len: usize,
     weight: u32,
-    mismatch_score: u8,
-    gap_extend1: u8,
-    gap_open1: u8,
-    gap_extend2: u8,
-    gap_open2: u8,
+    mismatch_score: i32,
+    gap_extend1: i32,
+    gap_open1: i32,
+    gap_extend2: i32,
+    gap_open2: i32,
+) -> c_int {
+    error::catch(|| {
+        let scoring = match Scoring::two_piece(mismatch_score, gap_extend1, gap_open1, gap_extend2, gap_open2) {
+            Ok(scoring) => scoring,
+            Err(status) => return metrics::record_status(status),
+        };
+
+        unsafe { add_sequence_uniform(graph, seq, len, weight as usize, scoring, SequenceInfo::default()) }
+    })
+}
+
+/// Shared implementation of the `poasta_add_sequence*` functions: every base gets `weight`.
+pub(crate) unsafe fn add_sequence_uniform(
+    graph: *mut PoastaGraph,
+    seq: *const c_char,
+    len: usize,
+    weight: usize,
+    scoring: Scoring,
+    info: SequenceInfo,
+) -> c_int {
+    unsafe { add_sequence_weighted(graph, seq, &vec![weight; len], scoring, info) }
+}
+
+/// Like `add_sequence_uniform`, with a weight per base; the sequence is as long as `weights`.
+pub(crate) unsafe fn add_sequence_weighted(
+    graph: *mut PoastaGraph,
+    seq: *const c_char,
+    weights: &[usize],
+    scoring: Scoring,
+    info: SequenceInfo,
 ) -> c_int {
     if graph.is_null() || seq.is_null() {
-        return -1;
+        metrics::record_status(-1);
+        return error::fail(-1, "graph or sequence is NULL");
     }
 
-    let graph_inner = unsafe { &mut (*graph).0 };
-    let seq_slice = unsafe { slice::from_raw_parts(seq as *const u8, len) };
-    
-    // Create a dummy name for the sequence (e.g. "seq_N")
-    let seq_name = format!("seq_{}", graph_inner.sequences.len());
-    // Use the provided weight for all bases in the sequence
-    let weights = vec![weight as usize; len];
-
-    if graph_inner.is_empty() {
-        // First sequence, just add it
-        match graph_inner.add_alignment_with_weights(&seq_name, seq_slice, None, &weights) {
-            Ok(_) => 0,
-            Err(_) => -2,
-        }
-    } else {
-        // Align and then add
-        let scoring = GapAffine2Piece::new(mismatch_score, gap_extend1, gap_open1, gap_extend2, gap_open2);
-        
-        // Always use Global alignment
-        let aln_type = AlignmentType::Global;
-
-        let aligner = PoastaAligner::new(Affine2PieceMinGapCost(scoring), aln_type);
-        
-        let result = aligner.align::<u32, _>(graph_inner, seq_slice);
-        
-        match graph_inner.add_alignment_with_weights(&seq_name, seq_slice, Some(&result.alignment), &weights) {
-            Ok(_) => 0,
-            Err(_) => -3,
-        }
+    let graph = unsafe { &mut *graph };
+    let seq_slice = unsafe { slice::from_raw_parts(seq as *const u8, weights.len()) };
+
+    let _span = trace::span(PoastaTraceLevel::Info, c"add_sequence");
+    let status = graph.add_sequence(seq_slice, weights, scoring, info);
+    metrics::record_status(status);
+
+    status
+}
+
+/// Hands a vector over to C as a pointer to its first element (NULL if empty).
+/// Reclaim it with `free_c_array` using the same length.
+pub(crate) fn into_c_array<T>(values: Vec<T>) -> *mut T {
+    if values.is_empty() {
+        return ptr::null_mut();
+    }
+
+    Box::into_raw(values.into_boxed_slice()) as *mut T
+}
+
+/// Takes back ownership of an array created with `into_c_array`.
+pub(crate) unsafe fn free_c_array<T>(values: *mut T, len: usize) -> Vec<T> {
+    if values.is_null() {
+        return Vec::new();
     }
+
+    unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(values, len)) }.into_vec()
 }
 
-/// Generates the MSA from the graph.
+/// Generates the MSA from the graph, in the columns of poasta's FASTA writer. Like that writer,
+/// it leaves out one of the leading gaps of rows that don't start in the first column, so those
+/// rows are one column short; poasta_get_msa_padded gives rows of the same width.
 /// Returns a PoastaMsa struct. Caller must free it with poasta_free_msa.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn poasta_get_msa(graph: *mut PoastaGraph) -> PoastaMsa {
-    if graph.is_null() {
-        return PoastaMsa { sequences: ptr::null_mut(), num_sequences: 0 };
-    }
+pub unsafe extern "C" fn poasta_get_msa(graph: *const PoastaGraph) -> PoastaMsa {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaMsa::empty();
+        }
 
-    let graph_inner = unsafe { &(*graph).0 };
-    let mut buffer = Vec::new();
-    
-    // Write MSA to buffer in FASTA format
-    if let Err(_) = poa_graph_to_fasta(graph_inner, &mut buffer) {
-        return PoastaMsa { sequences: ptr::null_mut(), num_sequences: 0 };
-    }
+        let _span = trace::span(PoastaTraceLevel::Info, c"msa");
+        let _usage = resource::track(resource::PoastaOperation::Msa);
+        let graph = unsafe { &*graph };
 
-    // Parse FASTA buffer to extract sequences
-    let cursor = Cursor::new(buffer);
-    let mut reader = std::io::BufReader::new(cursor);
-    let mut line = String::new();
-    let mut sequences = Vec::new();
-    let mut current_seq = String::new();
-    let mut in_seq = false;
-
-    // Simple FASTA parser
-    while reader.read_line(&mut line).unwrap() > 0 {
-        let trimmed = line.trim();
-        if trimmed.starts_with('>') {
-            if in_seq {
-                sequences.push(current_seq.clone());
-                current_seq.clear();
-            }
-            in_seq = true;
-        } else {
-            if in_seq {
-                current_seq.push_str(trimmed);
-            }
+        msa::poasta_msa(graph, &msa::Columns::like_poasta(graph))
+    })
+}
+
+/// Like poasta_get_msa, but computes the MSA only if its rows and names, including their NUL
+/// terminators, take at most `max_bytes` (0 means no cap), so a pathological graph can't make the
+/// library allocate gigabytes. The MSA is stored in `*msa_out`, which is empty on failure; the
+/// caller must free it with poasta_free_msa. Returns 0, -1 if the graph or `msa_out` is NULL, and
+/// -11 if the MSA would be too large.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_msa_capped(graph: *const PoastaGraph, max_bytes: usize, msa_out: *mut PoastaMsa) -> c_int {
+    error::catch(|| {
+        if msa_out.is_null() {
+            return error::fail(-1, "output is NULL");
+        }
+
+        unsafe { msa_out.write(PoastaMsa::empty()) };
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
         }
-        line.clear();
-    }
-    if in_seq && !current_seq.is_empty() {
-        sequences.push(current_seq);
-    }
 
+        let _usage = resource::track(resource::PoastaOperation::Msa);
+        let graph = unsafe { &*graph };
+        let columns = msa::Columns::like_poasta(graph);
+        let size: usize = msa::msa_names(graph).iter().map(|name| name.len() + columns.count + 2).sum();
+        if max_bytes > 0 && size > max_bytes {
+            return error::fail(limit::TRUNCATED, &format!("MSA takes {size} bytes, more than {max_bytes}"));
+        }
+
+        unsafe { msa_out.write(msa::poasta_msa(graph, &columns)) };
+
+        0
+    })
+}
+
+/// Hands MSA rows and the names of their sequences over to C. Free the result with
+/// poasta_free_msa.
+pub(crate) fn into_c_msa(names: Vec<String>, rows: Vec<Vec<u8>>) -> PoastaMsa {
     // Convert to C strings
-    let mut c_seqs = Vec::with_capacity(sequences.len());
-    for s in sequences {
-        let c_str = CString::new(s).unwrap();
-        c_seqs.push(c_str.into_raw());
-    }
+    let c_seqs: Vec<*mut c_char> = rows.into_iter()
+        .map(|row| CString::new(row).unwrap().into_raw())
+        .collect();
+    let c_names: Vec<*mut c_char> = names.into_iter()
+        .map(|name| CString::new(name).unwrap().into_raw())
+        .collect();
 
-    let ptr = c_seqs.as_mut_ptr();
     let len = c_seqs.len();
-    std::mem::forget(c_seqs);
+    let ptr = Box::into_raw(c_seqs.into_boxed_slice()) as *mut *mut c_char;
+    let names = Box::into_raw(c_names.into_boxed_slice()) as *mut *mut c_char;
 
     PoastaMsa {
         sequences: ptr,
         num_sequences: len,
+        names,
     }
 }
 
-/// Returns the graph in GFA format as a C string.
+/// Returns the graph in GFA format as a C string, without provisional nodes and edges (see
+/// poasta_set_min_support). Like poasta's command line tool, walks end up to two bases early;
+/// poasta_graph_from_gfa corrects that when reading them back.
 /// The caller must free the string using free().
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn poasta_get_gfa(graph: *mut PoastaGraph) -> *mut c_char {
-    if graph.is_null() {
-        return ptr::null_mut();
-    }
+pub unsafe extern "C" fn poasta_get_gfa(graph: *const PoastaGraph) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
 
-    let graph_inner = unsafe { &(*graph).0 };
-    let mut buffer = Vec::new();
+        let _span = trace::span(PoastaTraceLevel::Info, c"gfa");
+        if unsafe { (*graph).min_support } > 1 {
+            let gfa = gfa::layered_gfa(unsafe { &*graph }, PoastaProvisional::Exclude);
+            return CString::new(gfa).unwrap_or_default().into_raw();
+        }
 
-    if let Err(_) = graph_to_gfa(&mut buffer, graph_inner) {
-        return ptr::null_mut();
-    }
+        let graph_inner = unsafe { &(*graph).graph };
+        let mut buffer = Vec::new();
+
+        if graph_to_gfa(&mut buffer, graph_inner).is_err() {
+            return ptr::null_mut();
+        }
 
-    let s = String::from_utf8(buffer).unwrap_or_default();
-    let c_str = CString::new(s).unwrap();
-    c_str.into_raw()
+        let s = String::from_utf8(buffer).unwrap_or_default();
+        let c_str = CString::new(s).unwrap();
+        c_str.into_raw()
+    })
+}
+
+/// Like poasta_get_gfa, but stops writing the GFA once it exceeds `max_bytes` (0 means no cap),
+/// so a pathological graph can't make the library allocate gigabytes. The GFA, or as many
+/// complete lines of it as fit in `max_bytes`, is stored in `*out` (NULL on other failures), which
+/// the caller must free using free(). Returns 0, -1 if the graph or `out` is NULL, and -11 if the
+/// GFA was truncated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_gfa_capped(graph: *const PoastaGraph, max_bytes: usize, out: *mut *mut c_char) -> c_int {
+    error::catch(|| {
+        if out.is_null() {
+            return error::fail(-1, "output is NULL");
+        }
+
+        unsafe { *out = ptr::null_mut() };
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let _span = trace::span(PoastaTraceLevel::Info, c"gfa");
+        let graph = unsafe { &*graph };
+        let mut gfa = limit::CappedOutput::new(max_bytes);
+        let written = if graph.min_support > 1 {
+            gfa::write_layered_gfa(&mut gfa, graph, PoastaProvisional::Exclude).is_ok()
+        } else {
+            graph_to_gfa(&mut gfa, &graph.graph).is_ok()
+        };
+
+        if !written && !gfa.is_truncated() {
+            return error::fail(IO_ERROR, "writing the GFA failed");
+        }
+
+        gfa.finish(out)
+    })
 }
 
 /// Frees the PoastaMsa.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn poasta_free_msa(msa: PoastaMsa) {
-    if !msa.sequences.is_null() {
-        let slice = unsafe { slice::from_raw_parts_mut(msa.sequences, msa.num_sequences) };
-        for &mut ptr in slice {
-            if !ptr.is_null() {
+    error::catch(|| {
+        for strings in [msa.sequences, msa.names] {
+            if !strings.is_null() {
+                let slice = unsafe { slice::from_raw_parts_mut(strings, msa.num_sequences) };
+                for &mut ptr in slice {
+                    if !ptr.is_null() {
+                        unsafe {
+                            drop(CString::from_raw(ptr));
+                        }
+                    }
+                }
                 unsafe {
-                    drop(CString::from_raw(ptr));
+                    drop(Vec::from_raw_parts(strings, msa.num_sequences, msa.num_sequences));
                 }
             }
         }
-        unsafe {
-            drop(Vec::from_raw_parts(msa.sequences, msa.num_sequences, msa.num_sequences));
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add, consensus, graph_of, outputs};
+
+    fn add_named(graph: &mut PoastaGraph, name: &CStr, seq: &str) -> c_int {
+        unsafe { poasta_add_sequence_named(graph, name.as_ptr(), seq.as_ptr().cast(), seq.len(), 4, 2, 6) }
+    }
+
+    #[test]
+    fn names_replace_made_up_ones_in_the_output() {
+        let mut graph = PoastaGraph::new();
+        assert_eq!(add_named(&mut graph, c"read/1", "ACGT"), 0);
+        add(&mut graph, "AGGT");
+
+        let (_, msa, gfa) = outputs(&graph);
+        assert_eq!(msa, ">read/1\nACGT\n>seq_1\nAGGT\n");
+        assert!(gfa.contains("\tread/1\t") && gfa.contains("\tseq_1\t"));
+
+        for name in [c"", c"read 1", c"read\t1"] {
+            assert_eq!(add_named(&mut graph, name, "ACGT"), -1, "{name:?}");
         }
+        assert_eq!(graph.sequences.len(), 2);
+    }
+
+    #[test]
+    fn bases_are_weighted_individually() {
+        // The heavier G outweighs the Cs of the other two reads
+        let read = "ACGTA";
+        let mut graph = graph_of(&["ACCTA", "ACCTA"]);
+        let status = unsafe { poasta_add_sequence_with_weights(&mut graph, read.as_ptr().cast(), read.len(), [1, 1, 5, 1, 1].as_ptr(), 4, 2, 6) };
+        assert_eq!(status, 0);
+        assert_eq!(consensus(&graph), "ACGTA");
+
+        // The same weight for every base is poasta_add_sequence_with_weight
+        let mut uniform = graph_of(&["ACCTA", "ACCTA"]);
+        let mut weighted = uniform.shallow_clone();
+        assert_eq!(unsafe { poasta_add_sequence_with_weight(&mut uniform, read.as_ptr().cast(), read.len(), 3, 4, 2, 6) }, 0);
+        assert_eq!(unsafe { poasta_add_sequence_with_weights(&mut weighted, read.as_ptr().cast(), read.len(), [3; 5].as_ptr(), 4, 2, 6) }, 0);
+        assert_eq!(outputs(&uniform), outputs(&weighted));
+        assert_eq!(consensus(&uniform), "ACGTA");
+
+        assert_eq!(unsafe { poasta_add_sequence_with_weights(&mut graph, read.as_ptr().cast(), read.len(), ptr::null(), 4, 2, 6) }, -1);
+    }
+
+    #[test]
+    fn scores_are_the_alignment_costs() {
+        let add_scored = |graph: &mut PoastaGraph, read: &str| {
+            let mut score = -1;
+            let status = unsafe { poasta_add_sequence_scored(graph, read.as_ptr().cast(), read.len(), 4, 2, 6, &mut score) };
+            (status, score)
+        };
+
+        // A mismatch, then a two base deletion
+        let mut graph = PoastaGraph::new();
+        assert_eq!(add_scored(&mut graph, "ACGTACGTAA"), (0, 0));
+        assert_eq!(add_scored(&mut graph, "ACCTACGTAA"), (0, 4));
+        assert_eq!(add_scored(&mut graph, "ACGTGTAA"), (0, 10));
+        assert_eq!(add_scored(&mut graph, "ACGTACGTAA"), (0, 0));
+
+        let read = "ACGT";
+        assert_eq!(unsafe { poasta_add_sequence_scored(&mut graph, read.as_ptr().cast(), read.len(), 4, 2, 6, ptr::null_mut()) }, 0);
+        assert_eq!(graph.sequences.len(), 5);
     }
 }
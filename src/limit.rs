@@ -0,0 +1,88 @@
+//! Size caps on text output, so a pathological graph can't make the library allocate gigabytes on
+//! behalf of an embedder.
+
+use std::ffi::CString;
+use std::fmt;
+use std::io;
+use std::os::raw::{c_char, c_int};
+
+use crate::error::{self, PoastaError};
+
+/// Status returned when output was cut off at its maximum size.
+pub(crate) const TRUNCATED: c_int = PoastaError::Truncated as c_int;
+
+/// Output buffer that stops accepting text once it would exceed `max_bytes` (0 means no cap), so
+/// writers producing the output fail early instead of building all of it.
+pub(crate) struct CappedOutput {
+    buf: Vec<u8>,
+    max_bytes: usize,
+    truncated: bool,
+}
+
+impl CappedOutput {
+    pub(crate) fn new(max_bytes: usize) -> Self {
+        CappedOutput { buf: Vec::new(), max_bytes, truncated: false }
+    }
+
+    pub(crate) fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Appends `bytes` if they fit, otherwise fills the buffer up to the cap and marks it as
+    /// truncated. Returns whether all of them fit.
+    fn push(&mut self, bytes: &[u8]) -> bool {
+        if self.truncated {
+            return false;
+        }
+
+        if self.max_bytes > 0 && self.buf.len() + bytes.len() > self.max_bytes {
+            let room = self.max_bytes - self.buf.len();
+            self.buf.extend_from_slice(&bytes[..room]);
+            self.truncated = true;
+
+            return false;
+        }
+
+        self.buf.extend_from_slice(bytes);
+
+        true
+    }
+
+    /// Hands the output over to C in `*out`, cut back to its last complete line if it was
+    /// truncated. Returns 0, or -11 if the output was truncated.
+    pub(crate) fn finish(mut self, out: *mut *mut c_char) -> c_int {
+        if self.truncated {
+            let complete = self.buf.iter().rposition(|byte| *byte == b'\n').map_or(0, |newline| newline + 1);
+            self.buf.truncate(complete);
+        }
+
+        unsafe { *out = CString::new(self.buf).unwrap_or_default().into_raw() };
+
+        match self.truncated {
+            true => error::fail(TRUNCATED, &format!("the limit is {} bytes", self.max_bytes)),
+            false => 0,
+        }
+    }
+}
+
+impl io::Write for CappedOutput {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        match self.push(bytes) {
+            true => Ok(bytes.len()),
+            false => Err(io::Error::other("output exceeds its maximum size")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Write for CappedOutput {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.push(s.as_bytes()) {
+            true => Ok(()),
+            false => Err(fmt::Error),
+        }
+    }
+}
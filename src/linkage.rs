@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::ptr;
+use std::slice;
+
+use crate::msa::{Columns, GAP};
+use crate::{error, free_c_array, into_c_array, PoastaGraph};
+
+/// Struct to hold the joint allele counts of two MSA columns.
+#[repr(C)]
+pub struct PoastaLinkage {
+    pub col_a: usize,
+    pub col_b: usize,
+
+    /// Alleles seen in each column (bases, or `-` for deletions), most frequent first.
+    pub alleles_a: *mut u8,
+    pub num_alleles_a: usize,
+    pub alleles_b: *mut u8,
+    pub num_alleles_b: usize,
+
+    /// `counts[i * num_alleles_b + j]` is the number of sequences with allele `alleles_a[i]` in
+    /// column `col_a` and allele `alleles_b[j]` in column `col_b`.
+    pub counts: *mut usize,
+
+    /// Number of sequences spanning both columns, i.e., the sum of all counts.
+    pub num_sequences: usize,
+}
+
+/// Struct to hold the linkage of each pair of a list of columns.
+#[repr(C)]
+pub struct PoastaLinkageList {
+    pub pairs: *mut PoastaLinkage,
+    pub num_pairs: usize,
+}
+
+/// Returns the joint allele counts of MSA columns `col_a` and `col_b` over the active sequences,
+/// from the paths the sequences were added along, for linkage and phasing analysis of nearby
+/// variants. Only sequences spanning both columns are counted; a gap within a sequence counts as
+/// a deletion allele (`-`). The result has no alleles if a column is out of range.
+/// Caller must free the result with poasta_free_linkage.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_column_linkage(graph: *const PoastaGraph, col_a: usize, col_b: usize) -> PoastaLinkage {
+    error::catch(|| {
+        if graph.is_null() {
+            return linkage(&[], col_a, col_b, 0, 1);
+        }
+
+        let graph = unsafe { &*graph };
+        let alleles = read_alleles(graph, &Columns::new(graph), &[col_a, col_b]);
+        linkage(&alleles, col_a, col_b, 0, 1)
+    })
+}
+
+/// Returns the linkage (see poasta_column_linkage) of every pair of the `n` columns in `cols`, in
+/// the order (0, 1), (0, 2), ..., (1, 2), ... of their indices in `cols`. The alleles of all
+/// sequences are looked up once, which is much faster than one call per pair.
+/// Caller must free the result with poasta_free_linkage_list.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_column_linkage_bulk(graph: *const PoastaGraph, cols: *const usize, n: usize) -> PoastaLinkageList {
+    error::catch(|| {
+        if graph.is_null() || cols.is_null() {
+            return PoastaLinkageList { pairs: ptr::null_mut(), num_pairs: 0 };
+        }
+
+        let cols = unsafe { slice::from_raw_parts(cols, n) };
+        let graph = unsafe { &*graph };
+        let alleles = read_alleles(graph, &Columns::new(graph), cols);
+
+        let mut pairs = Vec::with_capacity(n * n.saturating_sub(1) / 2);
+        for a in 0..n {
+            for b in a + 1..n {
+                pairs.push(linkage(&alleles, cols[a], cols[b], a, b));
+            }
+        }
+
+        let num_pairs = pairs.len();
+        PoastaLinkageList { pairs: into_c_array(pairs), num_pairs }
+    })
+}
+
+/// Frees a PoastaLinkage.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_linkage(linkage: PoastaLinkage) {
+    error::catch(|| {
+        unsafe {
+            free_c_array(linkage.alleles_a, linkage.num_alleles_a);
+            free_c_array(linkage.alleles_b, linkage.num_alleles_b);
+            free_c_array(linkage.counts, linkage.num_alleles_a * linkage.num_alleles_b);
+        }
+    })
+}
+
+/// Frees a PoastaLinkageList, including its pairs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_linkage_list(list: PoastaLinkageList) {
+    error::catch(|| {
+        for pair in unsafe { free_c_array(list.pairs, list.num_pairs) } {
+            unsafe { poasta_free_linkage(pair) };
+        }
+    })
+}
+
+/// The allele of each active sequence in each of the columns, None where the sequence doesn't
+/// span the column (or the column doesn't exist).
+pub(crate) fn read_alleles(graph: &PoastaGraph, columns: &Columns, cols: &[usize]) -> Vec<Vec<Option<u8>>> {
+    let mut indices: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, col) in cols.iter().enumerate() {
+        indices.entry(*col).or_default().push(i);
+    }
+
+    graph.active_sequences()
+        .map(|record| {
+            let first = record.path.first().map(|node| columns.of_node[node]);
+            let last = record.path.last().map(|node| columns.of_node[node]);
+            let mut alleles: Vec<Option<u8>> = cols.iter()
+                .map(|col| (first.is_some_and(|first| first <= *col) && last.is_some_and(|last| *col <= last)).then_some(GAP))
+                .collect();
+
+            for (node, base) in record.path.iter().zip(&record.seq) {
+                for i in indices.get(&columns.of_node[node]).into_iter().flatten() {
+                    alleles[*i] = Some(base.to_ascii_uppercase());
+                }
+            }
+
+            alleles
+        })
+        .collect()
+}
+
+/// Joint allele counts of the columns at indices `a` and `b` of `alleles` (see `read_alleles`).
+fn linkage(alleles: &[Vec<Option<u8>>], col_a: usize, col_b: usize, a: usize, b: usize) -> PoastaLinkage {
+    let pairs: Vec<(u8, u8)> = alleles.iter()
+        .filter_map(|read| Some((read[a]?, read[b]?)))
+        .collect();
+
+    let alleles_a = by_frequency(pairs.iter().map(|(allele, _)| *allele));
+    let alleles_b = by_frequency(pairs.iter().map(|(_, allele)| *allele));
+
+    let mut counts = vec![0; alleles_a.len() * alleles_b.len()];
+    for (allele_a, allele_b) in &pairs {
+        let i = alleles_a.iter().position(|allele| allele == allele_a).unwrap();
+        let j = alleles_b.iter().position(|allele| allele == allele_b).unwrap();
+        counts[i * alleles_b.len() + j] += 1;
+    }
+
+    PoastaLinkage {
+        col_a,
+        col_b,
+        num_alleles_a: alleles_a.len(),
+        alleles_a: into_c_array(alleles_a),
+        num_alleles_b: alleles_b.len(),
+        alleles_b: into_c_array(alleles_b),
+        counts: into_c_array(counts),
+        num_sequences: pairs.len(),
+    }
+}
+
+/// The distinct alleles, most frequent first (ties by allele).
+pub(crate) fn by_frequency(alleles: impl Iterator<Item = u8>) -> Vec<u8> {
+    let mut counts = [0usize; 256];
+    for allele in alleles {
+        counts[allele as usize] += 1;
+    }
+
+    let mut distinct: Vec<u8> = (0..=255).filter(|allele| counts[*allele as usize] > 0).collect();
+    distinct.sort_by_key(|allele| std::cmp::Reverse(counts[*allele as usize]));
+
+    distinct
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::graph_of;
+
+    /// The alleles of both columns, and the counts of each pair.
+    fn contents(linkage: &PoastaLinkage) -> (Vec<u8>, Vec<u8>, Vec<usize>) {
+        let (a, b) = (linkage.num_alleles_a, linkage.num_alleles_b);
+        unsafe {
+            (
+                slice::from_raw_parts(linkage.alleles_a, a).to_vec(),
+                slice::from_raw_parts(linkage.alleles_b, b).to_vec(),
+                slice::from_raw_parts(linkage.counts, a * b).to_vec(),
+            )
+        }
+    }
+
+    #[test]
+    fn alleles_are_counted_jointly() {
+        // Two haplotypes, G..G and C..T, and a read deleting the base of column 9
+        let graph = graph_of(&["ACGTTGCAGGATCC", "ACGTTGCAGGATCC", "ACGTTGCAGGATCC", "ACCTTGCAGTATCC", "ACCTTGCAGTATCC", "ACGTTGCAGATCC"]);
+        let linkage = unsafe { poasta_column_linkage(&graph, 2, 9) };
+        assert_eq!((linkage.col_a, linkage.col_b, linkage.num_sequences), (2, 9, 6));
+        assert_eq!(contents(&linkage), (b"GC".to_vec(), b"GT-".to_vec(), vec![3, 0, 1, 0, 2, 0]));
+        unsafe { poasta_free_linkage(linkage) };
+
+        let linkage = unsafe { poasta_column_linkage(&graph, 2, 100) };
+        assert_eq!((linkage.num_alleles_b, linkage.num_sequences), (0, 0));
+        unsafe { poasta_free_linkage(linkage) };
+    }
+
+    #[test]
+    fn bulk_linkage_covers_every_pair() {
+        let graph = graph_of(&["ACGTTGCAGGATCC", "ACCTTGCAGTATCC"]);
+        let cols = [2, 9, 12];
+        let list = unsafe { poasta_column_linkage_bulk(&graph, cols.as_ptr(), cols.len()) };
+        let pairs = unsafe { slice::from_raw_parts(list.pairs, list.num_pairs) };
+        let pair_cols: Vec<_> = pairs.iter().map(|pair| (pair.col_a, pair.col_b)).collect();
+        assert_eq!(pair_cols, [(2, 9), (2, 12), (9, 12)]);
+
+        for pair in pairs {
+            let single = unsafe { poasta_column_linkage(&graph, pair.col_a, pair.col_b) };
+            assert_eq!(contents(pair), contents(&single));
+            unsafe { poasta_free_linkage(single) };
+        }
+        unsafe { poasta_free_linkage_list(list) };
+    }
+}
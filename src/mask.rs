@@ -0,0 +1,183 @@
+use std::ops::Range;
+use std::os::raw::c_int;
+use std::slice;
+
+use poasta::graphs::AlignableRefGraph;
+use serde::{Deserialize, Serialize};
+
+use crate::consensus::heaviest_path;
+use crate::error;
+use crate::graph::NodeIx;
+use crate::reference::ref_positions;
+use crate::PoastaGraph;
+
+/// Half-open interval `[start, end)` of 0-based positions.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct PoastaInterval {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Coordinate system of positions passed to the library.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoastaCoordinates {
+    /// Positions on the graph's backbone (see poasta_graph_set_backbone).
+    Reference = 0,
+
+    /// Positions in the current consensus.
+    Consensus = 1,
+}
+
+/// Regions excluded from variant reporting, e.g., known homopolymer or primer artifacts.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct MaskedRegions {
+    regions: Regions,
+
+    /// Whether the consensus confidence isn't reported for masked bases either.
+    pub confidence: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum Regions {
+    /// Backbone intervals, which also cover nodes added later.
+    Reference(Vec<Range<usize>>),
+
+    /// Consensus nodes at the time the regions were set.
+    Nodes(Vec<NodeIx>),
+}
+
+/// Masks the given regions (an array of `n` intervals) of the graph, replacing any regions masked
+/// before: variants in them are left out of the HTML report and, if `mask_confidence` is set, the
+/// report doesn't show the consensus confidence of their bases either. With
+/// `PoastaCoordinates::Reference`, the intervals are backbone positions (e.g., from a BED file)
+/// and also mask nodes added later. With `PoastaCoordinates::Consensus`, they are positions in
+/// the current consensus. Pass `n = 0` to unmask everything.
+/// Returns -1 if reference coordinates are given for a graph without backbone.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_set_masked_regions(
+    graph: *mut PoastaGraph,
+    regions: *const PoastaInterval,
+    n: usize,
+    coordinates: PoastaCoordinates,
+    mask_confidence: bool,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || (regions.is_null() && n > 0) {
+            return error::fail(-1, "graph is NULL, or regions is NULL and n isn't 0");
+        }
+
+        let graph = unsafe { &mut *graph };
+        if n == 0 {
+            graph.masked_regions = None;
+            graph.touch();
+            return 0;
+        }
+
+        let intervals = unsafe { slice::from_raw_parts(regions, n) };
+        let regions = match coordinates {
+            PoastaCoordinates::Reference if graph.backbone.is_none() => return error::fail(-1, "the graph has no backbone"),
+            PoastaCoordinates::Reference => Regions::Reference(intervals.iter().map(|i| i.start..i.end).collect()),
+            PoastaCoordinates::Consensus => {
+                let consensus = heaviest_path(graph);
+                let nodes = intervals.iter()
+                    .flat_map(|i| consensus.get(i.start.min(consensus.len())..i.end.min(consensus.len())).unwrap_or_default())
+                    .copied()
+                    .collect();
+
+                Regions::Nodes(nodes)
+            },
+        };
+
+        graph.masked_regions = Some(MaskedRegions { regions, confidence: mask_confidence });
+        graph.touch();
+
+        0
+    })
+}
+
+impl MaskedRegions {
+    /// The same regions in a copy of the graph, given the copy's node for each node.
+    pub(crate) fn translated(&self, node_map: impl Fn(NodeIx) -> Option<NodeIx>) -> Self {
+        let regions = match &self.regions {
+            Regions::Reference(intervals) => Regions::Reference(intervals.clone()),
+            Regions::Nodes(nodes) => Regions::Nodes(nodes.iter().filter_map(|node| node_map(*node)).collect()),
+        };
+
+        MaskedRegions { regions, confidence: self.confidence }
+    }
+
+    /// Whether each node (by index) is masked. Nodes aligned to a masked node are masked as well.
+    pub(crate) fn masked_nodes(&self, graph: &PoastaGraph) -> Vec<bool> {
+        let mut masked = vec![false; graph.graph.node_count_with_start_and_end()];
+        let nodes: Vec<NodeIx> = match &self.regions {
+            Regions::Reference(intervals) => {
+                let positions = ref_positions(graph).unwrap_or_default();
+                graph.graph.all_nodes()
+                    .filter(|node| {
+                        positions.get(node.index())
+                            .copied()
+                            .flatten()
+                            .is_some_and(|pos| intervals.iter().any(|interval| interval.contains(&pos)))
+                    })
+                    .collect()
+            },
+            Regions::Nodes(nodes) => nodes.clone(),
+        };
+
+        for node in nodes {
+            masked[node.index()] = true;
+            for aligned in graph.graph.get_aligned_nodes(node) {
+                masked[aligned.index()] = true;
+            }
+        }
+
+        masked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+    use crate::reference::poasta_graph_init_reference;
+    use crate::testing::{add, graph_of};
+
+    const REFERENCE: &str = "ACGTTGCAGGATCCTTAGCA";
+
+    /// Indices of the sequence's bases on masked nodes.
+    fn masked_bases(graph: &PoastaGraph, index: usize) -> Vec<usize> {
+        let masked = graph.masked_regions.as_ref().unwrap().masked_nodes(graph);
+        (0..graph.sequences[index].path.len())
+            .filter(|pos| masked[graph.sequences[index].path[*pos].index()])
+            .collect()
+    }
+
+    #[test]
+    fn reference_regions_cover_nodes_added_later() {
+        let mut graph = PoastaGraph::new();
+        unsafe { poasta_graph_init_reference(&mut graph, REFERENCE.as_ptr().cast(), REFERENCE.len(), 1) };
+        let regions = [PoastaInterval { start: 2, end: 4 }, PoastaInterval { start: 10, end: 11 }];
+        assert_eq!(unsafe { poasta_set_masked_regions(&mut graph, regions.as_ptr(), 2, PoastaCoordinates::Reference, false) }, 0);
+
+        // A substitution at position 3 is aligned to a masked backbone node
+        add(&mut graph, "ACGATGCAGGATCCTTAGCA");
+        assert_eq!(masked_bases(&graph, 1), [2, 3, 10]);
+
+        assert_eq!(unsafe { poasta_set_masked_regions(&mut graph, ptr::null(), 0, PoastaCoordinates::Reference, false) }, 0);
+        assert!(graph.masked_regions.is_none());
+    }
+
+    #[test]
+    fn consensus_regions_mask_the_current_consensus() {
+        let mut graph = graph_of(&[REFERENCE, REFERENCE]);
+        let regions = [PoastaInterval { start: 18, end: 25 }];
+        assert_eq!(unsafe { poasta_set_masked_regions(&mut graph, regions.as_ptr(), 1, PoastaCoordinates::Consensus, true) }, 0);
+        assert_eq!(masked_bases(&graph, 0), [18, 19]);
+        assert!(graph.masked_regions.as_ref().unwrap().confidence);
+
+        assert_eq!(unsafe { poasta_set_masked_regions(&mut graph, regions.as_ptr(), 1, PoastaCoordinates::Reference, false) }, -1);
+    }
+}
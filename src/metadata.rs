@@ -0,0 +1,83 @@
+//! Data hosts attach to a graph: a pointer for the current process and a byte blob that is saved
+//! with the graph.
+
+use std::ffi::c_void;
+use std::os::raw::c_int;
+use std::ptr;
+use std::slice;
+
+use crate::error;
+use crate::PoastaGraph;
+
+/// Attaches a pointer of the host's (e.g., to its own record of the cluster) to the graph, which
+/// the library never dereferences. It isn't copied to clones or saved with the graph.
+/// Returns -1 if the graph is NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_set_user_data(graph: *mut PoastaGraph, user_data: *mut c_void) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        unsafe { (*graph).user_data = user_data as usize };
+
+        0
+    })
+}
+
+/// Returns the pointer set with poasta_graph_set_user_data, or NULL if none was set.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_get_user_data(graph: *const PoastaGraph) -> *mut c_void {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        unsafe { (*graph).user_data as *mut c_void }
+    })
+}
+
+/// Stores a copy of `len` bytes at `data` with the graph (e.g., cluster IDs or provenance JSON),
+/// replacing earlier metadata. Unlike the user data, the metadata is copied to clones and saved
+/// with the graph. A `len` of 0 clears it. Returns -1 if the graph is NULL, or `data` is NULL and
+/// `len` isn't 0.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_set_metadata(graph: *mut PoastaGraph, data: *const u8, len: usize) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || (data.is_null() && len > 0) {
+            return error::fail(-1, "graph is NULL, or data is NULL and len isn't 0");
+        }
+
+        let metadata = match len {
+            0 => Vec::new(),
+            _ => unsafe { slice::from_raw_parts(data, len) }.to_vec(),
+        };
+        unsafe { (*graph).metadata = metadata };
+
+        0
+    })
+}
+
+/// Returns the metadata set with poasta_graph_set_metadata and stores its length in `*len`, or
+/// NULL (and 0) if there is none. The bytes belong to the graph and stay valid until its metadata
+/// is changed or it is freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_get_metadata(graph: *const PoastaGraph, len: *mut usize) -> *const u8 {
+    error::catch(|| {
+        if !len.is_null() {
+            unsafe { *len = 0 };
+        }
+        if graph.is_null() || len.is_null() {
+            return ptr::null();
+        }
+
+        let metadata = unsafe { &(*graph).metadata };
+        if metadata.is_empty() {
+            return ptr::null();
+        }
+
+        unsafe { *len = metadata.len() };
+
+        metadata.as_ptr()
+    })
+}
@@ -0,0 +1,142 @@
+//! Process-wide counters, so services embedding the library can export health metrics without
+//! wrapping every call.
+
+use std::ffi::CString;
+use std::fmt::Write;
+use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{error, resource};
+
+/// Number of error codes counted separately; status `-i` is counted at index `i`.
+pub const POASTA_NUM_ERROR_CODES: usize = 16;
+
+static SEQUENCES_ADDED: AtomicU64 = AtomicU64::new(0);
+static ALIGNMENTS_RUN: AtomicU64 = AtomicU64::new(0);
+static CELLS_COMPUTED: AtomicU64 = AtomicU64::new(0);
+static BYTES_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static ERRORS: [AtomicU64; POASTA_NUM_ERROR_CODES] = [const { AtomicU64::new(0) }; POASTA_NUM_ERROR_CODES];
+
+/// Struct to hold cumulative counters since the library was loaded.
+#[repr(C)]
+pub struct PoastaMetrics {
+    /// Sequences successfully added by the poasta_add_sequence* functions.
+    pub sequences_added: u64,
+
+    pub alignments_run: u64,
+
+    /// Alignment states visited by poasta's aligner, or matrix cells filled by the exhaustive
+    /// aligner.
+    pub cells_computed: u64,
+
+    /// Total size of all heap allocations made by the library (0 without the `alloc-metrics`
+    /// feature).
+    pub bytes_allocated: u64,
+
+    /// `errors[i]` is the number of times an add function returned `-i`.
+    pub errors: [u64; POASTA_NUM_ERROR_CODES],
+}
+
+/// Returns the current value of all counters.
+/// Part of the fast query tier: never allocates and never panics.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_metrics_snapshot() -> PoastaMetrics {
+    error::catch(|| {
+        PoastaMetrics {
+            sequences_added: SEQUENCES_ADDED.load(Ordering::Relaxed),
+            alignments_run: ALIGNMENTS_RUN.load(Ordering::Relaxed),
+            cells_computed: CELLS_COMPUTED.load(Ordering::Relaxed),
+            bytes_allocated: BYTES_ALLOCATED.load(Ordering::Relaxed),
+            errors: std::array::from_fn(|code| ERRORS[code].load(Ordering::Relaxed)),
+        }
+    })
+}
+
+/// Returns all counters in the Prometheus text exposition format.
+/// The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_metrics_text() -> *mut c_char {
+    error::catch(|| {
+        let metrics = poasta_metrics_snapshot();
+        let counters = [
+            ("poasta_sequences_added_total", "Sequences added to a graph.", metrics.sequences_added),
+            ("poasta_alignments_total", "Alignments of a sequence to a graph.", metrics.alignments_run),
+            ("poasta_cells_computed_total", "Alignment states computed.", metrics.cells_computed),
+            ("poasta_allocated_bytes_total", "Bytes allocated on the heap.", metrics.bytes_allocated),
+        ];
+
+        let mut text = String::new();
+        for (name, help, value) in counters {
+            writeln!(text, "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}").unwrap();
+        }
+
+        writeln!(text, "# HELP poasta_errors_total Errors returned when adding a sequence, by status code.").unwrap();
+        writeln!(text, "# TYPE poasta_errors_total counter").unwrap();
+        for (code, count) in metrics.errors.iter().enumerate().filter(|(_, count)| **count > 0) {
+            writeln!(text, "poasta_errors_total{{code=\"-{code}\"}} {count}").unwrap();
+        }
+
+        CString::new(text).unwrap().into_raw()
+    })
+}
+
+/// Counts the outcome of adding a sequence, and records it as the thread's last error if it failed.
+/// Returns the status.
+pub(crate) fn record_status(status: c_int) -> c_int {
+    error::record(status);
+    if status >= 0 {
+        SEQUENCES_ADDED.fetch_add(1, Ordering::Relaxed);
+    } else if let Some(errors) = ERRORS.get(status.unsigned_abs() as usize) {
+        errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    status
+}
+
+/// Counts an alignment that computed `cells` states.
+pub(crate) fn record_alignment(cells: usize) {
+    ALIGNMENTS_RUN.fetch_add(1, Ordering::Relaxed);
+    CELLS_COMPUTED.fetch_add(cells as u64, Ordering::Relaxed);
+    resource::add_cells(cells as u64);
+}
+
+#[cfg(feature = "alloc-metrics")]
+mod allocator {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::Ordering;
+
+    use super::BYTES_ALLOCATED;
+    use crate::resource;
+
+    /// The system allocator, counting the bytes allocated through it.
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            BYTES_ALLOCATED.fetch_add(layout.size() as u64, Ordering::Relaxed);
+            resource::allocated(layout.size());
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            BYTES_ALLOCATED.fetch_add(layout.size() as u64, Ordering::Relaxed);
+            resource::allocated(layout.size());
+            unsafe { System.alloc_zeroed(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            resource::freed(layout.size());
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            BYTES_ALLOCATED.fetch_add(new_size.saturating_sub(layout.size()) as u64, Ordering::Relaxed);
+            resource::freed(layout.size());
+            resource::allocated(new_size);
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+}
@@ -0,0 +1,186 @@
+//! Alignment modes other than global alignment, e.g., for amplicon reads with ragged ends.
+
+use std::os::raw::{c_char, c_int};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{add_sequence_uniform, error, metrics, PoastaGraph, Scoring, SequenceInfo};
+
+/// How a sequence is aligned to the graph.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PoastaAlignmentType {
+    /// The whole sequence is aligned to a full path through the graph.
+    #[default]
+    Global = 0,
+
+    /// Like Global, but gaps at the ends selected in `PoastaAlignmentMode` are free.
+    EndsFree = 1,
+
+    /// Only the best matching part of the sequence is aligned, to any part of the graph, e.g., for
+    /// fragments partially overlapping the graph. The rest of the sequence is clipped, i.e., added
+    /// as new nodes, and so is a sequence without any matching part.
+    Local = 2,
+}
+
+/// Alignment type, and for ends-free alignment, which ends are free.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoastaAlignmentMode {
+    pub aln_type: PoastaAlignmentType,
+
+    /// Bases before the part of the sequence aligned to the graph are free.
+    pub query_free_begin: bool,
+
+    /// Bases after the part of the sequence aligned to the graph are free.
+    pub query_free_end: bool,
+
+    /// The alignment may start at any node, skipping the graph before it for free.
+    pub graph_free_begin: bool,
+
+    /// The alignment may end at any node, skipping the graph after it for free.
+    pub graph_free_end: bool,
+
+    /// Bonus for each matching base in Local alignment, without which any single matching base
+    /// would be as good as a long alignment. 0 means half the mismatch cost, rounded up.
+    pub match_bonus: u8,
+}
+
+impl PoastaAlignmentMode {
+    /// The same mode with the free ends implied by its type, and a match bonus only if it is local.
+    pub(crate) fn normalized(self) -> Self {
+        match self.aln_type {
+            PoastaAlignmentType::Global => PoastaAlignmentMode::default(),
+            PoastaAlignmentType::EndsFree => PoastaAlignmentMode { match_bonus: 0, ..self },
+            PoastaAlignmentType::Local => PoastaAlignmentMode {
+                aln_type: PoastaAlignmentType::Local,
+                query_free_begin: true,
+                query_free_end: true,
+                graph_free_begin: true,
+                graph_free_end: true,
+                match_bonus: self.match_bonus,
+            },
+        }
+    }
+
+    /// Whether no end is free, i.e., the mode is global alignment, which poasta's aligner supports.
+    pub(crate) fn is_global(&self) -> bool {
+        let mode = self.normalized();
+        !(mode.query_free_begin || mode.query_free_end || mode.graph_free_begin || mode.graph_free_end)
+    }
+
+    /// Bonus for matching bases with the given mismatch cost, 0 unless the mode is local.
+    pub(crate) fn match_bonus(&self, mismatch: i32) -> i32 {
+        match self.aln_type {
+            PoastaAlignmentType::Local if self.match_bonus > 0 => self.match_bonus as i32,
+            PoastaAlignmentType::Local => ((mismatch + 1) / 2).max(1),
+            _ => 0,
+        }
+    }
+}
+
+/// Adds a sequence to the graph like poasta_add_sequence, aligned in the given mode. With free
+/// ends, e.g., for amplicon reads with ragged ends, overhanging bases become new nodes instead of
+/// being forced into gaps, and missing ends of the graph are skipped. Freeing the same end of both
+/// the sequence and the graph lets the alignment skip that end entirely. Local alignment clips
+/// both ends of the sequence to its best matching part instead. Modes other than Global use a full
+/// dynamic programming aligner, which is slower than the default aligner.
+/// Returns the same codes as poasta_add_sequence.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_add_sequence_mode(
+    graph: *mut PoastaGraph,
+    seq: *const c_char,
+    len: usize,
+    mismatch_score: i32,
+    gap_extend: i32,
+    gap_open: i32,
+    mode: PoastaAlignmentMode,
+) -> c_int {
+    error::catch(|| {
+        let scoring = match Scoring::affine(mismatch_score, gap_extend, gap_open) {
+            Ok(scoring) => scoring,
+            Err(status) => return metrics::record_status(status),
+        };
+        let info = SequenceInfo { mode: mode.normalized(), ..SequenceInfo::default() };
+        unsafe { add_sequence_uniform(graph, seq, len, 1, scoring, info) }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dp::DpBuffers;
+    use crate::testing::{consensus, graph_of, scoring};
+
+    const REFERENCE: &str = "ACGTACGTAA";
+
+    /// Cost of aligning `read` to a graph of REFERENCE in `mode`.
+    fn cost(read: &str, mode: PoastaAlignmentMode) -> i64 {
+        let mut graph = graph_of(&[REFERENCE]);
+        let info = SequenceInfo { mode: mode.normalized(), ..SequenceInfo::default() };
+        let (status, cost) = graph.add_sequence_scored(read.as_bytes(), &vec![1; read.len()], scoring(), info, &mut DpBuffers::default());
+        assert_eq!(status, 0);
+
+        cost.unwrap()
+    }
+
+    fn ends_free(query: bool, graph: bool) -> PoastaAlignmentMode {
+        PoastaAlignmentMode {
+            aln_type: PoastaAlignmentType::EndsFree,
+            query_free_begin: query,
+            query_free_end: query,
+            graph_free_begin: graph,
+            graph_free_end: graph,
+            match_bonus: 0,
+        }
+    }
+
+    #[test]
+    fn only_the_selected_ends_are_free() {
+        // The fragment skips one base before and three after it in the graph, and the overhanging
+        // read has three extra bases before the graph
+        let (fragment, overhanging) = ("CGTACG", "TTTACGTACGTAA");
+        let global = PoastaAlignmentMode::default();
+        assert_eq!(cost(fragment, global), 20);
+        assert_eq!(cost(fragment, ends_free(false, true)), 0);
+        assert_eq!(cost(overhanging, global), 12);
+        assert_eq!(cost(overhanging, ends_free(true, false)), 0);
+        assert_eq!(cost(overhanging, ends_free(false, true)), 12);
+
+        // Free ends are ignored unless the type is EndsFree
+        let flagged = PoastaAlignmentMode { aln_type: PoastaAlignmentType::Global, ..ends_free(true, true) };
+        assert!(flagged.is_global());
+        assert_eq!(cost(fragment, flagged), 20);
+    }
+
+    #[test]
+    fn sequences_are_added_in_the_mode() {
+        let mut graph = graph_of(&[REFERENCE, REFERENCE]);
+        let read = "CGTACG";
+        let status = unsafe { poasta_add_sequence_mode(&mut graph, read.as_ptr().cast(), read.len(), 4, 2, 6, ends_free(false, true)) };
+        assert_eq!(status, 0);
+        assert_eq!(consensus(&graph), REFERENCE);
+        assert_eq!(unsafe { poasta_add_sequence_mode(&mut graph, read.as_ptr().cast(), read.len(), -1, 2, 6, ends_free(false, true)) }, -1);
+    }
+
+    #[test]
+    fn local_alignment_clips_to_the_best_matching_part() {
+        // Every matching base earns the bonus, half the mismatch cost unless given
+        let local = PoastaAlignmentMode { aln_type: PoastaAlignmentType::Local, ..PoastaAlignmentMode::default() };
+        let flanked = "GGGGACGTACGTAAGGGG";
+        assert_eq!(cost(REFERENCE, local), -20);
+        assert_eq!(cost(flanked, local), -20);
+        assert_eq!(cost(flanked, PoastaAlignmentMode { match_bonus: 3, ..local }), -30);
+        assert_eq!(cost(flanked, PoastaAlignmentMode::default()), 28);
+        assert_eq!(cost("CTACG", local), -8);
+
+        // The clipped flanks become new nodes around the shared ones
+        let mut graph = graph_of(&[REFERENCE]);
+        let status = unsafe { poasta_add_sequence_mode(&mut graph, flanked.as_ptr().cast(), flanked.len(), 4, 2, 6, local) };
+        assert_eq!(status, 0);
+        let (reference, read) = (&graph.sequences[0].path, &graph.sequences[1].path);
+        assert_eq!(read.len(), flanked.len());
+        assert_eq!(&read[4..14], &reference[..]);
+        assert!(read[..4].iter().chain(&read[14..]).all(|node| !reference.contains(node)));
+    }
+}
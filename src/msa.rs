@@ -0,0 +1,518 @@
+//! Multiple sequence alignment rows computed from the sequence paths.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ffi::CString;
+use std::io::{self, Write};
+use std::iter;
+use std::ops::Range;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::graph::{NodeIx, PoastaGraph, SequenceRecord};
+use crate::limit::CappedOutput;
+use crate::resource::{self, PoastaOperation};
+use crate::view::GraphView;
+use crate::{error, free_c_array, into_c_array, into_c_msa, PoastaMsa};
+
+/// Options of poasta_get_msa_padded and poasta_get_msa_fasta.
+#[repr(C)]
+pub struct PoastaMsaOptions {
+    /// Character filling each row before the first and after the last base of its sequence, e.g.,
+    /// `.` or ` `; 0 to use `-` like for the gaps within the sequence.
+    pub pad: c_char,
+
+    /// Number of MSA columns per line of FASTA output; 0 to write each row on a single line.
+    pub line_width: usize,
+}
+
+/// Gap character in MSA rows.
+pub(crate) const GAP: u8 = b'-';
+
+/// Assignment of graph nodes to MSA columns. Aligned nodes share a column, and columns are in
+/// topological order.
+pub(crate) struct Columns {
+    pub of_node: HashMap<NodeIx, usize>,
+    pub count: usize,
+}
+
+impl Columns {
+    pub(crate) fn new(graph: &PoastaGraph) -> Self {
+        let view = GraphView::new(graph);
+        let (start, end) = (view.start_node(), view.end_node());
+
+        // Group aligned nodes, the first node of a group (by index) represents it
+        let mut group_of: HashMap<NodeIx, usize> = HashMap::new();
+        let mut groups: Vec<Vec<NodeIx>> = Vec::new();
+        for node in view.all_nodes().filter(|n| *n != start && *n != end) {
+            if group_of.contains_key(&node) {
+                continue;
+            }
+
+            let mut group = vec![node];
+            group.extend_from_slice(graph.graph.get_aligned_nodes(node));
+            for member in &group {
+                group_of.insert(*member, groups.len());
+            }
+            groups.push(group);
+        }
+
+        // Kahn's algorithm on the groups, taking the lowest ready group first for stable output
+        let mut successors: Vec<Vec<usize>> = vec![Vec::new(); groups.len()];
+        let mut in_degree = vec![0; groups.len()];
+        for (g, group) in groups.iter().enumerate() {
+            for succ in group.iter().flat_map(|n| view.successors(*n)).filter(|s| *s != end) {
+                let succ_group = group_of[&succ];
+                if succ_group != g && !successors[g].contains(&succ_group) {
+                    successors[g].push(succ_group);
+                    in_degree[succ_group] += 1;
+                }
+            }
+        }
+
+        let mut ready: BTreeSet<usize> = (0..groups.len()).filter(|g| in_degree[*g] == 0).collect();
+        let mut column_of_group = vec![0; groups.len()];
+        let mut column = 0;
+        while let Some(g) = ready.pop_first() {
+            column_of_group[g] = column;
+            column += 1;
+
+            for succ in &successors[g] {
+                in_degree[*succ] -= 1;
+                if in_degree[*succ] == 0 {
+                    ready.insert(*succ);
+                }
+            }
+        }
+
+        let of_node = group_of.into_iter()
+            .map(|(node, g)| (node, column_of_group[g]))
+            .collect();
+
+        Columns { of_node, count: groups.len() }
+    }
+
+    /// The columns of poasta's FASTA writer (`poa_graph_to_fasta`), which numbers groups of
+    /// aligned nodes in the reverse postorder of a depth-first search from the start node.
+    pub(crate) fn like_poasta(graph: &PoastaGraph) -> Self {
+        let graph = &*graph.graph;
+        let (start, end) = (graph.start_node(), graph.end_node());
+
+        // Aligned nodes are visited together, with their successors searched after the node's
+        let mut visited = HashSet::new();
+        let mut stack: Vec<(NodeIx, Vec<NodeIx>)> = vec![(start, graph.successors(start).collect())];
+        let mut postorder = Vec::new();
+        while let Some((_, successors)) = stack.last_mut() {
+            match iter::from_fn(|| successors.pop()).find(|succ| !visited.contains(succ)) {
+                Some(child) => {
+                    visited.insert(child);
+                    let mut successors: Vec<NodeIx> = graph.successors(child).collect();
+                    for aligned in graph.get_aligned_nodes(child) {
+                        if visited.insert(*aligned) {
+                            successors.extend(graph.successors(*aligned));
+                        }
+                    }
+                    stack.push((child, successors));
+                },
+                None => postorder.push(stack.pop().unwrap().0),
+            }
+        }
+
+        let mut of_node = HashMap::new();
+        let mut count = 0;
+        for node in postorder.iter().rev().filter(|node| **node != start && **node != end) {
+            if !of_node.contains_key(node) {
+                of_node.insert(*node, count);
+                for aligned in graph.get_aligned_nodes(*node) {
+                    of_node.insert(*aligned, count);
+                }
+                count += 1;
+            }
+        }
+
+        Columns { of_node, count }
+    }
+}
+
+/// Name and gapped row of each active sequence, in insertion order, restricted to the given column
+/// range. All MSA outputs are built from these rows, which are computed as they are iterated, so
+/// writers can stop early.
+pub(crate) fn named_rows<'a>(
+    graph: &'a PoastaGraph,
+    columns: &'a Columns,
+    range: Range<usize>,
+) -> impl Iterator<Item = (String, Vec<u8>)> + 'a {
+    graph.sequences.iter()
+        .enumerate()
+        .filter(|(_, record)| !record.info.masked)
+        .map(move |(ix, record)| (graph.sequence_name(ix), msa_row(record, columns, range.clone())))
+}
+
+/// The MSA of poasta_get_msa handed over to C, see into_c_msa, with `columns` from
+/// `Columns::like_poasta`. Like poasta's FASTA writer, rows that don't start in the first column
+/// lack one of their leading gaps.
+pub(crate) fn poasta_msa(graph: &PoastaGraph, columns: &Columns) -> PoastaMsa {
+    let (names, rows) = named_rows(graph, columns, 0..columns.count)
+        .map(|(name, mut row)| {
+            if row.first() == Some(&GAP) {
+                row.remove(0);
+            }
+
+            (name, row)
+        })
+        .unzip();
+
+    into_c_msa(names, rows)
+}
+
+/// One gapped row per active sequence, in insertion order, restricted to the given column range.
+pub(crate) fn msa_rows(graph: &PoastaGraph, columns: &Columns, range: Range<usize>) -> Vec<Vec<u8>> {
+    graph.active_sequences()
+        .map(|record| msa_row(record, columns, range.clone()))
+        .collect()
+}
+
+/// Gapped row of a sequence, restricted to the given column range.
+fn msa_row(record: &SequenceRecord, columns: &Columns, range: Range<usize>) -> Vec<u8> {
+    let mut row = vec![GAP; range.len()];
+    for (node, base) in record.path.iter().zip(&record.seq) {
+        let col = columns.of_node[node];
+        if range.contains(&col) {
+            row[col - range.start] = *base;
+        }
+    }
+
+    row
+}
+
+/// Name of the sequence of each row of `msa_rows`.
+pub(crate) fn msa_names(graph: &PoastaGraph) -> Vec<String> {
+    (0..graph.sequences.len())
+        .filter(|ix| !graph.sequences[*ix].info.masked)
+        .map(|ix| graph.sequence_name(ix))
+        .collect()
+}
+
+/// Returns columns `col_start` (inclusive) to `col_end` (exclusive) of the MSA, e.g., for
+/// scrollable alignment viewers. The range is clamped to the MSA width. Only the requested columns
+/// are materialized, so this works on graphs whose full MSA wouldn't fit in memory.
+/// Returns a PoastaMsa struct. Caller must free it with poasta_free_msa.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_msa_window(graph: *const PoastaGraph, col_start: usize, col_end: usize) -> PoastaMsa {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaMsa::empty();
+        }
+
+        let _usage = resource::track(PoastaOperation::Msa);
+        let graph = unsafe { &*graph };
+        let columns = Columns::new(graph);
+        let end = col_end.min(columns.count);
+        let start = col_start.min(end);
+
+        let (names, rows) = named_rows(graph, &columns, start..end).unzip();
+        into_c_msa(names, rows)
+    })
+}
+
+/// Returns the full-width MSA (the columns of poasta_get_msa_window) with the end gaps of every
+/// row filled with `options.pad`. `options.line_width` is ignored.
+/// Returns a PoastaMsa struct. Caller must free it with poasta_free_msa.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_msa_padded(graph: *const PoastaGraph, options: PoastaMsaOptions) -> PoastaMsa {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaMsa::empty();
+        }
+
+        let _usage = resource::track(PoastaOperation::Msa);
+        let graph = unsafe { &*graph };
+        let columns = Columns::new(graph);
+        let (names, rows) = named_rows(graph, &columns, 0..columns.count)
+            .map(|(name, row)| (name, padded_row(row, options.pad)))
+            .unzip();
+
+        into_c_msa(names, rows)
+    })
+}
+
+/// Returns the full-width MSA as FASTA text, with the end gaps of every row filled with
+/// `options.pad` and the rows wrapped after `options.line_width` columns, for parsers that expect
+/// fixed-width records.
+/// The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_msa_fasta(graph: *const PoastaGraph, options: PoastaMsaOptions) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let mut fasta = Vec::new();
+        write_msa_fasta(&mut fasta, unsafe { &*graph }, &options).unwrap();
+
+        CString::new(fasta).unwrap_or_default().into_raw()
+    })
+}
+
+/// Like poasta_get_msa_fasta, but stops writing the FASTA once it exceeds `max_bytes` (0 means no
+/// cap), computing only the rows written, so a pathological graph can't make the library allocate
+/// gigabytes. The FASTA, or as many complete lines of it as fit in `max_bytes`, is stored in
+/// `*out` (NULL on other failures), which the caller must free using free(). Returns 0, -1 if the
+/// graph or `out` is NULL, and -11 if the FASTA was truncated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_msa_fasta_capped(
+    graph: *const PoastaGraph,
+    options: PoastaMsaOptions,
+    max_bytes: usize,
+    out: *mut *mut c_char,
+) -> c_int {
+    error::catch(|| {
+        if out.is_null() {
+            return error::fail(-1, "output is NULL");
+        }
+
+        unsafe { *out = ptr::null_mut() };
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let mut fasta = CappedOutput::new(max_bytes);
+        let _ = write_msa_fasta(&mut fasta, unsafe { &*graph }, &options);
+
+        fasta.finish(out)
+    })
+}
+
+/// Writes the FASTA of poasta_get_msa_fasta to `out` row by row, stopping at the first failed
+/// write.
+fn write_msa_fasta(out: &mut impl Write, graph: &PoastaGraph, options: &PoastaMsaOptions) -> io::Result<()> {
+    let _usage = resource::track(PoastaOperation::Msa);
+    let columns = Columns::new(graph);
+    for (name, row) in named_rows(graph, &columns, 0..columns.count) {
+        let row = padded_row(row, options.pad);
+        writeln!(out, ">{name}")?;
+
+        let width = if options.line_width == 0 { row.len().max(1) } else { options.line_width };
+        for line in row.chunks(width) {
+            out.write_all(line)?;
+            out.write_all(b"\n")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The row with its end gaps filled with `pad`, unless it is 0.
+fn padded_row(mut row: Vec<u8>, pad: c_char) -> Vec<u8> {
+    if pad != 0 {
+        let first = row.iter().position(|base| *base != GAP).unwrap_or(row.len());
+        let last = row.iter().rposition(|base| *base != GAP).map_or(first, |last| last + 1);
+        row[..first].fill(pad as u8);
+        row[last..].fill(pad as u8);
+    }
+
+    row
+}
+
+/// Number of MSA columns per block of CLUSTAL output.
+const CLUSTAL_WIDTH: usize = 60;
+
+/// Returns the full-width MSA (the columns of poasta_get_msa_window) in CLUSTAL format, for tools
+/// such as Jalview or MEGA: blocks of 60 columns with a line per active sequence, its name padded
+/// to the width of the longest one, followed by a line marking the columns in which all sequences
+/// have the same base with `*`.
+/// The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_msa_clustal(graph: *const PoastaGraph) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let mut clustal = Vec::new();
+        write_msa_clustal(&mut clustal, unsafe { &*graph }).unwrap();
+
+        CString::new(clustal).unwrap_or_default().into_raw()
+    })
+}
+
+/// Writes the CLUSTAL text of poasta_get_msa_clustal to `out`, stopping at the first failed write.
+fn write_msa_clustal(out: &mut impl Write, graph: &PoastaGraph) -> io::Result<()> {
+    let _usage = resource::track(PoastaOperation::Msa);
+    let columns = Columns::new(graph);
+    let (names, rows): (Vec<String>, Vec<Vec<u8>>) = named_rows(graph, &columns, 0..columns.count).unzip();
+    let name_width = names.iter().map(String::len).max().unwrap_or(0) + 1;
+
+    writeln!(out, "CLUSTAL W multiple sequence alignment\n")?;
+    for start in (0..columns.count).step_by(CLUSTAL_WIDTH) {
+        let end = (start + CLUSTAL_WIDTH).min(columns.count);
+        writeln!(out)?;
+        for (name, row) in names.iter().zip(&rows) {
+            write!(out, "{name:name_width$}")?;
+            out.write_all(&row[start..end])?;
+            writeln!(out)?;
+        }
+
+        let conserved: String = (start..end)
+            .map(|col| {
+                let first = rows[0][col].to_ascii_uppercase();
+                let same = first != GAP && rows.iter().all(|row| row[col].to_ascii_uppercase() == first);
+                if same { '*' } else { ' ' }
+            })
+            .collect();
+        writeln!(out, "{:name_width$}{conserved}", "")?;
+    }
+
+    Ok(())
+}
+
+/// MSA symbols in a single array, see poasta_get_msa_matrix.
+#[repr(C)]
+pub struct PoastaMsaMatrix {
+    /// `num_rows * num_cols` symbols, row by row, with `-` for gaps.
+    pub symbols: *mut u8,
+    pub num_rows: usize,
+    pub num_cols: usize,
+}
+
+/// Returns the full-width MSA (the columns of poasta_get_msa_window) as a matrix with a row per
+/// active sequence, in insertion order, e.g., to hand it to numpy without parsing a string per
+/// row. The names of the rows are those of poasta_get_msa_window. The matrix is empty if the
+/// graph is NULL or has no active sequences. Caller must free it with poasta_free_msa_matrix.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_msa_matrix(graph: *const PoastaGraph) -> PoastaMsaMatrix {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaMsaMatrix { symbols: ptr::null_mut(), num_rows: 0, num_cols: 0 };
+        }
+
+        let _usage = resource::track(PoastaOperation::Msa);
+        let graph = unsafe { &*graph };
+        let columns = Columns::new(graph);
+        let rows = msa_rows(graph, &columns, 0..columns.count);
+        let num_rows = rows.len();
+
+        PoastaMsaMatrix {
+            symbols: into_c_array(rows.concat()),
+            num_rows,
+            num_cols: if num_rows > 0 { columns.count } else { 0 },
+        }
+    })
+}
+
+/// Frees a PoastaMsaMatrix.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_msa_matrix(matrix: PoastaMsaMatrix) {
+    error::catch(|| {
+        unsafe { free_c_array(matrix.symbols, matrix.num_rows * matrix.num_cols) };
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+    use std::slice;
+
+    use poasta::io::fasta::poa_graph_to_fasta;
+
+    use super::*;
+    use crate::poasta_free_msa;
+    use crate::testing::{graph_of, take};
+
+    /// Rows of poasta_get_msa.
+    fn msa_of(graph: &PoastaGraph) -> Vec<String> {
+        rows_of(unsafe { crate::poasta_get_msa(graph) })
+    }
+
+    /// Rows of a PoastaMsa, freed.
+    fn rows_of(msa: PoastaMsa) -> Vec<String> {
+        let rows = unsafe { slice::from_raw_parts(msa.sequences, msa.num_sequences) }
+            .iter()
+            .map(|row| unsafe { CStr::from_ptr(*row) }.to_str().unwrap().to_owned())
+            .collect();
+        unsafe { poasta_free_msa(msa) };
+
+        rows
+    }
+
+    /// Rows written by poasta's FASTA writer, with the lines of each record joined.
+    fn poasta_fasta_rows(graph: &PoastaGraph) -> Vec<String> {
+        let mut fasta = Vec::new();
+        poa_graph_to_fasta(&graph.graph, &mut fasta).unwrap();
+
+        String::from_utf8(fasta).unwrap()
+            .split('>')
+            .skip(1)
+            .map(|record| record.lines().skip(1).collect())
+            .collect()
+    }
+
+    #[test]
+    fn msa_matches_poasta_fasta_writer() {
+        let graphs = [
+            graph_of(&["ACGTACGTACGT", "ACGTTCGTACGT", "ACGTACGTAGT", "ACGTACGAACGTT"]),
+            graph_of(&["ACGTACGT", "CGTACGT", "GTACGTTTT", "ACGAACGT"]),
+            graph_of(&["GATTACAGATTACA", "GATTCAGATTTACA", "TTACAGATT", "GATTACAGATTACAGATTACA"]),
+        ];
+
+        for graph in &graphs {
+            assert_eq!(msa_of(graph), poasta_fasta_rows(graph));
+        }
+    }
+
+    #[test]
+    fn end_gaps_are_padded() {
+        let graph = graph_of(&["ACGTACGT", "GTAACG", "ACGTAACGT", "ACGTA"]);
+        let rows = rows_of(unsafe { poasta_get_msa_window(&graph, 0, usize::MAX) });
+        assert_eq!(rows, ["ACGTA-CGT", "--GTA-ACG", "ACGTAACGT", "ACGTA----"]);
+
+        let options = PoastaMsaOptions { pad: b'.' as c_char, line_width: 0 };
+        let padded = rows_of(unsafe { poasta_get_msa_padded(&graph, options) });
+        assert_eq!(padded, ["ACGTA-CGT", "..GTA-ACG", "ACGTAACGT", "ACGTA...."]);
+
+        let options = PoastaMsaOptions { pad: b'.' as c_char, line_width: 4 };
+        let fasta = take(unsafe { poasta_get_msa_fasta(&graph, options) });
+        assert_eq!(fasta, ">seq_0\nACGT\nA-CG\nT\n>seq_1\n..GT\nA-AC\nG\n>seq_2\nACGT\nAACG\nT\n>seq_3\nACGT\nA...\n.\n");
+    }
+
+    #[test]
+    fn clustal_marks_conserved_columns() {
+        let graph = graph_of(&["ACGTACGT", "ACGTTCGT"]);
+        let clustal = take(unsafe { poasta_get_msa_clustal(&graph) });
+
+        assert_eq!(clustal, "CLUSTAL W multiple sequence alignment\n\n\n\
+            seq_0 ACGTACGT\n\
+            seq_1 ACGTTCGT\n      \
+            **** ***\n");
+    }
+
+    #[test]
+    fn matrix_holds_the_rows_of_the_msa() {
+        let graph = graph_of(&["ACGTACGT", "ACGTTCGT", "CGTACGT"]);
+        let columns = Columns::new(&graph);
+        let matrix = unsafe { poasta_get_msa_matrix(&graph) };
+
+        assert_eq!((matrix.num_rows, matrix.num_cols), (3, columns.count));
+        let symbols = unsafe { slice::from_raw_parts(matrix.symbols, matrix.num_rows * matrix.num_cols) };
+        assert_eq!(symbols, msa_rows(&graph, &columns, 0..columns.count).concat());
+        unsafe { poasta_free_msa_matrix(matrix) };
+    }
+
+    #[test]
+    fn windows_are_slices_of_the_msa() {
+        let graph = graph_of(&["ACGTACGTACGT", "ACGTTCGTACGT", "CGTACGTAGT"]);
+        let full = rows_of(unsafe { poasta_get_msa_window(&graph, 0, usize::MAX) });
+        let width = full[0].len();
+        assert!(full.iter().all(|row| row.len() == width));
+
+        let window = rows_of(unsafe { poasta_get_msa_window(&graph, 3, 8) });
+        assert_eq!(window, full.iter().map(|row| row[3..8].to_string()).collect::<Vec<_>>());
+
+        // Ranges are clamped to the MSA width
+        let tail = rows_of(unsafe { poasta_get_msa_window(&graph, width - 2, width + 5) });
+        assert_eq!(tail, full.iter().map(|row| row[width - 2..].to_string()).collect::<Vec<_>>());
+        let past_end = rows_of(unsafe { poasta_get_msa_window(&graph, width + 1, width + 5) });
+        assert_eq!(past_end, vec![String::new(); 3]);
+    }
+}
@@ -0,0 +1,209 @@
+//! Multiple consensus paths for mixed samples, e.g., two haplotypes or a mixed amplicon, whose
+//! single consensus would average them.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::Arc;
+
+use crate::consensus::consensus_bases;
+use crate::graph::NodeIx;
+use crate::linkage::read_alleles;
+use crate::msa::{Columns, GAP};
+use crate::phase::variable_columns;
+use crate::{error, free_c_array, into_c_array, PoastaGraph};
+
+/// Fraction of the variable columns a sequence spans at which it may differ from a path while
+/// still supporting it, allowing for sequencing errors.
+const MAX_DISAGREEMENT: f64 = 0.2;
+
+/// Struct to hold one of several consensus paths of a graph.
+#[repr(C)]
+pub struct PoastaConsensusPath {
+    /// Consensus sequence along the path, NUL-terminated.
+    pub sequence: *mut c_char,
+    pub len: usize,
+
+    /// Indices (in insertion order) of the active sequences closest to this path.
+    pub reads: *mut usize,
+    pub num_reads: usize,
+}
+
+/// Struct to hold the consensus paths of a graph, most supported first.
+#[repr(C)]
+pub struct PoastaConsensusPaths {
+    pub paths: *mut PoastaConsensusPath,
+    pub num_paths: usize,
+}
+
+/// Extracts up to `max_paths` mutually divergent consensus paths, for inputs with several
+/// haplotypes or a mixed amplicon. The heaviest path of the active sequences is taken first; the
+/// sequences agreeing with it at the variable MSA columns (see poasta_phase_blocks), up to a few
+/// sequencing errors, are set aside, and the heaviest path of the remaining sequences is taken
+/// next, and so on. A path is only kept if at least `min_support` sequences agree with it and it
+/// differs from the paths before it at some variable column. Each sequence is then counted for the
+/// kept path it differs least from. Caller must free the result with poasta_free_consensus_multi.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_consensus_multi(
+    graph: *const PoastaGraph,
+    max_paths: usize,
+    min_support: usize,
+) -> PoastaConsensusPaths {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaConsensusPaths { paths: ptr::null_mut(), num_paths: 0 };
+        }
+
+        let paths: Vec<PoastaConsensusPath> = consensus_paths(unsafe { &*graph }, max_paths, min_support.max(1))
+            .into_iter()
+            .map(|(sequence, reads)| PoastaConsensusPath {
+                len: sequence.len(),
+                sequence: CString::new(sequence).unwrap().into_raw(),
+                num_reads: reads.len(),
+                reads: into_c_array(reads),
+            })
+            .collect();
+
+        let num_paths = paths.len();
+        PoastaConsensusPaths { paths: into_c_array(paths), num_paths }
+    })
+}
+
+/// Frees PoastaConsensusPaths, including their sequences and reads.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_consensus_multi(paths: PoastaConsensusPaths) {
+    error::catch(|| {
+        unsafe {
+            for path in free_c_array(paths.paths, paths.num_paths) {
+                drop(CString::from_raw(path.sequence));
+                free_c_array(path.reads, path.num_reads);
+            }
+        }
+    })
+}
+
+/// Consensus sequence and closest sequences of each path.
+fn consensus_paths(graph: &PoastaGraph, max_paths: usize, min_support: usize) -> Vec<(Vec<u8>, Vec<usize>)> {
+    let columns = Columns::new(graph);
+    let alleles = read_alleles(graph, &columns, &(0..columns.count).collect::<Vec<_>>());
+    let variable = variable_columns(graph, &columns, &alleles);
+    let indices: Vec<usize> = graph.sequences.iter()
+        .enumerate()
+        .filter(|(_, record)| !record.info.masked)
+        .map(|(index, _)| index)
+        .collect();
+
+    // Number of variable columns a sequence spans and differs from the path's alleles at
+    let compare = |read: usize, path: &[Option<u8>]| {
+        let spanned: Vec<usize> = variable.iter().copied().filter(|col| alleles[read][*col].is_some()).collect();
+        let differing = spanned.iter().filter(|col| alleles[read][**col] != path[**col]).count();
+        (differing, spanned.len())
+    };
+    let disagreement = |read: usize, path: &[Option<u8>]| {
+        let (differing, spanned) = compare(read, path);
+        if spanned == 0 { 0.0 } else { differing as f64 / spanned as f64 }
+    };
+
+    let mut paths: Vec<(Vec<u8>, Vec<Option<u8>>)> = Vec::new();
+    let mut remaining: Vec<usize> = (0..indices.len()).collect();
+    let mut working = graph.shallow_clone();
+    while paths.len() < max_paths && remaining.len() >= min_support {
+        for (read, index) in indices.iter().enumerate() {
+            Arc::make_mut(&mut working.sequences)[*index].info.masked = !remaining.contains(&read);
+        }
+
+        let bases = consensus_bases(&working);
+        let path_alleles = path_alleles(&columns, &bases, &variable, graph);
+        let (supporting, rest): (Vec<usize>, Vec<usize>) = remaining.iter()
+            .partition(|read| disagreement(**read, &path_alleles) <= MAX_DISAGREEMENT);
+
+        let divergent = paths.iter()
+            .all(|(_, other)| variable.iter().any(|col| other[*col] != path_alleles[*col]));
+        if supporting.len() < min_support || !divergent {
+            break;
+        }
+
+        paths.push((bases.into_iter().map(|(_, base)| base).collect(), path_alleles));
+        remaining = rest;
+    }
+
+    // Count each sequence for the path it differs least from, the first on ties
+    let mut reads: Vec<Vec<usize>> = vec![Vec::new(); paths.len()];
+    for (read, index) in indices.iter().enumerate() {
+        let closest = paths.iter()
+            .enumerate()
+            .min_by(|(_, (_, a)), (_, (_, b))| disagreement(read, a).total_cmp(&disagreement(read, b)))
+            .map(|(i, _)| i);
+
+        if let Some(closest) = closest {
+            reads[closest].push(*index);
+        }
+    }
+
+    let mut result: Vec<(Vec<u8>, Vec<usize>)> = paths.into_iter()
+        .map(|(sequence, _)| sequence)
+        .zip(reads)
+        .collect();
+    result.sort_by_key(|(_, reads)| std::cmp::Reverse(reads.len()));
+
+    result
+}
+
+/// The allele of a path in each column (indexed by column): its base where it has a node in the
+/// column, a gap elsewhere within the path and None outside it. Only the variable columns are set.
+fn path_alleles(columns: &Columns, bases: &[(NodeIx, u8)], variable: &[usize], graph: &PoastaGraph) -> Vec<Option<u8>> {
+    let mut alleles = vec![None; columns.count];
+    let (Some((first, _)), Some((last, _))) = (bases.first(), bases.last()) else {
+        return alleles;
+    };
+
+    let (first, last) = (columns.of_node[first], columns.of_node[last]);
+    for col in variable.iter().filter(|col| (first..=last).contains(*col)) {
+        alleles[*col] = Some(GAP);
+    }
+
+    for (node, _) in bases {
+        let col = columns.of_node[node];
+        if alleles[col].is_some() {
+            alleles[col] = Some(graph.graph.get_symbol(*node).to_ascii_uppercase());
+        }
+    }
+
+    alleles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::graph_of;
+
+    // Two haplotypes, differing at two columns, with an error in one read of the first
+    const READS: [&str; 5] = ["ACGTTGCAGGATCC", "ACCTTGCAGTATCC", "ACGTTGCAGGATCC", "ACCTTGCAGTATCC", "ACGTTGCAGGATCA"];
+
+    #[test]
+    fn haplotypes_get_a_path_each() {
+        let graph = graph_of(&READS);
+        let paths: Vec<_> = consensus_paths(&graph, 4, 2).into_iter()
+            .map(|(sequence, reads)| (String::from_utf8(sequence).unwrap(), reads))
+            .collect();
+
+        assert_eq!(paths, [
+            ("ACGTTGCAGGATCC".to_string(), vec![0, 2, 4]),
+            ("ACCTTGCAGTATCC".to_string(), vec![1, 3]),
+        ]);
+        assert_eq!(consensus_paths(&graph, 1, 2).len(), 1);
+        assert_eq!(consensus_paths(&graph, 4, 3).len(), 1);
+    }
+
+    #[test]
+    fn paths_are_returned_over_the_ffi() {
+        let graph = graph_of(&READS[..4]);
+        let paths = unsafe { poasta_get_consensus_multi(&graph, 2, 0) };
+        assert_eq!(paths.num_paths, 2);
+
+        let path = unsafe { &*paths.paths.add(1) };
+        assert_eq!(unsafe { std::ffi::CStr::from_ptr(path.sequence) }, c"ACCTTGCAGTATCC");
+        assert_eq!(unsafe { std::slice::from_raw_parts(path.reads, path.num_reads) }, [1, 3]);
+        unsafe { poasta_free_consensus_multi(paths) };
+    }
+}
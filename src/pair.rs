@@ -0,0 +1,159 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use crate::pairwise::{align_affine, PairColumn};
+use crate::scoring::checked_cost;
+use crate::{error, free_c_array, into_c_array};
+
+/// Configuration of poasta_pair_consensus.
+#[repr(C)]
+pub struct PoastaPairConfig {
+    pub mismatch_score: i32,
+    pub gap_extend: i32,
+    pub gap_open: i32,
+}
+
+/// Struct to hold the merged consensus of two sequences.
+#[repr(C)]
+pub struct PoastaPairConsensus {
+    pub sequence: *mut c_char,
+    pub len: usize,
+
+    /// Consensus positions where the two sequences disagree, in increasing order. A base only
+    /// present in the second sequence is reported at the position of the following consensus base
+    /// (or at `len` if it is at the end).
+    pub disagreements: *mut usize,
+    pub num_disagreements: usize,
+}
+
+impl PoastaPairConsensus {
+    fn empty() -> Self {
+        PoastaPairConsensus { sequence: ptr::null_mut(), len: 0, disagreements: ptr::null_mut(), num_disagreements: 0 }
+    }
+}
+
+/// Merges exactly two sequences (e.g., the template and complement strand of a duplex read, or
+/// the two reads of a pair) by aligning them to each other, without building a graph.
+/// Where the two sequences disagree, the consensus follows the first sequence: mismatches take
+/// its base, bases only present in the first sequence are kept, and bases only present in the
+/// second sequence are dropped. Each disagreement is reported. The consensus is empty if a
+/// sequence is NULL or a cost is negative or above 255.
+/// Caller must free the result with poasta_free_pair_consensus.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_pair_consensus(
+    seq1: *const c_char,
+    len1: usize,
+    seq2: *const c_char,
+    len2: usize,
+    config: PoastaPairConfig,
+) -> PoastaPairConsensus {
+    error::catch(|| {
+        if seq1.is_null() || seq2.is_null() {
+            return PoastaPairConsensus::empty();
+        }
+        let costs = (
+            checked_cost("mismatch_score", config.mismatch_score),
+            checked_cost("gap_extend", config.gap_extend),
+            checked_cost("gap_open", config.gap_open),
+        );
+        let (Ok(mismatch), Ok(gap_extend), Ok(gap_open)) = costs else {
+            return PoastaPairConsensus::empty();
+        };
+
+        let seq1 = unsafe { slice::from_raw_parts(seq1 as *const u8, len1) };
+        let seq2 = unsafe { slice::from_raw_parts(seq2 as *const u8, len2) };
+        let columns = align_affine(
+            seq1,
+            seq2,
+            mismatch as usize,
+            gap_open as usize,
+            gap_extend as usize,
+        );
+
+        let mut consensus = Vec::with_capacity(len1);
+        let mut disagreements = Vec::new();
+        for column in columns {
+            let disagree = match column {
+                PairColumn::Aligned(i, j) => {
+                    consensus.push(seq1[i]);
+                    seq1[i] != seq2[j]
+                },
+                PairColumn::OnlyFirst(i) => {
+                    consensus.push(seq1[i]);
+                    true
+                },
+                PairColumn::OnlySecond(_) => {
+                    // Reported at the next consensus position
+                    if disagreements.last() != Some(&consensus.len()) {
+                        disagreements.push(consensus.len());
+                    }
+                    false
+                },
+            };
+
+            if disagree && disagreements.last() != Some(&(consensus.len() - 1)) {
+                disagreements.push(consensus.len() - 1);
+            }
+        }
+
+        PoastaPairConsensus {
+            len: consensus.len(),
+            sequence: CString::new(consensus).unwrap().into_raw(),
+            num_disagreements: disagreements.len(),
+            disagreements: into_c_array(disagreements),
+        }
+    })
+}
+
+/// Frees a PoastaPairConsensus.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_pair_consensus(consensus: PoastaPairConsensus) {
+    error::catch(|| {
+        if !consensus.sequence.is_null() {
+            drop(unsafe { CString::from_raw(consensus.sequence) });
+        }
+
+        unsafe {
+            free_c_array(consensus.disagreements, consensus.num_disagreements);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::array;
+
+    const CONFIG: PoastaPairConfig = PoastaPairConfig { mismatch_score: 4, gap_extend: 2, gap_open: 6 };
+
+    fn merge(seq1: &str, seq2: &str, config: PoastaPairConfig) -> Option<(String, Vec<usize>)> {
+        let consensus = unsafe { poasta_pair_consensus(seq1.as_ptr().cast(), seq1.len(), seq2.as_ptr().cast(), seq2.len(), config) };
+        if consensus.sequence.is_null() {
+            return None;
+        }
+
+        let sequence = unsafe { slice::from_raw_parts(consensus.sequence as *const u8, consensus.len) };
+        let disagreements = array(consensus.disagreements, consensus.num_disagreements);
+        let merged = (String::from_utf8(sequence.to_vec()).unwrap(), disagreements.to_vec());
+        unsafe { poasta_free_pair_consensus(consensus) };
+
+        Some(merged)
+    }
+
+    #[test]
+    fn the_first_sequence_wins_disagreements() {
+        // A substitution, a base missing from the second sequence and a base only in it
+        let seq1 = "ACGTTGCAGGATCCTTAGCA";
+        let seq2 = "ACGTTCCAGGTCCTTAGTCA";
+        assert_eq!(merge(seq1, seq2, CONFIG), Some((seq1.to_string(), vec![5, 10, 18])));
+        assert_eq!(merge(seq1, seq1, CONFIG), Some((seq1.to_string(), vec![])));
+    }
+
+    #[test]
+    fn out_of_range_costs_give_an_empty_consensus() {
+        let config = PoastaPairConfig { gap_open: 256, ..CONFIG };
+        assert_eq!(merge("ACGT", "ACGT", config), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Plain sequence-to-sequence comparisons used by the graph-level helpers.
+
+/// Unit-cost edit (Levenshtein) distance between two sequences.
+pub(crate) fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+/// Fraction of identical positions, defined as `1 - edit_distance / max(len)`.
+pub(crate) fn identity(a: &[u8], b: &[u8]) -> f64 {
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 1.0;
+    }
+
+    1.0 - edit_distance(a, b) as f64 / max_len as f64
+}
+
+/// Best occurrence of `pattern` in `text` under unit edit costs (semi-global alignment: the
+/// pattern must align end-to-end, the text has free ends).
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Occurrence {
+    pub start: usize,
+    /// End position in the text (exclusive).
+    pub end: usize,
+    pub distance: usize,
+}
+
+/// Finds the leftmost best occurrence of `pattern` in `text`.
+pub(crate) fn locate(pattern: &[u8], text: &[u8]) -> Occurrence {
+    last_row(pattern, text)
+        .into_iter()
+        .min_by_key(|occurrence| occurrence.distance)
+        .unwrap()
+}
+
+/// Finds non-overlapping occurrences of `pattern` in `text` with at most `max_distance` edits,
+/// from left to right. Of overlapping candidates, the best one is kept.
+pub(crate) fn locate_all(pattern: &[u8], text: &[u8], max_distance: usize) -> Vec<Occurrence> {
+    let row = last_row(pattern, text);
+    let mut occurrences = Vec::new();
+    let mut last_end = 0;
+    let mut end = 0;
+
+    while end < row.len() {
+        if row[end].distance > max_distance || row[end].start < last_end || row[end].start == row[end].end {
+            end += 1;
+            continue;
+        }
+
+        // Competing candidates end within one pattern length
+        let best = row[end..row.len().min(end + pattern.len() + 1)].iter()
+            .filter(|occurrence| occurrence.start >= last_end)
+            .min_by_key(|occurrence| occurrence.distance)
+            .copied()
+            .unwrap();
+
+        occurrences.push(best);
+        last_end = best.end;
+        end = best.end + 1;
+    }
+
+    occurrences
+}
+
+/// The best occurrence of `pattern` ending at each position of `text`.
+fn last_row(pattern: &[u8], text: &[u8]) -> Vec<Occurrence> {
+    // Each cell holds (distance, start position in text)
+    let mut prev: Vec<(usize, usize)> = (0..=text.len()).map(|j| (0, j)).collect();
+    let mut curr = vec![(0, 0); text.len() + 1];
+
+    for (i, cp) in pattern.iter().enumerate() {
+        curr[0] = (i + 1, 0);
+        for (j, ct) in text.iter().enumerate() {
+            let substitution = (prev[j].0 + usize::from(cp != ct), prev[j].1);
+            let deletion = (prev[j + 1].0 + 1, prev[j + 1].1);
+            let insertion = (curr[j].0 + 1, curr[j].1);
+
+            curr[j + 1] = substitution.min(deletion).min(insertion);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev.into_iter()
+        .enumerate()
+        .map(|(end, (distance, start))| Occurrence { start, end, distance })
+        .collect()
+}
+
+/// A column of a pairwise alignment, holding positions in the first and/or second sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PairColumn {
+    Aligned(usize, usize),
+    OnlyFirst(usize),
+    OnlySecond(usize),
+}
+
+/// Global alignment of two sequences with affine gap costs (a gap of length `l` costs
+/// `gap_open + l * gap_extend`), minimizing the total cost.
+pub(crate) fn align_affine(a: &[u8], b: &[u8], mismatch: usize, gap_open: usize, gap_extend: usize) -> Vec<PairColumn> {
+    const INF: usize = usize::MAX / 4;
+    let width = b.len() + 1;
+    let cell = |i: usize, j: usize| i * width + j;
+
+    // Best cost ending in a match/mismatch (m), a gap in b (x: a only) and a gap in a (y: b only)
+    let size = (a.len() + 1) * width;
+    let (mut m, mut x, mut y) = (vec![INF; size], vec![INF; size], vec![INF; size]);
+    m[0] = 0;
+
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            if i > 0 && j > 0 {
+                let prev = cell(i - 1, j - 1);
+                let cost = if a[i - 1] == b[j - 1] { 0 } else { mismatch };
+                m[cell(i, j)] = m[prev].min(x[prev]).min(y[prev]) + cost;
+            }
+            if i > 0 {
+                let prev = cell(i - 1, j);
+                x[cell(i, j)] = (m[prev].min(y[prev]) + gap_open).min(x[prev]) + gap_extend;
+            }
+            if j > 0 {
+                let prev = cell(i, j - 1);
+                y[cell(i, j)] = (m[prev].min(x[prev]) + gap_open).min(y[prev]) + gap_extend;
+            }
+        }
+    }
+
+    // 0: m, 1: x, 2: y
+    let best_state = |c: usize| [m[c], x[c], y[c]].iter().enumerate().min_by_key(|(_, v)| **v).unwrap().0;
+    let (mut i, mut j) = (a.len(), b.len());
+    let mut state = best_state(cell(i, j));
+    let mut columns = Vec::with_capacity(a.len().max(b.len()));
+
+    while i > 0 || j > 0 {
+        let c = cell(i, j);
+        match state {
+            0 => {
+                columns.push(PairColumn::Aligned(i - 1, j - 1));
+                i -= 1;
+                j -= 1;
+                state = best_state(cell(i, j));
+            },
+            1 => {
+                columns.push(PairColumn::OnlyFirst(i - 1));
+                i -= 1;
+                let prev = cell(i, j);
+                state = if x[c] == x[prev] + gap_extend { 1 } else if x[c] == m[prev] + gap_open + gap_extend { 0 } else { 2 };
+            },
+            _ => {
+                columns.push(PairColumn::OnlySecond(j - 1));
+                j -= 1;
+                let prev = cell(i, j);
+                state = if y[c] == y[prev] + gap_extend { 2 } else if y[c] == m[prev] + gap_open + gap_extend { 0 } else { 1 };
+            },
+        }
+    }
+
+    columns.reverse();
+    columns
+}
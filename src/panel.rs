@@ -0,0 +1,276 @@
+//! Panels of small reference graphs that reads are typed against, e.g., HLA alleles or 16S
+//! sequences.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+use std::thread;
+
+use crate::alphabet::PoastaAlphabet;
+use crate::dp::DpBuffers;
+use crate::mode::PoastaAlignmentMode;
+use crate::{error, is_valid_name, PoastaGraph, Scoring, SequenceInfo};
+
+/// Opaque set of reference graphs, one per reference, which can't be changed once created.
+pub struct PoastaPanel {
+    graphs: Vec<PoastaGraph>,
+    names: Vec<CString>,
+}
+
+/// How reads are aligned to the references of a panel.
+#[repr(C)]
+pub struct PoastaPanelConfig {
+    pub mismatch_score: i32,
+    pub gap_extend: i32,
+    pub gap_open: i32,
+
+    /// Mode reads are aligned in, e.g., with free graph ends for reads covering only part of the
+    /// references.
+    pub mode: PoastaAlignmentMode,
+
+    /// Smallest margin between the best and second best score for a hit to be unambiguous. Ties
+    /// are always ambiguous.
+    pub min_margin: u32,
+}
+
+/// Struct to hold the reference a read aligns to best.
+#[repr(C)]
+pub struct PoastaPanelHit {
+    /// Index of the reference in the panel, or -1 if there is no hit.
+    pub index: i64,
+
+    /// Name of the reference, owned by the panel (NULL if there is no hit).
+    pub name: *const c_char,
+
+    /// Cost of the read's alignment to the reference.
+    pub score: i64,
+
+    /// Index of the reference with the second lowest cost, or -1 if the panel has a single
+    /// reference.
+    pub second_index: i64,
+
+    /// Cost of the second best alignment minus `score` (0 if there is no second best).
+    pub margin: i64,
+
+    /// Whether the margin is below the configured minimum, so the read can't be assigned to a
+    /// reference with confidence.
+    pub ambiguous: bool,
+}
+
+impl PoastaPanelHit {
+    /// No hit, for reads that can't be typed.
+    pub(crate) fn none() -> Self {
+        PoastaPanelHit { index: -1, name: ptr::null(), score: 0, second_index: -1, margin: 0, ambiguous: false }
+    }
+}
+
+/// Creates a panel of `n` references from the NUL-terminated sequences in `seqs`, each in a graph
+/// of its own, for typing reads against the references with poasta_panel_best_hit. `names` gives
+/// a NUL-terminated name for each reference, or is NULL to call them `seq_N`.
+/// Returns NULL if a sequence is NULL or empty, or a name is NULL, empty or contains whitespace.
+/// Free the panel with poasta_panel_free.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_panel_create(
+    seqs: *const *const c_char,
+    names: *const *const c_char,
+    n: usize,
+) -> *mut PoastaPanel {
+    error::catch(|| {
+        if seqs.is_null() && n > 0 {
+            error::fail(-1, "sequences are NULL");
+            return ptr::null_mut();
+        }
+
+        let seqs = if n > 0 { unsafe { slice::from_raw_parts(seqs, n) } } else { &[] };
+        let names = (!names.is_null() && n > 0).then(|| unsafe { slice::from_raw_parts(names, n) });
+
+        let mut panel = PoastaPanel { graphs: Vec::with_capacity(n), names: Vec::with_capacity(n) };
+        for (i, seq) in seqs.iter().enumerate() {
+            let seq = if seq.is_null() { &[] } else { unsafe { CStr::from_ptr(*seq) }.to_bytes() };
+            if seq.is_empty() {
+                error::fail(-1, "reference sequence is NULL or empty");
+                return ptr::null_mut();
+            }
+
+            let name = match names {
+                Some(names) if names[i].is_null() => None,
+                Some(names) => unsafe { CStr::from_ptr(names[i]) }.to_str().ok()
+                    .filter(|name| is_valid_name(name))
+                    .map(str::to_string),
+                None => Some(format!("seq_{i}")),
+            };
+            let Some(name) = name else {
+                error::fail(-1, "reference name is NULL, empty or contains whitespace");
+                return ptr::null_mut();
+            };
+
+            let mut graph = PoastaGraph::new();
+            let info = SequenceInfo { name: Some(name.clone()), ..SequenceInfo::default() };
+            let scoring = PoastaAlphabet::detect(seq).default_scoring();
+            if graph.add_sequence(seq, &vec![1; seq.len()], scoring, info) != 0 {
+                error::fail(-2, "couldn't build the reference graph");
+                return ptr::null_mut();
+            }
+
+            panel.names.push(CString::new(name).unwrap());
+            panel.graphs.push(graph);
+        }
+
+        Box::into_raw(Box::new(panel))
+    })
+}
+
+/// Frees a panel.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_panel_free(panel: *mut PoastaPanel) {
+    error::catch(|| {
+        if !panel.is_null() {
+            unsafe {
+                drop(Box::from_raw(panel));
+            }
+        }
+    })
+}
+
+/// Aligns a read to every reference of the panel and returns the one with the lowest alignment
+/// cost, the first of them on ties, which types the read against the panel in a single call. The
+/// hit also gives the runner-up and how much worse it is, and is flagged as ambiguous if the
+/// margin is below `config.min_margin`, so unresolvable reads can be set aside instead of being
+/// assigned anyway. The references are split over `n_threads` threads (0 means one per available
+/// core). The panel isn't changed. Returns no hit (index -1) if the panel or read is NULL, the
+/// read is empty, a cost is negative or above 255 or the panel has no references.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_panel_best_hit(
+    panel: *const PoastaPanel,
+    read: *const c_char,
+    len: usize,
+    config: PoastaPanelConfig,
+    n_threads: usize,
+) -> PoastaPanelHit {
+    error::catch(|| {
+        if panel.is_null() || read.is_null() || len == 0 {
+            return PoastaPanelHit::none();
+        }
+
+        let Ok(scoring) = Scoring::affine(config.mismatch_score, config.gap_extend, config.gap_open) else {
+            return PoastaPanelHit::none();
+        };
+
+        let panel = unsafe { &*panel };
+        let read = unsafe { slice::from_raw_parts(read as *const u8, len) };
+        let scores = panel_scores(panel, read, &scoring, config.mode, n_threads);
+
+        let mut ranked: Vec<(i64, usize)> = scores.into_iter().zip(0..).collect();
+        ranked.sort_unstable();
+
+        let Some((score, index)) = ranked.first().copied() else {
+            return PoastaPanelHit::none();
+        };
+
+        let second = ranked.get(1).copied();
+        let margin = second.map_or(0, |(second_score, _)| second_score - score);
+        PoastaPanelHit {
+            index: index as i64,
+            name: panel.names[index].as_ptr(),
+            score,
+            second_index: second.map_or(-1, |(_, second_index)| second_index as i64),
+            margin,
+            ambiguous: second.is_some() && (margin == 0 || margin < config.min_margin.into()),
+        }
+    })
+}
+
+/// Cost of the read's alignment to each reference of the panel.
+fn panel_scores(panel: &PoastaPanel, read: &[u8], scoring: &Scoring, mode: PoastaAlignmentMode, n_threads: usize) -> Vec<i64> {
+    let mode = mode.normalized();
+
+    let n_threads = match n_threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let chunk = panel.graphs.len().div_ceil(n_threads).max(1);
+
+    let align_chunk = |graphs: &[PoastaGraph]| -> Vec<i64> {
+        let mut buffers = DpBuffers::default();
+        graphs.iter()
+            .map(|graph| graph.align_scored(read, scoring, mode, &mut buffers).1)
+            .collect()
+    };
+
+    if n_threads == 1 || panel.graphs.len() <= 1 {
+        return align_chunk(&panel.graphs);
+    }
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = panel.graphs.chunks(chunk)
+            .map(|graphs| scope.spawn(move || align_chunk(graphs)))
+            .collect();
+
+        workers.into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFERENCES: [&CStr; 3] = [c"ACGTTGCAGGATCC", c"ACCTTGCAGTATCC", c"ACGTTGCAGGATAC"];
+
+    fn panel() -> *mut PoastaPanel {
+        let seqs = REFERENCES.map(CStr::as_ptr);
+        let names = [c"a", c"b", c"c"].map(CStr::as_ptr);
+        let panel = unsafe { poasta_panel_create(seqs.as_ptr(), names.as_ptr(), seqs.len()) };
+        assert!(!panel.is_null());
+
+        panel
+    }
+
+    fn config(min_margin: u32) -> PoastaPanelConfig {
+        PoastaPanelConfig { mismatch_score: 4, gap_extend: 2, gap_open: 6, mode: PoastaAlignmentMode::default(), min_margin }
+    }
+
+    fn best_hit(panel: *const PoastaPanel, read: &str, min_margin: u32, n_threads: usize) -> PoastaPanelHit {
+        unsafe { poasta_panel_best_hit(panel, read.as_ptr().cast(), read.len(), config(min_margin), n_threads) }
+    }
+
+    #[test]
+    fn reads_are_typed_against_the_closest_reference() {
+        let panel = panel();
+        for n_threads in [1, 2, 0] {
+            let hit = best_hit(panel, "ACCTTGCAGTATCA", 0, n_threads);
+            assert_eq!((hit.index, hit.score), (1, 4));
+            assert_eq!(unsafe { CStr::from_ptr(hit.name) }, c"b");
+        }
+
+        assert_eq!(best_hit(panel, "", 0, 1).index, -1);
+        assert_eq!(unsafe { poasta_panel_best_hit(panel, c"ACGT".as_ptr(), 4, PoastaPanelConfig { mismatch_score: -1, ..config(0) }, 1) }.index, -1);
+        unsafe { poasta_panel_free(panel) };
+
+        let seqs = [c"ACGT".as_ptr(), c"".as_ptr()];
+        assert!(unsafe { poasta_panel_create(seqs.as_ptr(), ptr::null(), 2) }.is_null());
+    }
+
+    #[test]
+    fn hits_within_the_margin_are_ambiguous() {
+        let panel = panel();
+        let hit = best_hit(panel, "ACGTTGCAGGATCC", 4, 1);
+        assert_eq!((hit.index, hit.score, hit.second_index, hit.margin, hit.ambiguous), (0, 0, 2, 4, false));
+        assert!(best_hit(panel, "ACGTTGCAGGATCC", 5, 1).ambiguous);
+
+        // Ties are ambiguous whatever the minimum, and go to the first reference
+        let hit = best_hit(panel, "ACGTTGCAGGATGC", 0, 1);
+        assert_eq!((hit.index, hit.second_index, hit.margin, hit.ambiguous), (0, 2, 0, true));
+        unsafe { poasta_panel_free(panel) };
+
+        // A single reference has no runner-up
+        let seqs = [REFERENCES[0].as_ptr()];
+        let panel = unsafe { poasta_panel_create(seqs.as_ptr(), ptr::null(), 1) };
+        let hit = best_hit(panel, "ACGTTGCAGGATGC", 10, 1);
+        assert_eq!((hit.index, hit.second_index, hit.margin, hit.ambiguous), (0, -1, 0, false));
+        assert_eq!(unsafe { CStr::from_ptr(hit.name) }, c"seq_0");
+        unsafe { poasta_panel_free(panel) };
+    }
+}
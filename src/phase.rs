@@ -0,0 +1,218 @@
+//! Phase blocks of linked variants, for basic phasing of amplicons without external tools.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::graph::NodeIx;
+use crate::linkage::{by_frequency, read_alleles};
+use crate::msa::Columns;
+use crate::{error, free_c_array, into_c_array, PoastaGraph};
+
+/// Struct to hold a haplotype of a phase block.
+#[repr(C)]
+pub struct PoastaHaplotype {
+    /// Allele at each column of the block (a base, or `-` for a deletion), NUL-terminated.
+    pub alleles: *mut c_char,
+
+    /// Number of sequences with this haplotype.
+    pub num_reads: usize,
+}
+
+/// Struct to hold a block of variable MSA columns phased together.
+#[repr(C)]
+pub struct PoastaPhaseBlock {
+    /// The variable columns of the block, in order.
+    pub columns: *mut usize,
+    pub num_columns: usize,
+
+    /// Haplotypes of the sequences spanning the whole block, most supported first.
+    pub haplotypes: *mut PoastaHaplotype,
+    pub num_haplotypes: usize,
+}
+
+/// Struct to hold the phase blocks of a graph.
+#[repr(C)]
+pub struct PoastaPhaseBlocks {
+    pub blocks: *mut PoastaPhaseBlock,
+    pub num_blocks: usize,
+}
+
+/// Groups the variable MSA columns into phase blocks, from the alleles of the active sequences (see
+/// poasta_column_linkage). A column is variable if at least two of its alleles are each seen in at
+/// least two sequences; masked regions are left out. Consecutive variable columns are in the same
+/// block while the linkage (r² of their most frequent alleles) between them is at least
+/// `min_linkage`. Each block lists its haplotypes with the number of sequences supporting them.
+/// Caller must free the result with poasta_free_phase_blocks.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_phase_blocks(graph: *const PoastaGraph, min_linkage: f64) -> PoastaPhaseBlocks {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaPhaseBlocks { blocks: ptr::null_mut(), num_blocks: 0 };
+        }
+
+        let blocks: Vec<PoastaPhaseBlock> = phase_blocks(unsafe { &*graph }, min_linkage)
+            .into_iter()
+            .map(|Block { columns, haplotypes }| {
+                let haplotypes: Vec<PoastaHaplotype> = haplotypes.into_iter()
+                    .map(|(alleles, num_reads)| PoastaHaplotype {
+                        alleles: CString::new(alleles).unwrap().into_raw(),
+                        num_reads,
+                    })
+                    .collect();
+
+                PoastaPhaseBlock {
+                    num_columns: columns.len(),
+                    columns: into_c_array(columns),
+                    num_haplotypes: haplotypes.len(),
+                    haplotypes: into_c_array(haplotypes),
+                }
+            })
+            .collect();
+
+        let num_blocks = blocks.len();
+        PoastaPhaseBlocks { blocks: into_c_array(blocks), num_blocks }
+    })
+}
+
+/// Frees PoastaPhaseBlocks, including their blocks and haplotypes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_phase_blocks(blocks: PoastaPhaseBlocks) {
+    error::catch(|| {
+        unsafe {
+            for block in free_c_array(blocks.blocks, blocks.num_blocks) {
+                free_c_array(block.columns, block.num_columns);
+                for haplotype in free_c_array(block.haplotypes, block.num_haplotypes) {
+                    drop(CString::from_raw(haplotype.alleles));
+                }
+            }
+        }
+    })
+}
+
+struct Block {
+    columns: Vec<usize>,
+
+    /// Alleles and number of sequences of each haplotype.
+    haplotypes: Vec<(Vec<u8>, usize)>,
+}
+
+fn phase_blocks(graph: &PoastaGraph, min_linkage: f64) -> Vec<Block> {
+    let columns = Columns::new(graph);
+    let alleles = read_alleles(graph, &columns, &(0..columns.count).collect::<Vec<_>>());
+    let variable = variable_columns(graph, &columns, &alleles);
+
+    let mut blocks: Vec<Vec<usize>> = Vec::new();
+    for (i, col) in variable.iter().enumerate() {
+        match blocks.last_mut() {
+            Some(block) if r_squared(&alleles, variable[i - 1], *col) >= min_linkage => block.push(*col),
+            _ => blocks.push(vec![*col]),
+        }
+    }
+
+    blocks.into_iter()
+        .map(|columns| {
+            let mut counts: HashMap<Vec<u8>, usize> = HashMap::new();
+            for read in &alleles {
+                if let Some(haplotype) = columns.iter().map(|col| read[*col]).collect::<Option<Vec<u8>>>() {
+                    *counts.entry(haplotype).or_default() += 1;
+                }
+            }
+
+            let mut haplotypes: Vec<(Vec<u8>, usize)> = counts.into_iter().collect();
+            haplotypes.sort_by(|(a, count_a), (b, count_b)| count_b.cmp(count_a).then_with(|| a.cmp(b)));
+
+            Block { columns, haplotypes }
+        })
+        .collect()
+}
+
+/// The unmasked columns where at least two alleles are each seen in at least two sequences, given
+/// the alleles of the sequences in all columns (see `read_alleles`).
+pub(crate) fn variable_columns(graph: &PoastaGraph, columns: &Columns, alleles: &[Vec<Option<u8>>]) -> Vec<usize> {
+    let masked = graph.masked_regions.as_ref().map(|regions| regions.masked_nodes(graph));
+    let is_masked = |node: NodeIx| masked.as_ref().is_some_and(|masked| masked[node.index()]);
+
+    let mut masked_columns = vec![false; columns.count];
+    for (node, col) in &columns.of_node {
+        masked_columns[*col] |= is_masked(*node);
+    }
+
+    (0..columns.count)
+        .filter(|col| !masked_columns[*col] && is_variable(alleles.iter().filter_map(|read| read[*col])))
+        .collect()
+}
+
+/// Whether at least two alleles are each seen at least twice.
+fn is_variable(alleles: impl Iterator<Item = u8>) -> bool {
+    let mut counts = [0usize; 256];
+    for allele in alleles {
+        counts[allele as usize] += 1;
+    }
+
+    counts.iter().filter(|count| **count >= 2).count() >= 2
+}
+
+/// Squared correlation between carrying the most frequent allele of column `a` and of column `b`,
+/// over the sequences spanning both. 0 if either column has a single allele among them.
+fn r_squared(alleles: &[Vec<Option<u8>>], a: usize, b: usize) -> f64 {
+    let pairs: Vec<(u8, u8)> = alleles.iter()
+        .filter_map(|read| Some((read[a]?, read[b]?)))
+        .collect();
+
+    let (Some(major_a), Some(major_b)) = (
+        by_frequency(pairs.iter().map(|(allele, _)| *allele)).first().copied(),
+        by_frequency(pairs.iter().map(|(_, allele)| *allele)).first().copied(),
+    ) else {
+        return 0.0;
+    };
+
+    let n = pairs.len() as f64;
+    let p_a = pairs.iter().filter(|(allele, _)| *allele == major_a).count() as f64 / n;
+    let p_b = pairs.iter().filter(|(_, allele)| *allele == major_b).count() as f64 / n;
+    let p_ab = pairs.iter().filter(|pair| **pair == (major_a, major_b)).count() as f64 / n;
+
+    let variance = p_a * (1.0 - p_a) * p_b * (1.0 - p_b);
+    if variance <= 0.0 {
+        return 0.0;
+    }
+
+    let d = p_ab - p_a * p_b;
+    d * d / variance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::graph_of;
+
+    #[test]
+    fn linked_variants_are_phased_together() {
+        // Columns 2 and 9 are linked (G..G and C..T), column 12 isn't linked to them
+        let graph = graph_of(&["ACGTTGCAGGATCC", "ACGTTGCAGGATAC", "ACGTTGCAGGATAC", "ACCTTGCAGTATCC", "ACCTTGCAGTATAC"]);
+        let blocks: Vec<_> = phase_blocks(&graph, 0.5).into_iter()
+            .map(|block| (block.columns, block.haplotypes))
+            .collect();
+
+        assert_eq!(blocks, [
+            (vec![2, 9], vec![(b"GG".to_vec(), 3), (b"CT".to_vec(), 2)]),
+            (vec![12], vec![(b"A".to_vec(), 3), (b"C".to_vec(), 2)]),
+        ]);
+        assert_eq!(phase_blocks(&graph, 0.0).len(), 1);
+    }
+
+    #[test]
+    fn blocks_are_returned_over_the_ffi() {
+        let graph = graph_of(&["ACGTTGCAGGATCC", "ACGTTGCAGGATCC", "ACCTTGCAGTATCC", "ACCTTGCAGTATCC"]);
+        let blocks = unsafe { poasta_phase_blocks(&graph, 0.5) };
+        assert_eq!(blocks.num_blocks, 1);
+
+        let block = unsafe { &*blocks.blocks };
+        assert_eq!(unsafe { std::slice::from_raw_parts(block.columns, block.num_columns) }, [2, 9]);
+        let haplotype = unsafe { &*block.haplotypes };
+        assert_eq!(unsafe { std::ffi::CStr::from_ptr(haplotype.alleles) }, c"CT");
+        assert_eq!((block.num_haplotypes, haplotype.num_reads), (2, 2));
+        unsafe { poasta_free_phase_blocks(blocks) };
+    }
+}
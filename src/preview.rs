@@ -0,0 +1,103 @@
+//! Reduced copies of large graphs for visualization, which keep the heavily supported structure
+//! and leave out low-weight detail.
+
+use std::collections::{HashMap, HashSet};
+use std::os::raw::c_int;
+use std::ptr;
+
+use crate::graph::{NodeIx, SequenceRecord};
+use crate::{error, PoastaGraph};
+
+/// Returns a copy of the graph with at most `max_nodes` nodes, for rendering or exporting (e.g.,
+/// with poasta_get_gfa) graphs too large to draw. The nodes with the highest weight (summed over
+/// the sequences passing through them) are kept; of the nodes tied at the cutoff weight, evenly
+/// spaced ones are. Each sequence keeps the bases in kept nodes, which are connected in its order,
+/// so rare variants and errors collapse into the structure around them. Sequences without kept
+/// bases are left out, and so are masked regions; links and the backbone are kept where their
+/// nodes and sequence are. The graph itself is left untouched, so analysis can continue on the
+/// full structure. Returns NULL if the graph is NULL or `max_nodes` is 0. Must be freed with
+/// poasta_free_graph.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_preview(graph: *const PoastaGraph, max_nodes: usize) -> *mut PoastaGraph {
+    error::catch(|| {
+        if graph.is_null() || max_nodes == 0 {
+            error::fail(-1, "graph is NULL or max_nodes is 0");
+            return ptr::null_mut();
+        }
+
+        match preview(unsafe { &*graph }, max_nodes) {
+            Ok(preview) => Box::into_raw(Box::new(preview)),
+            Err(status) => {
+                error::fail(status, "the reduced sequences can't be added to the preview");
+                ptr::null_mut()
+            },
+        }
+    })
+}
+
+fn preview(graph: &PoastaGraph, max_nodes: usize) -> Result<PoastaGraph, c_int> {
+    let kept = kept_nodes(graph, max_nodes);
+
+    // The bases of each sequence in kept nodes, and where each sequence ended up
+    let mut records = Vec::new();
+    let mut new_index = HashMap::new();
+    for (ix, record) in graph.sequences.iter().enumerate() {
+        let bases: Vec<usize> = (0..record.path.len())
+            .filter(|pos| kept.contains(&record.path[*pos]))
+            .collect();
+        if bases.is_empty() {
+            continue;
+        }
+
+        new_index.insert(ix, records.len());
+        records.push(SequenceRecord {
+            seq: bases.iter().map(|pos| record.seq[*pos]).collect(),
+            weights: bases.iter().map(|pos| record.weights[*pos]).collect(),
+            path: bases.iter().map(|pos| record.path[*pos]).collect(),
+            ..record.clone()
+        });
+    }
+
+    let mut preview = graph.empty_like();
+    let mut node_map = HashMap::new();
+    preview.append_records(&records, |node| graph.graph.get_aligned_nodes(node), &mut node_map)?;
+
+    preview.links = graph.links.iter()
+        .filter_map(|(from, to)| Some((*node_map.get(from)?, *node_map.get(to)?)))
+        .collect();
+    preview.backbone = graph.backbone.and_then(|backbone| new_index.get(&backbone).copied());
+
+    Ok(preview)
+}
+
+/// The `max_nodes` nodes with the highest weight.
+fn kept_nodes(graph: &PoastaGraph, max_nodes: usize) -> HashSet<NodeIx> {
+    let mut weights: HashMap<NodeIx, usize> = HashMap::new();
+    for record in graph.sequences.iter() {
+        for (node, weight) in record.path.iter().zip(&record.weights) {
+            *weights.entry(*node).or_default() += weight;
+        }
+    }
+
+    if weights.len() <= max_nodes {
+        return weights.into_keys().collect();
+    }
+
+    let mut by_weight: Vec<usize> = weights.values().copied().collect();
+    by_weight.sort_unstable_by(|a, b| b.cmp(a));
+    let cutoff = by_weight[max_nodes - 1];
+
+    let mut tied: Vec<NodeIx> = weights.iter()
+        .filter(|(_, weight)| **weight == cutoff)
+        .map(|(node, _)| *node)
+        .collect();
+    tied.sort_unstable();
+
+    let slots = max_nodes - by_weight.iter().take_while(|weight| **weight > cutoff).count();
+    let spaced: Vec<NodeIx> = (0..slots).map(|ix| tied[ix * tied.len() / slots]).collect();
+
+    weights.into_iter()
+        .filter(|(node, weight)| *weight > cutoff || spaced.binary_search(node).is_ok())
+        .map(|(node, _)| node)
+        .collect()
+}
@@ -0,0 +1,285 @@
+use std::borrow::Cow;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::slice;
+
+use serde::{Deserialize, Serialize};
+
+use crate::alphabet::PoastaAlphabet;
+use crate::consensus::{consensus_bases, node_support, PoastaConsensus};
+use crate::error;
+use crate::pairwise::{locate, Occurrence};
+use crate::PoastaGraph;
+
+/// Number of bases beyond the primer length searched for a primer at either end of a read.
+const PRIMER_SEARCH_SLACK: usize = 100;
+
+/// Configures primers that are located, trimmed and used to orient every sequence added to the
+/// graph from now on. `fwd` is expected at the start of the amplicon, and the reverse complement
+/// of `rev` at its end. Sequences that match the primers better when reverse complemented are
+/// reverse complemented before alignment. A primer is only trimmed if it is found with at most
+/// `max_mismatch` edits. Pass two empty primers to disable trimming. Returns -1 if the graph's
+/// alphabet isn't DNA or RNA (see poasta_graph_set_alphabet).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_set_primers(
+    graph: *mut PoastaGraph,
+    fwd: *const c_char,
+    fwd_len: usize,
+    rev: *const c_char,
+    rev_len: usize,
+    max_mismatch: u8,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || (fwd.is_null() && fwd_len > 0) || (rev.is_null() && rev_len > 0) {
+            return error::fail(-1, "graph is NULL, or a primer is NULL and its length isn't 0");
+        }
+
+        let fwd = if fwd_len > 0 { unsafe { slice::from_raw_parts(fwd as *const u8, fwd_len) } } else { &[] };
+        let rev = if rev_len > 0 { unsafe { slice::from_raw_parts(rev as *const u8, rev_len) } } else { &[] };
+
+        let graph = unsafe { &mut *graph };
+        let alphabet = graph.alphabet();
+        if (!fwd.is_empty() || !rev.is_empty()) && alphabet != PoastaAlphabet::Auto && !alphabet.is_nucleotide() {
+            return error::fail(-1, &format!("primers can't be reverse complemented in a {} graph", alphabet.name()));
+        }
+
+        graph.primers = (!fwd.is_empty() || !rev.is_empty()).then(|| Primers {
+            fwd: fwd.to_vec(),
+            rev_rc: reverse_complement(rev, PoastaAlphabet::Dna),
+            max_mismatch: max_mismatch as usize,
+        });
+
+        0
+    })
+}
+
+/// Returns the consensus (see poasta_get_consensus) between the forward primer `fwd` and the
+/// reverse complement of the reverse primer `rev`, both left out, so consensus sequences of
+/// amplicons start and end at the same place however ragged the ends of their reads are. The
+/// forward primer is searched in the whole consensus and the reverse one after it, each found if
+/// it matches with at most `max_mismatch` edits; an empty or NULL primer leaves that end of the
+/// consensus as is. Returns an empty consensus with last error -1 if the graph is NULL, its
+/// alphabet isn't DNA or RNA, or a primer isn't found.
+/// Caller must free the result with poasta_free_consensus.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_consensus_between(
+    graph: *const PoastaGraph,
+    fwd: *const c_char,
+    rev: *const c_char,
+    max_mismatch: u8,
+) -> PoastaConsensus {
+    error::catch(|| {
+        if graph.is_null() {
+            error::fail(-1, "graph is NULL");
+            return PoastaConsensus::empty();
+        }
+
+        let primer = |seq: *const c_char| if seq.is_null() { &[][..] } else { unsafe { CStr::from_ptr(seq) }.to_bytes() };
+        let (fwd, rev) = (primer(fwd), primer(rev));
+
+        let graph = unsafe { &*graph };
+        let alphabet = graph.alphabet();
+        if (!fwd.is_empty() || !rev.is_empty()) && alphabet != PoastaAlphabet::Auto && !alphabet.is_nucleotide() {
+            error::fail(-1, &format!("primers can't be reverse complemented in a {} graph", alphabet.name()));
+            return PoastaConsensus::empty();
+        }
+
+        let primers = Primers {
+            fwd: fwd.to_ascii_uppercase(),
+            rev_rc: reverse_complement(&rev.to_ascii_uppercase(), PoastaAlphabet::Dna),
+            max_mismatch: max_mismatch as usize,
+        };
+        let primers = primers.spelled_for(alphabet);
+
+        let bases = consensus_bases(graph);
+        let consensus: Vec<u8> = bases.iter().map(|(_, base)| base.to_ascii_uppercase()).collect();
+
+        let mut start = 0;
+        if !primers.fwd.is_empty() {
+            let hit = locate(&primers.fwd, &consensus);
+            if hit.distance > primers.max_mismatch {
+                error::fail(-1, "the forward primer isn't in the consensus");
+                return PoastaConsensus::empty();
+            }
+            start = hit.end;
+        }
+
+        let mut end = consensus.len();
+        if !primers.rev_rc.is_empty() {
+            let hit = locate(&primers.rev_rc, &consensus[start..]);
+            if hit.distance > primers.max_mismatch {
+                error::fail(-1, "the reverse primer isn't in the consensus after the forward one");
+                return PoastaConsensus::empty();
+            }
+            end = start + hit.start;
+        }
+
+        let node_support = node_support(graph);
+        let (support, sequence) = bases[start..end].iter()
+            .map(|(node, base)| (node_support[node.index()], *base))
+            .unzip();
+
+        PoastaConsensus::new(sequence, support, graph.active_sequences().count())
+    })
+}
+
+/// Amplicon primers, stored in the orientation they appear in on the forward strand, with the
+/// reverse primer complemented as DNA.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Primers {
+    pub fwd: Vec<u8>,
+    pub rev_rc: Vec<u8>,
+    pub max_mismatch: usize,
+}
+
+impl Primers {
+    /// Orients `seq` to the forward strand and trims the primers off. Returns the trimmed
+    /// sequence with its weights. Sequences of a graph with a non-nucleotide alphabet are left as is.
+    pub(crate) fn apply(&self, seq: &[u8], weights: &[usize], alphabet: PoastaAlphabet) -> (Vec<u8>, Vec<usize>) {
+        if alphabet != PoastaAlphabet::Auto && !alphabet.is_nucleotide() {
+            return (seq.to_vec(), weights.to_vec());
+        }
+
+        let primers = self.spelled_for(alphabet);
+        let (fwd_hit, rev_hit) = primers.find(seq);
+        let rc = reverse_complement(seq, alphabet);
+        let (rc_fwd_hit, rc_rev_hit) = primers.find(&rc);
+
+        let (seq, weights, fwd_hit, rev_hit) = if total_distance(rc_fwd_hit, rc_rev_hit) < total_distance(fwd_hit, rev_hit) {
+            (rc, weights.iter().rev().copied().collect(), rc_fwd_hit, rc_rev_hit)
+        } else {
+            (seq.to_vec(), weights.to_vec(), fwd_hit, rev_hit)
+        };
+
+        let start = fwd_hit.filter(|hit| hit.distance <= primers.max_mismatch).map_or(0, |hit| hit.end);
+        let end = rev_hit.filter(|hit| hit.distance <= primers.max_mismatch).map_or(seq.len(), |hit| hit.start);
+
+        // Primers overlapping each other (or covering the whole read) are left in place
+        if start >= end {
+            return (seq, weights);
+        }
+
+        (seq[start..end].to_vec(), weights[start..end].to_vec())
+    }
+
+    /// The primers with U instead of T for RNA, like the reads they are searched in.
+    fn spelled_for(&self, alphabet: PoastaAlphabet) -> Cow<'_, Primers> {
+        if alphabet != PoastaAlphabet::Rna {
+            return Cow::Borrowed(self);
+        }
+
+        let to_rna = |seq: &[u8]| seq.iter().map(|base| match base { b'T' => b'U', b't' => b'u', _ => *base }).collect();
+        Cow::Owned(Primers { fwd: to_rna(&self.fwd), rev_rc: to_rna(&self.rev_rc), max_mismatch: self.max_mismatch })
+    }
+
+    /// Best hits of the forward primer at the start and of the reverse primer at the end of `seq`.
+    fn find(&self, seq: &[u8]) -> (Option<Occurrence>, Option<Occurrence>) {
+        let fwd_hit = (!self.fwd.is_empty()).then(|| {
+            let window = &seq[..seq.len().min(self.fwd.len() + PRIMER_SEARCH_SLACK)];
+            locate(&self.fwd, window)
+        });
+
+        let rev_hit = (!self.rev_rc.is_empty()).then(|| {
+            let offset = seq.len().saturating_sub(self.rev_rc.len() + PRIMER_SEARCH_SLACK);
+            let hit = locate(&self.rev_rc, &seq[offset..]);
+            Occurrence { start: hit.start + offset, end: hit.end + offset, distance: hit.distance }
+        });
+
+        (fwd_hit, rev_hit)
+    }
+}
+
+fn total_distance(fwd_hit: Option<Occurrence>, rev_hit: Option<Occurrence>) -> usize {
+    fwd_hit.map_or(0, |hit| hit.distance) + rev_hit.map_or(0, |hit| hit.distance)
+}
+
+/// Reverse complement of a nucleotide sequence; IUPAC codes are complemented and case is kept.
+/// Adenine is complemented to U in RNA and to T otherwise.
+pub(crate) fn reverse_complement(seq: &[u8], alphabet: PoastaAlphabet) -> Vec<u8> {
+    let thymine = if alphabet == PoastaAlphabet::Rna { b'U' } else { b'T' };
+    seq.iter()
+        .rev()
+        .map(|&base| {
+            let complement = match base.to_ascii_uppercase() {
+                b'A' => thymine,
+                b'T' | b'U' => b'A',
+                b'C' => b'G',
+                b'G' => b'C',
+                b'R' => b'Y',
+                b'Y' => b'R',
+                b'K' => b'M',
+                b'M' => b'K',
+                b'B' => b'V',
+                b'V' => b'B',
+                b'D' => b'H',
+                b'H' => b'D',
+                other => other,
+            };
+
+            if base.is_ascii_lowercase() { complement.to_ascii_lowercase() } else { complement }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::poasta_free_consensus;
+    use crate::testing::{add, array, graph_of};
+
+    const FWD: &str = "ACGTACGG";
+    const REV: &str = "TTGCCAAG";
+    const INSERT: &str = "GATTACAGATTTACAGCA";
+
+    /// The amplicon of INSERT, with the primers in place.
+    fn amplicon() -> String {
+        let rev_rc = String::from_utf8(reverse_complement(REV.as_bytes(), PoastaAlphabet::Dna)).unwrap();
+        format!("{FWD}{INSERT}{rev_rc}")
+    }
+
+    #[test]
+    fn reads_are_oriented_and_trimmed() {
+        let mut graph = PoastaGraph::new();
+        let status = unsafe { poasta_set_primers(&mut graph, FWD.as_ptr().cast(), FWD.len(), REV.as_ptr().cast(), REV.len(), 1) };
+        assert_eq!(status, 0);
+
+        let reverse = String::from_utf8(reverse_complement(amplicon().as_bytes(), PoastaAlphabet::Dna)).unwrap();
+        let with_error = amplicon().replacen("ACGTACGG", "ACGTTCGG", 1);
+        for read in [format!("TT{}G", amplicon()), reverse, with_error] {
+            add(&mut graph, &read);
+        }
+        assert!(graph.sequences.iter().all(|record| record.seq == INSERT.as_bytes()));
+
+        // Primers with more errors than allowed stay on the read
+        let too_many = amplicon().replacen("ACGTACGG", "ACCTTCGG", 1);
+        add(&mut graph, &too_many);
+        assert_eq!(graph.sequences[3].seq.len(), too_many.len() - REV.len());
+    }
+
+    fn consensus_between(graph: &PoastaGraph, fwd: &CStr, rev: &CStr, max_mismatch: u8) -> (String, Vec<usize>) {
+        let result = unsafe { poasta_get_consensus_between(graph, fwd.as_ptr(), rev.as_ptr(), max_mismatch) };
+        let sequence = if result.sequence.is_null() { String::new() } else { unsafe { CStr::from_ptr(result.sequence) }.to_str().unwrap().to_string() };
+        let support = array(result.support, result.len).to_vec();
+        unsafe { poasta_free_consensus(result) };
+
+        (sequence, support)
+    }
+
+    #[test]
+    fn consensus_is_cut_between_the_primers() {
+        // The reads have ragged ends around the amplicon, and one has an error in the reverse primer
+        let amplicon = amplicon();
+        let with_error = amplicon.replacen("CTTGGCAA", "CTTGCCAA", 1);
+        let graph = graph_of(&[&format!("TT{amplicon}G"), &amplicon[2..], &with_error]);
+
+        let (sequence, support) = consensus_between(&graph, c"ACGTACGG", c"TTGCCAAG", 0);
+        assert_eq!(sequence, INSERT);
+        assert_eq!(support, [3; INSERT.len()]);
+
+        // Only one end is cut without the other primer, leaving the overhang of the first read
+        let after_fwd = format!("{}G", &amplicon[FWD.len()..]);
+        assert_eq!(consensus_between(&graph, c"ACGTACGG", c"", 0).0, after_fwd);
+        assert_eq!(consensus_between(&graph, c"ACGTTCGG", c"", 1).0, after_fwd);
+        assert_eq!(consensus_between(&graph, c"ACGTTCGG", c"TTGCCAAG", 0).0, "");
+    }
+}
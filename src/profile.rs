@@ -0,0 +1,198 @@
+use std::ptr;
+
+use crate::consensus::heaviest_path;
+use crate::msa::{Columns, GAP};
+use crate::{error, free_c_array, into_c_array, PoastaGraph};
+
+/// Struct to hold the differences of sequences to the consensus.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct PoastaErrorCounts {
+    /// Consensus bases covered by the sequences.
+    pub bases: usize,
+    pub substitutions: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+
+    /// Substitutions, insertions and deletions per covered consensus base.
+    pub substitution_rate: f64,
+    pub insertion_rate: f64,
+    pub deletion_rate: f64,
+}
+
+/// Struct to hold the sequencing errors observed in a graph.
+#[repr(C)]
+pub struct PoastaObservedErrors {
+    pub total: PoastaErrorCounts,
+
+    /// Errors by consensus base: A, C, G, T and any other symbol. Insertions count towards the
+    /// consensus base before them.
+    pub by_base: [PoastaErrorCounts; 5],
+
+    /// Errors of each sequence in insertion order, if requested (NULL otherwise).
+    pub by_read: *mut PoastaErrorCounts,
+    pub num_reads: usize,
+}
+
+/// Compares every sequence in the graph (including inactive ones) to the consensus along the
+/// alignment it was added with, and counts substitutions, insertions and deletions, in total, by
+/// consensus base and, if `per_read` is set, for each sequence. This gives a sequencing error QC
+/// report for free. Sequence ends extending past the consensus are ignored.
+/// Caller must free the result with poasta_free_observed_errors.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_error_profile(graph: *const PoastaGraph, per_read: bool) -> PoastaObservedErrors {
+    error::catch(|| {
+        let mut profile = PoastaObservedErrors {
+            total: PoastaErrorCounts::default(),
+            by_base: [PoastaErrorCounts::default(); 5],
+            by_read: ptr::null_mut(),
+            num_reads: 0,
+        };
+        if graph.is_null() {
+            return profile;
+        }
+
+        let graph = unsafe { &*graph };
+        let columns = Columns::new(graph);
+        let mut consensus_row = vec![GAP; columns.count];
+        for node in heaviest_path(graph) {
+            consensus_row[columns.of_node[&node]] = graph.graph.get_symbol(node).to_ascii_uppercase();
+        }
+
+        let mut by_read = Vec::new();
+        for record in graph.sequences.iter() {
+            let mut read = PoastaErrorCounts::default();
+            let mut row = vec![GAP; columns.count];
+            for (node, base) in record.path.iter().zip(&record.seq) {
+                row[columns.of_node[node]] = base.to_ascii_uppercase();
+            }
+
+            // Overhangs past either end of the consensus aren't errors
+            let covered = |col: &usize| row[*col] != GAP && consensus_row[*col] != GAP;
+            let (Some(first), Some(last)) = ((0..columns.count).find(covered), (0..columns.count).rfind(covered)) else {
+                by_read.push(read);
+                continue;
+            };
+
+            let mut context = 0;
+            for col in first..=last {
+                let (base, consensus_base) = (row[col], consensus_row[col]);
+                if consensus_base != GAP {
+                    context = base_index(consensus_base);
+                }
+
+                let by_base = &mut profile.by_base[context];
+                match (base, consensus_base) {
+                    (GAP, GAP) => (),
+                    (GAP, _) => {
+                        read.bases += 1;
+                        read.deletions += 1;
+                        by_base.bases += 1;
+                        by_base.deletions += 1;
+                    },
+                    (_, GAP) => {
+                        read.insertions += 1;
+                        by_base.insertions += 1;
+                    },
+                    (base, consensus_base) => {
+                        read.bases += 1;
+                        by_base.bases += 1;
+                        if base != consensus_base {
+                            read.substitutions += 1;
+                            by_base.substitutions += 1;
+                        }
+                    },
+                }
+            }
+
+            add_counts(&mut profile.total, &read);
+            by_read.push(read);
+        }
+
+        for counts in profile.by_base.iter_mut().chain([&mut profile.total]).chain(&mut by_read) {
+            set_rates(counts);
+        }
+
+        if per_read {
+            profile.num_reads = by_read.len();
+            profile.by_read = into_c_array(by_read);
+        }
+
+        profile
+    })
+}
+
+/// Frees a PoastaObservedErrors.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_observed_errors(profile: PoastaObservedErrors) {
+    error::catch(|| {
+        unsafe { free_c_array(profile.by_read, profile.num_reads) };
+    })
+}
+
+fn base_index(base: u8) -> usize {
+    match base {
+        b'A' => 0,
+        b'C' => 1,
+        b'G' => 2,
+        b'T' => 3,
+        _ => 4,
+    }
+}
+
+fn add_counts(total: &mut PoastaErrorCounts, counts: &PoastaErrorCounts) {
+    total.bases += counts.bases;
+    total.substitutions += counts.substitutions;
+    total.insertions += counts.insertions;
+    total.deletions += counts.deletions;
+}
+
+fn set_rates(counts: &mut PoastaErrorCounts) {
+    let bases = counts.bases.max(1) as f64;
+    counts.substitution_rate = counts.substitutions as f64 / bases;
+    counts.insertion_rate = counts.insertions as f64 / bases;
+    counts.deletion_rate = counts.deletions as f64 / bases;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::graph_of;
+
+    const REFERENCE: &str = "ACGTTGCAGGATCCTTAGCA";
+
+    fn counts(counts: &PoastaErrorCounts) -> (usize, usize, usize, usize) {
+        (counts.bases, counts.substitutions, counts.insertions, counts.deletions)
+    }
+
+    #[test]
+    fn errors_are_counted_by_read_and_consensus_base() {
+        let reads = [
+            REFERENCE,
+            REFERENCE,
+            REFERENCE,
+            "ACGTTCCAGGATCCTTAGCA",
+            "ACGTTGCAGGTCCTTAGCA",
+            "ACGTTGCAGGATGCCTTAGCA",
+            "GTTGCAGGATCCTTAGCA",
+        ];
+        let graph = graph_of(&reads);
+        let profile = unsafe { poasta_error_profile(&graph, true) };
+        let by_read: Vec<_> = unsafe { std::slice::from_raw_parts(profile.by_read, profile.num_reads) }.iter().map(counts).collect();
+
+        // The consensus start the last read doesn't cover isn't deleted in it
+        assert_eq!(by_read, [(20, 0, 0, 0), (20, 0, 0, 0), (20, 0, 0, 0), (20, 1, 0, 0), (20, 0, 0, 1), (20, 0, 1, 0), (18, 0, 0, 0)]);
+        assert_eq!(counts(&profile.total), (138, 1, 1, 1));
+        assert_eq!(profile.total.substitution_rate, 1.0 / 138.0);
+
+        // The substituted G, the deleted A, and the G inserted after a T
+        assert_eq!(counts(&profile.by_base[2]).1, 1);
+        assert_eq!(counts(&profile.by_base[0]).3, 1);
+        assert_eq!(counts(&profile.by_base[3]).2, 1);
+        unsafe { poasta_free_observed_errors(profile) };
+
+        let profile = unsafe { poasta_error_profile(&graph, false) };
+        assert!(profile.by_read.is_null());
+        unsafe { poasta_free_observed_errors(profile) };
+    }
+}
@@ -0,0 +1,73 @@
+//! When and in which batch each sequence was added, so streaming runs can be audited.
+
+use std::os::raw::c_int;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error;
+use crate::PoastaGraph;
+
+/// Struct to hold when and in which batch a sequence was added to the graph.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoastaProvenance {
+    /// Number of sequences added to the graph before this one.
+    pub insertion: u64,
+
+    /// Milliseconds since the Unix epoch, 0 if unknown.
+    pub timestamp_ms: u64,
+
+    /// Batch set with poasta_set_batch_id when the sequence was added, 0 for none.
+    pub batch_id: u64,
+}
+
+impl PoastaProvenance {
+    /// Provenance of a sequence added now as the graph's next sequence.
+    pub(crate) fn now(graph: &PoastaGraph) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+
+        PoastaProvenance { insertion: graph.insertions, timestamp_ms, batch_id: graph.batch_id }
+    }
+}
+
+/// Tags the sequences added from now on with `batch_id` (0 for none, the default), e.g., the ID
+/// of the chunk of a stream they came from. Returns -1 if the graph is NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_set_batch_id(graph: *mut PoastaGraph, batch_id: u64) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        unsafe { (*graph).batch_id = batch_id };
+
+        0
+    })
+}
+
+/// Stores when and in which batch the sequence at `index` (in insertion order) was added in
+/// `*provenance`. Sequences copied from other graphs keep their provenance.
+/// Returns -1 if the graph or `provenance` is NULL or the index is out of range.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_sequence_provenance(
+    graph: *const PoastaGraph,
+    index: usize,
+    provenance: *mut PoastaProvenance,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || provenance.is_null() {
+            return error::fail(-1, "graph or provenance is NULL");
+        }
+
+        let Some(record) = unsafe { &*graph }.sequences.get(index) else {
+            return error::fail(-1, "sequence index out of range");
+        };
+
+        unsafe { *provenance = record.info.provenance.unwrap_or_default() };
+
+        0
+    })
+}
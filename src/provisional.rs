@@ -0,0 +1,156 @@
+//! Minimum evidence thresholds: nodes and edges are provisional until enough sequences support
+//! them, and hidden from the consensus and GFA export until then.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use crate::consensus::selected_node_support;
+use crate::dot::{graph_dot, DotStyle};
+use crate::error;
+use crate::gfa::layered_gfa;
+use crate::graph::{NodeIx, Selection};
+use crate::PoastaGraph;
+
+/// Sets the number of active sequences a node or edge needs to pass through it before it shows up
+/// in the consensus and GFA export (1 by default, 0 behaves like 1). Provisional elements stay in
+/// the graph, so new sequences still align to them and confirm them, which hides singleton
+/// sequencing errors without destructive pruning.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_set_min_support(graph: *mut PoastaGraph, min_support: usize) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let graph = unsafe { &mut *graph };
+        graph.min_support = min_support.max(1);
+        graph.touch();
+
+        0
+    })
+}
+
+/// How exports treat provisional nodes and edges.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoastaProvisional {
+    /// Leave them out, like poasta_get_gfa does.
+    #[default]
+    Exclude = 0,
+    /// Export them like confirmed elements.
+    Include = 1,
+    /// Export them, marked as provisional.
+    Mark = 2,
+}
+
+/// Returns the graph as GFA, with provisional nodes and edges excluded, included, or included and
+/// marked with a `pv:i:1` tag: non-branching runs of nodes as segments, and a walk per sequence
+/// with its offsets in the first and last segment, which poasta_graph_from_gfa reads back.
+/// Segments are never partly provisional when marking.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_gfa_layers(graph: *const PoastaGraph, provisional: PoastaProvisional) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let gfa = layered_gfa(unsafe { &*graph }, provisional);
+        CString::new(gfa).unwrap_or_default().into_raw()
+    })
+}
+
+/// Returns the graph in Graphviz DOT format, one node per base, with provisional nodes and edges
+/// excluded, included, or included and drawn in gray with dashed edges.
+/// Returns NULL if the graph is NULL. The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_dot_layers(graph: *const PoastaGraph, provisional: PoastaProvisional) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let dot = graph_dot(unsafe { &*graph }, provisional, DotStyle::default());
+        CString::new(dot).unwrap_or_default().into_raw()
+    })
+}
+
+/// Support of every node and edge, compared against the graph's minimum.
+pub(crate) struct Evidence {
+    nodes: Vec<usize>,
+    edges: HashMap<(NodeIx, NodeIx), usize>,
+    min_support: usize,
+}
+
+impl Evidence {
+    pub(crate) fn new(graph: &PoastaGraph) -> Self {
+        Evidence::selected(graph, Selection::default())
+    }
+
+    /// Support counting the sequences `selection` includes.
+    pub(crate) fn selected(graph: &PoastaGraph, selection: Selection) -> Self {
+        let mut edges = HashMap::new();
+        for pair in graph.selected_sequences(selection).flat_map(|record| record.path.windows(2)) {
+            *edges.entry((pair[0], pair[1])).or_default() += 1;
+        }
+
+        Evidence { nodes: selected_node_support(graph, selection), edges, min_support: graph.min_support }
+    }
+
+    pub(crate) fn is_confirmed_node(&self, node: NodeIx) -> bool {
+        self.nodes[node.index()] >= self.min_support
+    }
+
+    pub(crate) fn is_confirmed_edge(&self, from: NodeIx, to: NodeIx) -> bool {
+        self.edges.get(&(from, to)).copied().unwrap_or(0) >= self.min_support
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poasta_get_gfa;
+    use crate::testing::{add, consensus, graph_of, take};
+
+    #[test]
+    fn unsupported_nodes_are_hidden_until_confirmed() {
+        let mut graph = graph_of(&["ACGTA", "ACGTATTT"]);
+        assert_eq!(consensus(&graph), "ACGTATTT");
+
+        assert_eq!(unsafe { poasta_set_min_support(&mut graph, 2) }, 0);
+        assert_eq!(consensus(&graph), "ACGTA");
+        let gfa = take(unsafe { poasta_get_gfa(&graph) });
+        assert_eq!(gfa.lines().filter(|line| line.starts_with('S')).collect::<Vec<_>>(), ["S\ts0\tACGTA"]);
+
+        // A second read confirms the tail
+        add(&mut graph, "ACGTATTT");
+        assert_eq!(consensus(&graph), "ACGTATTT");
+    }
+
+    #[test]
+    fn exports_exclude_include_or_mark_provisional_elements() {
+        let mut graph = graph_of(&["ACGTA", "ACGTA", "ACCTA", "ACGTAT"]);
+        unsafe { poasta_set_min_support(&mut graph, 2) };
+        let gfa = |provisional| take(unsafe { poasta_get_gfa_layers(&graph, provisional) });
+        let dot = |provisional| take(unsafe { poasta_get_dot_layers(&graph, provisional) });
+        let lines = |text: &str, kind: char| text.lines().filter(|line| line.starts_with(kind)).count();
+
+        // Walks through provisional segments are left out with them
+        let excluded = gfa(PoastaProvisional::Exclude);
+        assert_eq!((lines(&excluded, 'S'), lines(&excluded, 'L'), lines(&excluded, 'W')), (1, 0, 2));
+        assert_eq!(dot(PoastaProvisional::Exclude).matches("[label=").count(), 5);
+
+        let included = gfa(PoastaProvisional::Include);
+        assert_eq!((lines(&included, 'S'), lines(&included, 'L'), lines(&included, 'W')), (4, 4, 4));
+        assert!(!included.contains("pv:i:1"));
+        assert_eq!(dot(PoastaProvisional::Include).matches("[label=").count(), 7);
+
+        // Marking splits the confirmed TA from the provisional T
+        let marked = gfa(PoastaProvisional::Mark);
+        assert!(marked.contains("S\ts2\tC\tpv:i:1\nS\ts3\tTA\nS\ts4\tT\tpv:i:1\n"));
+        assert_eq!(marked.matches("pv:i:1").count(), 5);
+        assert_eq!(dot(PoastaProvisional::Mark).matches("style=dashed").count(), 3);
+    }
+}
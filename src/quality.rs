@@ -0,0 +1,107 @@
+//! Base weights from sequencing qualities, e.g., for reads from FASTQ files.
+
+use std::os::raw::{c_char, c_int};
+use std::slice;
+
+
+use crate::{add_sequence_weighted, error, metrics, PoastaGraph, Scoring, SequenceInfo};
+
+/// Offset of Phred+33 quality characters.
+const PHRED_OFFSET: u8 = b'!';
+
+/// How Phred qualities are turned into base weights.
+#[repr(C)]
+pub struct PoastaQualityWeights {
+    /// Weight per quality point, so a base gets `round(quality * scale)`. 0 means 1, i.e., the
+    /// Phred score itself.
+    pub scale: f64,
+
+    /// Qualities above this are treated as this, since basecallers tend to overstate high
+    /// qualities. 0 means no cap.
+    pub max_quality: u8,
+
+    /// Lowest weight of a base. A base with weight 0 doesn't count towards the consensus.
+    pub min_weight: u32,
+}
+
+impl PoastaQualityWeights {
+    /// Weight of a base with the given Phred+33 quality character, or None if it isn't one.
+    fn weight(&self, qual: u8) -> Option<usize> {
+        let quality = qual.checked_sub(PHRED_OFFSET)?;
+        let quality = if self.max_quality > 0 { quality.min(self.max_quality) } else { quality };
+        let scale = if self.scale > 0.0 { self.scale } else { 1.0 };
+
+        Some(((quality as f64 * scale).round() as usize).max(self.min_weight as usize))
+    }
+}
+
+/// Adds a sequence to the graph (Global alignment) with base weights from its Phred+33 qualities
+/// (`qual`, `len` characters as in FASTQ files), scaled as configured in `weights`.
+/// Returns -1 if `qual` is NULL or contains characters below `!`, and the status codes of
+/// poasta_add_sequence otherwise.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_add_sequence_with_quals(
+    graph: *mut PoastaGraph,
+    seq: *const c_char,
+    qual: *const c_char,
+    len: usize,
+    weights: PoastaQualityWeights,
+    mismatch_score: i32,
+    gap_extend: i32,
+    gap_open: i32,
+) -> c_int {
+    error::catch(|| {
+        if qual.is_null() {
+            metrics::record_status(-1);
+            return error::fail(-1, "qualities are NULL");
+        }
+
+        let qual = unsafe { slice::from_raw_parts(qual as *const u8, len) };
+        let Some(base_weights) = qual.iter().map(|q| weights.weight(*q)).collect::<Option<Vec<usize>>>() else {
+            metrics::record_status(-1);
+            return error::fail(-1, "qualities contain characters below '!'");
+        };
+
+        let scoring = match Scoring::affine(mismatch_score, gap_extend, gap_open) {
+            Ok(scoring) => scoring,
+            Err(status) => return metrics::record_status(status),
+        };
+        unsafe { add_sequence_weighted(graph, seq, &base_weights, scoring, SequenceInfo::default()) }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poasta_add_sequence_with_weights;
+    use crate::testing::{graph_of, outputs};
+
+    #[test]
+    fn qualities_are_scaled_and_capped() {
+        let weights = PoastaQualityWeights { scale: 0.0, max_quality: 0, min_weight: 0 };
+        assert_eq!([b'!', b'+', b'I'].map(|qual| weights.weight(qual)), [Some(0), Some(10), Some(40)]);
+
+        let weights = PoastaQualityWeights { scale: 0.5, max_quality: 30, min_weight: 1 };
+        assert_eq!([b'!', b'+', b'I'].map(|qual| weights.weight(qual)), [Some(1), Some(5), Some(15)]);
+        assert_eq!(weights.weight(b' '), None);
+    }
+
+    #[test]
+    fn sequences_are_added_with_their_quality_weights() {
+        let (read, qual) = ("ACGTA", "+5?+!");
+        let weights = PoastaQualityWeights { scale: 0.0, max_quality: 0, min_weight: 1 };
+        let mut graph = graph_of(&["ACCTA", "ACCTA"]);
+        let status = unsafe { poasta_add_sequence_with_quals(&mut graph, read.as_ptr().cast(), qual.as_ptr().cast(), read.len(), weights, 4, 2, 6) };
+        assert_eq!(status, 0);
+
+        let mut weighted = graph_of(&["ACCTA", "ACCTA"]);
+        let status = unsafe { poasta_add_sequence_with_weights(&mut weighted, read.as_ptr().cast(), read.len(), [10, 20, 30, 10, 1].as_ptr(), 4, 2, 6) };
+        assert_eq!(status, 0);
+        assert_eq!(outputs(&graph), outputs(&weighted));
+        assert_eq!(outputs(&graph).0, "ACGTA");
+
+        let weights = PoastaQualityWeights { scale: 0.0, max_quality: 0, min_weight: 0 };
+        let status = unsafe { poasta_add_sequence_with_quals(&mut graph, read.as_ptr().cast(), c"++ ++".as_ptr(), read.len(), weights, 4, 2, 6) };
+        assert_eq!(status, -1);
+    }
+}
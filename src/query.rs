@@ -0,0 +1,345 @@
+//! Read-only alignment of queries to a graph, e.g., to score candidate reads before deciding which
+//! to add.
+
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use poasta::aligner::Alignment;
+
+use crate::dp::DpBuffers;
+use crate::graph::NodeIx;
+use crate::mode::PoastaAlignmentMode;
+use crate::{error, free_c_array, into_c_array, PoastaGraph, Scoring};
+
+/// How sequences are aligned by poasta_align_query and poasta_add_sequences.
+#[repr(C)]
+pub struct PoastaAlignParams {
+    pub mismatch_score: i32,
+    pub gap_extend: i32,
+    pub gap_open: i32,
+    pub mode: PoastaAlignmentMode,
+}
+
+impl PoastaAlignParams {
+    /// The scoring of the parameters, or -1 if a cost is negative or above 255.
+    pub(crate) fn scoring(&self) -> Result<Scoring, c_int> {
+        Scoring::affine(self.mismatch_score, self.gap_extend, self.gap_open)
+    }
+}
+
+/// Kind of a column of an alignment of a query to a graph path.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoastaEditOp {
+    Match = 0,
+    Mismatch = 1,
+    /// Query base not aligned to the graph.
+    Insertion = 2,
+    /// Graph node skipped by the query.
+    Deletion = 3,
+}
+
+/// Struct to hold a column of an alignment of a query to a graph path.
+#[repr(C)]
+pub struct PoastaEdit {
+    pub op: PoastaEditOp,
+
+    /// Graph node (as in poasta_node_ref_position), or -1 for insertions.
+    pub node: i64,
+
+    /// Position in the query, or -1 for deletions.
+    pub query_pos: i64,
+}
+
+/// Struct to hold the alignment of a query to a graph.
+#[repr(C)]
+pub struct PoastaQueryAlignment {
+    /// 0 on success, otherwise a negative status code and the other fields are empty.
+    pub status: c_int,
+
+    /// Cost of the alignment; negative for local alignments with more match bonus than penalties.
+    pub score: i64,
+
+    /// The alignment in CIGAR format with `=`, `X`, `I`, `D`, and `S` for query ends left out
+    /// of ends-free or local alignments, NUL-terminated.
+    pub cigar: *mut c_char,
+
+    /// The alignment column by column.
+    pub edits: *mut PoastaEdit,
+    pub num_edits: usize,
+}
+
+impl PoastaQueryAlignment {
+    /// A failed alignment with the given status.
+    pub(crate) fn failed(status: c_int) -> Self {
+        PoastaQueryAlignment { status, score: 0, cigar: ptr::null_mut(), edits: ptr::null_mut(), num_edits: 0 }
+    }
+}
+
+/// Aligns a sequence to the graph like poasta_add_sequence_mode would, but without adding it, e.g.,
+/// to score candidate reads, filter them and add only the best ones. The sequence is aligned as
+/// given: primer trimming, the length filter and rotation of circular sequences don't apply.
+/// `status` is -1 if the graph or sequence is NULL or empty, or a cost is negative or above 255.
+/// Caller must free the result with poasta_free_query_alignment.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_align_query(
+    graph: *const PoastaGraph,
+    seq: *const c_char,
+    len: usize,
+    params: PoastaAlignParams,
+) -> PoastaQueryAlignment {
+    error::catch(|| {
+        if graph.is_null() || seq.is_null() || len == 0 {
+            return PoastaQueryAlignment::failed(error::fail(-1, "graph or sequence is NULL or empty"));
+        }
+
+        let graph = unsafe { &*graph };
+        if graph.graph.is_empty() {
+            return PoastaQueryAlignment::failed(error::fail(-1, "graph is empty"));
+        }
+
+        let scoring = match params.scoring() {
+            Ok(scoring) => scoring,
+            Err(status) => return PoastaQueryAlignment::failed(status),
+        };
+
+        let seq = unsafe { slice::from_raw_parts(seq as *const u8, len) };
+        align_query(graph, seq, &scoring, params.mode, &mut DpBuffers::default()).into_c()
+    })
+}
+
+/// Frees a PoastaQueryAlignment.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_query_alignment(alignment: PoastaQueryAlignment) {
+    error::catch(|| {
+        if !alignment.cigar.is_null() {
+            drop(unsafe { CString::from_raw(alignment.cigar) });
+        }
+
+        unsafe { free_c_array(alignment.edits, alignment.num_edits) };
+    })
+}
+
+/// Struct to hold the alignments of a batch of queries, in the order of the queries.
+#[repr(C)]
+pub struct PoastaQueryAlignments {
+    pub alignments: *mut PoastaQueryAlignment,
+    pub num_alignments: usize,
+}
+
+/// Aligns the `n` sequences `seqs[i]` of `lens[i]` bases to the graph like poasta_align_query,
+/// spread over `n_threads` threads (0 means one per available CPU) that take the next query as
+/// they finish one, so queries of different lengths keep all threads busy. The graph isn't
+/// changed, so threads share it without copying or locking. The threads are started for each call
+/// and stopped before it returns, which takes tens of microseconds, negligible next to aligning
+/// a batch; for many tiny batches, call poasta_align_query from the host's own threads instead. A
+/// thread pool (e.g., rayon's) isn't used so the library adds no dependency and leaves no threads
+/// running in the host's process between calls. An alignment's `status` is a
+/// `PoastaError` code of its own query: `InvalidArgument` if its sequence is NULL or empty, and
+/// `Panic` if aligning it hit a bug, which leaves the other queries unaffected. These per-query
+/// failures aren't recorded in poasta_last_error, which only reflects failures of the whole call.
+/// The result is empty if the graph is NULL or empty, `seqs` or `lens` is NULL or a cost is
+/// negative or above 255. Caller must free it with poasta_free_query_alignments.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_align_queries_parallel(
+    graph: *const PoastaGraph,
+    seqs: *const *const c_char,
+    lens: *const usize,
+    n: usize,
+    params: PoastaAlignParams,
+    n_threads: usize,
+) -> PoastaQueryAlignments {
+    error::catch(|| {
+        if graph.is_null() || seqs.is_null() || lens.is_null() {
+            error::fail(-1, "graph, sequences or lengths are NULL");
+            return PoastaQueryAlignments { alignments: ptr::null_mut(), num_alignments: 0 };
+        }
+
+        let graph = unsafe { &*graph };
+        if graph.graph.is_empty() {
+            error::fail(-1, "graph is empty");
+            return PoastaQueryAlignments { alignments: ptr::null_mut(), num_alignments: 0 };
+        }
+
+        let Ok(scoring) = params.scoring() else {
+            return PoastaQueryAlignments { alignments: ptr::null_mut(), num_alignments: 0 };
+        };
+
+        let queries: Vec<Option<&[u8]>> = unsafe { slice::from_raw_parts(seqs, n).iter().zip(slice::from_raw_parts(lens, n)) }
+            .map(|(seq, len)| (!seq.is_null() && *len > 0).then(|| unsafe { slice::from_raw_parts(*seq as *const u8, *len) }))
+            .collect();
+
+        let n_threads = match n_threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+
+        let next = AtomicUsize::new(0);
+        let align_next = || -> Vec<(usize, Result<QueryResult, c_int>)> {
+            let mut buffers = DpBuffers::default();
+            let mut results = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(query) = queries.get(i) else {
+                    return results;
+                };
+
+                // Caught here rather than by error::catch, as a worker's last error is lost with
+                // its thread and a panic would otherwise fail the whole batch.
+                let result = match query {
+                    Some(seq) => panic::catch_unwind(AssertUnwindSafe(|| align_query(graph, seq, &scoring, params.mode, &mut buffers)))
+                        .map_err(|_| {
+                            buffers = DpBuffers::default();
+                            error::PANIC
+                        }),
+                    None => Err(error::INVALID_ARGUMENT),
+                };
+                results.push((i, result));
+            }
+        };
+
+        let mut results = if n_threads == 1 || n <= 1 {
+            align_next()
+        } else {
+            thread::scope(|scope| {
+                let workers: Vec<_> = (0..n_threads.min(n))
+                    .map(|_| scope.spawn(align_next))
+                    .collect();
+
+                workers.into_iter()
+                    .flat_map(|worker| worker.join().unwrap())
+                    .collect()
+            })
+        };
+        results.sort_unstable_by_key(|(i, _)| *i);
+
+        PoastaQueryAlignments {
+            alignments: into_c_array(results.into_iter()
+                .map(|(_, result)| result.map_or_else(PoastaQueryAlignment::failed, QueryResult::into_c))
+                .collect()),
+            num_alignments: n,
+        }
+    })
+}
+
+/// Frees PoastaQueryAlignments.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_query_alignments(alignments: PoastaQueryAlignments) {
+    error::catch(|| {
+        for alignment in unsafe { free_c_array(alignments.alignments, alignments.num_alignments) } {
+            unsafe { poasta_free_query_alignment(alignment) };
+        }
+    })
+}
+
+/// Alignment of a query, before it is handed over to C.
+struct QueryResult {
+    score: i64,
+    edits: Vec<PoastaEdit>,
+    cigar: String,
+}
+
+impl QueryResult {
+    fn into_c(self) -> PoastaQueryAlignment {
+        PoastaQueryAlignment {
+            status: 0,
+            score: self.score,
+            cigar: CString::new(self.cigar).unwrap().into_raw(),
+            num_edits: self.edits.len(),
+            edits: into_c_array(self.edits),
+        }
+    }
+}
+
+/// Aligns `seq` to the non-empty graph with `scoring` in `mode`.
+fn align_query(graph: &PoastaGraph, seq: &[u8], scoring: &Scoring, mode: PoastaAlignmentMode, buffers: &mut DpBuffers) -> QueryResult {
+    let mode = mode.normalized();
+    let (alignment, score) = graph.align_scored(seq, scoring, mode, buffers);
+
+    let edits = edits(graph, seq, &alignment);
+    let cigar = cigar(&edits, mode);
+
+    QueryResult { score, edits, cigar }
+}
+
+/// The columns of an alignment of `seq` to the graph.
+fn edits(graph: &PoastaGraph, seq: &[u8], alignment: &Alignment<NodeIx>) -> Vec<PoastaEdit> {
+    alignment.iter()
+        .filter_map(|pair| {
+            let (op, node, query_pos) = match (pair.rpos, pair.qpos) {
+                (Some(node), Some(qpos)) if graph.graph.get_symbol(node) == seq[qpos] => (PoastaEditOp::Match, Some(node), Some(qpos)),
+                (Some(node), Some(qpos)) => (PoastaEditOp::Mismatch, Some(node), Some(qpos)),
+                (None, Some(qpos)) => (PoastaEditOp::Insertion, None, Some(qpos)),
+                (Some(node), None) => (PoastaEditOp::Deletion, Some(node), None),
+                (None, None) => return None,
+            };
+
+            Some(PoastaEdit {
+                op,
+                node: node.map_or(-1, |node| node.index() as i64),
+                query_pos: query_pos.map_or(-1, |qpos| qpos as i64),
+            })
+        })
+        .collect()
+}
+
+/// CIGAR string of the alignment columns, with the leading and trailing insertions of free query
+/// ends as soft clips.
+fn cigar(edits: &[PoastaEdit], mode: PoastaAlignmentMode) -> String {
+    let leading = edits.iter().take_while(|edit| edit.op == PoastaEditOp::Insertion).count();
+    let trailing = edits[leading..].iter().rev().take_while(|edit| edit.op == PoastaEditOp::Insertion).count();
+
+    let mut ops: Vec<u8> = edits.iter()
+        .map(|edit| match edit.op {
+            PoastaEditOp::Match => b'=',
+            PoastaEditOp::Mismatch => b'X',
+            PoastaEditOp::Insertion => b'I',
+            PoastaEditOp::Deletion => b'D',
+        })
+        .collect();
+
+    if mode.query_free_begin {
+        ops[..leading].fill(b'S');
+    }
+    if mode.query_free_end {
+        let len = ops.len();
+        ops[len - trailing..].fill(b'S');
+    }
+
+    let mut cigar = String::new();
+    for run in ops.chunk_by(|a, b| a == b) {
+        cigar.push_str(&format!("{}{}", run.len(), run[0] as char));
+    }
+
+    cigar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{poasta_last_error, PoastaError};
+    use crate::testing::graph_of;
+
+    #[test]
+    fn invalid_queries_fail_on_their_own() {
+        let graph = graph_of(&["ACGTTGCA", "ACGATGCA"]);
+        let seqs = [c"ACGTTGCA".as_ptr(), ptr::null(), c"ACGATGCA".as_ptr()];
+        let lens = [8, 0, 8];
+        let params = PoastaAlignParams { mismatch_score: 4, gap_extend: 2, gap_open: 6, mode: PoastaAlignmentMode::default() };
+
+        let before = poasta_last_error();
+        let alignments = unsafe { poasta_align_queries_parallel(&graph, seqs.as_ptr(), lens.as_ptr(), 3, params, 2) };
+        let statuses: Vec<_> = unsafe { slice::from_raw_parts(alignments.alignments, alignments.num_alignments) }.iter()
+            .map(|alignment| (alignment.status, alignment.score))
+            .collect();
+        unsafe { poasta_free_query_alignments(alignments) };
+
+        assert_eq!(statuses, [(0, 0), (PoastaError::InvalidArgument as c_int, 0), (0, 0)]);
+        assert_eq!(poasta_last_error(), before);
+    }
+}
@@ -0,0 +1,93 @@
+use std::os::raw::c_char;
+use std::slice;
+
+use crate::circular::ORIGIN_ANCHOR_LEN;
+use crate::concatemer::concatemer_segments;
+use crate::consensus::{heaviest_path, node_support, PoastaConsensus};
+use crate::{error, PoastaGraph, Scoring, SequenceInfo};
+
+/// Maximum relative deviation from the median repeat length for a repeat to be used.
+const RCA_LENGTH_TOLERANCE: f64 = 0.2;
+
+/// Configuration of poasta_rca_consensus.
+#[repr(C)]
+pub struct PoastaRcaConfig {
+    pub mismatch_score: i32,
+    pub gap_extend: i32,
+    pub gap_open: i32,
+
+    /// Maximum number of edits when locating repeat boundaries.
+    pub max_mismatch: u8,
+}
+
+/// Computes the consensus of the repeats within a single rolling-circle read.
+/// The read is split into repeats by locating its own start (see poasta_split_concatemers), and
+/// the repeats whose length is within 20% of the median repeat length are aligned into a graph.
+/// The support of a consensus base is the number of repeats supporting it, and its confidence
+/// their fraction. The consensus is empty if a cost is negative or above 255.
+/// Caller must free the result with poasta_free_consensus.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_rca_consensus(seq: *const c_char, len: usize, config: PoastaRcaConfig) -> PoastaConsensus {
+    error::catch(|| {
+        if seq.is_null() || len == 0 {
+            return PoastaConsensus::empty();
+        }
+        let Ok(scoring) = Scoring::affine(config.mismatch_score, config.gap_extend, config.gap_open) else {
+            return PoastaConsensus::empty();
+        };
+
+        let seq = unsafe { slice::from_raw_parts(seq as *const u8, len) };
+        let anchor = &seq[..len.min(ORIGIN_ANCHOR_LEN)];
+        let segments = concatemer_segments(seq, anchor, config.max_mismatch as usize);
+
+        let mut lengths: Vec<usize> = segments.iter().map(|(start, end)| end - start).collect();
+        lengths.sort_unstable();
+        let median = lengths[lengths.len() / 2] as f64;
+
+        let mut graph = PoastaGraph::new();
+        for &(start, end) in &segments {
+            if ((end - start) as f64 - median).abs() > RCA_LENGTH_TOLERANCE * median {
+                continue;
+            }
+
+            let repeat = &seq[start..end];
+            if graph.add_sequence(repeat, &vec![1; repeat.len()], scoring.clone(), SequenceInfo::default()) != 0 {
+                return PoastaConsensus::empty();
+            }
+        }
+
+        let path = heaviest_path(&graph);
+        let node_support = node_support(&graph);
+
+        let sequence = path.iter().map(|node| graph.graph.get_symbol(*node)).collect();
+        let support = path.iter().map(|node| node_support[node.index()]).collect();
+
+        PoastaConsensus::new(sequence, support, graph.sequences.len())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::poasta_free_consensus;
+
+    const COPY: &str = "ACGTTGCAGGATCCTTAGCAGGTACCAATTCGGACTAGCTTGACCGTAAGCTTCAGGATC";
+
+    #[test]
+    fn repeats_are_merged_into_one_copy() {
+        // One repeat with a substitution after the anchor, and a partial repeat at the end
+        let with_error = format!("{}A{}", &COPY[..45], &COPY[46..]);
+        let read = format!("{COPY}{with_error}{COPY}{}", &COPY[..34]);
+        let config = PoastaRcaConfig { mismatch_score: 4, gap_extend: 2, gap_open: 6, max_mismatch: 0 };
+
+        let consensus = unsafe { poasta_rca_consensus(read.as_ptr().cast(), read.len(), config) };
+        let sequence = unsafe { slice::from_raw_parts(consensus.sequence as *const u8, consensus.len) };
+        let support = unsafe { slice::from_raw_parts(consensus.support, consensus.len) };
+        let confidence = unsafe { slice::from_raw_parts(consensus.confidence, consensus.len) };
+        assert_eq!(sequence, COPY.as_bytes());
+        assert_eq!(support[0], 3);
+        assert_eq!(support[45], 2);
+        assert!((confidence[45] - 2.0 / 3.0).abs() < 1e-9);
+        unsafe { poasta_free_consensus(consensus) };
+    }
+}
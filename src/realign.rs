@@ -0,0 +1,176 @@
+//! Realignment of an updated version of a sequence already in the graph (e.g., a re-basecalled
+//! read), restricted to a band around the old version's alignment.
+
+use std::os::raw::{c_char, c_int};
+use std::slice;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::circular::origin_offset;
+use crate::consensus::topological_order;
+use crate::dp::{self, DpBuffers};
+use crate::filter::LENGTH_OUTLIER;
+use crate::graph::{NodeIx, SequenceInfo};
+use crate::view::GraphView;
+use crate::{error, metrics, PoastaGraph};
+
+/// How `poasta_realign_updated` aligns the new version of a sequence.
+#[repr(C)]
+pub struct PoastaRealignConfig {
+    /// Number of query positions the new alignment may deviate from the old one, on top of the
+    /// difference in length between the versions. 0 means 32.
+    pub band: usize,
+
+    /// Whether to deactivate the old version (see poasta_sequence_set_active), so only the new
+    /// version takes part in consensus and MSA computation.
+    pub replace: bool,
+}
+
+const DEFAULT_BAND: usize = 32;
+
+/// Adds `new_seq`, an updated version of the sequence at `old_index` (in insertion order). The new
+/// version gets the scoring, alignment mode, name and other details of the old one, and weight 1
+/// per base. Where the dynamic programming aligner is used (custom scoring, codon-aware or
+/// homopolymer-aware gaps, or modes other than Global), it only fills a band around the old
+/// version's alignment, which is much faster than aligning from scratch when the versions are
+/// nearly identical; if no alignment fits in the band, the sequence is aligned without it.
+/// poasta's aligner is already quick for nearly identical sequences and is used as usual.
+/// Primer trimming and the length filter apply as for poasta_add_sequence.
+/// Returns the same codes as poasta_add_sequence, and -1 if the index is out of range.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_realign_updated(
+    graph: *mut PoastaGraph,
+    old_index: usize,
+    new_seq: *const c_char,
+    len: usize,
+    config: PoastaRealignConfig,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || new_seq.is_null() {
+            metrics::record_status(-1);
+            return error::fail(-1, "graph or sequence is NULL");
+        }
+
+        let graph = unsafe { &mut *graph };
+        if old_index >= graph.sequences.len() {
+            metrics::record_status(-1);
+            return error::fail(-1, "sequence index out of range");
+        }
+
+        let seq = unsafe { slice::from_raw_parts(new_seq as *const u8, len) };
+        let status = realign_updated(graph, old_index, seq, &config);
+        metrics::record_status(status);
+
+        status
+    })
+}
+
+fn realign_updated(graph: &mut PoastaGraph, old_index: usize, seq: &[u8], config: &PoastaRealignConfig) -> c_int {
+    let (mut seq, weights) = match &graph.primers {
+        Some(primers) => primers.apply(seq, &vec![1; seq.len()], graph.alphabet()),
+        None => (seq.to_vec(), vec![1; seq.len()]),
+    };
+
+    if graph.length_filter.is_some_and(|filter| !filter.accepts(graph, seq.len())) {
+        return LENGTH_OUTLIER;
+    }
+
+    // Circular sequences were added rotated to the origin, and so is the new version
+    if graph.circular {
+        let offset = origin_offset(graph, &seq);
+        seq.rotate_left(offset);
+    }
+
+    let old = &graph.sequences[old_index];
+    let (scoring, mode) = (old.scoring.clone(), old.info.mode);
+    let info = SequenceInfo { masked: false, approximate: false, ..old.info.clone() };
+
+    // poasta's aligner is quick for nearly identical sequences by itself
+    let alignment = graph.uses_dp_aligner(&scoring, mode).then(|| {
+        let view = GraphView::new(graph);
+        let width = if config.band > 0 { config.band } else { DEFAULT_BAND } + old.seq.len().abs_diff(seq.len());
+        let band = band_around(&view, &old.path, width, seq.len());
+
+        dp::align_banded(&view, &seq, &graph.dp_costs(&scoring, mode), Some(&band), &mut DpBuffers::default())
+            .map(|(alignment, _)| alignment)
+    });
+
+    let alignment = alignment.flatten().unwrap_or_else(|| graph.align(&seq, &scoring, mode));
+    if graph.commit(&seq, &weights, Some(&alignment), scoring, info) != 0 {
+        return -3;
+    }
+
+    if config.replace {
+        graph.sequences_mut()[old_index].info.masked = true;
+    }
+
+    0
+}
+
+/// The (first, last) query column (0 before the first base) of each node, by node index, within
+/// `width` of the column the old version's `path` reached at the node. Nodes off the path get the
+/// column of the last path node before them in topological order.
+fn band_around(view: &GraphView, path: &[NodeIx], width: usize, len: usize) -> Vec<(usize, usize)> {
+    let mut path_col = vec![None; view.node_count_with_start_and_end()];
+    for (qpos, node) in path.iter().enumerate() {
+        path_col[node.index()] = Some(qpos + 1);
+    }
+
+    let mut band = vec![(0, width.min(len)); view.node_count_with_start_and_end()];
+    let mut col = 0;
+    for node in topological_order(view) {
+        col = path_col[node.index()].unwrap_or(col);
+        band[node.index()] = (col.saturating_sub(width), (col + width).min(len));
+    }
+
+    band
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homopolymer::poasta_graph_set_homopolymer_gaps;
+    use crate::sequence::poasta_sequence_set_active;
+    use crate::testing::{add, graph_of, outputs};
+
+    const READS: [&str; 3] = ["ACGTCAAAAAAGCTGA", "ACGTCAAAAAGCTGA", "ACCTCAAAAAAGCTGA"];
+    const UPDATED: &str = "ACGTCAAAAAAGCTTGA";
+
+    /// A graph of READS, realigned with the updated version of the last one.
+    fn realigned(homopolymer: bool, replace: bool) -> PoastaGraph {
+        let mut graph = graph_of(&READS);
+        if homopolymer {
+            assert_eq!(unsafe { poasta_graph_set_homopolymer_gaps(&mut graph, 3, 1) }, 0);
+        }
+
+        let config = PoastaRealignConfig { band: 2, replace };
+        let status = unsafe { poasta_realign_updated(&mut graph, 2, UPDATED.as_ptr().cast(), UPDATED.len(), config) };
+        assert_eq!(status, 0);
+
+        graph
+    }
+
+    #[test]
+    fn updates_align_as_if_added_from_scratch() {
+        for homopolymer in [false, true] {
+            let mut added = graph_of(&READS);
+            if homopolymer {
+                assert_eq!(unsafe { poasta_graph_set_homopolymer_gaps(&mut added, 3, 1) }, 0);
+            }
+            add(&mut added, UPDATED);
+            assert_eq!(outputs(&realigned(homopolymer, false)), outputs(&added));
+
+            // Replacing the old version deactivates it
+            assert_eq!(unsafe { poasta_sequence_set_active(&mut added, 2, false) }, 0);
+            assert_eq!(outputs(&realigned(homopolymer, true)), outputs(&added));
+        }
+    }
+
+    #[test]
+    fn only_sequences_in_the_graph_can_be_updated() {
+        let mut graph = graph_of(&READS);
+        let config = PoastaRealignConfig { band: 0, replace: true };
+        assert_eq!(unsafe { poasta_realign_updated(&mut graph, 3, UPDATED.as_ptr().cast(), UPDATED.len(), config) }, -1);
+        assert_eq!(graph.sequences.len(), 3);
+    }
+}
@@ -0,0 +1,251 @@
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::slice;
+
+use poasta::aligner::scoring::GapAffine;
+use poasta::graphs::AlignableRefGraph;
+
+use crate::consensus::{consensus_sequence, heaviest_path};
+use crate::graph::NodeIx;
+use crate::{error, free_c_array, into_c_array, metrics, PoastaGraph, Scoring, SequenceInfo};
+
+/// Struct to hold the reference coordinates of the consensus.
+#[repr(C)]
+pub struct PoastaRefMap {
+    /// 0-based backbone position of each consensus base, -1 for bases inserted relative to the
+    /// backbone.
+    pub positions: *mut i64,
+    pub len: usize,
+}
+
+/// Seeds an empty graph with a reference sequence, which becomes the graph's backbone (see
+/// poasta_graph_set_backbone), so reads align against a stable coordinate system. Every base of
+/// the reference gets `weight`. A reference with weight 0 is inactive (see
+/// poasta_sequence_set_active): it guides the alignment of reads but is left out of the
+/// consensus and MSA. Primers and the length filter don't apply to the reference.
+/// Returns -1 if the graph already has sequences and -2 if adding the reference failed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_init_reference(graph: *mut PoastaGraph, ref_seq: *const c_char, len: usize, weight: u32) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || ref_seq.is_null() || len == 0 {
+            return error::fail(-1, "graph or reference is NULL or the reference is empty");
+        }
+
+        let graph = unsafe { &mut *graph };
+        if !graph.sequences.is_empty() {
+            return error::fail(-1, "the graph already has sequences");
+        }
+
+        let seq = unsafe { slice::from_raw_parts(ref_seq as *const u8, len) };
+        let weights = vec![weight as usize; len];
+
+        // The reference is never aligned, but graph rebuilds may realign it with poasta's default costs
+        let scoring = Scoring::Affine(GapAffine::new(4, 2, 6));
+        let info = SequenceInfo { masked: weight == 0, ..SequenceInfo::default() };
+
+        let status = match graph.commit(seq, &weights, None, scoring, info) {
+            0 => 0,
+            _ => -2,
+        };
+        metrics::record_status(status);
+
+        if status == 0 {
+            graph.backbone = Some(0);
+        }
+
+        status
+    })
+}
+
+/// Designates the sequence at `index` (in insertion order) as the graph's backbone, the reference
+/// sequence that the positions of mapped reads (e.g., in SAM records) refer to.
+/// Returns -1 if the index is out of range.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_set_backbone(graph: *mut PoastaGraph, index: usize) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let graph = unsafe { &mut *graph };
+        if index >= graph.sequences.len() {
+            return error::fail(-1, "sequence index out of range");
+        }
+
+        graph.backbone = Some(index);
+        graph.touch();
+
+        0
+    })
+}
+
+/// The node of each base of the backbone, if the graph has one.
+pub(crate) fn backbone_path(graph: &PoastaGraph) -> Option<&[NodeIx]> {
+    graph.backbone.map(|index| graph.sequences[index].path.as_slice())
+}
+
+/// Returns the 0-based backbone position of `node` (a node index). Nodes aligned to a backbone
+/// node, e.g., a variant base, get the position of that node. Returns -1 if the graph has no
+/// backbone, the node doesn't exist or it is inserted relative to the backbone.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_node_ref_position(graph: *const PoastaGraph, node: usize) -> i64 {
+    error::catch(|| {
+        if graph.is_null() {
+            return i64::from(error::fail(-1, "graph is NULL"));
+        }
+
+        let Some(positions) = ref_positions(unsafe { &*graph }) else {
+            return i64::from(error::fail(-1, "the graph has no backbone"));
+        };
+
+        positions.get(node).copied().flatten().map_or(-1, |pos| pos as i64)
+    })
+}
+
+/// Returns the backbone position of each consensus base, to compare the consensus to linear
+/// annotations. The map is empty if the graph has no backbone.
+/// Caller must free the result with poasta_free_ref_map.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_consensus_to_ref_map(graph: *const PoastaGraph) -> PoastaRefMap {
+    error::catch(|| {
+        let empty = PoastaRefMap { positions: ptr::null_mut(), len: 0 };
+        if graph.is_null() {
+            return empty;
+        }
+
+        let graph = unsafe { &*graph };
+        let Some(positions) = ref_positions(graph) else {
+            return empty;
+        };
+
+        let map: Vec<i64> = heaviest_path(graph).iter()
+            .map(|node| positions[node.index()].map_or(-1, |pos| pos as i64))
+            .collect();
+
+        let len = map.len();
+        PoastaRefMap { positions: into_c_array(map), len }
+    })
+}
+
+/// Returns the part of the consensus aligned to backbone positions `ref_start` (inclusive) to
+/// `ref_end` (exclusive), including bases inserted within that interval, e.g., to extract a single
+/// exon. Returns NULL if the graph has no backbone or no consensus base is aligned to the interval.
+/// The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_consensus_region(graph: *const PoastaGraph, ref_start: usize, ref_end: usize) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let graph = unsafe { &*graph };
+        let Some(positions) = ref_positions(graph) else {
+            return ptr::null_mut();
+        };
+
+        let in_region: Vec<bool> = heaviest_path(graph).iter()
+            .map(|node| positions[node.index()].is_some_and(|pos| (ref_start..ref_end).contains(&pos)))
+            .collect();
+
+        let (Some(first), Some(last)) = (in_region.iter().position(|x| *x), in_region.iter().rposition(|x| *x)) else {
+            return ptr::null_mut();
+        };
+
+        let consensus = consensus_sequence(graph);
+        CString::new(&consensus[first..=last]).unwrap().into_raw()
+    })
+}
+
+/// Frees a PoastaRefMap.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_ref_map(map: PoastaRefMap) {
+    error::catch(|| {
+        unsafe { free_c_array(map.positions, map.len) };
+    })
+}
+
+/// Backbone position of every node (by index), if the graph has a backbone.
+pub(crate) fn ref_positions(graph: &PoastaGraph) -> Option<Vec<Option<usize>>> {
+    let backbone = backbone_path(graph)?;
+
+    let mut positions = vec![None; graph.graph.node_count_with_start_and_end()];
+    for (pos, node) in backbone.iter().enumerate() {
+        positions[node.index()] = Some(pos);
+        for aligned in graph.graph.get_aligned_nodes(*node) {
+            positions[aligned.index()] = Some(pos);
+        }
+    }
+
+    Some(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add, consensus, graph_of, take};
+
+    const REFERENCE: &str = "ACGTTGCAGGATCCTTAGCA";
+
+    fn with_reference(weight: u32, reads: &[&str]) -> PoastaGraph {
+        let mut graph = PoastaGraph::new();
+        assert_eq!(unsafe { poasta_graph_init_reference(&mut graph, REFERENCE.as_ptr().cast(), REFERENCE.len(), weight) }, 0);
+        for read in reads {
+            add(&mut graph, read);
+        }
+
+        graph
+    }
+
+    #[test]
+    fn references_become_the_backbone() {
+        let graph = with_reference(1, &[]);
+        assert_eq!(graph.backbone, Some(0));
+        assert_eq!(consensus(&graph), REFERENCE);
+
+        // A reference of weight 0 only guides the alignment
+        let graph = with_reference(0, &["ACGTTGCAGGTCCTTAGCA", "ACGTTGCAGGTCCTTAGCA"]);
+        assert_eq!(consensus(&graph), "ACGTTGCAGGTCCTTAGCA");
+        assert!(graph.sequences[0].info.masked);
+
+        let mut graph = graph_of(&["ACGT"]);
+        assert_eq!(unsafe { poasta_graph_init_reference(&mut graph, REFERENCE.as_ptr().cast(), REFERENCE.len(), 1) }, -1);
+        assert_eq!(graph.backbone, None);
+    }
+
+    #[test]
+    fn nodes_are_projected_onto_the_backbone() {
+        // Two reads insert a G after position 11, one substitutes the G at position 5
+        let inserted = "ACGTTGCAGGATGCCTTAGCA";
+        let graph = with_reference(0, &[inserted, inserted, "ACGTTCCAGGATCCTTAGCA"]);
+        let map = unsafe { poasta_consensus_to_ref_map(&graph) };
+        let positions = unsafe { slice::from_raw_parts(map.positions, map.len) }.to_vec();
+        unsafe { poasta_free_ref_map(map) };
+
+        let expected: Vec<i64> = (0..12).chain([-1]).chain(12..20).collect();
+        assert_eq!(positions, expected);
+
+        let variant = graph.sequences[3].path[5].index();
+        let insertion = graph.sequences[1].path[12].index();
+        assert_ne!(graph.sequences[3].path[5], graph.sequences[0].path[5]);
+        assert_eq!(unsafe { poasta_node_ref_position(&graph, variant) }, 5);
+        assert_eq!(unsafe { poasta_node_ref_position(&graph, insertion) }, -1);
+
+        let graph = graph_of(&[REFERENCE]);
+        assert_eq!(unsafe { poasta_node_ref_position(&graph, 2) }, -1);
+        assert_eq!(unsafe { poasta_consensus_to_ref_map(&graph) }.len, 0);
+    }
+
+    #[test]
+    fn regions_include_bases_inserted_within_them() {
+        let inserted = "ACGTTGCAGGATGCCTTAGCA";
+        let graph = with_reference(0, &[inserted, inserted]);
+        let region = |start, end| unsafe { poasta_get_consensus_region(&graph, start, end) };
+
+        assert_eq!(take(region(8, 14)), "GGATGCC");
+        assert_eq!(take(region(0, 4)), "ACGT");
+        assert_eq!(take(region(18, 40)), "CA");
+        assert!(region(20, 40).is_null());
+        assert!(unsafe { poasta_get_consensus_region(&graph_of(&[REFERENCE]), 0, 4) }.is_null());
+    }
+}
@@ -0,0 +1,94 @@
+//! Iterative refinement, which offsets the order dependence of progressive alignment by
+//! realigning each sequence against all the others.
+
+use std::os::raw::c_int;
+
+use crate::dp::DpBuffers;
+use crate::sequence::remove_sequence;
+use crate::timeout::APPROXIMATE;
+use crate::{error, PoastaGraph};
+
+/// Refines the graph in rounds: each sequence in turn is removed and realigned against the graph
+/// of all the others (with its scoring and alignment mode), so the first sequences, which were
+/// aligned against a tiny graph, benefit from the later ones. Rounds repeat until the total cost
+/// of the realignments stops decreasing, or `iterations` rounds (0 for no limit) ran; a round that
+/// increased the total is undone. Sequences keep their index, name and provenance, but nodes are
+/// renumbered, and links and masked regions on nodes only a realigned sequence passed through are
+/// dropped. If a round ends with a sequence added along an approximate alignment after a timeout,
+/// its cost is unknown and refinement stops. Returns 0 on success, -1 if the graph is NULL, and
+/// the code of poasta_add_sequence if realigning a sequence failed, leaving the graph unchanged.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_refine(graph: *mut PoastaGraph, iterations: usize) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let graph = unsafe { &mut *graph };
+        if graph.sequences.len() < 2 {
+            return 0;
+        }
+
+        let original = graph.shallow_clone();
+        let mut last_total = None;
+        let mut rounds = 0;
+        while iterations == 0 || rounds < iterations {
+            let before = graph.shallow_clone();
+            let total = match refine_round(graph) {
+                Ok(total) => total,
+                Err(status) => {
+                    restore(graph, original);
+                    return status;
+                },
+            };
+            rounds += 1;
+
+            match (total, last_total) {
+                (Some(total), Some(last)) if total > last => {
+                    restore(graph, before);
+                    break;
+                },
+                (Some(total), Some(last)) if total == last => break,
+                (Some(total), _) => last_total = Some(total),
+                (None, _) => break,
+            }
+        }
+
+        // Realignments aren't additions the cache statistics should count
+        (graph.cache.hits, graph.cache.misses) = (original.cache.hits, original.cache.misses);
+
+        0
+    })
+}
+
+/// Realigns every sequence once, and returns the total cost of the realignments, None if one of
+/// them was approximate.
+fn refine_round(graph: &mut PoastaGraph) -> Result<Option<i64>, c_int> {
+    let mut buffers = DpBuffers::default();
+    let mut total = Some(0);
+
+    // Taking the first sequence out and adding it at the end restores the order after a round
+    for _ in 0..graph.sequences.len() {
+        let record = graph.sequences[0].clone();
+        let backbone = graph.backbone == Some(0);
+        remove_sequence(graph, 0)?;
+
+        let (status, cost) = graph.insert_sequence(&record.seq, &record.weights, record.scoring, record.info, &mut buffers);
+        match status {
+            0 | APPROXIMATE => (),
+            status => return Err(error::fail(status, "realigning a sequence failed")),
+        }
+
+        total = total.zip(cost).map(|(total, cost)| total + cost);
+        if backbone {
+            graph.backbone = Some(graph.sequences.len() - 1);
+        }
+    }
+
+    Ok(total)
+}
+
+/// Replaces the graph with an earlier copy of it, as a change of the current graph.
+fn restore(graph: &mut PoastaGraph, saved: PoastaGraph) {
+    *graph = PoastaGraph { user_data: graph.user_data, generation: graph.generation + 1, ..saved };
+}
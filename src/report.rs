@@ -0,0 +1,236 @@
+use std::ffi::CStr;
+use std::fmt::Write as _;
+use std::fs;
+use std::os::raw::{c_char, c_int};
+
+use crate::consensus::{heaviest_path, node_support};
+use crate::error;
+use crate::graph::NodeIx;
+use crate::msa::{msa_rows, Columns, GAP};
+use crate::{PoastaGraph, IO_ERROR};
+
+/// Options of poasta_write_report_html.
+#[repr(C)]
+pub struct PoastaReportOptions {
+    /// Report title (NUL-terminated); NULL for a default title.
+    pub title: *const c_char,
+
+    /// Minimum fraction of sequences with a non-consensus allele for a column to be listed in the
+    /// variant table.
+    pub min_variant_frequency: f64,
+
+    /// Whether to include the colored MSA (which can be large for big graphs).
+    pub show_msa: bool,
+}
+
+/// Writes a standalone HTML report of the graph to the file at `path` (NUL-terminated): a summary,
+/// the consensus colored by confidence, a coverage track, the colored MSA and a table of variant
+/// columns outside the masked regions (see poasta_set_masked_regions). Returns 0 on success, -1 on invalid arguments and -5 if the file couldn't be written.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_write_report_html(
+    graph: *const PoastaGraph,
+    path: *const c_char,
+    options: PoastaReportOptions,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || path.is_null() {
+            return error::fail(-1, "graph or path is NULL");
+        }
+
+        let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
+            return error::fail(-1, "path isn't valid UTF-8");
+        };
+
+        let title = if options.title.is_null() {
+            "POA report".to_string()
+        } else {
+            unsafe { CStr::from_ptr(options.title) }.to_string_lossy().into_owned()
+        };
+
+        let html = render_report(unsafe { &*graph }, &title, &options);
+        match fs::write(path, html) {
+            Ok(()) => 0,
+            Err(err) => error::fail(IO_ERROR, &err.to_string()),
+        }
+    })
+}
+
+fn render_report(graph: &PoastaGraph, title: &str, options: &PoastaReportOptions) -> String {
+    let columns = Columns::new(graph);
+    let rows = msa_rows(graph, &columns, 0..columns.count);
+    let consensus = heaviest_path(graph);
+    let support = node_support(graph);
+    let num_sequences = graph.active_sequences().count().max(1);
+    let masked = graph.masked_regions.as_ref().map(|regions| regions.masked_nodes(graph));
+    let is_masked = |node: NodeIx| masked.as_ref().is_some_and(|masked| masked[node.index()]);
+    let mask_confidence = graph.masked_regions.as_ref().is_some_and(|regions| regions.confidence);
+
+    // Consensus in MSA coordinates
+    let mut consensus_row = vec![GAP; columns.count];
+    for node in &consensus {
+        consensus_row[columns.of_node[node]] = graph.graph.get_symbol(*node);
+    }
+
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>", escape(title)).unwrap();
+    html.push_str(STYLE);
+    writeln!(html, "</head>\n<body>\n<h1>{}</h1>", escape(title)).unwrap();
+    writeln!(
+        html,
+        "<p>{} sequences, {} MSA columns, consensus length {}.</p>",
+        graph.active_sequences().count(),
+        columns.count,
+        consensus.len(),
+    ).unwrap();
+
+    html.push_str("<h2>Consensus</h2>\n<p class=\"seq\">");
+    for node in &consensus {
+        if mask_confidence && is_masked(*node) {
+            write!(html, "<span title=\"masked\">{}</span>", escape_byte(graph.graph.get_symbol(*node))).unwrap();
+            continue;
+        }
+
+        let confidence = support[node.index()] as f64 / num_sequences as f64;
+        write!(
+            html,
+            "<span style=\"opacity:{:.2}\" title=\"{:.0}%\">{}</span>",
+            0.25 + 0.75 * confidence,
+            confidence * 100.0,
+            escape_byte(graph.graph.get_symbol(*node)),
+        ).unwrap();
+    }
+    html.push_str("</p>\n");
+
+    html.push_str("<h2>Coverage</h2>\n");
+    writeln!(html, "<svg class=\"coverage\" viewBox=\"0 0 {} 100\" preserveAspectRatio=\"none\">", columns.count.max(1)).unwrap();
+    for col in 0..columns.count {
+        let coverage = rows.iter().filter(|row| row[col] != GAP).count();
+        let height = 100.0 * coverage as f64 / num_sequences as f64;
+        writeln!(html, "<rect x=\"{col}\" y=\"{:.1}\" width=\"1\" height=\"{height:.1}\"><title>column {col}: {coverage}</title></rect>", 100.0 - height).unwrap();
+    }
+    html.push_str("</svg>\n");
+
+    if options.show_msa {
+        html.push_str("<h2>Alignment</h2>\n<div class=\"msa\">\n");
+        write_msa_row(&mut html, "consensus", &consensus_row);
+        let active = graph.sequences.iter().enumerate().filter(|(_, record)| !record.info.masked);
+        for ((ix, _), row) in active.zip(&rows) {
+            write_msa_row(&mut html, &graph.sequence_name(ix), row);
+        }
+        html.push_str("</div>\n");
+    }
+
+    // Columns of masked nodes
+    let mut masked_columns = vec![false; columns.count];
+    for (node, col) in &columns.of_node {
+        masked_columns[*col] |= is_masked(*node);
+    }
+
+    html.push_str("<h2>Variants</h2>\n<table>\n<tr><th>Column</th><th>Consensus</th><th>Alleles</th><th>Non-consensus</th></tr>\n");
+    for col in (0..columns.count).filter(|col| !masked_columns[*col]) {
+        let mut alleles: Vec<(u8, usize)> = Vec::new();
+        for row in &rows {
+            match alleles.iter_mut().find(|(allele, _)| *allele == row[col]) {
+                Some((_, count)) => *count += 1,
+                None => alleles.push((row[col], 1)),
+            }
+        }
+        alleles.sort_by_key(|(allele, count)| (std::cmp::Reverse(*count), *allele));
+
+        let non_consensus = rows.iter().filter(|row| row[col] != consensus_row[col]).count();
+        let frequency = non_consensus as f64 / num_sequences as f64;
+        if non_consensus == 0 || frequency < options.min_variant_frequency {
+            continue;
+        }
+
+        let alleles: Vec<String> = alleles.iter()
+            .map(|(allele, count)| format!("{}:{count}", escape_byte(*allele)))
+            .collect();
+        writeln!(
+            html,
+            "<tr><td>{col}</td><td>{}</td><td>{}</td><td>{:.1}%</td></tr>",
+            escape_byte(consensus_row[col]),
+            alleles.join(" "),
+            frequency * 100.0,
+        ).unwrap();
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+
+    html
+}
+
+fn write_msa_row(html: &mut String, name: &str, row: &[u8]) {
+    write!(html, "<div><span class=\"name\">{}</span>", escape(name)).unwrap();
+    for base in row {
+        let class = match base.to_ascii_uppercase() {
+            b'A' => "a",
+            b'C' => "c",
+            b'G' => "g",
+            b'T' | b'U' => "t",
+            GAP => "gap",
+            _ => "other",
+        };
+        write!(html, "<span class=\"{class}\">{}</span>", escape_byte(*base)).unwrap();
+    }
+    html.push_str("</div>\n");
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn escape_byte(byte: u8) -> String {
+    escape(&String::from_utf8_lossy(&[byte]))
+}
+
+const STYLE: &str = "<style>
+body { font-family: sans-serif; margin: 2em; }
+.seq, .msa { font-family: monospace; white-space: pre; overflow-x: auto; }
+.seq { word-break: break-all; white-space: normal; }
+.name { display: inline-block; width: 10em; color: #555; }
+.a { background: #8fd18f; } .c { background: #8fb8f0; } .g { background: #f5c27a; } .t { background: #f08f8f; }
+.gap { color: #bbb; } .other { background: #ddd; }
+.coverage { width: 100%; height: 6em; background: #f4f4f4; }
+.coverage rect { fill: #4a7bb7; }
+table { border-collapse: collapse; } td, th { border: 1px solid #ccc; padding: 0.2em 0.6em; }
+</style>
+";
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+    use crate::testing::{graph_of, temp_path};
+
+    const OPTIONS: PoastaReportOptions = PoastaReportOptions { title: ptr::null(), min_variant_frequency: 0.0, show_msa: true };
+
+    #[test]
+    fn variant_columns_are_listed() {
+        let graph = graph_of(&["ACGT", "ACGT", "AGGT"]);
+        let html = render_report(&graph, "<Run 1>", &OPTIONS);
+        assert!(html.contains("<title>&lt;Run 1&gt;</title>"));
+        assert!(html.contains("<p>3 sequences, 4 MSA columns, consensus length 4.</p>"));
+        assert!(html.contains("<h2>Alignment</h2>"));
+        assert_eq!(html.matches("<tr><td>").count(), 1);
+        assert!(html.contains("<tr><td>1</td><td>C</td><td>C:2 G:1</td><td>33.3%</td>"));
+
+        let options = PoastaReportOptions { min_variant_frequency: 0.5, show_msa: false, ..OPTIONS };
+        let html = render_report(&graph, "Run 1", &options);
+        assert!(!html.contains("<h2>Alignment</h2>"));
+        assert_eq!(html.matches("<tr><td>").count(), 0);
+    }
+
+    #[test]
+    fn reports_are_written_to_the_path() {
+        let graph = graph_of(&["ACGT", "AGGT"]);
+        let path = temp_path("report.html");
+        assert_eq!(unsafe { poasta_write_report_html(&graph, path.as_ptr(), OPTIONS) }, 0);
+        let html = fs::read_to_string(path.to_str().unwrap()).unwrap();
+        fs::remove_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(html, render_report(&graph, "POA report", &OPTIONS));
+
+        let missing = c"/nonexistent/report.html";
+        assert_eq!(unsafe { poasta_write_report_html(&graph, missing.as_ptr(), OPTIONS) }, IO_ERROR);
+    }
+}
@@ -0,0 +1,202 @@
+//! Resources used by each alignment, commit and MSA computation, reported to a host callback so
+//! multi-tenant services can attribute costs to their requests.
+
+use std::cell::Cell;
+use std::ffi::c_void;
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::error;
+
+/// Operations whose resources are reported.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoastaOperation {
+    /// Aligning a sequence to a graph, whether to add it or not.
+    Align = 0,
+    /// Adding an aligned sequence to a graph.
+    Commit = 1,
+    /// Computing an MSA, in any of its output formats.
+    Msa = 2,
+}
+
+/// Receives the resources of a finished operation: its wall-clock time in nanoseconds, the most
+/// memory it had allocated at once in bytes, and the alignment cells it computed (like the
+/// `cells_computed` metric).
+pub type PoastaResourceCallback = Option<
+    unsafe extern "C" fn(operation: PoastaOperation, wall_ns: u64, peak_bytes: u64, cells: u64, user_data: *mut c_void),
+>;
+
+type Callback = unsafe extern "C" fn(PoastaOperation, u64, u64, u64, *mut c_void);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The host's callback with its user data pointer.
+static CALLBACK: Mutex<Option<(Callback, usize)>> = Mutex::new(None);
+
+thread_local! {
+    static CELLS: Cell<u64> = const { Cell::new(0) };
+
+    /// Whether an operation is being tracked on this thread, which includes any nested ones.
+    static TRACKING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Calls `callback` with `user_data` at the end of every alignment, commit and MSA computation,
+/// from the thread that ran it. Sequences copied into a graph without realignment (e.g., by
+/// poasta_graph_deserialize) are reported as commits, and operations nested in others count
+/// towards the outer one. Peak memory is only measured with the `alloc-metrics` feature and is 0
+/// otherwise. Pass NULL to stop reporting. Returns 0.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_set_resource_callback(callback: PoastaResourceCallback, user_data: *mut c_void) -> c_int {
+    error::catch(|| {
+        *CALLBACK.lock().unwrap() = callback.map(|callback| (callback, user_data as usize));
+        ENABLED.store(callback.is_some(), Ordering::Relaxed);
+
+        0
+    })
+}
+
+/// Tracks the resources of an operation until dropped.
+pub(crate) struct Usage {
+    active: Option<(PoastaOperation, Instant, u64, i64)>,
+}
+
+/// Starts tracking `operation`, unless no callback is set or an operation is already tracked on
+/// this thread.
+pub(crate) fn track(operation: PoastaOperation) -> Usage {
+    if !ENABLED.load(Ordering::Relaxed) || TRACKING.get() {
+        return Usage { active: None };
+    }
+
+    TRACKING.set(true);
+    Usage { active: Some((operation, Instant::now(), CELLS.get(), memory::start_peak())) }
+}
+
+/// Keeps operations on this thread from being reported until dropped, for work the host didn't
+/// ask for, e.g., copying a graph still shared with a clone before changing it.
+pub(crate) fn suppress() -> Suppressed {
+    Suppressed { tracking: TRACKING.replace(true) }
+}
+
+pub(crate) struct Suppressed {
+    tracking: bool,
+}
+
+impl Drop for Suppressed {
+    fn drop(&mut self) {
+        TRACKING.set(self.tracking);
+    }
+}
+
+/// Counts alignment cells computed on this thread.
+pub(crate) fn add_cells(cells: u64) {
+    CELLS.set(CELLS.get() + cells);
+}
+
+/// Alignment cells computed on this thread so far.
+#[cfg(test)]
+pub(crate) fn cells() -> u64 {
+    CELLS.get()
+}
+
+impl Drop for Usage {
+    fn drop(&mut self) {
+        let Some((operation, start, cells, baseline)) = self.active else {
+            return;
+        };
+
+        TRACKING.set(false);
+        let wall_ns = start.elapsed().as_nanos() as u64;
+        let peak_bytes = (memory::peak() - baseline).max(0) as u64;
+        let cells = CELLS.get() - cells;
+
+        // Not holding the lock while calling back, which may call into the library again
+        let callback = *CALLBACK.lock().unwrap();
+        if let Some((callback, user_data)) = callback {
+            unsafe { callback(operation, wall_ns, peak_bytes, cells, user_data as *mut c_void) };
+        }
+    }
+}
+
+#[cfg(feature = "alloc-metrics")]
+pub(crate) use memory::{allocated, freed};
+
+/// Bytes allocated by each thread, net of what it freed, and the peak since tracking started.
+#[cfg(feature = "alloc-metrics")]
+mod memory {
+    use std::cell::Cell;
+
+    thread_local! {
+        static LIVE: Cell<i64> = const { Cell::new(0) };
+        static PEAK: Cell<i64> = const { Cell::new(0) };
+    }
+
+    pub(crate) fn allocated(bytes: usize) {
+        // Thread-locals may already be gone while a thread exits
+        let _ = LIVE.try_with(|live| {
+            live.set(live.get() + bytes as i64);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(live.get())));
+        });
+    }
+
+    pub(crate) fn freed(bytes: usize) {
+        let _ = LIVE.try_with(|live| live.set(live.get() - bytes as i64));
+    }
+
+    /// Resets the peak to the current net allocation, and returns it.
+    pub(super) fn start_peak() -> i64 {
+        let live = LIVE.get();
+        PEAK.set(live);
+
+        live
+    }
+
+    pub(super) fn peak() -> i64 {
+        PEAK.get()
+    }
+}
+
+#[cfg(not(feature = "alloc-metrics"))]
+mod memory {
+    pub(super) fn start_peak() -> i64 {
+        0
+    }
+
+    pub(super) fn peak() -> i64 {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::testing::{add, graph_of};
+
+    thread_local! {
+        static COMMITS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe extern "C" fn count(operation: PoastaOperation, _wall_ns: u64, _peak_bytes: u64, _cells: u64, _user_data: *mut c_void) {
+        if operation == PoastaOperation::Commit {
+            COMMITS.set(COMMITS.get() + 1);
+        }
+    }
+
+    #[test]
+    fn copying_a_shared_graph_reports_no_commits() {
+        let mut graph = graph_of(&["ACGTTGCA", "ACGATGCA", "ACGTTCCA"]);
+        let clone = graph.shallow_clone();
+
+        poasta_set_resource_callback(Some(count), ptr::null_mut());
+        add(&mut graph, "ACGATGCAT");
+        poasta_set_resource_callback(None, ptr::null_mut());
+
+        assert_eq!(COMMITS.get(), 1);
+        assert!(!Arc::ptr_eq(&graph.graph, &clone.graph));
+    }
+}
@@ -0,0 +1,219 @@
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+
+use poasta::aligner::{AlignedPair, Alignment};
+
+use crate::error;
+use crate::filter::LENGTH_OUTLIER;
+use crate::graph::NodeIx;
+use crate::reference::backbone_path;
+use crate::strand::PoastaStrand;
+use crate::trace::{self, PoastaTraceLevel};
+use crate::{add_sequence_uniform, is_valid_name, metrics, PoastaGraph, Scoring, SequenceInfo};
+
+const FLAG_UNMAPPED: u16 = 0x4;
+const FLAG_REVERSE: u16 = 0x10;
+const FLAG_SECONDARY: u16 = 0x100;
+const FLAG_SUPPLEMENTARY: u16 = 0x800;
+
+/// Adds the read of a SAM record (one line of SAM text, NUL-terminated) to the graph, with weight
+/// 1 for every base, named by its QNAME, and records its strand from the flags.
+/// If the graph has a backbone (see poasta_graph_set_backbone), the record is mapped and the
+/// graph neither trims primers nor rotates circular sequences, the read is added along the
+/// alignment given by its position and CIGAR on the backbone instead of being aligned, which is
+/// much faster. Otherwise it is aligned like in poasta_add_sequence.
+/// Returns the same status codes as poasta_add_sequence, and -1 for header lines, secondary and
+/// supplementary records and records without a sequence.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_add_from_sam(
+    graph: *mut PoastaGraph,
+    sam_line: *const c_char,
+    mismatch_score: i32,
+    gap_extend: i32,
+    gap_open: i32,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || sam_line.is_null() {
+            metrics::record_status(-1);
+            return error::fail(-1, "graph or SAM line is NULL");
+        }
+
+        let Some(record) = SamRecord::parse(unsafe { CStr::from_ptr(sam_line) }.to_bytes()) else {
+            metrics::record_status(-1);
+            return error::fail(-1, "not a SAM record with a sequence");
+        };
+
+        let scoring = match Scoring::affine(mismatch_score, gap_extend, gap_open) {
+            Ok(scoring) => scoring,
+            Err(status) => return metrics::record_status(status),
+        };
+        let info = SequenceInfo {
+            name: record.name.map(str::to_string),
+            strand: record.strand(),
+            ..SequenceInfo::default()
+        };
+
+        let graph_ref = unsafe { &mut *graph };
+        let hint = (graph_ref.primers.is_none() && !graph_ref.circular)
+            .then(|| backbone_path(graph_ref).and_then(|backbone| record.alignment(backbone)))
+            .flatten();
+
+        let Some(alignment) = hint else {
+            return unsafe { add_sequence_uniform(graph, record.seq.as_ptr() as *const c_char, record.seq.len(), 1, scoring, info) };
+        };
+
+        let _span = trace::span(PoastaTraceLevel::Info, c"add_sequence");
+        let status = if graph_ref.length_filter.is_some_and(|filter| !filter.accepts(graph_ref, record.seq.len())) {
+            LENGTH_OUTLIER
+        } else {
+            let weights = vec![1; record.seq.len()];
+            graph_ref.commit(record.seq, &weights, Some(&alignment), scoring, info)
+        };
+        metrics::record_status(status);
+
+        status
+    })
+}
+
+/// The fields of a SAM record used to add its read.
+struct SamRecord<'a> {
+    /// QNAME, None if it is `*`.
+    name: Option<&'a str>,
+    flags: u16,
+
+    /// 0-based position of the first aligned base on the reference.
+    pos: usize,
+    cigar: &'a [u8],
+    seq: &'a [u8],
+}
+
+impl<'a> SamRecord<'a> {
+    /// None for header lines, secondary and supplementary records, and records without sequence.
+    fn parse(line: &'a [u8]) -> Option<Self> {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.starts_with(b"@") {
+            return None;
+        }
+
+        let fields: Vec<&[u8]> = line.split(|c| *c == b'\t').collect();
+        if fields.len() < 11 {
+            return None;
+        }
+
+        let flags: u16 = std::str::from_utf8(fields[1]).ok()?.parse().ok()?;
+        let pos: usize = std::str::from_utf8(fields[3]).ok()?.parse().ok()?;
+        let seq = fields[9];
+        if flags & (FLAG_SECONDARY | FLAG_SUPPLEMENTARY) != 0 || seq == b"*" || seq.is_empty() {
+            return None;
+        }
+
+        let name = std::str::from_utf8(fields[0]).ok().filter(|name| *name != "*" && is_valid_name(name));
+
+        Some(SamRecord { name, flags, pos: pos.saturating_sub(1), cigar: fields[5], seq })
+    }
+
+    fn is_mapped(&self) -> bool {
+        self.flags & FLAG_UNMAPPED == 0 && self.cigar != b"*"
+    }
+
+    fn strand(&self) -> PoastaStrand {
+        if !self.is_mapped() {
+            PoastaStrand::Unknown
+        } else if self.flags & FLAG_REVERSE != 0 {
+            PoastaStrand::Reverse
+        } else {
+            PoastaStrand::Forward
+        }
+    }
+
+    /// Alignment of the read to the backbone nodes given by its position and CIGAR, or None if
+    /// the read is unmapped or the CIGAR doesn't fit the read and backbone.
+    fn alignment(&self, backbone: &[NodeIx]) -> Option<Alignment<NodeIx>> {
+        if !self.is_mapped() {
+            return None;
+        }
+
+        let mut alignment = Vec::with_capacity(self.seq.len());
+        let (mut ref_pos, mut qpos) = (self.pos, 0);
+        let mut count = 0usize;
+
+        for &c in self.cigar {
+            if c.is_ascii_digit() {
+                count = count.checked_mul(10)?.checked_add((c - b'0') as usize)?;
+                continue;
+            }
+
+            match c {
+                b'M' | b'=' | b'X' => {
+                    let nodes = backbone.get(ref_pos..ref_pos.checked_add(count)?)?;
+                    alignment.extend(nodes.iter().zip(qpos..).map(|(node, q)| AlignedPair::new(Some(*node), Some(q))));
+                    ref_pos += count;
+                    qpos += count;
+                },
+                b'I' | b'S' => {
+                    alignment.extend((qpos..qpos + count).map(|q| AlignedPair::new(None, Some(q))));
+                    qpos += count;
+                },
+                b'D' | b'N' => ref_pos += count,
+                b'H' | b'P' => (),
+                _ => return None,
+            }
+
+            count = 0;
+        }
+
+        (qpos == self.seq.len() && ref_pos <= backbone.len()).then_some(alignment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reference::poasta_graph_init_reference;
+    use crate::resource;
+
+    const REFERENCE: &str = "ACGTTGCAGGATCCTTAGCA";
+
+    fn add_sam(graph: &mut PoastaGraph, line: &CStr) -> c_int {
+        unsafe { poasta_add_from_sam(graph, line.as_ptr(), 4, 2, 6) }
+    }
+
+    #[test]
+    fn mapped_records_follow_their_cigar_on_the_backbone() {
+        let mut graph = PoastaGraph::new();
+        assert_eq!(unsafe { poasta_graph_init_reference(&mut graph, REFERENCE.as_ptr().cast(), REFERENCE.len(), 1) }, 0);
+
+        // GCAGG at position 6, a deleted A, then TCCATT with an inserted A
+        let cells = resource::cells();
+        assert_eq!(add_sam(&mut graph, c"read1\t16\tref\t6\t60\t5M1D3M1I2M\t*\t0\t0\tGCAGGTCCATT\t*"), 0);
+        assert_eq!(resource::cells(), cells);
+
+        let backbone = &graph.sequences[0].path;
+        let record = &graph.sequences[1];
+        assert_eq!(record.info.name.as_deref(), Some("read1"));
+        assert_eq!(record.info.strand, PoastaStrand::Reverse);
+        assert_eq!(record.path[..5], backbone[5..10]);
+        assert_eq!(record.path[5..8], backbone[11..14]);
+        assert!(!backbone.contains(&record.path[8]));
+        assert_eq!(record.path[9..], backbone[14..16]);
+    }
+
+    #[test]
+    fn only_primary_records_with_a_sequence_are_added() {
+        let mut graph = PoastaGraph::new();
+        let lines = [
+            c"@HD\tVN:1.6",
+            c"read1\t256\tref\t1\t60\t4M\t*\t0\t0\tACGT\t*",
+            c"read1\t2048\tref\t1\t60\t4M\t*\t0\t0\tACGT\t*",
+            c"read1\t0\tref\t1\t60\t4M\t*\t0\t0\t*\t*",
+        ];
+        for line in lines {
+            assert_eq!(add_sam(&mut graph, line), -1, "{line:?}");
+        }
+
+        // Without a backbone, reads are aligned
+        assert_eq!(add_sam(&mut graph, c"read2\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\t*\n"), 0);
+        assert_eq!(graph.sequences[0].info.strand, PoastaStrand::Unknown);
+    }
+}
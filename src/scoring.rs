@@ -0,0 +1,613 @@
+//! Reusable scoring configurations with per-pair substitution costs.
+//!
+//! poasta's aligner only knows a single mismatch cost, so sequences added with a scoring
+//! configuration are aligned with the DP aligner.
+
+use std::collections::HashSet;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::alphabet::PoastaAlphabet;
+use crate::coding::iupac_bases;
+use crate::error::{self, PoastaError};
+use crate::matrices;
+use crate::{add_sequence_uniform, metrics, PoastaGraph, Scoring, SequenceInfo};
+
+/// Status returned when a scoring configuration is inconsistent.
+pub(crate) const INVALID_SCORING: c_int = PoastaError::InvalidScoring as c_int;
+
+/// Records INVALID_SCORING as the last error, and returns it.
+pub(crate) fn invalid_scoring() -> c_int {
+    error::fail(INVALID_SCORING, "matches must be free and mismatches cost at most two gaps")
+}
+
+/// Converts a cost given over the FFI to the 8 bits the aligners store costs in. Records -1 as the
+/// last error, naming the argument, if the cost is negative or above 255.
+pub(crate) fn checked_cost(name: &str, cost: i32) -> Result<u8, c_int> {
+    u8::try_from(cost).map_err(|_| error::fail(-1, &format!("{name} is {cost}, costs must be between 0 and 255")))
+}
+
+/// Opaque scoring configuration. Sequences added with it keep the costs they were aligned with,
+/// changing the configuration afterwards only affects sequences added later.
+pub struct PoastaScoring {
+    pub(crate) params: Arc<ScoringParams>,
+}
+
+/// How scoring configurations score gaps.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PoastaGapModel {
+    /// Each gapped base costs gap extend, without an opening cost, which is faster to align.
+    Linear = 0,
+    /// A gap of length `l` costs `gap_open + l * gap_extend` (the default).
+    #[default]
+    Affine = 1,
+    /// A gap costs the cheaper of two affine pieces, the second set with
+    /// poasta_scoring_set_long_gaps.
+    TwoPieceAffine = 2,
+}
+
+/// Substitution costs for every pair of symbols, together with a gap model.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ScoringParams {
+    /// Cost of aligning query symbol `b` to graph symbol `a`, at index `a * 256 + b`.
+    substitutions: Vec<u8>,
+
+    /// Cost of the mismatches without an override.
+    pub mismatch_score: u8,
+    pub gap_extend: u8,
+    pub gap_open: u8,
+
+    /// How gaps are scored, and the (gap extend, gap open) of the second piece of two-piece affine
+    /// gaps.
+    pub gap_model: PoastaGapModel,
+    pub long_gap: Option<(u8, u8)>,
+
+    /// Alphabet sequences scored with the configuration must be in, Auto for any. Not saved, since
+    /// graphs save their own alphabet.
+    #[serde(skip)]
+    pub alphabet: PoastaAlphabet,
+}
+
+impl ScoringParams {
+    /// Like `new`, from costs given over the FFI (see checked_cost).
+    pub(crate) fn checked(mismatch_score: i32, gap_extend: i32, gap_open: i32) -> Result<Self, c_int> {
+        Ok(ScoringParams::new(
+            checked_cost("mismatch_score", mismatch_score)?,
+            checked_cost("gap_extend", gap_extend)?,
+            checked_cost("gap_open", gap_open)?,
+        ))
+    }
+
+    pub(crate) fn new(mismatch_score: u8, gap_extend: u8, gap_open: u8) -> Self {
+        let substitutions = (0..256 * 256)
+            .map(|ix| if ix / 256 == ix % 256 { 0 } else { mismatch_score })
+            .collect();
+
+        ScoringParams {
+            substitutions,
+            mismatch_score,
+            gap_extend,
+            gap_open,
+            gap_model: PoastaGapModel::Affine,
+            long_gap: None,
+            alphabet: PoastaAlphabet::Auto,
+        }
+    }
+
+    /// Costs from `symbols.len()` x `symbols.len()` `costs`, row by row by graph symbol, for both
+    /// cases of letters. Pairs with other symbols cost as much as the most expensive pair.
+    pub(crate) fn from_matrix(symbols: &[u8], costs: &[u8], gap_extend: u8, gap_open: u8) -> Self {
+        let n = symbols.len();
+        let highest = costs.iter().copied().max().unwrap_or(0);
+        let mut params = ScoringParams::new(highest, gap_extend, gap_open);
+        for (i, a) in symbols.iter().enumerate() {
+            for (j, b) in symbols.iter().enumerate() {
+                for a in [a.to_ascii_uppercase(), a.to_ascii_lowercase()] {
+                    for b in [b.to_ascii_uppercase(), b.to_ascii_lowercase()] {
+                        params.set_substitution(a, b, costs[i * n + j]);
+                    }
+                }
+            }
+        }
+
+        params
+    }
+
+    pub(crate) fn substitution(&self, graph_symbol: u8, query_symbol: u8) -> u8 {
+        self.substitutions[graph_symbol as usize * 256 + query_symbol as usize]
+    }
+
+    /// Sets the cost of aligning `query_symbol` to `graph_symbol`, but not the other way around.
+    pub(crate) fn set_substitution(&mut self, graph_symbol: u8, query_symbol: u8, score: u8) {
+        self.substitutions[graph_symbol as usize * 256 + query_symbol as usize] = score;
+    }
+
+    /// Highest substitution cost that can still be chosen over a deletion followed by an
+    /// insertion.
+    fn max_substitution(&self) -> u32 {
+        let cheapest = self.gaps().iter().map(|(extend, open)| *open as u32 + *extend as u32).min().unwrap();
+
+        2 * cheapest
+    }
+
+    /// (gap extend, gap open) of each gap piece of the gap model; a gap costs the cheapest.
+    pub(crate) fn gaps(&self) -> Vec<(u8, u8)> {
+        match (self.gap_model, self.long_gap) {
+            (PoastaGapModel::Linear, _) => vec![(self.gap_extend, 0)],
+            (PoastaGapModel::TwoPieceAffine, Some(long_gap)) => vec![(self.gap_extend, self.gap_open), long_gap],
+            _ => vec![(self.gap_extend, self.gap_open)],
+        }
+    }
+
+    /// Whether the matrix makes sense with the gap model: matches are free and every substitution
+    /// is at most as expensive as replacing it by two gaps.
+    pub(crate) fn is_valid(&self) -> bool {
+        let max_substitution = self.max_substitution();
+
+        (0..=255u8).all(|a| self.substitution(a, a) == 0)
+            && self.substitutions.iter().all(|&score| score as u32 <= max_substitution)
+    }
+}
+
+/// Creates a scoring configuration where every mismatch costs `mismatch_score`, with the given
+/// affine gap penalties. Free it with poasta_scoring_free.
+/// Returns NULL with last error -1 if a cost is negative or above 255.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_scoring_create(mismatch_score: i32, gap_extend: i32, gap_open: i32) -> *mut PoastaScoring {
+    error::catch(|| {
+        let Ok(params) = ScoringParams::checked(mismatch_score, gap_extend, gap_open) else {
+            return ptr::null_mut();
+        };
+
+        Box::into_raw(Box::new(PoastaScoring { params: Arc::new(params) }))
+    })
+}
+
+/// A full substitution matrix, e.g., 4x4 for DNA or a 24x24 protein table.
+#[repr(C)]
+pub struct PoastaSubstitutionMatrix {
+    /// The symbols of the rows and columns, e.g., "ACGT". Letters stand for both cases.
+    pub symbols: *const c_char,
+    pub num_symbols: usize,
+
+    /// `num_symbols * num_symbols` costs, row by row: `costs[i * num_symbols + j]` is the cost of
+    /// aligning `symbols[j]` in a sequence to `symbols[i]` in the graph.
+    pub costs: *const i32,
+}
+
+/// Creates a scoring configuration from a substitution matrix, e.g., to make transitions cheaper
+/// than transversions. Pairs with a symbol outside the matrix cost as much as its most expensive
+/// substitution. Free it with poasta_scoring_free.
+/// Returns NULL with last error -1 for NULL arrays, repeated symbols or costs that are negative or
+/// above 255, and -6 if a cost is inconsistent with the gap model (a nonzero cost for a match, or a
+/// cost above that of a deletion plus an insertion).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_scoring_create_matrix(
+    matrix: PoastaSubstitutionMatrix,
+    gap_extend: i32,
+    gap_open: i32,
+) -> *mut PoastaScoring {
+    error::catch(|| {
+        let n = matrix.num_symbols;
+        if n > 0 && (matrix.symbols.is_null() || matrix.costs.is_null()) {
+            error::fail(-1, "matrix symbols or costs are NULL");
+            return ptr::null_mut();
+        }
+
+        let (symbols, costs) = match n {
+            0 => (&[][..], &[][..]),
+            _ => unsafe { (slice::from_raw_parts(matrix.symbols as *const u8, n), slice::from_raw_parts(matrix.costs, n * n)) },
+        };
+
+        let mut seen = HashSet::new();
+        if let Some(symbol) = symbols.iter().find(|symbol| !seen.insert(symbol.to_ascii_uppercase())) {
+            error::fail(-1, &format!("{} is in the matrix twice", symbol.escape_ascii()));
+            return ptr::null_mut();
+        }
+
+        let costs: Result<Vec<u8>, c_int> = costs.iter().map(|&cost| checked_cost("a matrix cost", cost)).collect();
+        let (Ok(costs), Ok(gap_extend), Ok(gap_open)) =
+            (costs, checked_cost("gap_extend", gap_extend), checked_cost("gap_open", gap_open))
+        else {
+            return ptr::null_mut();
+        };
+
+        into_config(ScoringParams::from_matrix(symbols, &costs, gap_extend, gap_open))
+    })
+}
+
+/// Built-in protein substitution matrices.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)] // Only C callers construct presets
+pub enum PoastaMatrixPreset {
+    /// For moderately diverged proteins, BLAST's default.
+    Blosum62 = 0,
+    /// For distantly related proteins.
+    Blosum45 = 1,
+    /// For distantly related proteins, from evolutionary models.
+    Pam250 = 2,
+}
+
+/// Creates a scoring configuration from a built-in protein matrix over the 20 amino acids, B, Z,
+/// X and `*`. Its similarity scores are turned into costs: a pair costs how much less similar it
+/// is than the average of its two symbols aligned to themselves, rounded up, and pairs with other
+/// symbols cost as much as the most expensive pair. Sequences added with it must be proteins, and
+/// a graph without an alphabet set (see poasta_graph_set_alphabet) becomes a protein graph when
+/// its first sequence is added with it. BLAST's protein gap costs (gap extend 1, gap open 11, see
+/// poasta_alphabet_default_params) suit all presets. Free it with poasta_scoring_free.
+/// Returns NULL with last error -1 if a gap cost is negative or above 255, and -6 if the gaps are
+/// too cheap for the matrix (the most expensive pair would cost more than a deletion plus an
+/// insertion).
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_scoring_create_preset(preset: PoastaMatrixPreset, gap_extend: i32, gap_open: i32) -> *mut PoastaScoring {
+    error::catch(|| {
+        let (Ok(gap_extend), Ok(gap_open)) = (checked_cost("gap_extend", gap_extend), checked_cost("gap_open", gap_open)) else {
+            return ptr::null_mut();
+        };
+
+        let matrix = match preset {
+            PoastaMatrixPreset::Blosum62 => &matrices::BLOSUM62,
+            PoastaMatrixPreset::Blosum45 => &matrices::BLOSUM45,
+            PoastaMatrixPreset::Pam250 => &matrices::PAM250,
+        };
+
+        let params = ScoringParams::from_matrix(matrices::SYMBOLS, &matrices::costs(matrix), gap_extend, gap_open);
+        into_config(ScoringParams { alphabet: PoastaAlphabet::Protein, ..params })
+    })
+}
+
+/// Hands the parameters over to C as a configuration if they are valid, NULL otherwise.
+fn into_config(params: ScoringParams) -> *mut PoastaScoring {
+    if !params.is_valid() {
+        invalid_scoring();
+        return ptr::null_mut();
+    }
+
+    Box::into_raw(Box::new(PoastaScoring { params: Arc::new(params) }))
+}
+
+/// Frees a scoring configuration.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_scoring_free(config: *mut PoastaScoring) {
+    error::catch(|| {
+        if !config.is_null() {
+            unsafe {
+                drop(Box::from_raw(config));
+            }
+        }
+    })
+}
+
+/// Sets the cost of aligning `a` to `b` (in both directions and for both cases of letters), e.g.,
+/// to make C<->T cheap for bisulfite-converted reads.
+/// Returns 0 on success, -1 for invalid arguments (including costs that are negative or above 255)
+/// and -6 if the cost is inconsistent with the gap model (a nonzero cost for a match, or a cost
+/// above that of a deletion plus an insertion).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_scoring_set_pair(config: *mut PoastaScoring, a: c_char, b: c_char, score: i32) -> c_int {
+    error::catch(|| {
+        if config.is_null() {
+            return error::fail(-1, "scoring configuration is NULL");
+        }
+        let score = match checked_cost("score", score) {
+            Ok(score) => score,
+            Err(status) => return status,
+        };
+
+        let config = unsafe { &mut *config };
+        let (a, b) = (a as u8, b as u8);
+        if (a.eq_ignore_ascii_case(&b) && score != 0) || score as u32 > config.params.max_substitution() {
+            return invalid_scoring();
+        }
+
+        let params = Arc::make_mut(&mut config.params);
+        for a in [a.to_ascii_uppercase(), a.to_ascii_lowercase()] {
+            for b in [b.to_ascii_uppercase(), b.to_ascii_lowercase()] {
+                params.set_substitution(a, b, score);
+                params.set_substitution(b, a, score);
+            }
+        }
+
+        0
+    })
+}
+
+/// Makes N cost `cost` against any nucleotide or other ambiguity code instead of a mismatch, so
+/// masked bases neither attract nor repel alignment, e.g., with a cost of 0 or half a mismatch.
+/// With `all_codes`, every IUPAC ambiguity code (R, Y, S, W, K, M, B, D, H, V and N) costs `cost`
+/// against the bases it stands for and the codes sharing one of them with it. Both cases, and U
+/// for T, are set. Returns 0 on success, -1 if the configuration is NULL or the cost is negative or
+/// above 255, and -6 if the cost is above that of a deletion plus an insertion.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_scoring_set_ambiguity(config: *mut PoastaScoring, cost: i32, all_codes: bool) -> c_int {
+    error::catch(|| {
+        if config.is_null() {
+            return error::fail(-1, "scoring configuration is NULL");
+        }
+        let cost = match checked_cost("cost", cost) {
+            Ok(cost) => cost,
+            Err(status) => return status,
+        };
+
+        let config = unsafe { &mut *config };
+        if cost as u32 > config.params.max_substitution() {
+            return invalid_scoring();
+        }
+
+        let codes: &[u8] = if all_codes { b"RYSWKMBDHVN" } else { b"N" };
+        let params = Arc::make_mut(&mut config.params);
+        for &code in codes {
+            let bases = iupac_bases(code).unwrap();
+            for &other in b"ACGTURYSWKMBDHVN" {
+                let overlaps = iupac_bases(other).unwrap().iter().any(|base| bases.contains(base));
+                if other == code || !overlaps {
+                    continue;
+                }
+
+                for a in [code, code.to_ascii_lowercase()] {
+                    for b in [other, other.to_ascii_lowercase()] {
+                        params.set_substitution(a, b, cost);
+                        params.set_substitution(b, a, cost);
+                    }
+                }
+            }
+        }
+
+        0
+    })
+}
+
+/// Adds a second gap piece with `gap_extend2` and `gap_open2` to the configuration and selects the
+/// TwoPieceAffine gap model, where each gap costs whichever piece is cheaper for its length, to
+/// model the long indels of ONT and PacBio reads. Setting it again replaces the piece.
+/// Returns 0 on success, -1 if the configuration is NULL, a cost is negative or above 255 or
+/// `gap_extend2` isn't below the configuration's gap extend cost (so the piece would never be
+/// cheaper for long gaps), and -6 if a substitution cost becomes higher than a deletion plus an
+/// insertion with the cheaper short gaps, leaving the configuration unchanged.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_scoring_set_long_gaps(config: *mut PoastaScoring, gap_extend2: i32, gap_open2: i32) -> c_int {
+    error::catch(|| {
+        if config.is_null() {
+            return error::fail(-1, "scoring configuration is NULL");
+        }
+        let (gap_extend2, gap_open2) = match (checked_cost("gap_extend2", gap_extend2), checked_cost("gap_open2", gap_open2)) {
+            (Ok(extend), Ok(open)) => (extend, open),
+            (Err(status), _) | (_, Err(status)) => return status,
+        };
+
+        let config = unsafe { &mut *config };
+        if gap_extend2 >= config.params.gap_extend {
+            return error::fail(-1, "the second gap piece must extend gaps more cheaply than the first");
+        }
+
+        let params = ScoringParams {
+            gap_model: PoastaGapModel::TwoPieceAffine,
+            long_gap: Some((gap_extend2, gap_open2)),
+            ..(*config.params).clone()
+        };
+        if !params.is_valid() {
+            return invalid_scoring();
+        }
+
+        config.params = Arc::new(params);
+
+        0
+    })
+}
+
+/// Selects how the configuration scores gaps. With Linear, gap open costs are ignored. Switching
+/// models keeps the gap costs, so switching back restores the previous model.
+/// Returns 0 on success, -1 if the configuration is NULL or TwoPieceAffine is selected before a
+/// second piece was set, and -6 if a substitution cost becomes higher than a deletion plus an
+/// insertion with the model's gaps, leaving the configuration unchanged.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_scoring_set_gap_model(config: *mut PoastaScoring, model: PoastaGapModel) -> c_int {
+    error::catch(|| {
+        if config.is_null() {
+            return error::fail(-1, "scoring configuration is NULL");
+        }
+
+        let config = unsafe { &mut *config };
+        if model == PoastaGapModel::TwoPieceAffine && config.params.long_gap.is_none() {
+            return error::fail(-1, "two-piece gaps need a second piece, see poasta_scoring_set_long_gaps");
+        }
+
+        let params = ScoringParams { gap_model: model, ..(*config.params).clone() };
+        if !params.is_valid() {
+            return invalid_scoring();
+        }
+
+        config.params = Arc::new(params);
+
+        0
+    })
+}
+
+/// Checks that the configuration is consistent with its gap model.
+/// Returns 0 if it is, -1 for invalid arguments and -6 otherwise.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_scoring_validate(config: *const PoastaScoring) -> c_int {
+    error::catch(|| {
+        if config.is_null() {
+            return error::fail(-1, "scoring configuration is NULL");
+        }
+
+        let config = unsafe { &*config };
+        if config.params.is_valid() { 0 } else { invalid_scoring() }
+    })
+}
+
+/// Adds a sequence to the graph with a specified weight, scored with the given configuration
+/// (Global alignment).
+/// Returns the same codes as poasta_add_sequence, or -6 if the configuration is invalid, in which
+/// case the graph is left unchanged.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_add_sequence_scoring(
+    graph: *mut PoastaGraph,
+    seq: *const c_char,
+    len: usize,
+    weight: u32,
+    config: *const PoastaScoring,
+) -> c_int {
+    error::catch(|| {
+        if config.is_null() {
+            metrics::record_status(-1);
+            return error::fail(-1, "scoring configuration is NULL");
+        }
+
+        let config = unsafe { &*config };
+        if !config.params.is_valid() {
+            metrics::record_status(INVALID_SCORING);
+            return invalid_scoring();
+        }
+
+        let scoring = Scoring::Custom(Arc::clone(&config.params));
+        unsafe { add_sequence_uniform(graph, seq, len, weight as usize, scoring, SequenceInfo::default()) }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+    use crate::dp::DpBuffers;
+    use crate::poasta_add_sequence;
+    use crate::testing::graph_of;
+
+    const REFERENCE: &str = "ACGTACGTACGTACGTACGT";
+
+    /// Cost of aligning `read` to a graph of REFERENCE with the configuration.
+    fn cost(config: *const PoastaScoring, read: &str) -> i64 {
+        let mut graph = graph_of(&[REFERENCE]);
+        let scoring = Scoring::Custom(Arc::clone(&unsafe { &*config }.params));
+        let (status, cost) = graph.add_sequence_scored(read.as_bytes(), &vec![1; read.len()], scoring, SequenceInfo::default(), &mut DpBuffers::default());
+        assert_eq!(status, 0);
+
+        cost.unwrap()
+    }
+
+    #[test]
+    fn pair_overrides_are_symmetric_and_case_insensitive() {
+        let config = poasta_scoring_create(4, 2, 6);
+        assert_eq!(unsafe { poasta_scoring_set_pair(config, b'c' as c_char, b'T' as c_char, 1) }, 0);
+
+        let params = &unsafe { &*config }.params;
+        for (a, b) in [(b'C', b'T'), (b'T', b'C'), (b'c', b't'), (b't', b'C')] {
+            assert_eq!(params.substitution(a, b), 1);
+        }
+        assert_eq!(params.substitution(b'C', b'A'), 4);
+        assert_eq!(unsafe { poasta_scoring_validate(config) }, 0);
+        unsafe { poasta_scoring_free(config) };
+    }
+
+    #[test]
+    fn inconsistent_costs_are_rejected() {
+        // A deletion plus an insertion costs 2 * (6 + 2)
+        let config = poasta_scoring_create(4, 2, 6);
+        assert_eq!(unsafe { poasta_scoring_set_pair(config, b'A' as c_char, b'a' as c_char, 1) }, INVALID_SCORING);
+        assert_eq!(unsafe { poasta_scoring_set_pair(config, b'A' as c_char, b'G' as c_char, 17) }, INVALID_SCORING);
+        assert_eq!(unsafe { poasta_scoring_set_pair(config, b'A' as c_char, b'G' as c_char, 16) }, 0);
+        assert_eq!(unsafe { poasta_scoring_set_pair(config, b'A' as c_char, b'G' as c_char, 256) }, -1);
+        assert!(poasta_scoring_create(4, -2, 6).is_null());
+
+        // Configurations made invalid behind the checks can't add sequences
+        Arc::make_mut(&mut unsafe { &mut *config }.params).set_substitution(b'A', b'A', 1);
+        assert_eq!(unsafe { poasta_scoring_validate(config) }, INVALID_SCORING);
+        let mut graph = graph_of(&["ACGT"]);
+        let status = unsafe { poasta_add_sequence_scoring(&mut graph, c"ACGT".as_ptr(), 4, 1, config) };
+        assert_eq!(status, INVALID_SCORING);
+        assert_eq!(graph.sequences.len(), 1);
+        unsafe { poasta_scoring_free(config) };
+    }
+
+    #[test]
+    fn ambiguity_codes_cost_as_set_against_their_bases() {
+        let config = poasta_scoring_create(4, 2, 6);
+        assert_eq!(unsafe { poasta_scoring_set_ambiguity(config, 1, false) }, 0);
+        let params = &unsafe { &*config }.params;
+        assert_eq!([params.substitution(b'N', b'A'), params.substitution(b'u', b'n'), params.substitution(b'R', b'N')], [1, 1, 1]);
+        assert_eq!([params.substitution(b'R', b'A'), params.substitution(b'A', b'C')], [4, 4]);
+
+        // R (A or G) overlaps A, G, N and S (C or G), but not C, T or Y (C or T)
+        assert_eq!(unsafe { poasta_scoring_set_ambiguity(config, 2, true) }, 0);
+        let params = &unsafe { &*config }.params;
+        for other in [b'A', b'g', b'N', b'S'] {
+            assert_eq!(params.substitution(b'R', other), 2, "{}", other as char);
+        }
+        for other in [b'C', b't', b'Y'] {
+            assert_eq!(params.substitution(b'R', other), 4, "{}", other as char);
+        }
+        assert_eq!(params.substitution(b'R', b'R'), 0);
+
+        assert_eq!(unsafe { poasta_scoring_set_ambiguity(config, 17, true) }, INVALID_SCORING);
+        assert_eq!(unsafe { poasta_scoring_set_ambiguity(ptr::null_mut(), 1, true) }, -1);
+        unsafe { poasta_scoring_free(config) };
+    }
+
+    #[test]
+    fn gaps_cost_the_cheaper_piece() {
+        // An eight base deletion, and a one base deletion
+        let (long, short) = ("ACGTACGTACGT", "ACGTACGTACGTACGTACG");
+        let config = poasta_scoring_create(4, 2, 6);
+        assert_eq!((cost(config, long), cost(config, short)), (22, 8));
+
+        assert_eq!(unsafe { poasta_scoring_set_long_gaps(config, 1, 15) }, 0);
+        assert_eq!((cost(config, long), cost(config, short)), (22, 8));
+        assert_eq!(unsafe { poasta_scoring_set_long_gaps(config, 1, 10) }, 0);
+        assert_eq!((cost(config, long), cost(config, short)), (18, 8));
+
+        // The second piece must extend more cheaply, and keep substitutions consistent
+        assert_eq!(unsafe { poasta_scoring_set_long_gaps(config, 2, 0) }, -1);
+        assert_eq!(unsafe { poasta_scoring_set_pair(config, b'A' as c_char, b'G' as c_char, 16) }, 0);
+        assert_eq!(unsafe { poasta_scoring_set_long_gaps(config, 1, 5) }, INVALID_SCORING);
+        assert_eq!(unsafe { &*config }.params.long_gap, Some((1, 10)));
+        unsafe { poasta_scoring_free(config) };
+    }
+
+    #[test]
+    fn gap_models_can_be_switched() {
+        let (long, short) = ("ACGTACGTACGT", "ACGTACGTACGTACGTACG");
+        let config = poasta_scoring_create(4, 2, 6);
+        assert_eq!(unsafe { poasta_scoring_set_gap_model(config, PoastaGapModel::TwoPieceAffine) }, -1);
+
+        // Linear gaps ignore the opening cost
+        assert_eq!(unsafe { poasta_scoring_set_gap_model(config, PoastaGapModel::Linear) }, 0);
+        assert_eq!((cost(config, long), cost(config, short)), (16, 2));
+
+        // Switching back restores the previous costs, including the second piece
+        assert_eq!(unsafe { poasta_scoring_set_long_gaps(config, 1, 10) }, 0);
+        assert_eq!(unsafe { poasta_scoring_set_gap_model(config, PoastaGapModel::Affine) }, 0);
+        assert_eq!((cost(config, long), cost(config, short)), (22, 8));
+        assert_eq!(unsafe { poasta_scoring_set_gap_model(config, PoastaGapModel::TwoPieceAffine) }, 0);
+        assert_eq!(cost(config, long), 18);
+
+        // A mismatch costing more than two linear gaps is inconsistent
+        assert_eq!(unsafe { poasta_scoring_set_pair(config, b'A' as c_char, b'G' as c_char, 16) }, 0);
+        assert_eq!(unsafe { poasta_scoring_set_gap_model(config, PoastaGapModel::Linear) }, INVALID_SCORING);
+        assert_eq!(unsafe { &*config }.params.gap_model, PoastaGapModel::TwoPieceAffine);
+        unsafe { poasta_scoring_free(config) };
+    }
+
+    #[test]
+    fn costs_out_of_range_are_rejected_rather_than_truncated() {
+        assert_eq!([0, 255].map(|cost| checked_cost("gap_open", cost)), [Ok(0), Ok(255)]);
+        assert_eq!(checked_cost("gap_open", 260), Err(-1));
+        assert_eq!(checked_cost("gap_open", -1), Err(-1));
+        let message = unsafe { CStr::from_ptr(error::poasta_last_error_message()) };
+        assert_eq!(message, c"invalid argument: gap_open is -1, costs must be between 0 and 255");
+
+        // 260 would be a mismatch cost of 4 as a byte
+        let mut graph = graph_of(&[REFERENCE]);
+        let read = "ACGTACGTACCTACGTACGT";
+        assert_eq!(unsafe { poasta_add_sequence(&mut graph, read.as_ptr().cast(), read.len(), 260, 2, 6) }, -1);
+        assert!(poasta_scoring_create(260, 2, 6).is_null());
+        assert_eq!(graph.sequences.len(), 1);
+    }
+}
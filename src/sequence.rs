@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::slice;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::consensus::selected_consensus_sequence;
+use crate::error;
+use crate::graph::{NodeIx, PathWeight, Selection};
+use crate::pairwise::edit_distance;
+use crate::PoastaGraph;
+
+/// Includes or excludes the sequence at `index` (in insertion order) from consensus and MSA
+/// computation, without removing it from the graph. Inactive sequences still shape the graph
+/// that new sequences are aligned to. Returns -1 if the index is out of range.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_sequence_set_active(graph: *mut PoastaGraph, index: usize, active: bool) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let Some(record) = unsafe { &mut *graph }.sequences_mut().get_mut(index) else {
+            return error::fail(-1, "sequence index out of range");
+        };
+
+        record.info.masked = !active;
+
+        0
+    })
+}
+
+/// Measures the influence of the sequence at `index` on the consensus: returns the edit distance
+/// between the consensus with and without that sequence (regardless of whether it is currently
+/// active). Useful for contamination screening. Returns -1 if the index is out of range.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_loo_consensus_delta(graph: *const PoastaGraph, seq_index: usize) -> i64 {
+    error::catch(|| {
+        if graph.is_null() {
+            return i64::from(error::fail(-1, "graph is NULL"));
+        }
+
+        let graph = unsafe { &*graph };
+        if seq_index >= graph.sequences.len() {
+            return i64::from(error::fail(-1, "sequence index out of range"));
+        }
+
+        let with = selected_consensus_sequence(graph, Selection::toggled(seq_index, true));
+        let without = selected_consensus_sequence(graph, Selection::toggled(seq_index, false));
+
+        edit_distance(&with, &without) as i64
+    })
+}
+
+/// Counts `delta` more reads for the sequence at `index` (in insertion order), for reads the host
+/// already knows to be identical to it (e.g., from its own deduplication), without aligning them or
+/// adding sequences: the sequence's weights count once more per read in the consensus and other
+/// weighted output, and the bumped reads are kept when the graph is copied, rebuilt or saved.
+/// Returns 0 on success and -1 if the index is out of range or `delta` is 0.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_bump_path_weight(graph: *mut PoastaGraph, index: usize, delta: u32) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || delta == 0 {
+            return error::fail(-1, "graph is NULL or delta is 0");
+        }
+
+        let graph = unsafe { &mut *graph };
+        if index >= graph.sequences.len() {
+            return error::fail(-1, "sequence index out of range");
+        }
+
+        graph.sequences_mut()[index].info.copies += f64::from(delta);
+
+        0
+    })
+}
+
+/// Like poasta_bump_path_weight, but adds `delta` to the weight of every node of the path of
+/// `num_nodes` nodes at `nodes` (node indices, the segment names of poasta_get_gfa_with_paths and
+/// poasta_get_gfa_delta), which must be connected one after the other, e.g., a path an external
+/// exact matcher found. Bumping the same path again adds to its weight. The weight is kept as long
+/// as the path is, but not by graphs built by realigning the sequences.
+/// Returns 0 on success and -1 for invalid arguments, nodes that don't exist or aren't connected
+/// and a `delta` of 0.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_bump_node_path_weight(
+    graph: *mut PoastaGraph,
+    nodes: *const usize,
+    num_nodes: usize,
+    delta: u32,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || nodes.is_null() || num_nodes == 0 || delta == 0 {
+            return error::fail(-1, "graph or nodes is NULL, or num_nodes or delta is 0");
+        }
+
+        let graph = unsafe { &mut *graph };
+        let nodes = unsafe { slice::from_raw_parts(nodes, num_nodes) };
+        let (start, end) = (graph.graph.start_node(), graph.graph.end_node());
+        let path: Vec<NodeIx> = nodes.iter().map(|node| NodeIx::new(*node)).collect();
+        if let Some(node) = path.iter().find(|node| node.index() >= graph.graph.node_count_with_start_and_end() || **node == start || **node == end) {
+            return error::fail(-1, &format!("node {} doesn't exist", node.index()));
+        }
+
+        if let Some(pair) = path.windows(2).find(|pair| !graph.has_edges(pair)) {
+            return error::fail(-1, &format!("there is no edge from node {} to node {}", pair[0].index(), pair[1].index()));
+        }
+
+        match graph.path_weights.iter_mut().find(|bump| bump.path == path) {
+            Some(bump) => bump.weight += f64::from(delta),
+            None => graph.path_weights.push(PathWeight { path, weight: f64::from(delta) }),
+        }
+        graph.touch();
+
+        0
+    })
+}
+
+/// Removes the sequence at `index` (in insertion order) from the graph, e.g., to realign it after
+/// the graph was refined. poasta's graph can't be edited outside of poasta, so this rebuilds it
+/// from the other sequences along their paths: nothing is aligned again and they keep their
+/// alignments, but each of them is committed anew, which takes about as long as building the graph
+/// without the alignments. For leave-one-out analyses, poasta_sequence_set_active is much cheaper.
+/// Nodes only the removed sequence passed through are gone afterwards, and all weights are those
+/// of the other sequences. Later sequences move up one index and nodes are renumbered, so node IDs
+/// of earlier output no longer apply. Returns -1 if the index is out of range and -3 if the
+/// remaining sequences couldn't be added again, leaving the graph unchanged.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_remove_sequence(graph: *mut PoastaGraph, index: usize) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let graph = unsafe { &mut *graph };
+        if index >= graph.sequences.len() {
+            return error::fail(-1, "sequence index out of range");
+        }
+
+        match remove_sequence(graph, index) {
+            Ok(()) => 0,
+            Err(status) => status,
+        }
+    })
+}
+
+/// Like poasta_remove_sequence, for the first sequence named `name` (NUL-terminated) in the output,
+/// including made-up `seq_N` names. Returns -1 if there is none.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_remove_sequence_named(graph: *mut PoastaGraph, name: *const c_char) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || name.is_null() {
+            return error::fail(-1, "graph or name is NULL");
+        }
+
+        let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() else {
+            return error::fail(-1, "name isn't valid UTF-8");
+        };
+
+        let Some(index) = (0..unsafe { &*graph }.sequences.len()).find(|ix| unsafe { &*graph }.sequence_name(*ix) == name) else {
+            return error::fail(-1, &format!("no sequence is named {name}"));
+        };
+
+        unsafe { poasta_remove_sequence(graph, index) }
+    })
+}
+
+/// Rebuilds the graph from the other sequences along their paths, keeping everything that
+/// refers to their nodes or indices.
+pub(crate) fn remove_sequence(graph: &mut PoastaGraph, index: usize) -> Result<(), c_int> {
+    let mut copy = graph.empty_like();
+    let mut node_map = HashMap::new();
+    let aligned_nodes = |node| graph.graph.get_aligned_nodes(node);
+    copy.append_records(&graph.sequences[..index], aligned_nodes, &mut node_map)
+        .and_then(|()| copy.append_records(&graph.sequences[index + 1..], aligned_nodes, &mut node_map))
+        .map_err(|status| error::fail(status, "the remaining sequences can't be added again"))?;
+
+    copy.links = graph.links.iter()
+        .filter_map(|(from, to)| Some((*node_map.get(from)?, *node_map.get(to)?)))
+        .collect();
+    // Paths through the removed sequence's nodes or edges lose their weight
+    copy.path_weights = graph.path_weights.iter()
+        .filter_map(|bump| bump.translated(|node| node_map.get(&node).copied()))
+        .filter(|bump| copy.has_edges(&bump.path))
+        .collect();
+    copy.backbone = match graph.backbone {
+        Some(backbone) if backbone > index => Some(backbone - 1),
+        Some(backbone) if backbone < index => Some(backbone),
+        _ => None,
+    };
+    copy.masked_regions = graph.masked_regions.as_ref()
+        .map(|regions| regions.translated(|node| node_map.get(&node).copied()));
+    copy.cache.hits = graph.cache.hits;
+    copy.cache.misses = graph.cache.misses;
+    copy.user_data = graph.user_data;
+    copy.generation = graph.generation + 1;
+
+    *graph = copy;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::consensus::consensus_sequence;
+    use crate::serialize::{from_bytes, to_bytes};
+    use crate::testing::{consensus, graph_of, outputs};
+
+    const READS: [&str; 3] = ["ACGTTGCA", "ACGTTGCA", "ACGATGCA"];
+
+    #[test]
+    fn removing_a_sequence_is_like_never_adding_it() {
+        let reads = ["ACGTTGCA", "ACGATGCA", "ACGTTCCA", "ACGTTGCAT"];
+        for index in 0..reads.len() {
+            let mut graph = graph_of(&reads);
+            assert_eq!(unsafe { poasta_remove_sequence(&mut graph, index) }, 0);
+
+            let mut others = reads.to_vec();
+            others.remove(index);
+            assert_eq!(outputs(&graph), outputs(&graph_of(&others)), "removing {index}");
+        }
+    }
+
+    #[test]
+    fn inactive_sequences_are_left_out_of_consensus_and_msa() {
+        let mut graph = graph_of(&["ACGATGCA", "ACGATGCA", "ACGTTGCA", "ACGTTGCA", "ACGTTGCA"]);
+        let all = outputs(&graph);
+        assert_eq!(consensus(&graph), "ACGTTGCA");
+
+        for index in 2..4 {
+            assert_eq!(unsafe { poasta_sequence_set_active(&mut graph, index, false) }, 0);
+        }
+        let (consensus, msa, _) = outputs(&graph);
+        assert_eq!(consensus, "ACGATGCA");
+        assert_eq!(msa.matches('>').count(), 3);
+        assert!(!msa.contains(">seq_2") && !msa.contains(">seq_3"));
+
+        // Reactivating them restores the outputs
+        for index in 2..4 {
+            assert_eq!(unsafe { poasta_sequence_set_active(&mut graph, index, true) }, 0);
+        }
+        assert_eq!(outputs(&graph), all);
+        assert_eq!(unsafe { poasta_sequence_set_active(&mut graph, 5, false) }, -1);
+    }
+
+    #[test]
+    fn loo_consensus_delta_leaves_the_graph_as_it_was() {
+        let mut graph = graph_of(&["ACGTTGCA", "ACGATGCA", "ACGATCCA", "ACGAT"]);
+        graph.sequences_mut()[3].info.masked = true;
+        let (records, generation) = (Arc::as_ptr(&graph.sequences), graph.generation);
+
+        for index in 0..4 {
+            // The same as toggling the sequence on a copy
+            let mut copy = graph.shallow_clone();
+            unsafe { poasta_sequence_set_active(&mut copy, index, true) };
+            let with = consensus_sequence(&copy);
+            unsafe { poasta_sequence_set_active(&mut copy, index, false) };
+            let expected = edit_distance(&with, &consensus_sequence(&copy)) as i64;
+
+            assert_eq!(unsafe { poasta_loo_consensus_delta(&graph, index) }, expected);
+        }
+        assert_eq!(unsafe { poasta_loo_consensus_delta(&graph, 4) }, -1);
+
+        assert_eq!(graph.sequences.iter().map(|record| record.info.masked).collect::<Vec<_>>(), [false, false, false, true]);
+        assert_eq!((Arc::as_ptr(&graph.sequences), graph.generation), (records, generation));
+    }
+
+    #[test]
+    fn bumping_a_sequence_adds_weight_but_no_sequence() {
+        let mut graph = graph_of(&READS);
+        assert_eq!(consensus(&graph), "ACGTTGCA");
+
+        assert_eq!(unsafe { poasta_bump_path_weight(&mut graph, 2, 2) }, 0);
+        assert_eq!(graph.sequences.len(), READS.len());
+        assert_eq!(graph.graph.sequences.len(), READS.len());
+        assert_eq!(consensus(&graph), "ACGATGCA");
+
+        let loaded = from_bytes(&to_bytes(&graph)).unwrap();
+        assert_eq!(consensus(&loaded), "ACGATGCA");
+    }
+
+    #[test]
+    fn bumping_a_node_path_adds_weight_but_no_sequence() {
+        let mut graph = graph_of(&READS);
+        let nodes: Vec<usize> = graph.sequences[2].path.iter().map(|node| node.index()).collect();
+
+        assert_eq!(unsafe { poasta_bump_node_path_weight(&mut graph, nodes.as_ptr(), nodes.len(), 1) }, 0);
+        assert_eq!(unsafe { poasta_bump_node_path_weight(&mut graph, nodes.as_ptr(), nodes.len(), 1) }, 0);
+        assert_eq!(graph.path_weights.len(), 1);
+        assert_eq!(graph.sequences.len(), READS.len());
+        assert_eq!(consensus(&graph), "ACGATGCA");
+
+        let loaded = from_bytes(&to_bytes(&graph)).unwrap();
+        assert_eq!(consensus(&loaded), "ACGATGCA");
+    }
+
+    #[test]
+    fn bumped_node_paths_must_be_connected() {
+        let mut graph = graph_of(&READS);
+        let nodes: Vec<usize> = graph.sequences[0].path.iter().rev().map(|node| node.index()).collect();
+
+        assert_eq!(unsafe { poasta_bump_node_path_weight(&mut graph, nodes.as_ptr(), nodes.len(), 1) }, -1);
+        assert!(graph.path_weights.is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+//! Conversion of graphs to bytes and back, including all bookkeeping and configuration, such
+//! that a loaded graph behaves exactly like the saved one.
+//!
+//! The bytes start with a magic number for the file type, the format version that wrote them and
+//! the oldest version able to read them, followed by sections of
+//! `[tag u32 LE][length u64 LE][CRC-32 u32 LE][data]`.
+//! Readers skip sections with unknown tags, so later versions can add sections without breaking
+//! older readers, and bump the minimum reader version only for incompatible changes.
+
+use std::collections::{BTreeMap, HashMap};
+use std::os::raw::c_int;
+use std::ptr;
+use std::slice;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::error::{self, PoastaError};
+use crate::graph::NodeIx;
+use crate::trace::{self, PoastaTraceLevel};
+use crate::{free_c_array, into_c_array, PoastaGraph};
+
+/// The bytes are truncated, fail their checksum or aren't a serialized graph.
+pub(crate) const CORRUPTED: c_int = PoastaError::Corrupted as c_int;
+
+/// The bytes were written by a newer, incompatible version of the library.
+pub(crate) const UNSUPPORTED_VERSION: c_int = PoastaError::UnsupportedVersion as c_int;
+
+const MAGIC: &[u8; 8] = b"POAGRAPH";
+const FORMAT_VERSION: u16 = 1;
+
+/// Oldest format version that can read what this version writes.
+const MIN_READER_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + 4;
+const SECTION_HEADER_LEN: usize = 16;
+
+/// The graph with its sequences and configuration.
+const SECTION_GRAPH: u32 = 1;
+
+/// Struct to hold a serialized graph.
+#[repr(C)]
+pub struct PoastaBytes {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// Serializes the graph with its sequences, weights, metadata and configuration, so it can be
+/// loaded with poasta_graph_deserialize in a later run instead of realigning everything. The bytes
+/// can be read by later versions of the library. Returns an empty buffer if the graph is NULL.
+/// Caller must free the result with poasta_free_bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_serialize(graph: *const PoastaGraph) -> PoastaBytes {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaBytes { data: ptr::null_mut(), len: 0 };
+        }
+
+        let bytes = to_bytes(unsafe { &*graph });
+        let len = bytes.len();
+
+        PoastaBytes { data: into_c_array(bytes), len }
+    })
+}
+
+/// Loads a graph from `len` bytes written by poasta_graph_serialize. The graph behaves exactly
+/// like the serialized one, including the output of all functions and sequences added later. Its
+/// sequences are added again along their saved paths, without aligning them.
+/// Returns NULL with the reason as the last error: -1 if `buf` is NULL, -8 if the bytes are
+/// corrupted and -9 if they were written by a newer, incompatible version of the library.
+/// The caller must free the graph with poasta_free_graph.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_deserialize(buf: *const u8, len: usize) -> *mut PoastaGraph {
+    error::catch(|| {
+        if buf.is_null() {
+            error::fail(-1, "buffer is NULL");
+            return ptr::null_mut();
+        }
+
+        match from_bytes(unsafe { slice::from_raw_parts(buf, len) }) {
+            Ok(graph) => Box::into_raw(Box::new(graph)),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// Frees a PoastaBytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_bytes(bytes: PoastaBytes) {
+    error::catch(|| {
+        unsafe { free_c_array(bytes.data, bytes.len) };
+    })
+}
+
+/// poasta's graph is left out of the bytes, since it doesn't come back with the same internal
+/// order, which changes its output. Loading commits the sequences again along their paths instead,
+/// without aligning them, given the nodes each node was aligned to.
+pub(crate) fn to_bytes(graph: &PoastaGraph) -> Vec<u8> {
+    let _span = trace::span(PoastaTraceLevel::Debug, c"serialize");
+    let aligned: BTreeMap<NodeIx, &[NodeIx]> = graph.graph.all_nodes()
+        .map(|node| (node, graph.graph.get_aligned_nodes(node)))
+        .filter(|(_, aligned)| !aligned.is_empty())
+        .collect();
+    let data = bincode::serialize(&(graph, aligned)).expect("graphs can be serialized");
+
+    encode(MAGIC, &[(SECTION_GRAPH, &data)])
+}
+
+/// Returns CORRUPTED or UNSUPPORTED_VERSION if the bytes can't be loaded.
+pub(crate) fn from_bytes(bytes: &[u8]) -> Result<PoastaGraph, c_int> {
+    let _span = trace::span(PoastaTraceLevel::Debug, c"deserialize");
+    let sections = decode(MAGIC, bytes)?;
+    let data = section(&sections, SECTION_GRAPH)?;
+    let (saved, aligned): (PoastaGraph, BTreeMap<NodeIx, Vec<NodeIx>>) =
+        bincode::deserialize(data).map_err(|err| error::fail(CORRUPTED, &err.to_string()))?;
+
+    let mut graph = saved.empty_like();
+    let mut node_map = HashMap::new();
+    graph.append_records(&saved.sequences, |node| aligned.get(&node).map_or(&[], Vec::as_slice), &mut node_map)
+        .map_err(|_| error::fail(CORRUPTED, "the sequences can't be added again"))?;
+    let translate = |node: NodeIx| node_map.get(&node).copied();
+    graph.links = saved.links.iter()
+        .filter_map(|(from, to)| Some((translate(*from)?, translate(*to)?)))
+        .collect();
+    graph.path_weights = saved.path_weights.iter().filter_map(|bump| bump.translated(translate)).collect();
+    graph.cache = saved.cache;
+    graph.backbone = saved.backbone;
+    graph.masked_regions = saved.masked_regions.map(|regions| regions.translated(translate));
+
+    Ok(graph)
+}
+
+/// Writes the header for a file type (`magic`) and the given (tag, data) sections.
+pub(crate) fn encode(magic: &[u8; 8], sections: &[(u32, &[u8])]) -> Vec<u8> {
+    let len: usize = sections.iter().map(|(_, data)| SECTION_HEADER_LEN + data.len()).sum();
+    let mut bytes = Vec::with_capacity(HEADER_LEN + len);
+    bytes.extend_from_slice(magic);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&MIN_READER_VERSION.to_le_bytes());
+
+    for (tag, data) in sections {
+        write_section(&mut bytes, *tag, data);
+    }
+
+    bytes
+}
+
+/// The (tag, data) sections of bytes written by `encode` with the same `magic`.
+pub(crate) fn decode<'a>(magic: &[u8; 8], bytes: &'a [u8]) -> Result<Vec<(u32, &'a [u8])>, c_int> {
+    if bytes.len() < HEADER_LEN || &bytes[..magic.len()] != magic {
+        return Err(error::fail(CORRUPTED, "missing file header"));
+    }
+
+    let min_reader_version = u16::from_le_bytes(bytes[magic.len() + 2..HEADER_LEN].try_into().unwrap());
+    if min_reader_version > FORMAT_VERSION {
+        let message = format!("needs format version {min_reader_version}, this library reads version {FORMAT_VERSION}");
+        return Err(error::fail(UNSUPPORTED_VERSION, &message));
+    }
+
+    let mut sections = Vec::new();
+    let mut rest = &bytes[HEADER_LEN..];
+    while !rest.is_empty() {
+        let Some((tag, data, next)) = read_section(rest) else {
+            return Err(error::fail(CORRUPTED, "a section is truncated or fails its checksum"));
+        };
+        sections.push((tag, data));
+        rest = next;
+    }
+
+    Ok(sections)
+}
+
+/// The data of the first section with `tag`; sections with other tags are skipped.
+pub(crate) fn section<'a>(sections: &[(u32, &'a [u8])], tag: u32) -> Result<&'a [u8], c_int> {
+    optional_section(sections, tag).ok_or_else(|| error::fail(CORRUPTED, &format!("missing section {tag}")))
+}
+
+/// Like `section`, for sections older versions didn't write.
+pub(crate) fn optional_section<'a>(sections: &[(u32, &'a [u8])], tag: u32) -> Option<&'a [u8]> {
+    sections.iter()
+        .find(|(other, _)| *other == tag)
+        .map(|(_, data)| *data)
+}
+
+fn write_section(bytes: &mut Vec<u8>, tag: u32, data: &[u8]) {
+    bytes.extend_from_slice(&tag.to_le_bytes());
+    bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+    bytes.extend_from_slice(data);
+}
+
+/// Tag and data of the first section, and the bytes after it. None if the section is truncated or
+/// fails its checksum.
+fn read_section(bytes: &[u8]) -> Option<(u32, &[u8], &[u8])> {
+    let header = bytes.get(..SECTION_HEADER_LEN)?;
+    let tag = u32::from_le_bytes(header[..4].try_into().unwrap());
+    let len = usize::try_from(u64::from_le_bytes(header[4..12].try_into().unwrap())).ok()?;
+    let checksum = u32::from_le_bytes(header[12..].try_into().unwrap());
+
+    let end = SECTION_HEADER_LEN.checked_add(len)?;
+    let data = bytes.get(SECTION_HEADER_LEN..end)?;
+    if crc32fast::hash(data) != checksum {
+        return None;
+    }
+
+    Some((tag, data, &bytes[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::graph::{Scoring, SequenceInfo};
+    use crate::resource;
+    use crate::scoring::ScoringParams;
+    use crate::testing::{add, graph_of, outputs};
+
+    #[test]
+    fn loaded_graphs_behave_like_saved_ones() {
+        let mut graph = graph_of(&["ACGTTGCA", "ACGATGCA", "ACGTTCCA"]);
+        let mut loaded = from_bytes(&to_bytes(&graph)).unwrap();
+        assert_eq!(outputs(&loaded), outputs(&graph));
+        assert_eq!(to_bytes(&loaded), to_bytes(&graph));
+
+        add(&mut graph, "ACGATGCAT");
+        add(&mut loaded, "ACGATGCAT");
+        assert_eq!(outputs(&loaded), outputs(&graph));
+    }
+
+    #[test]
+    fn loading_commits_the_saved_paths_without_aligning() {
+        // Variants of a random sequence with substitutions, insertions and deletions
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let base: Vec<u8> = (0..150).map(|_| b"ACGT"[(next() % 4) as usize]).collect();
+        let reads: Vec<String> = (0..20)
+            .map(|_| {
+                let mut read = Vec::new();
+                for base in &base {
+                    match next() % 30 {
+                        0 => (),
+                        1 => read.extend([*base, b"ACGT"[(next() % 4) as usize]]),
+                        2 => read.push(b"ACGT"[(next() % 4) as usize]),
+                        _ => read.push(*base),
+                    }
+                }
+                let (start, end) = ((next() % 10) as usize, read.len() - (next() % 10) as usize);
+                String::from_utf8(read[start..end].to_vec()).unwrap()
+            })
+            .collect();
+
+        let mut graph = PoastaGraph::new();
+        for read in &reads[..16] {
+            add(&mut graph, read);
+        }
+
+        let bytes = to_bytes(&graph);
+        assert!(bytes.len() < bincode::serialize(&*graph.graph).unwrap().len());
+
+        let cells = resource::cells();
+        let mut loaded = from_bytes(&bytes).unwrap();
+        assert_eq!(resource::cells(), cells);
+        assert_eq!(outputs(&loaded), outputs(&graph));
+        assert!(to_bytes(&loaded) == bytes);
+
+        for read in &reads[16..] {
+            add(&mut graph, read);
+            add(&mut loaded, read);
+        }
+        assert_eq!(outputs(&loaded), outputs(&graph));
+    }
+
+    #[test]
+    fn custom_scorings_are_saved_once() {
+        let params = Arc::new(ScoringParams::new(4, 2, 6));
+        let mut graph = PoastaGraph::new();
+        for read in ["ACGTTGCA", "ACGATGCA", "ACGTTCCA", "ACGTTGCAT"] {
+            let scoring = Scoring::Custom(Arc::clone(&params));
+            assert_eq!(graph.add_sequence(read.as_bytes(), &vec![1; read.len()], scoring, SequenceInfo::default()), 0);
+        }
+
+        // The substitution matrix is 64 KiB
+        let bytes = to_bytes(&graph);
+        assert!(bytes.len() < 2 * 256 * 256, "{} bytes", bytes.len());
+
+        let loaded = from_bytes(&bytes).unwrap();
+        assert_eq!(outputs(&loaded), outputs(&graph));
+        let Scoring::Custom(first) = &loaded.sequences[0].scoring else {
+            panic!("the scoring isn't custom");
+        };
+        for record in loaded.sequences.iter() {
+            assert!(matches!(&record.scoring, Scoring::Custom(params) if Arc::ptr_eq(params, first)));
+        }
+    }
+
+    #[test]
+    fn newer_formats_are_rejected() {
+        let mut bytes = to_bytes(&graph_of(&["ACGTTGCA"]));
+        bytes[MAGIC.len() + 2..HEADER_LEN].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+
+        assert_eq!(from_bytes(&bytes).err(), Some(UNSUPPORTED_VERSION));
+    }
+
+    #[test]
+    fn corrupted_bytes_are_rejected() {
+        let bytes = to_bytes(&graph_of(&["ACGTTGCA"]));
+
+        // A flipped bit in the data of the graph section fails its checksum
+        let mut flipped = bytes.clone();
+        flipped[HEADER_LEN + SECTION_HEADER_LEN] ^= 1;
+        assert_eq!(from_bytes(&flipped).err(), Some(CORRUPTED));
+
+        assert_eq!(from_bytes(&bytes[..bytes.len() - 1]).err(), Some(CORRUPTED));
+        assert_eq!(from_bytes(b"POAGRAPX").err(), Some(CORRUPTED));
+    }
+}
@@ -0,0 +1,109 @@
+use std::ptr;
+
+use crate::pairwise::identity;
+use crate::{error, free_c_array, into_c_array, PoastaGraph};
+
+/// Struct to hold a list of graphs created by the library.
+#[repr(C)]
+pub struct PoastaGraphList {
+    pub graphs: *mut *mut PoastaGraph,
+    pub num_graphs: usize,
+}
+
+/// Splits a graph that contains divergent sequence families into one graph per family.
+/// Sequences are greedily clustered: each sequence joins the first-added member of the most
+/// similar family if their identity is at least `min_identity`, otherwise it founds a new family.
+/// Each family is realigned into a new graph (in the original insertion order).
+/// The input graph is left untouched. If all sequences belong to a single family, an empty list
+/// is returned. Each returned graph must be freed with poasta_free_graph, the list itself with
+/// poasta_free_graph_list.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_autosplit(graph: *const PoastaGraph, min_identity: f64) -> PoastaGraphList {
+    error::catch(|| {
+        let empty = PoastaGraphList { graphs: ptr::null_mut(), num_graphs: 0 };
+        if graph.is_null() {
+            return empty;
+        }
+
+        let graph = unsafe { &*graph };
+
+        // Each family is a list of indices into `graph.sequences`, the first one is the representative
+        let mut families: Vec<Vec<usize>> = Vec::new();
+        for (ix, record) in graph.sequences.iter().enumerate() {
+            let best = families.iter_mut()
+                .map(|family| (identity(&graph.sequences[family[0]].seq, &record.seq), family))
+                .filter(|(ident, _)| *ident >= min_identity)
+                .max_by(|(a, _), (b, _)| a.total_cmp(b));
+
+            match best {
+                Some((_, family)) => family.push(ix),
+                None => families.push(vec![ix]),
+            }
+        }
+
+        if families.len() < 2 {
+            return empty;
+        }
+
+        let mut graphs = Vec::with_capacity(families.len());
+        for family in &families {
+            match graph.rebuild_with(family.iter().map(|ix| &graph.sequences[*ix])) {
+                Ok(split) => graphs.push(Box::into_raw(Box::new(split))),
+                Err(_) => {
+                    for split in graphs {
+                        drop(unsafe { Box::from_raw(split) });
+                    }
+                    return empty;
+                }
+            }
+        }
+
+        PoastaGraphList {
+            num_graphs: graphs.len(),
+            graphs: into_c_array(graphs),
+        }
+    })
+}
+
+/// Frees the array of a PoastaGraphList. The graphs themselves are not freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_graph_list(list: PoastaGraphList) {
+    error::catch(|| {
+        unsafe {
+            free_c_array(list.graphs, list.num_graphs);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poasta_free_graph;
+    use crate::testing::{graph_of, outputs};
+
+    const FAMILY_A: [&str; 3] = ["ACGTTGCAACGTTGCA", "ACGTTGCAACGATGCA", "ACGTTGCAACGTTGCAT"];
+    const FAMILY_B: [&str; 2] = ["TTGACCGGTTAACCGG", "TTGACCGGATAACCGG"];
+
+    #[test]
+    fn families_are_split_into_graphs_of_their_own() {
+        let graph = graph_of(&[FAMILY_A[0], FAMILY_B[0], FAMILY_A[1], FAMILY_B[1], FAMILY_A[2]]);
+        let list = unsafe { poasta_graph_autosplit(&graph, 0.8) };
+        assert_eq!(list.num_graphs, 2);
+
+        let graphs = unsafe { free_c_array(list.graphs, list.num_graphs) };
+        assert_eq!(outputs(unsafe { &*graphs[0] }), outputs(&graph_of(&FAMILY_A)));
+        assert_eq!(outputs(unsafe { &*graphs[1] }), outputs(&graph_of(&FAMILY_B)));
+        for split in graphs {
+            unsafe { poasta_free_graph(split) };
+        }
+        assert_eq!(graph.sequences.len(), 5);
+    }
+
+    #[test]
+    fn a_single_family_isnt_split() {
+        let list = unsafe { poasta_graph_autosplit(&graph_of(&FAMILY_A), 0.8) };
+
+        assert_eq!(list.num_graphs, 0);
+        assert!(list.graphs.is_null());
+    }
+}
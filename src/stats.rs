@@ -0,0 +1,179 @@
+use std::mem;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::error;
+use crate::PoastaGraph;
+
+/// Struct to hold summary statistics of a graph.
+#[repr(C)]
+#[derive(Default)]
+pub struct PoastaGraphStats {
+    pub num_sequences: usize,
+
+    /// Number of nodes, excluding the special start and end node.
+    pub num_nodes: usize,
+
+    /// Sequences added without realignment thanks to the alignment cache, and sequences that had
+    /// to be aligned while the cache was enabled.
+    pub cache_hits: usize,
+    pub cache_misses: usize,
+}
+
+/// Returns summary statistics of the graph. Returns all zeros if the graph is NULL.
+/// Part of the fast query tier: never allocates and never panics.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_stats(graph: *const PoastaGraph) -> PoastaGraphStats {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaGraphStats::default();
+        }
+
+        let graph = unsafe { &*graph };
+
+        PoastaGraphStats {
+            num_sequences: graph.sequences.len(),
+            num_nodes: graph.graph.node_count(),
+            cache_hits: graph.cache.hits,
+            cache_misses: graph.cache.misses,
+        }
+    })
+}
+
+/// Returns a counter that changes whenever the graph's sequences, structure or output settings
+/// (consensus tie break, bisulfite consensus, minimum support, masked regions, backbone) change,
+/// so bindings can cache the MSA, consensus or GFA until it changes. Settings that only affect
+/// how later sequences are added don't change it. New, cloned and loaded graphs start at 0.
+/// Returns 0 if the graph is NULL. Part of the fast query tier: never allocates and never panics.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_generation(graph: *const PoastaGraph) -> u64 {
+    error::catch(|| {
+        if graph.is_null() {
+            return 0;
+        }
+
+        unsafe { (*graph).generation }
+    })
+}
+
+/// Struct to hold the expected cost of aligning a sequence to a graph.
+#[repr(C)]
+#[derive(Default)]
+pub struct PoastaCostEstimate {
+    /// Cells of the full alignment matrix, (nodes + 1) x (sequence length + 1).
+    pub matrix_cells: u64,
+
+    /// Whether the graph's configuration (codon-aware or homopolymer-aware gaps) makes sequences
+    /// align with the full dynamic programming aligner rather than poasta's aligner.
+    pub exhaustive: bool,
+
+    /// Alignment states the aligner is expected to compute, as counted by the `cells_computed`
+    /// metric.
+    pub expected_states: u64,
+
+    /// Expected memory of the aligner in bytes.
+    pub memory_bytes: u64,
+
+    /// Expected alignment time in milliseconds on a single modern x86-64 core.
+    pub time_ms: f64,
+}
+
+/// Alignment states poasta's aligner visits per matrix cell, measured for reads with about 10%
+/// errors. It visits fewer for more similar sequences, and up to three ((mis)match, insertion and
+/// deletion) for unrelated ones.
+const ASTAR_VISITED_FRACTION: f64 = 0.25;
+
+/// Measured per state visited by poasta's aligner, and per cell filled by the DP aligner.
+const ASTAR_BYTES_PER_STATE: f64 = 85.0;
+const ASTAR_NS_PER_STATE: f64 = 450.0;
+const DP_NS_PER_CELL: f64 = 40.0;
+
+/// Estimates the cost of aligning a sequence of `seq_len` bases to the graph with simple affine
+/// gap scoring (poasta_add_sequence), before running it, so schedulers can route large jobs to
+/// suitable workers or reject infeasible ones. Custom scoring and modes other than Global use the
+/// DP aligner like `exhaustive` configurations do, and cost about as much. The first sequence of
+/// a graph isn't aligned and costs nothing. Returns all zeros if the graph is NULL.
+/// Part of the fast query tier: never allocates and never panics.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_estimate_alignment_cost(graph: *const PoastaGraph, seq_len: usize) -> PoastaCostEstimate {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaCostEstimate::default();
+        }
+
+        let graph = unsafe { &*graph };
+        let exhaustive = graph.codon.is_some() || graph.homopolymer.is_some();
+        if graph.graph.is_empty() {
+            return PoastaCostEstimate { exhaustive, ..PoastaCostEstimate::default() };
+        }
+
+        let matrix_cells = (graph.graph.node_count() as u64 + 1).saturating_mul(seq_len as u64 + 1);
+        let (expected_states, memory_bytes, time_ms) = if exhaustive {
+            // A match and closed score, and an insertion and deletion score per gap length residue
+            let residues = if graph.codon.is_some() { 3 } else { 1 };
+            let bytes_per_cell = (mem::size_of::<i32>() * (2 + 2 * residues)) as u64;
+            let time_ms = matrix_cells as f64 * DP_NS_PER_CELL / 1e6;
+
+            (matrix_cells, matrix_cells.saturating_mul(bytes_per_cell), time_ms)
+        } else {
+            let states = matrix_cells as f64 * ASTAR_VISITED_FRACTION;
+            (states as u64, (states * ASTAR_BYTES_PER_STATE) as u64, states * ASTAR_NS_PER_STATE / 1e6)
+        };
+
+        PoastaCostEstimate { matrix_cells, exhaustive, expected_states, memory_bytes, time_ms }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homopolymer::poasta_graph_set_homopolymer_gaps;
+    use crate::provisional::poasta_set_min_support;
+    use crate::testing::{add, graph_of};
+    use crate::{poasta_add_sequence, poasta_free_graph, poasta_graph_clone};
+
+    #[test]
+    fn costs_follow_the_matrix_and_aligner() {
+        // 10 nodes and 9 bases, of which poasta's aligner visits a quarter
+        let mut graph = graph_of(&["ACGTACGTAA"]);
+        let estimate = unsafe { poasta_estimate_alignment_cost(&graph, 9) };
+        assert_eq!((estimate.matrix_cells, estimate.exhaustive, estimate.expected_states), (110, false, 27));
+        assert_eq!(estimate.memory_bytes, (27.5 * ASTAR_BYTES_PER_STATE) as u64);
+
+        // The DP aligner fills every cell with four scores
+        assert_eq!(unsafe { poasta_graph_set_homopolymer_gaps(&mut graph, 3, 1) }, 0);
+        let estimate = unsafe { poasta_estimate_alignment_cost(&graph, 9) };
+        assert_eq!((estimate.matrix_cells, estimate.exhaustive, estimate.expected_states), (110, true, 110));
+        assert_eq!(estimate.memory_bytes, 110 * 16);
+        assert!(estimate.time_ms > 0.0);
+    }
+
+    #[test]
+    fn first_sequences_cost_nothing() {
+        let estimate = unsafe { poasta_estimate_alignment_cost(&PoastaGraph::new(), 1000) };
+        assert_eq!((estimate.matrix_cells, estimate.expected_states, estimate.memory_bytes), (0, 0, 0));
+        assert_eq!(unsafe { poasta_estimate_alignment_cost(std::ptr::null(), 1000) }.matrix_cells, 0);
+    }
+
+    #[test]
+    fn generations_change_with_the_output() {
+        let mut graph = PoastaGraph::new();
+        assert_eq!(unsafe { poasta_graph_generation(&graph) }, 0);
+        add(&mut graph, "ACGTACGTAA");
+        let added = unsafe { poasta_graph_generation(&graph) };
+        assert_ne!(added, 0);
+
+        // Settings for later sequences and failed additions don't change it
+        assert_eq!(unsafe { poasta_graph_set_homopolymer_gaps(&mut graph, 3, 1) }, 0);
+        assert_eq!(unsafe { poasta_add_sequence(&mut graph, c"ACGT".as_ptr(), 4, -1, 2, 6) }, -1);
+        assert_eq!(unsafe { poasta_graph_generation(&graph) }, added);
+
+        assert_eq!(unsafe { poasta_set_min_support(&mut graph, 2) }, 0);
+        assert!(unsafe { poasta_graph_generation(&graph) } > added);
+
+        let clone = unsafe { poasta_graph_clone(&graph) };
+        assert_eq!(unsafe { poasta_graph_generation(clone) }, 0);
+        unsafe { poasta_free_graph(clone) };
+        assert_eq!(unsafe { poasta_graph_generation(std::ptr::null()) }, 0);
+    }
+}
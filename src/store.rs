@@ -0,0 +1,265 @@
+//! A file of graphs by key, so pipelines with many graphs can keep them on disk and only load
+//! the ones they work on.
+//!
+//! The file is a log of (key, graph) records. A graph stored again under the same key is appended,
+//! and the latest record wins. Only the keys and record positions are kept in memory.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use crate::error;
+use crate::serialize;
+use crate::{PoastaGraph, IO_ERROR};
+
+const MAGIC: &[u8; 8] = b"POASTORE";
+
+/// Key length (u32) and graph length (u64) in front of each record.
+const RECORD_HEADER_LEN: u64 = 12;
+
+/// Opaque pointer to an open graph store.
+pub struct PoastaStore {
+    file: File,
+
+    /// Position and length of the latest serialized graph of each key.
+    index: HashMap<Vec<u8>, (u64, u64)>,
+}
+
+/// Opens the graph store at `path` (NUL-terminated), creating the file if it doesn't exist.
+/// Returns NULL if the file couldn't be opened or isn't a graph store; poasta_last_error_message
+/// says why.
+/// The store must be closed with poasta_store_close.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_store_open(path: *const c_char) -> *mut PoastaStore {
+    error::catch(|| {
+        if path.is_null() {
+            error::fail(-1, "path is NULL");
+            return ptr::null_mut();
+        }
+
+        let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
+            error::fail(-1, "path isn't valid UTF-8");
+            return ptr::null_mut();
+        };
+
+        match PoastaStore::open(path) {
+            Ok(store) => Box::into_raw(Box::new(store)),
+            Err(err) => {
+                error::fail(IO_ERROR, &err.to_string());
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Closes the store. Graphs loaded from it stay valid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_store_close(store: *mut PoastaStore) {
+    error::catch(|| {
+        if !store.is_null() {
+            unsafe {
+                drop(Box::from_raw(store));
+            }
+        }
+    })
+}
+
+/// Stores a copy of the graph under `key` (NUL-terminated), replacing any graph stored under the
+/// same key. Returns 0 on success, -1 on invalid arguments and -5 if the file couldn't be written.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_store_put(store: *mut PoastaStore, key: *const c_char, graph: *const PoastaGraph) -> c_int {
+    error::catch(|| {
+        if store.is_null() || key.is_null() || graph.is_null() {
+            return error::fail(-1, "store, key or graph is NULL");
+        }
+
+        let key = unsafe { CStr::from_ptr(key) }.to_bytes();
+        match unsafe { &mut *store }.put(key, unsafe { &*graph }) {
+            Ok(()) => 0,
+            Err(err) => error::fail(IO_ERROR, &err.to_string()),
+        }
+    })
+}
+
+/// Loads the graph stored under `key` (NUL-terminated). Returns NULL if there is no such graph or
+/// it couldn't be read. The graph must be freed with poasta_free_graph.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_store_get(store: *mut PoastaStore, key: *const c_char) -> *mut PoastaGraph {
+    error::catch(|| {
+        let mut graph = ptr::null_mut();
+        unsafe { poasta_store_load(store, key, &mut graph) };
+
+        graph
+    })
+}
+
+/// Like poasta_store_get, but stores the graph in `*graph_out` and tells why loading failed.
+/// Returns 0 on success, -1 on invalid arguments or if there is no graph under `key`, -5 if the
+/// file couldn't be read, -8 if the stored graph is corrupted and -9 if it was written by a newer,
+/// incompatible version of the library. `*graph_out` is set to NULL on failure.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_store_load(store: *mut PoastaStore, key: *const c_char, graph_out: *mut *mut PoastaGraph) -> c_int {
+    error::catch(|| {
+        if graph_out.is_null() {
+            return error::fail(-1, "output is NULL");
+        }
+
+        unsafe { *graph_out = ptr::null_mut() };
+        if store.is_null() || key.is_null() {
+            return error::fail(-1, "store or key is NULL");
+        }
+
+        let key = unsafe { CStr::from_ptr(key) }.to_bytes();
+        match unsafe { &mut *store }.get(key) {
+            Ok(graph) => {
+                unsafe { *graph_out = Box::into_raw(Box::new(graph)) };
+                0
+            },
+            Err(status) => status,
+        }
+    })
+}
+
+impl PoastaStore {
+    fn open(path: &str) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let mut index = HashMap::new();
+
+        let file_len = file.metadata()?.len();
+        if file_len == 0 {
+            file.write_all(MAGIC)?;
+            return Ok(PoastaStore { file, index });
+        }
+
+        let mut magic = [0; MAGIC.len()];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a graph store"));
+        }
+
+        let mut pos = MAGIC.len() as u64;
+        let mut header = [0; RECORD_HEADER_LEN as usize];
+        while pos + RECORD_HEADER_LEN <= file_len {
+            file.read_exact(&mut header)?;
+            let key_len = u32::from_le_bytes(header[..4].try_into().unwrap()) as u64;
+            let graph_len = u64::from_le_bytes(header[4..].try_into().unwrap());
+
+            let graph_pos = pos + RECORD_HEADER_LEN + key_len;
+            if graph_pos.saturating_add(graph_len) > file_len {
+                break;
+            }
+
+            let mut key = vec![0; key_len as usize];
+            file.read_exact(&mut key)?;
+            index.insert(key, (graph_pos, graph_len));
+
+            pos = file.seek(SeekFrom::Current(graph_len as i64))?;
+        }
+
+        // Drop a record that was only partially written, e.g., because the process crashed
+        if pos < file_len {
+            file.set_len(pos)?;
+        }
+
+        Ok(PoastaStore { file, index })
+    }
+
+    fn put(&mut self, key: &[u8], graph: &PoastaGraph) -> io::Result<()> {
+        let bytes = serialize::to_bytes(graph);
+
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN as usize + key.len() + bytes.len());
+        record.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        record.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        record.extend_from_slice(key);
+        record.extend_from_slice(&bytes);
+
+        let pos = self.file.seek(SeekFrom::End(0))?;
+        self.file.write_all(&record)?;
+
+        let graph_pos = pos + RECORD_HEADER_LEN + key.len() as u64;
+        self.index.insert(key.to_vec(), (graph_pos, bytes.len() as u64));
+
+        Ok(())
+    }
+
+    fn get(&mut self, key: &[u8]) -> Result<PoastaGraph, c_int> {
+        let (pos, len) = *self.index.get(key).ok_or_else(|| error::fail(-1, "no graph is stored under the key"))?;
+
+        let mut bytes = vec![0; len as usize];
+        self.file.seek(SeekFrom::Start(pos)).map_err(|err| error::fail(IO_ERROR, &err.to_string()))?;
+        self.file.read_exact(&mut bytes).map_err(|err| error::fail(IO_ERROR, &err.to_string()))?;
+
+        serialize::from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::poasta_free_graph;
+    use crate::testing::{graph_of, outputs, temp_path};
+
+    /// Outputs of the graph stored under `key`, or the status it couldn't be loaded with.
+    fn load(store: *mut PoastaStore, key: &CStr) -> Result<(String, String, String), c_int> {
+        let mut graph = ptr::null_mut();
+        let status = unsafe { poasta_store_load(store, key.as_ptr(), &mut graph) };
+        if status != 0 {
+            return Err(status);
+        }
+
+        let loaded = outputs(unsafe { &*graph });
+        unsafe { poasta_free_graph(graph) };
+
+        Ok(loaded)
+    }
+
+    #[test]
+    fn graphs_survive_reopening_and_the_latest_wins() {
+        let path = temp_path("store.bin");
+        let (first, second) = (graph_of(&["ACGT", "ACGA"]), graph_of(&["TTGCA", "TTGCA", "TAGCA"]));
+
+        let store = unsafe { poasta_store_open(path.as_ptr()) };
+        assert_eq!(unsafe { poasta_store_put(store, c"a".as_ptr(), &first) }, 0);
+        assert_eq!(unsafe { poasta_store_put(store, c"b".as_ptr(), &first) }, 0);
+        assert_eq!(unsafe { poasta_store_put(store, c"b".as_ptr(), &second) }, 0);
+        unsafe { poasta_store_close(store) };
+
+        let store = unsafe { poasta_store_open(path.as_ptr()) };
+        assert_eq!(load(store, c"a"), Ok(outputs(&first)));
+        assert_eq!(load(store, c"b"), Ok(outputs(&second)));
+        assert_eq!(load(store, c"c"), Err(-1));
+        unsafe { poasta_store_close(store) };
+
+        fs::remove_file(path.to_str().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn partially_written_records_are_dropped() {
+        let path = temp_path("store-partial.bin");
+        let graph = graph_of(&["ACGT"]);
+        let store = unsafe { poasta_store_open(path.as_ptr()) };
+        unsafe { poasta_store_put(store, c"a".as_ptr(), &graph) };
+        unsafe { poasta_store_put(store, c"b".as_ptr(), &graph) };
+        unsafe { poasta_store_close(store) };
+
+        // Cut the last record short, as a crash while writing it would
+        let len = fs::metadata(path.to_str().unwrap()).unwrap().len();
+        File::options().write(true).open(path.to_str().unwrap()).unwrap().set_len(len - 3).unwrap();
+
+        let store = unsafe { poasta_store_open(path.as_ptr()) };
+        assert_eq!(load(store, c"a"), Ok(outputs(&graph)));
+        assert_eq!(load(store, c"b"), Err(-1));
+        assert_eq!(unsafe { poasta_store_put(store, c"b".as_ptr(), &graph) }, 0);
+        assert_eq!(load(store, c"b"), Ok(outputs(&graph)));
+        unsafe { poasta_store_close(store) };
+
+        fs::write(path.to_str().unwrap(), b"not a store").unwrap();
+        assert!(unsafe { poasta_store_open(path.as_ptr()) }.is_null());
+        fs::remove_file(path.to_str().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,113 @@
+use std::os::raw::c_int;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error;
+use crate::PoastaGraph;
+
+/// Strand a sequence was read from.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PoastaStrand {
+    #[default]
+    Unknown = 0,
+    Forward = 1,
+    Reverse = 2,
+}
+
+/// Struct to hold the weight a node gets from sequences of each strand.
+#[repr(C)]
+#[derive(Default)]
+pub struct PoastaStrandWeights {
+    pub forward: f64,
+    pub reverse: f64,
+
+    /// Weight of sequences without strand information.
+    pub unknown: f64,
+}
+
+/// Records the strand of the sequence at `index` (in insertion order); sequences are of unknown
+/// strand by default. Returns -1 if the index is out of range.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_sequence_set_strand(graph: *mut PoastaGraph, index: usize, strand: PoastaStrand) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let Some(record) = unsafe { &mut *graph }.sequences_mut().get_mut(index) else {
+            return error::fail(-1, "sequence index out of range");
+        };
+
+        record.info.strand = strand;
+
+        0
+    })
+}
+
+/// Returns the summed base weights of the active sequences passing through `node` (a node ID as
+/// in the GFA output), per strand, e.g., to test variant nodes for strand bias. Weight bumped onto
+/// a sequence counts for its strand, and weight bumped onto a node path as unknown.
+/// Returns all zeros if the graph is NULL or the node doesn't exist.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_node_strand_weights(graph: *const PoastaGraph, node: usize) -> PoastaStrandWeights {
+    error::catch(|| {
+        let mut weights = PoastaStrandWeights::default();
+        if graph.is_null() {
+            return weights;
+        }
+
+        let graph = unsafe { &*graph };
+        for record in graph.active_sequences() {
+            let weight: f64 = record.path.iter()
+                .zip(&record.weights)
+                .filter(|(n, _)| n.index() == node)
+                .map(|(_, w)| *w as f64 * record.info.weight_factor())
+                .sum();
+
+            match record.info.strand {
+                PoastaStrand::Forward => weights.forward += weight,
+                PoastaStrand::Reverse => weights.reverse += weight,
+                PoastaStrand::Unknown => weights.unknown += weight,
+            }
+        }
+
+        // Bumped paths come without a read, and so without a strand
+        for bump in &graph.path_weights {
+            weights.unknown += bump.path.iter().filter(|n| n.index() == node).count() as f64 * bump.weight;
+        }
+
+        weights
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::poasta_bump_path_weight;
+    use crate::testing::graph_of;
+
+    fn strand_weights(graph: &PoastaGraph, node: usize) -> (f64, f64, f64) {
+        let weights = unsafe { poasta_node_strand_weights(graph, node) };
+        (weights.forward, weights.reverse, weights.unknown)
+    }
+
+    #[test]
+    fn node_weights_are_split_by_strand() {
+        let mut graph = graph_of(&["ACGT", "ACGT", "ACGT", "AGGT"]);
+        let strands = [PoastaStrand::Forward, PoastaStrand::Reverse, PoastaStrand::Unknown, PoastaStrand::Forward];
+        for (index, strand) in strands.into_iter().enumerate() {
+            assert_eq!(unsafe { poasta_sequence_set_strand(&mut graph, index, strand) }, 0);
+        }
+        assert_eq!(unsafe { poasta_sequence_set_strand(&mut graph, 4, PoastaStrand::Forward) }, -1);
+
+        let (first, variant) = (graph.sequences[0].path[1].index(), graph.sequences[3].path[1].index());
+        assert_eq!(strand_weights(&graph, first), (1.0, 1.0, 1.0));
+        assert_eq!(strand_weights(&graph, variant), (1.0, 0.0, 0.0));
+
+        // Weight bumped onto a sequence counts for its strand
+        assert_eq!(unsafe { poasta_bump_path_weight(&mut graph, 1, 2) }, 0);
+        assert_eq!(strand_weights(&graph, first), (1.0, 3.0, 1.0));
+        assert_eq!(strand_weights(&graph, usize::MAX), (0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,65 @@
+//! Helpers shared by the unit tests.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+use poasta::aligner::scoring::GapAffine;
+
+use crate::consensus::consensus_sequence;
+use crate::msa::{poasta_get_msa_fasta, PoastaMsaOptions};
+use crate::{poasta_get_gfa, PoastaGraph, Scoring, SequenceInfo};
+
+/// The costs of the example in the README.
+pub(crate) fn scoring() -> Scoring {
+    Scoring::Affine(GapAffine::new(4, 2, 6))
+}
+
+/// A graph of `reads` added in order with weight 1 per base.
+pub(crate) fn graph_of(reads: &[&str]) -> PoastaGraph {
+    let mut graph = PoastaGraph::new();
+    for read in reads {
+        add(&mut graph, read);
+    }
+
+    graph
+}
+
+/// Adds `read` to the graph like poasta_add_sequence, and checks it was added.
+pub(crate) fn add(graph: &mut PoastaGraph, read: &str) {
+    let status = graph.add_sequence(read.as_bytes(), &vec![1; read.len()], scoring(), SequenceInfo::default());
+    assert_eq!(status, 0, "{read} can't be added");
+}
+
+pub(crate) fn consensus(graph: &PoastaGraph) -> String {
+    String::from_utf8(consensus_sequence(graph)).unwrap()
+}
+
+/// The consensus, MSA (as FASTA) and GFA of the graph, to compare graphs by their output.
+pub(crate) fn outputs(graph: &PoastaGraph) -> (String, String, String) {
+    let options = PoastaMsaOptions { pad: 0, line_width: 0 };
+    let msa = take(unsafe { poasta_get_msa_fasta(graph, options) });
+    let gfa = take(unsafe { poasta_get_gfa(graph) });
+
+    (consensus(graph), msa, gfa)
+}
+
+/// A string returned by the library, freed.
+pub(crate) fn take(string: *mut c_char) -> String {
+    assert!(!string.is_null());
+    unsafe { CString::from_raw(string) }.into_string().unwrap()
+}
+
+/// An array returned by the library, which is NULL when empty.
+pub(crate) fn array<'a, T>(values: *const T, len: usize) -> &'a [T] {
+    if values.is_null() {
+        return &[];
+    }
+
+    unsafe { std::slice::from_raw_parts(values, len) }
+}
+
+/// A path for a file `name` in the temporary directory, unique to this process.
+pub(crate) fn temp_path(name: &str) -> CString {
+    let path = std::env::temp_dir().join(format!("poasta-c-{}-{name}", std::process::id()));
+    CString::new(path.to_str().unwrap()).unwrap()
+}
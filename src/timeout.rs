@@ -0,0 +1,272 @@
+use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use poasta::aligner::{AlignedPair, Alignment};
+use poasta::graphs::AlignableRefGraph;
+use serde::{Deserialize, Serialize};
+
+use crate::consensus::heaviest_path;
+use crate::dp::{DpBuffers, DpCosts};
+use crate::error::{self, PoastaError};
+use crate::graph::NodeIx;
+use crate::mode::PoastaAlignmentMode;
+use crate::pairwise::{align_affine, PairColumn};
+use crate::{PoastaGraph, Scoring};
+
+/// Status returned when aligning a sequence took longer than the graph's time budget.
+pub(crate) const TIMEOUT: c_int = PoastaError::Timeout as c_int;
+
+/// Status returned when a sequence was added along an approximate alignment after a timeout.
+pub(crate) const APPROXIMATE: c_int = 1;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub(crate) struct AlignmentTimeout {
+    pub budget: Duration,
+
+    /// Whether to fall back to an approximate alignment instead of rejecting the sequence.
+    pub approximate: bool,
+}
+
+/// Limits the time spent aligning each sequence added to the graph to `timeout_ms` milliseconds
+/// (0 disables the limit, the default). When the limit is hit, the add functions return -7
+/// (TIMEOUT) without adding the sequence, or, if `approximate` is set, add it along its alignment
+/// to the consensus and return 1 (see poasta_sequence_is_approximate).
+/// Each alignment runs on a thread of its own, which is stopped when the limit is hit; the add
+/// functions only return once it has stopped.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_set_alignment_timeout(graph: *mut PoastaGraph, timeout_ms: u64, approximate: bool) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        let timeout = (timeout_ms > 0).then(|| AlignmentTimeout { budget: Duration::from_millis(timeout_ms), approximate });
+        unsafe { (*graph).timeout = timeout };
+
+        0
+    })
+}
+
+/// Returns 1 if the sequence at `index` (in insertion order) was added along an approximate
+/// alignment after a timeout, 0 if it was aligned exactly and -1 if the index is out of range.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_sequence_is_approximate(graph: *const PoastaGraph, index: usize) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        match unsafe { &*graph }.sequences.get(index) {
+            Some(record) => c_int::from(record.info.approximate),
+            None => error::fail(-1, "sequence index out of range"),
+        }
+    })
+}
+
+/// Aligns `seq` to the graph on a worker thread, giving up after `budget`.
+/// Returns -7 on timeout and -3 if the aligner failed.
+pub(crate) fn align_with_timeout(
+    graph: &PoastaGraph,
+    seq: &[u8],
+    scoring: &Scoring,
+    mode: PoastaAlignmentMode,
+    budget: Duration,
+) -> Result<(Alignment<NodeIx>, i64), c_int> {
+    // The worker only needs what the aligner looks at
+    let worker_graph = PoastaGraph {
+        graph: Arc::clone(&graph.graph),
+        links: graph.links.clone(),
+        codon: graph.codon,
+        homopolymer: graph.homopolymer,
+        ..PoastaGraph::new()
+    };
+    let (seq, scoring) = (seq.to_vec(), scoring.clone());
+    let cancel = Arc::new(AtomicBool::new(false));
+    let mut buffers = DpBuffers::default();
+    buffers.cancel = Some(Arc::clone(&cancel));
+
+    let (sender, receiver) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        // A cancelled alignment unwinds out of the aligner, see check_cancelled
+        let aligned = panic::catch_unwind(AssertUnwindSafe(|| worker_graph.align_scored(&seq, &scoring, mode, &mut buffers)));
+        if let Ok(alignment) = aligned {
+            let _ = sender.send(alignment);
+        }
+    });
+
+    let result = match receiver.recv_timeout(budget) {
+        Ok(alignment) => Ok(alignment),
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            cancel.store(true, Ordering::Relaxed);
+            Err(TIMEOUT)
+        },
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(-3),
+    };
+
+    // Let the worker release the graph before it is changed
+    let _ = worker.join();
+    result
+}
+
+/// Payload of the unwinding that stops a cancelled alignment.
+struct Cancelled;
+
+/// Stops the alignment running on this thread if `cancel` is set, by unwinding to the
+/// catch_unwind in align_with_timeout. resume_unwind skips the panic hook, so nothing is printed.
+pub(crate) fn check_cancelled(cancel: &AtomicBool) {
+    if cancel.load(Ordering::Relaxed) {
+        panic::resume_unwind(Box::new(Cancelled));
+    }
+}
+
+/// A graph that stops poasta's aligner, which can't be interrupted otherwise, once `cancel` is
+/// set: the aligner visits successors for every state it expands.
+pub(crate) struct Cancellable<'a, G> {
+    graph: &'a G,
+    cancel: &'a AtomicBool,
+}
+
+impl<'a, G> Cancellable<'a, G> {
+    pub(crate) fn new(graph: &'a G, cancel: &'a AtomicBool) -> Self {
+        Cancellable { graph, cancel }
+    }
+}
+
+impl<G: AlignableRefGraph> AlignableRefGraph for Cancellable<'_, G> {
+    type NodeIndex = G::NodeIndex;
+    type NodeIterator<'b> = G::NodeIterator<'b> where Self: 'b;
+    type PredecessorIterator<'b> = G::PredecessorIterator<'b> where Self: 'b;
+    type SuccessorIterator<'b> = G::SuccessorIterator<'b> where Self: 'b;
+
+    fn all_nodes(&self) -> Self::NodeIterator<'_> {
+        self.graph.all_nodes()
+    }
+
+    fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    fn node_count_with_start_and_end(&self) -> usize {
+        self.graph.node_count_with_start_and_end()
+    }
+
+    fn edge_count(&self) -> usize {
+        self.graph.edge_count()
+    }
+
+    fn start_node(&self) -> Self::NodeIndex {
+        self.graph.start_node()
+    }
+
+    fn end_node(&self) -> Self::NodeIndex {
+        self.graph.end_node()
+    }
+
+    fn predecessors(&self, node: Self::NodeIndex) -> Self::PredecessorIterator<'_> {
+        check_cancelled(self.cancel);
+        self.graph.predecessors(node)
+    }
+
+    fn successors(&self, node: Self::NodeIndex) -> Self::SuccessorIterator<'_> {
+        check_cancelled(self.cancel);
+        self.graph.successors(node)
+    }
+
+    fn in_degree(&self, node: Self::NodeIndex) -> usize {
+        self.graph.in_degree(node)
+    }
+
+    fn out_degree(&self, node: Self::NodeIndex) -> usize {
+        self.graph.out_degree(node)
+    }
+
+    fn is_end(&self, node: Self::NodeIndex) -> bool {
+        self.graph.is_end(node)
+    }
+
+    fn get_symbol_char(&self, node: Self::NodeIndex) -> char {
+        self.graph.get_symbol_char(node)
+    }
+
+    fn is_symbol_equal(&self, node: Self::NodeIndex, symbol: u8) -> bool {
+        check_cancelled(self.cancel);
+        self.graph.is_symbol_equal(node, symbol)
+    }
+
+    fn get_node_ordering(&self) -> Vec<usize> {
+        self.graph.get_node_ordering()
+    }
+}
+
+/// Alignment of `seq` to the consensus path only, which is quick to compute but may miss better
+/// alignments through the rest of the graph. None if there is no consensus.
+pub(crate) fn consensus_alignment(graph: &PoastaGraph, seq: &[u8], scoring: &Scoring) -> Option<Alignment<NodeIx>> {
+    let path = heaviest_path(graph);
+    if path.is_empty() {
+        return None;
+    }
+
+    let costs = DpCosts::from_scoring(scoring);
+    let (gap_open, gap_extend) = costs.gaps[0];
+    let symbols: Vec<u8> = path.iter().map(|node| graph.graph.get_symbol(*node)).collect();
+
+    let alignment = align_affine(&symbols, seq, costs.mismatch as usize, gap_open as usize, gap_extend as usize)
+        .into_iter()
+        .map(|column| match column {
+            PairColumn::Aligned(i, j) => AlignedPair::new(Some(path[i]), Some(j)),
+            PairColumn::OnlyFirst(i) => AlignedPair::new(Some(path[i]), None),
+            PairColumn::OnlySecond(j) => AlignedPair::new(None, Some(j)),
+        })
+        .collect();
+
+    Some(alignment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::PoastaAlignmentType;
+    use crate::testing::{add, graph_of, scoring};
+    use crate::SequenceInfo;
+
+    /// Unrelated random bases, which take far longer than a millisecond to align to each other.
+    fn random_read(mut state: u64) -> String {
+        (0..4000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                b"ACGT"[(state % 4) as usize] as char
+            })
+            .collect()
+    }
+
+    #[test]
+    fn timed_out_alignments_stop_before_the_graph_changes() {
+        let local = PoastaAlignmentMode { aln_type: PoastaAlignmentType::Local, ..PoastaAlignmentMode::default() };
+
+        // poasta's aligner and the DP aligner
+        for mode in [PoastaAlignmentMode::default(), local] {
+            let first = random_read(1);
+            let mut graph = graph_of(&[&first]);
+            let built = Arc::as_ptr(&graph.graph);
+
+            graph.timeout = Some(AlignmentTimeout { budget: Duration::from_millis(1), approximate: false });
+            let second = random_read(2);
+            let info = SequenceInfo { mode, ..SequenceInfo::default() };
+            assert_eq!(graph.add_sequence(second.as_bytes(), &vec![1; second.len()], scoring(), info), TIMEOUT);
+
+            // The worker has released the graph, so it is changed in place rather than rebuilt
+            assert_eq!(Arc::strong_count(&graph.graph), 1);
+            graph.timeout = None;
+            add(&mut graph, &first);
+            assert_eq!(Arc::as_ptr(&graph.graph), built);
+            assert_eq!(graph.sequences.len(), 2);
+        }
+    }
+}
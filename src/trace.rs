@@ -0,0 +1,177 @@
+//! Timing spans around the major operations, reported to a host callback, so hosts can see where
+//! time goes inside the library, e.g., by turning them into OpenTelemetry spans.
+//! Spans are only recorded when built with the `tracing` feature.
+
+use std::ffi::c_void;
+use std::os::raw::{c_char, c_int};
+
+use crate::error;
+
+/// Verbosity of the timing spans.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PoastaTraceLevel {
+    #[default]
+    Off = 0,
+    /// Calls into the library, e.g., adding a sequence or computing the MSA.
+    Info = 1,
+    /// Steps within those calls, e.g., aligning and committing a sequence.
+    Debug = 2,
+}
+
+/// Receives a finished span: its name (a static string), level and duration in nanoseconds.
+pub type PoastaTraceCallback = Option<unsafe extern "C" fn(name: *const c_char, level: PoastaTraceLevel, duration_ns: u64, user_data: *mut c_void)>;
+
+/// Records spans up to `level` (`PoastaTraceLevel::Off` by default).
+/// Returns -1 if the library was built without the `tracing` feature.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_set_trace_level(level: PoastaTraceLevel) -> c_int {
+    error::catch(|| {
+        #[cfg(feature = "tracing")]
+        {
+            enabled::LEVEL.store(level as u8, std::sync::atomic::Ordering::Relaxed);
+            0
+        }
+
+        #[cfg(not(feature = "tracing"))]
+        {
+            let _ = level;
+            error::fail(-1, "the library was built without the tracing feature")
+        }
+    })
+}
+
+/// Sends finished spans to `callback` (called from the thread that ran the operation) together
+/// with `user_data`. Without a callback (the default, or after passing NULL), spans are dropped.
+/// Returns -1 if the library was built without the `tracing` feature.
+#[unsafe(no_mangle)]
+pub extern "C" fn poasta_set_trace_callback(callback: PoastaTraceCallback, user_data: *mut c_void) -> c_int {
+    error::catch(|| {
+        #[cfg(feature = "tracing")]
+        {
+            *enabled::CALLBACK.lock().unwrap() = callback.map(|callback| (callback, user_data as usize));
+            0
+        }
+
+        #[cfg(not(feature = "tracing"))]
+        {
+            let _ = (callback, user_data);
+            error::fail(-1, "the library was built without the tracing feature")
+        }
+    })
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) use enabled::{span, suppress};
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use disabled::{span, suppress};
+
+#[cfg(feature = "tracing")]
+mod enabled {
+    use std::cell::Cell;
+    use std::ffi::CStr;
+    use std::sync::atomic::{AtomicU8, Ordering};
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    use super::PoastaTraceLevel;
+
+    type Callback = unsafe extern "C" fn(*const std::os::raw::c_char, PoastaTraceLevel, u64, *mut std::ffi::c_void);
+
+    pub(super) static LEVEL: AtomicU8 = AtomicU8::new(PoastaTraceLevel::Off as u8);
+
+    /// The host's callback with its user data pointer.
+    pub(super) static CALLBACK: Mutex<Option<(Callback, usize)>> = Mutex::new(None);
+
+    thread_local! {
+        /// Whether spans on this thread are dropped, see `suppress`.
+        static SUPPRESSED: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// Times an operation until dropped.
+    pub(crate) struct Span {
+        active: Option<(&'static CStr, PoastaTraceLevel, Instant)>,
+    }
+
+    pub(crate) fn span(level: PoastaTraceLevel, name: &'static CStr) -> Span {
+        let enabled = level as u8 <= LEVEL.load(Ordering::Relaxed) && !SUPPRESSED.get();
+        Span { active: enabled.then(|| (name, level, Instant::now())) }
+    }
+
+    /// Drops the spans on this thread until dropped, for work the host didn't ask for, e.g.,
+    /// copying a graph still shared with a clone before changing it.
+    pub(crate) fn suppress() -> Suppressed {
+        Suppressed { suppressed: SUPPRESSED.replace(true) }
+    }
+
+    pub(crate) struct Suppressed {
+        suppressed: bool,
+    }
+
+    impl Drop for Suppressed {
+        fn drop(&mut self) {
+            SUPPRESSED.set(self.suppressed);
+        }
+    }
+
+    impl Drop for Span {
+        fn drop(&mut self) {
+            let Some((name, level, start)) = self.active else {
+                return;
+            };
+
+            // Not holding the lock while calling back, which may call into the library again
+            let callback = *CALLBACK.lock().unwrap();
+            if let Some((callback, user_data)) = callback {
+                let duration_ns = start.elapsed().as_nanos() as u64;
+                unsafe { callback(name.as_ptr(), level, duration_ns, user_data as *mut std::ffi::c_void) };
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod disabled {
+    use std::ffi::CStr;
+
+    use super::PoastaTraceLevel;
+
+    pub(crate) struct Span;
+
+    #[inline(always)]
+    pub(crate) fn span(_level: PoastaTraceLevel, _name: &'static CStr) -> Span {
+        Span
+    }
+
+    #[inline(always)]
+    pub(crate) fn suppress() {}
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::ffi::CStr;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::testing::graph_of;
+
+    static NAMES: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+    unsafe extern "C" fn record(name: *const c_char, _level: PoastaTraceLevel, _duration_ns: u64, _user_data: *mut c_void) {
+        NAMES.lock().unwrap().push(name as usize);
+    }
+
+    #[test]
+    fn span_names_are_static() {
+        assert_eq!(poasta_set_trace_callback(Some(record), std::ptr::null_mut()), 0);
+        assert_eq!(poasta_set_trace_level(PoastaTraceLevel::Debug), 0);
+        graph_of(&["ACGTTGCA", "ACGATGCA"]);
+        poasta_set_trace_level(PoastaTraceLevel::Off);
+        poasta_set_trace_callback(None, std::ptr::null_mut());
+
+        // Still readable after the calls returned
+        let names: Vec<&CStr> = NAMES.lock().unwrap().iter().map(|name| unsafe { CStr::from_ptr(*name as *const c_char) }).collect();
+        assert!(names.contains(&c"commit"));
+    }
+}
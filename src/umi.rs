@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use crate::{add_sequence_uniform, error, free_c_array, into_c_array, metrics, PoastaGraph, Scoring, SequenceInfo};
+
+/// Struct to hold the UMI family sizes of a graph.
+#[repr(C)]
+pub struct PoastaUmiCounts {
+    pub umis: *mut *mut c_char,
+    pub counts: *mut usize,
+    pub num_umis: usize,
+}
+
+/// Adds a sequence tagged with a UMI to the graph (Global alignment).
+/// The first sequence seen for a UMI gets `weight` for all its bases, every following sequence
+/// with the same UMI gets `duplicate_weight` instead. This keeps PCR duplicates from inflating
+/// the consensus weights. `umi` must be a NUL-terminated string; pass NULL to add an untagged
+/// sequence with `weight`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_add_sequence_umi(
+    graph: *mut PoastaGraph,
+    seq: *const c_char,
+    len: usize,
+    umi: *const c_char,
+    weight: u32,
+    duplicate_weight: u32,
+    mismatch_score: i32,
+    gap_extend: i32,
+    gap_open: i32,
+) -> c_int {
+    error::catch(|| {
+        if graph.is_null() {
+            return metrics::record_status(error::fail(-1, "graph is NULL"));
+        }
+
+        let umi = if umi.is_null() {
+            None
+        } else {
+            Some(unsafe { CStr::from_ptr(umi) }.to_bytes().to_vec())
+        };
+
+        let is_duplicate = umi.as_ref().is_some_and(|umi| unsafe { &*graph }.umi_counts.contains_key(umi));
+        let weight = if is_duplicate { duplicate_weight } else { weight };
+
+        let scoring = match Scoring::affine(mismatch_score, gap_extend, gap_open) {
+            Ok(scoring) => scoring,
+            Err(status) => return metrics::record_status(status),
+        };
+        let info = SequenceInfo { umi, ..SequenceInfo::default() };
+
+        unsafe { add_sequence_uniform(graph, seq, len, weight as usize, scoring, info) }
+    })
+}
+
+/// Returns the number of sequences added for each UMI, in order of first occurrence.
+/// Caller must free the result with poasta_free_umi_counts.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_umi_counts(graph: *const PoastaGraph) -> PoastaUmiCounts {
+    error::catch(|| {
+        if graph.is_null() {
+            return PoastaUmiCounts { umis: ptr::null_mut(), counts: ptr::null_mut(), num_umis: 0 };
+        }
+
+        let graph = unsafe { &*graph };
+        let mut seen = HashSet::new();
+        let families: Vec<(&[u8], usize)> = graph.sequences.iter()
+            .filter_map(|record| record.info.umi.as_deref())
+            .filter(|umi| seen.insert(*umi))
+            .map(|umi| (umi, graph.umi_counts[umi]))
+            .collect();
+
+        let num_umis = families.len();
+        let umis = families.iter()
+            .map(|(umi, _)| CString::new(*umi).unwrap().into_raw())
+            .collect();
+        let counts = families.iter().map(|(_, count)| *count).collect();
+
+        PoastaUmiCounts {
+            umis: into_c_array(umis),
+            counts: into_c_array(counts),
+            num_umis,
+        }
+    })
+}
+
+/// Frees the PoastaUmiCounts.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_umi_counts(counts: PoastaUmiCounts) {
+    error::catch(|| {
+        unsafe {
+            for umi in free_c_array(counts.umis, counts.num_umis) {
+                if !umi.is_null() {
+                    drop(CString::from_raw(umi));
+                }
+            }
+            free_c_array(counts.counts, counts.num_umis);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::remove_sequence;
+    use crate::serialize::{from_bytes, to_bytes};
+
+    fn add(graph: &mut PoastaGraph, umi: &CStr) -> usize {
+        let status = unsafe { poasta_add_sequence_umi(graph, c"ACGTTGCA".as_ptr(), 8, umi.as_ptr(), 5, 1, 4, 2, 6) };
+        assert_eq!(status, 0);
+
+        graph.sequences.last().unwrap().weights[0]
+    }
+
+    fn counts(graph: &PoastaGraph) -> Vec<(String, usize)> {
+        let counts = unsafe { poasta_umi_counts(graph) };
+        let families = unsafe { (0..counts.num_umis).map(|i| {
+            let umi = CStr::from_ptr(*counts.umis.add(i)).to_str().unwrap().to_string();
+            (umi, *counts.counts.add(i))
+        }) }.collect();
+        unsafe { poasta_free_umi_counts(counts) };
+
+        families
+    }
+
+    #[test]
+    fn umi_counts_follow_added_and_removed_sequences() {
+        let mut graph = PoastaGraph::new();
+        assert_eq!([add(&mut graph, c"AAA"), add(&mut graph, c"CCC"), add(&mut graph, c"AAA")], [5, 5, 1]);
+        assert_eq!(counts(&graph), [("AAA".to_string(), 2), ("CCC".to_string(), 1)]);
+
+        let saved = from_bytes(&to_bytes(&graph)).unwrap();
+        assert_eq!(counts(&saved), counts(&graph));
+        assert_eq!(counts(&graph.shallow_clone()), counts(&graph));
+
+        remove_sequence(&mut graph, 1).unwrap();
+        assert_eq!(counts(&graph), [("AAA".to_string(), 2)]);
+        assert_eq!(add(&mut graph, c"CCC"), 5);
+        assert_eq!(add(&mut graph, c"AAA"), 1);
+    }
+}
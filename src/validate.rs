@@ -0,0 +1,265 @@
+//! Consistency checks of a graph's structure and bookkeeping.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+use petgraph::algo::toposort;
+use petgraph::stable_graph::{NodeIndex, StableDiGraph};
+use petgraph::visit::{EdgeRef, IntoEdgeReferences, NodeIndexable};
+use serde::Deserialize;
+
+use crate::graph::NodeIx;
+use crate::{error, free_c_array, into_c_array, PoastaGraph};
+
+/// Struct to hold the result of validating a graph.
+#[repr(C)]
+pub struct PoastaValidation {
+    /// Description of each violated invariant; the graph is consistent if there are none.
+    pub problems: *mut *mut c_char,
+    pub num_problems: usize,
+}
+
+/// Mirror of poasta's graph, whose edges aren't otherwise accessible.
+#[derive(Deserialize)]
+struct RawGraph {
+    graph: StableDiGraph<RawNode, RawEdge, u32>,
+    sequences: Vec<(String, NodeIndex<u32>)>,
+    _topological_sorted: Vec<NodeIndex<u32>>,
+    start_node: NodeIndex<u32>,
+    end_node: NodeIndex<u32>,
+}
+
+#[derive(Deserialize)]
+struct RawNode {
+    symbol: u8,
+    aligned_nodes: Vec<NodeIndex<u32>>,
+}
+
+#[derive(Deserialize)]
+struct RawEdge {
+    weight: usize,
+    sequence_ids: Vec<usize>,
+}
+
+/// Checks the invariants of the graph: it is acyclic, every node lies on a sequence path, aligned
+/// nodes are aligned to each other and have different symbols, every sequence is in the graph's
+/// alphabet and its path spells it along existing edges, and the weight and sequences of every edge match the paths
+/// through it. Useful after loading, merging or otherwise changing a graph.
+/// Caller must free the result with poasta_free_validation.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_validate(graph: *const PoastaGraph) -> PoastaValidation {
+    error::catch(|| {
+        let problems = if graph.is_null() {
+            vec!["graph is NULL".to_string()]
+        } else {
+            validate(unsafe { &*graph })
+        };
+
+        let num_problems = problems.len();
+        let problems = problems.into_iter()
+            .map(|problem| CString::new(problem).unwrap().into_raw())
+            .collect();
+
+        PoastaValidation { problems: into_c_array(problems), num_problems }
+    })
+}
+
+/// Frees a PoastaValidation.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_free_validation(validation: PoastaValidation) {
+    error::catch(|| {
+        unsafe {
+            for problem in free_c_array(validation.problems, validation.num_problems) {
+                if !problem.is_null() {
+                    drop(CString::from_raw(problem));
+                }
+            }
+        }
+    })
+}
+
+pub(crate) fn validate(graph: &PoastaGraph) -> Vec<String> {
+    let bytes = bincode::serialize(&*graph.graph).expect("graphs can be serialized");
+    let Ok(raw) = bincode::deserialize::<RawGraph>(&bytes) else {
+        return vec!["graph can't be read".to_string()];
+    };
+
+    let mut problems = Vec::new();
+    let g = &raw.graph;
+    let (start, end) = (raw.start_node, raw.end_node);
+
+    if let Err(cycle) = toposort(g, None) {
+        problems.push(format!("cycle through node {}", cycle.node_id().index()));
+    }
+
+    if g.neighbors_directed(start, petgraph::Incoming).next().is_some() {
+        problems.push("start node has incoming edges".to_string());
+    }
+    if g.neighbors(end).next().is_some() {
+        problems.push("end node has outgoing edges".to_string());
+    }
+
+    for node in g.node_indices() {
+        for &other in &g[node].aligned_nodes {
+            if !g.contains_node(other) {
+                problems.push(format!("node {} is aligned to missing node {}", node.index(), other.index()));
+            } else if !g[other].aligned_nodes.contains(&node) {
+                problems.push(format!("node {} is aligned to node {}, but not vice versa", node.index(), other.index()));
+            } else if g[other].symbol == g[node].symbol {
+                problems.push(format!("aligned nodes {} and {} have the same symbol", node.index(), other.index()));
+            }
+        }
+    }
+
+    if raw.sequences.len() != graph.sequences.len() {
+        problems.push(format!(
+            "graph has {} sequences, but {} are tracked",
+            raw.sequences.len(),
+            graph.sequences.len(),
+        ));
+    }
+
+    // Weight and sequences each edge should have according to the sequence paths
+    let mut expected: HashMap<(NodeIx, NodeIx), (usize, Vec<usize>)> = HashMap::new();
+    let mut on_path = vec![false; g.node_bound()];
+    let alphabet = graph.alphabet();
+
+    for (i, record) in graph.sequences.iter().enumerate() {
+        if record.path.len() != record.seq.len() || record.weights.len() != record.seq.len() {
+            problems.push(format!(
+                "sequence {i} has {} bases, {} weights and {} path nodes",
+                record.seq.len(),
+                record.weights.len(),
+                record.path.len(),
+            ));
+            continue;
+        }
+
+        if let Some((pos, symbol)) = alphabet.first_foreign(&record.seq) {
+            problems.push(format!("sequence {i} has {} at position {pos}, which isn't {}", symbol.escape_ascii(), alphabet.name()));
+        }
+
+        if let Some((_, first)) = raw.sequences.get(i)
+            && record.path.first() != Some(first)
+        {
+            problems.push(format!("sequence {i} starts at node {}, but its path doesn't", first.index()));
+        }
+
+        for (pos, (node, base)) in record.path.iter().zip(&record.seq).enumerate() {
+            match g.node_weight(*node) {
+                None => problems.push(format!("sequence {i} visits missing node {} at position {pos}", node.index())),
+                Some(data) if data.symbol != *base => problems.push(format!(
+                    "sequence {i} has {} at position {pos}, but node {} has {}",
+                    *base as char,
+                    node.index(),
+                    data.symbol as char,
+                )),
+                Some(_) => on_path[node.index()] = true,
+            }
+        }
+
+        for (pos, pair) in record.path.windows(2).enumerate() {
+            let (weight, ids) = expected.entry((pair[0], pair[1])).or_default();
+            *weight += record.weights[pos] + record.weights[pos + 1];
+            ids.push(i);
+        }
+    }
+
+    for node in g.node_indices() {
+        if node != start && node != end && !on_path[node.index()] {
+            problems.push(format!("node {} is not on any sequence path", node.index()));
+        }
+    }
+
+    for edge in g.edge_references() {
+        let (from, to) = (edge.source(), edge.target());
+        if from == start || to == end {
+            continue;
+        }
+
+        let data = edge.weight();
+        match expected.remove(&(from, to)) {
+            None => problems.push(format!("edge {} -> {} is not on any sequence path", from.index(), to.index())),
+            Some((weight, mut ids)) => {
+                if data.weight != weight {
+                    problems.push(format!(
+                        "edge {} -> {} has weight {}, but its sequences add up to {weight}",
+                        from.index(),
+                        to.index(),
+                        data.weight,
+                    ));
+                }
+
+                let mut edge_ids = data.sequence_ids.clone();
+                edge_ids.sort_unstable();
+                ids.sort_unstable();
+                if edge_ids != ids {
+                    problems.push(format!("edge {} -> {} doesn't list the sequences through it", from.index(), to.index()));
+                }
+            },
+        }
+    }
+
+    let mut missing: Vec<_> = expected.into_iter()
+        .filter(|((from, to), _)| g.contains_node(*from) && g.contains_node(*to))
+        .collect();
+    missing.sort_by_key(|((from, to), _)| (from.index(), to.index()));
+    for ((from, to), (_, ids)) in missing {
+        problems.push(format!("sequence {} has no edge {} -> {}", ids[0], from.index(), to.index()));
+    }
+
+    if graph.backbone.is_some_and(|index| index >= graph.sequences.len()) {
+        problems.push("backbone sequence doesn't exist".to_string());
+    }
+
+    for (from, to) in &graph.links {
+        if !g.contains_node(*from) || !g.contains_node(*to) {
+            problems.push(format!("link {} -> {} has a missing node", from.index(), to.index()));
+        }
+    }
+
+    for bump in &graph.path_weights {
+        if let Some(node) = bump.path.iter().find(|node| !g.contains_node(**node)) {
+            problems.push(format!("a bumped path visits missing node {}", node.index()));
+        } else if let Some(pair) = bump.path.windows(2).find(|pair| !g.contains_edge(pair[0], pair[1])) {
+            problems.push(format!("a bumped path has no edge {} -> {}", pair[0].index(), pair[1].index()));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::graph_of;
+
+    #[test]
+    fn built_graphs_are_consistent() {
+        let graph = graph_of(&["ACGTTGCA", "ACGATGCA", "ACGTTGCAT", "CGTTCCA"]);
+        let validation = unsafe { poasta_graph_validate(&graph) };
+        assert_eq!(validation.num_problems, 0);
+        unsafe { poasta_free_validation(validation) };
+    }
+
+    #[test]
+    fn inconsistent_bookkeeping_is_reported() {
+        let reads = ["ACGTTGCA", "ACGATGCA"];
+        let mut graph = graph_of(&reads);
+        let node = graph.sequences[1].path[3].index();
+        graph.sequences_mut()[1].seq[3] = b'C';
+        assert!(validate(&graph).contains(&format!("sequence 1 has C at position 3, but node {node} has A")));
+
+        // A sequence without weights for all bases also leaves its edges unaccounted for
+        let mut graph = graph_of(&reads);
+        graph.sequences_mut()[0].weights.pop();
+        let problems = validate(&graph);
+        assert_eq!(problems[0], "sequence 0 has 8 bases, 7 weights and 8 path nodes");
+        assert!(problems.contains(&"edge 2 -> 3 has weight 4, but its sequences add up to 2".to_string()));
+
+        let mut graph = graph_of(&reads);
+        graph.backbone = Some(2);
+        assert_eq!(validate(&graph), ["backbone sequence doesn't exist"]);
+    }
+}
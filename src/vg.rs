@@ -0,0 +1,169 @@
+use std::ffi::CString;
+use std::fmt::{self, Write};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use crate::error;
+use crate::gfa::Segments;
+use crate::limit::CappedOutput;
+use crate::provisional::PoastaProvisional;
+use crate::PoastaGraph;
+
+/// Returns the graph in vg's JSON graph format, to load it with `vg view -Jv` or `odgi build`:
+/// nodes are the same segments as in poasta_get_gfa_layers (with ids counting from 1), and each
+/// sequence gets a path named like its GFA walk, whose mappings give the offset and length of the
+/// part of each node it covers. Like poasta_get_gfa, nodes and edges below the minimum support
+/// are left out, and so are the paths through them.
+/// The caller must free the string using free().
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_vg_json(graph: *const PoastaGraph) -> *mut c_char {
+    error::catch(|| {
+        if graph.is_null() {
+            return ptr::null_mut();
+        }
+
+        let mut json = String::new();
+        write_vg_json(&mut json, unsafe { &*graph }).unwrap();
+
+        CString::new(json).unwrap_or_default().into_raw()
+    })
+}
+
+/// Like poasta_get_vg_json, but stops writing the JSON once it exceeds `max_bytes` (0 means no
+/// cap), so a pathological graph can't make the library allocate gigabytes. The JSON is stored in
+/// `*out`, or an empty string if it was truncated, since JSON that is cut off can't be parsed; the
+/// caller must free it using free(). `*out` is NULL on other failures. Returns 0, -1 if the graph
+/// or `out` is NULL, and -11 if the JSON was truncated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_get_vg_json_capped(graph: *const PoastaGraph, max_bytes: usize, out: *mut *mut c_char) -> c_int {
+    error::catch(|| {
+        if out.is_null() {
+            return error::fail(-1, "output is NULL");
+        }
+
+        unsafe { *out = ptr::null_mut() };
+        if graph.is_null() {
+            return error::fail(-1, "graph is NULL");
+        }
+
+        // The JSON is a single line, so nothing is left of it when it's truncated
+        let mut json = CappedOutput::new(max_bytes);
+        let _ = write_vg_json(&mut json, unsafe { &*graph });
+
+        json.finish(out)
+    })
+}
+
+/// Writes the vg JSON of poasta_get_vg_json to `out`, stopping at the first failed write.
+fn write_vg_json(out: &mut impl Write, graph: &PoastaGraph) -> fmt::Result {
+    let provisional = if graph.min_support > 1 { PoastaProvisional::Exclude } else { PoastaProvisional::Include };
+    let segments = Segments::new(graph, provisional);
+
+    write!(out, r#"{{"node":["#)?;
+    for (id, nodes) in segments.nodes.iter().enumerate() {
+        let symbols: String = nodes.iter().map(|node| graph.graph.get_symbol(*node) as char).collect();
+        let sep = if id > 0 { "," } else { "" };
+        write!(out, r#"{sep}{{"id":"{}","sequence":"{symbols}"}}"#, id + 1)?;
+    }
+
+    write!(out, r#"],"edge":["#)?;
+    let mut sep = "";
+    for (id, nodes) in segments.nodes.iter().enumerate() {
+        for succ in segments.successors(*nodes.last().unwrap()) {
+            write!(out, r#"{sep}{{"from":"{}","to":"{}"}}"#, id + 1, segments.segment_of[&succ].0 + 1)?;
+            sep = ",";
+        }
+    }
+
+    write!(out, r#"],"path":["#)?;
+    let mut sep = "";
+    for (seq_id, record) in graph.sequences.iter().enumerate() {
+        let Some(walk) = segments.walk(&record.path) else {
+            continue;
+        };
+
+        // Offsets of the walk's start and end within its first and last node
+        let before_last: usize = walk.segments[..walk.segments.len() - 1].iter().map(|id| segments.nodes[*id].len()).sum();
+        let last_rank = walk.segments.len() - 1;
+
+        let mut mappings = Vec::with_capacity(walk.segments.len());
+        for (rank, id) in walk.segments.iter().enumerate() {
+            let offset = if rank == 0 { walk.start } else { 0 };
+            let end = if rank == last_rank { walk.end - before_last } else { segments.nodes[*id].len() };
+
+            let mut position = format!(r#""node_id":"{}""#, id + 1);
+            if offset > 0 {
+                write!(position, r#","offset":"{offset}""#).unwrap();
+            }
+
+            mappings.push(format!(
+                r#"{{"position":{{{position}}},"edit":[{{"from_length":{len},"to_length":{len}}}],"rank":"{}"}}"#,
+                rank + 1,
+                len = end - offset,
+            ));
+        }
+
+        write!(out, r#"{sep}{{"name":"{}","mapping":[{}]}}"#, json_escape(&graph.sequence_name(seq_id)), mappings.join(","))?;
+        sep = ",";
+    }
+
+    write!(out, "]}}")
+}
+
+/// A string as the contents of a JSON string.
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limit::TRUNCATED;
+    use crate::testing::{graph_of, take};
+
+    /// The mapping of a path onto a node.
+    fn mapping(rank: usize, node: usize, len: usize) -> String {
+        format!(r#"{{"position":{{"node_id":"{node}"}},"edit":[{{"from_length":{len},"to_length":{len}}}],"rank":"{rank}"}}"#)
+    }
+
+    #[test]
+    fn paths_map_onto_the_segments() {
+        let graph = graph_of(&["ACGT", "AGGT", "ACGTT"]);
+        let json = take(unsafe { poasta_get_vg_json(&graph) });
+
+        let nodes = r#"{"id":"1","sequence":"A"},{"id":"2","sequence":"C"},{"id":"3","sequence":"G"},{"id":"4","sequence":"GTT"}"#;
+        let edges = r#"{"from":"1","to":"3"},{"from":"1","to":"2"},{"from":"2","to":"4"},{"from":"3","to":"4"}"#;
+        let paths = [
+            ("seq_0", [mapping(1, 1, 1), mapping(2, 2, 1), mapping(3, 4, 2)]),
+            ("seq_1", [mapping(1, 1, 1), mapping(2, 3, 1), mapping(3, 4, 2)]),
+            ("seq_2", [mapping(1, 1, 1), mapping(2, 2, 1), mapping(3, 4, 3)]),
+        ];
+        let paths: Vec<String> = paths.iter()
+            .map(|(name, mappings)| format!(r#"{{"name":"{name}","mapping":[{}]}}"#, mappings.join(",")))
+            .collect();
+
+        assert_eq!(json, format!(r#"{{"node":[{nodes}],"edge":[{edges}],"path":[{}]}}"#, paths.join(",")));
+    }
+
+    #[test]
+    fn truncated_json_is_empty() {
+        let graph = graph_of(&["ACGT", "AGGT"]);
+        let mut out = ptr::null_mut();
+        assert_eq!(unsafe { poasta_get_vg_json_capped(&graph, 50, &mut out) }, TRUNCATED);
+        assert_eq!(take(out), "");
+
+        assert_eq!(unsafe { poasta_get_vg_json_capped(&graph, 0, &mut out) }, 0);
+        assert_eq!(take(out), take(unsafe { poasta_get_vg_json(&graph) }));
+        assert_eq!(json_escape("a\"b\\\n"), "a\\\"b\\\\\\u000a");
+    }
+}
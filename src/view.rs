@@ -0,0 +1,144 @@
+//! A read-only adjacency view of a graph, including edges that only exist in our bookkeeping.
+
+use std::iter::Copied;
+use std::slice::Iter;
+
+use poasta::graphs::AlignableRefGraph;
+
+use crate::graph::{NodeIx, PoastaGraph};
+
+/// Materialized adjacency lists of a [`PoastaGraph`] with its extra links applied.
+///
+/// Links replace the implicit start/end edges of their endpoints, such that (global) alignments
+/// have to pass through them.
+pub(crate) struct GraphView<'a> {
+    graph: &'a PoastaGraph,
+    nodes: Vec<NodeIx>,
+    successors: Vec<Vec<NodeIx>>,
+    predecessors: Vec<Vec<NodeIx>>,
+    ranks: Vec<usize>,
+}
+
+impl<'a> GraphView<'a> {
+    pub(crate) fn new(graph: &'a PoastaGraph) -> Self {
+        let poa = &graph.graph;
+        let num_nodes = poa.node_count_with_start_and_end();
+        let (start, end) = (poa.start_node(), poa.end_node());
+
+        let mut successors = vec![Vec::new(); num_nodes];
+        let mut predecessors = vec![Vec::new(); num_nodes];
+
+        let has_link_in: Vec<_> = graph.links.iter().map(|(_, to)| *to).collect();
+        let has_link_out: Vec<_> = graph.links.iter().map(|(from, _)| *from).collect();
+
+        let nodes: Vec<NodeIx> = poa.all_nodes().collect();
+        for node in &nodes {
+            for succ in poa.successors(*node) {
+                if (*node == start && has_link_in.contains(&succ)) || (succ == end && has_link_out.contains(node)) {
+                    continue;
+                }
+
+                successors[node.index()].push(succ);
+                predecessors[succ.index()].push(*node);
+            }
+        }
+
+        for (from, to) in &graph.links {
+            if !successors[from.index()].contains(to) {
+                successors[from.index()].push(*to);
+                predecessors[to.index()].push(*from);
+            }
+        }
+
+        let ranks = topological_ranks(&nodes, &successors, &predecessors);
+
+        GraphView { graph, nodes, successors, predecessors, ranks }
+    }
+}
+
+/// Kahn's algorithm, returning the rank of each node indexed by node index.
+fn topological_ranks(nodes: &[NodeIx], successors: &[Vec<NodeIx>], predecessors: &[Vec<NodeIx>]) -> Vec<usize> {
+    let mut in_degree: Vec<usize> = predecessors.iter().map(|p| p.len()).collect();
+    let mut stack: Vec<NodeIx> = nodes.iter().copied().filter(|n| in_degree[n.index()] == 0).collect();
+    let mut ranks = vec![0; successors.len()];
+
+    let mut rank = 0;
+    while let Some(node) = stack.pop() {
+        ranks[node.index()] = rank;
+        rank += 1;
+
+        for succ in &successors[node.index()] {
+            in_degree[succ.index()] -= 1;
+            if in_degree[succ.index()] == 0 {
+                stack.push(*succ);
+            }
+        }
+    }
+
+    ranks
+}
+
+impl AlignableRefGraph for GraphView<'_> {
+    type NodeIndex = NodeIx;
+    type NodeIterator<'b> = Copied<Iter<'b, NodeIx>> where Self: 'b;
+    type PredecessorIterator<'b> = Copied<Iter<'b, NodeIx>> where Self: 'b;
+    type SuccessorIterator<'b> = Copied<Iter<'b, NodeIx>> where Self: 'b;
+
+    fn all_nodes(&self) -> Self::NodeIterator<'_> {
+        self.nodes.iter().copied()
+    }
+
+    fn node_count(&self) -> usize {
+        self.graph.graph.node_count()
+    }
+
+    fn node_count_with_start_and_end(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn edge_count(&self) -> usize {
+        let total: usize = self.successors.iter().map(|s| s.len()).sum();
+
+        total - self.out_degree(self.start_node()) - self.in_degree(self.end_node())
+    }
+
+    fn start_node(&self) -> NodeIx {
+        self.graph.graph.start_node()
+    }
+
+    fn end_node(&self) -> NodeIx {
+        self.graph.graph.end_node()
+    }
+
+    fn predecessors(&self, node: NodeIx) -> Self::PredecessorIterator<'_> {
+        self.predecessors[node.index()].iter().copied()
+    }
+
+    fn successors(&self, node: NodeIx) -> Self::SuccessorIterator<'_> {
+        self.successors[node.index()].iter().copied()
+    }
+
+    fn in_degree(&self, node: NodeIx) -> usize {
+        self.predecessors[node.index()].len()
+    }
+
+    fn out_degree(&self, node: NodeIx) -> usize {
+        self.successors[node.index()].len()
+    }
+
+    fn is_end(&self, node: NodeIx) -> bool {
+        self.graph.graph.is_end(node)
+    }
+
+    fn get_symbol_char(&self, node: NodeIx) -> char {
+        self.graph.graph.get_symbol_char(node)
+    }
+
+    fn is_symbol_equal(&self, node: NodeIx, symbol: u8) -> bool {
+        self.graph.graph.is_symbol_equal(node, symbol)
+    }
+
+    fn get_node_ordering(&self) -> Vec<usize> {
+        self.ranks.clone()
+    }
+}
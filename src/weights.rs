@@ -0,0 +1,57 @@
+use std::os::raw::c_int;
+
+use crate::error;
+use crate::PoastaGraph;
+
+/// Multiplies the consensus weights of all sequences added so far by `factor` (between 0 and 1
+/// to decay them), so streaming applications can emphasize recent reads without rebuilding the
+/// graph, including the weights bumped onto sequences and node paths. Sequences added and weights
+/// bumped afterwards start at their full weight.
+/// Returns -1 if the factor isn't positive and finite.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poasta_graph_decay_weights(graph: *mut PoastaGraph, factor: f64) -> c_int {
+    error::catch(|| {
+        if graph.is_null() || !factor.is_finite() || factor <= 0.0 {
+            return error::fail(-1, "graph is NULL or the factor isn't positive and finite");
+        }
+
+        let graph = unsafe { &mut *graph };
+        for record in graph.sequences_mut() {
+            record.info.scale *= factor;
+            record.info.copies *= factor;
+        }
+        for bump in &mut graph.path_weights {
+            bump.weight *= factor;
+        }
+
+        0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add, consensus, graph_of};
+
+    #[test]
+    fn recent_reads_outweigh_decayed_ones() {
+        let mut graph = graph_of(&["ACGTTGCA", "ACGTTGCA", "ACGTTGCA"]);
+        assert_eq!(unsafe { poasta_graph_decay_weights(&mut graph, 0.25) }, 0);
+        assert!(graph.sequences.iter().all(|record| record.info.scale == 0.25));
+
+        // Two new reads weigh 2 against 0.75
+        add(&mut graph, "ACGATGCA");
+        add(&mut graph, "ACGATGCA");
+        assert_eq!(consensus(&graph), "ACGATGCA");
+        assert_eq!(graph.sequences[4].info.scale, 1.0);
+    }
+
+    #[test]
+    fn factors_must_be_positive_and_finite() {
+        let mut graph = graph_of(&["ACGT"]);
+        for factor in [0.0, -0.5, f64::NAN, f64::INFINITY] {
+            assert_eq!(unsafe { poasta_graph_decay_weights(&mut graph, factor) }, -1);
+        }
+        assert_eq!(graph.sequences[0].info.scale, 1.0);
+    }
+}